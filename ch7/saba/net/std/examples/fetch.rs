@@ -0,0 +1,83 @@
+//! Linuxなどのホストで、URLを読み込んでページのテキストを表示する
+//!
+//! cargo run --example fetch -- http://localhost:8000/
+//!
+//! https:のサーバーの証明書はOSのルート証明書で検証する。SSL_CERT_FILEで別のファイルを指定できる
+//! HTTP_PROXYとNO_PROXYを指定すると、プロキシを通してリクエストを送る
+//! file:///path/to/index.html のように、ローカルのファイルも読み込める
+
+use net_std::fs::StdFileSystem;
+use net_std::http::unix_time;
+use net_std::http::HttpClient;
+use saba_core::browser::Browser;
+use saba_core::display_item::DisplayItem;
+use saba_core::http::proxy::ProxyConfig;
+use saba_core::scheme::file::FileHandler;
+use std::env;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+/// 大文字と小文字のどちらの名前でも指定できる環境変数を読む
+fn env_var(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| env::var(name).ok())
+}
+
+fn main() -> ExitCode {
+    let url = match env::args().nth(1) {
+        Some(url) => url,
+        None => {
+            eprintln!("usage: fetch <url>");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let browser = Browser::with_clock(Rc::new(unix_time));
+    browser
+        .borrow_mut()
+        .set_network_client(Rc::new(HttpClient::new()));
+    browser.borrow_mut().register_scheme_handler(
+        "file",
+        Rc::new(FileHandler::new(Rc::new(StdFileSystem::new()))),
+    );
+    if let Some(proxy) = env_var(&["HTTP_PROXY", "http_proxy"]) {
+        let proxy = match ProxyConfig::parse(&proxy) {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                eprintln!("invalid HTTP_PROXY: {:?}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let no_proxy = env_var(&["NO_PROXY", "no_proxy"]).unwrap_or_default();
+        browser
+            .borrow_mut()
+            .set_proxy(Some(proxy.with_no_proxy(&no_proxy)));
+    }
+
+    let response = match browser.borrow().load(&url) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("failed to load {}: {:?}", url, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "{} {} {}",
+        response.version(),
+        response.status_code(),
+        response.reason()
+    );
+    for header in response.headers() {
+        println!("{}: {}", header.name(), header.value());
+    }
+    println!();
+
+    let page = browser.borrow().current_page();
+    page.borrow_mut().receive_response(response);
+    for item in page.borrow().display_items() {
+        if let DisplayItem::Text { text, .. } = item {
+            println!("{}", text);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,57 @@
+use saba_core::error::Error;
+use saba_core::scheme::file::FileSystem;
+use std::fs;
+
+/// OSのファイルシステムからファイルを読み込む。file:のURLのパスをそのままOSのパスとして使う
+#[derive(Debug, Clone, Default)]
+pub struct StdFileSystem;
+
+impl StdFileSystem {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl FileSystem for StdFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        fs::read(path).map_err(|e| Error::Other(format!("failed to read {}: {}", path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saba_core::browser::Browser;
+    use saba_core::scheme::file::FileHandler;
+    use std::env;
+    use std::process;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_load_file() {
+        let dir = env::temp_dir().join(format!("saba-fs-test-{}", process::id()));
+        fs::create_dir_all(&dir).expect("failed to create a directory");
+        let path = dir.join("my page.html");
+        fs::write(&path, "<p>hello</p>").expect("failed to write a file");
+
+        let browser = Browser::new();
+        browser.borrow_mut().register_scheme_handler(
+            "file",
+            Rc::new(FileHandler::new(Rc::new(StdFileSystem::new()))),
+        );
+        let url = format!("file://{}", path.display()).replace(' ', "%20");
+        let response = browser.borrow().load(&url);
+        let missing = browser
+            .borrow()
+            .load(&format!("file://{}/missing.html", dir.display()));
+        fs::remove_dir_all(&dir).expect("failed to remove a directory");
+
+        let response = response.expect("failed to load");
+        assert_eq!(
+            Ok("text/html".to_string()),
+            response.header_value("Content-Type")
+        );
+        assert_eq!(b"<p>hello</p>".to_vec(), response.body());
+        assert!(missing.is_err());
+    }
+}
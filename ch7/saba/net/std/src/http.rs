@@ -0,0 +1,1153 @@
+use crate::resolver::SystemLookup;
+use saba_core::error::Error;
+use saba_core::http::client::BodyReader;
+use saba_core::http::client::CompleteBody;
+use saba_core::http::client::NetworkClient;
+use saba_core::http::h2::connection::H2Connection;
+use saba_core::http::h2::pool::SessionPool;
+use saba_core::http::h2::ALPN_PROTOCOL;
+use saba_core::http::pool::Connection;
+use saba_core::http::pool::ConnectionPool;
+use saba_core::http::pool::PooledBody;
+use saba_core::http::proxy::ProxyConfig;
+use saba_core::http::request::HttpRequest;
+use saba_core::http::HttpResponse;
+use saba_core::resolver::IpAddress;
+use saba_core::resolver::Resolver;
+use saba_core::tls::client::TlsConfig;
+use saba_core::tls::connection::TlsConnection;
+use saba_core::tls::trust::TrustStore;
+use saba_core::url::host::Host;
+use std::cell::OnceCell;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 信頼するルート証明書を探すファイル。主なLinuxのディストリビューションの場所を並べる
+const CA_BUNDLE_PATHS: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+fn io_error(message: &str, e: std::io::Error) -> Error {
+    match e.kind() {
+        // タイムアウトは、プラットフォームによってWouldBlockとして報告される
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => {
+            Error::Timeout(format!("{}: timed out", message))
+        }
+        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+            Error::ConnectionReset(format!("{}: {}", message, e))
+        }
+        _ => Error::Network(format!("{}: {}", message, e)),
+    }
+}
+
+fn socket_addr(address: IpAddress, port: u16) -> SocketAddr {
+    match address {
+        IpAddress::V4(octets) => SocketAddr::new(IpAddr::V4(Ipv4Addr::from(octets)), port),
+        IpAddress::V6(segments) => SocketAddr::new(IpAddr::V6(Ipv6Addr::from(segments)), port),
+    }
+}
+
+/// OSのルート証明書を読み込む。SSL_CERT_FILEが指定されている場合はそのファイルを使う
+pub fn system_trust_store() -> TrustStore {
+    let mut trust_store = TrustStore::new();
+    let paths: Vec<String> = match env::var("SSL_CERT_FILE") {
+        Ok(path) => vec![path],
+        Err(_) => CA_BUNDLE_PATHS
+            .iter()
+            .map(|path| path.to_string())
+            .collect(),
+    };
+    for path in paths {
+        if let Ok(pem) = fs::read_to_string(&path) {
+            if trust_store.add_pem(&pem).is_ok() && !trust_store.is_empty() {
+                break;
+            }
+        }
+    }
+    trust_store
+}
+
+/// OSの乱数生成器で埋める
+fn fill_random(buf: &mut [u8]) {
+    File::open("/dev/urandom")
+        .and_then(|mut file| file.read_exact(buf))
+        .expect("failed to read /dev/urandom");
+}
+
+/// 現在のUNIX時間の秒数。ブラウザやTLSの証明書の検証で使う時計
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug)]
+struct TcpConnection {
+    stream: TcpStream,
+}
+
+impl TcpConnection {
+    /// アドレスを順に試し、最初に接続できたものを使う
+    fn connect(
+        addrs: &[SocketAddr],
+        connect_timeout: Duration,
+        read_timeout: Duration,
+    ) -> Result<Self, Error> {
+        let mut last_error = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(addr, connect_timeout) {
+                Ok(stream) => {
+                    let configured = stream
+                        .set_read_timeout(Some(read_timeout))
+                        .and_then(|_| stream.set_write_timeout(Some(read_timeout)))
+                        .and_then(|_| stream.set_nodelay(true));
+                    if let Err(e) = configured {
+                        return Err(io_error("Failed to configure TCP stream", e));
+                    }
+                    return Ok(Self { stream });
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        // 接続のタイムアウトも、接続の失敗として扱う
+        match last_error {
+            Some(e) => Err(Error::Connect(format!(
+                "Failed to connect to TCP stream: {}",
+                e
+            ))),
+            None => Err(Error::Dns("Failed to find IP addresses".to_string())),
+        }
+    }
+}
+
+impl Connection for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        loop {
+            match self.stream.write(buf) {
+                Ok(bytes) => return Ok(bytes),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(io_error("Failed to send a request to TCP stream", e)),
+            }
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            match self.stream.read(buf) {
+                Ok(bytes) => return Ok(bytes),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(io_error("Failed to receive a response from TCP stream", e)),
+            }
+        }
+    }
+}
+
+/// std::netを使うHTTPクライアント。WasabiOSではなく、Linuxなどのホストでエンジンを動かす
+/// ときに使う
+#[derive(Debug)]
+pub struct HttpClient {
+    // 使い終わった接続は閉じずに、同じホストへの次のリクエストで使う
+    pool: Rc<RefCell<ConnectionPool<TcpConnection>>>,
+    tls_pool: Rc<RefCell<ConnectionPool<TlsConnection<TcpConnection>>>>,
+    /// ALPNでHTTP/2に決まった接続は、同じホストへのリクエストで共有する
+    h2_pool: RefCell<SessionPool<TlsConnection<TcpConnection>>>,
+    h2c_pool: RefCell<SessionPool<TcpConnection>>,
+    /// http:のリクエストを、最初からHTTP/2で送る
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-3.3
+    http2_prior_knowledge: bool,
+    /// OSのルート証明書を読み込むのは時間がかかるので、最初のhttps:のリクエストまで遅らせる
+    tls_config: OnceCell<Rc<TlsConfig>>,
+    proxy: RefCell<Option<ProxyConfig>>,
+    resolver: Resolver,
+    connect_timeout: Duration,
+    /// 読み書きのそれぞれについて、これより長く待たされた場合は失敗にする
+    read_timeout: Duration,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        Self {
+            pool: Rc::new(RefCell::new(ConnectionPool::new())),
+            tls_pool: Rc::new(RefCell::new(ConnectionPool::new())),
+            h2_pool: RefCell::new(SessionPool::new()),
+            h2c_pool: RefCell::new(SessionPool::new()),
+            http2_prior_knowledge: false,
+            tls_config: OnceCell::new(),
+            proxy: RefCell::new(None),
+            resolver: Resolver::new(Rc::new(SystemLookup::new())).with_clock(Rc::new(unix_time)),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+        }
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// https:のサーバーの証明書を、OSのルート証明書の代わりに指定したもので検証する
+    pub fn with_trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.tls_config = OnceCell::from(Self::new_tls_config(trust_store));
+        self
+    }
+
+    /// hostsの表などを設定したリゾルバで、接続先のホストとプロキシを解決する
+    pub fn with_resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// ホストを、接続先のアドレスの一覧にする
+    fn resolve(&self, host: Option<Host>, port: u16) -> Result<Vec<SocketAddr>, Error> {
+        let addresses = self.resolver.resolve_host(&host.unwrap_or(Host::Empty))?;
+        Ok(addresses
+            .into_iter()
+            .map(|address| socket_addr(address, port))
+            .collect())
+    }
+
+    /// サーバーがHTTP/2に対応しているとわかっている場合に、http:のリクエストをh2cで送る。
+    /// プロキシを通すリクエストはHTTP/1.1で送る
+    pub fn with_http2_prior_knowledge(mut self) -> Self {
+        self.http2_prior_knowledge = true;
+        self
+    }
+
+    /// すべてのリクエストをプロキシを通して送る
+    pub fn with_proxy(self, proxy: ProxyConfig) -> Self {
+        self.set_proxy(Some(proxy));
+        self
+    }
+
+    fn new_tls_config(trust_store: TrustStore) -> Rc<TlsConfig> {
+        Rc::new(
+            TlsConfig::new(trust_store, Rc::new(fill_random))
+                .with_alpn_protocols(vec![ALPN_PROTOCOL.to_string(), "http/1.1".to_string()])
+                .with_clock(Rc::new(unix_time)),
+        )
+    }
+
+    fn tls_config(&self) -> Rc<TlsConfig> {
+        self.tls_config
+            .get_or_init(|| Self::new_tls_config(system_trust_store()))
+            .clone()
+    }
+
+    /// HTTP/2の接続は、リクエストの途中でなくても1つと数える
+    pub fn idle_connections(&self) -> usize {
+        self.pool.borrow().idle_count()
+            + self.tls_pool.borrow().idle_count()
+            + self.h2_pool.borrow().len()
+            + self.h2c_pool.borrow().len()
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkClient for HttpClient {
+    /// POSTやHEADなど、任意のメソッドとヘッダのリクエストを送信する
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        let (mut response, mut body) = self.open(request)?;
+        while let Some(bytes) = body.read()? {
+            response.extend_body(&bytes);
+        }
+        Ok(response)
+    }
+
+    /// HTTP/1.1の接続では、ボディを受信した分ずつ返す。HTTP/2の接続では、ボディをすべて
+    /// 受信してから返す
+    fn open(&self, request: &HttpRequest) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let url = request.url();
+        let host = url.host();
+        let port = url.port();
+        let proxy = self
+            .proxy
+            .borrow()
+            .clone()
+            .filter(|proxy| !proxy.is_bypassed(&url));
+        let connect = |host: Option<Host>, port: u16| {
+            let addrs = self.resolve(host, port)?;
+            TcpConnection::connect(&addrs, self.connect_timeout, self.read_timeout)
+        };
+
+        match (url.scheme().as_str(), proxy) {
+            ("http", None) if self.http2_prior_knowledge => {
+                complete_body(self.h2c_pool.borrow_mut().send(&host, port, request, || {
+                    H2Connection::connect(connect(url.host_kind(), port)?)
+                }))
+            }
+            ("http", None) => {
+                let bytes = request.serialize()?;
+                streaming_body(ConnectionPool::open(
+                    &self.pool,
+                    &host,
+                    port,
+                    &request.method(),
+                    &bytes,
+                    || connect(url.host_kind(), port),
+                ))
+            }
+            // プロキシへの接続は、接続先のホストによらず使い回せる
+            ("http", Some(proxy)) => {
+                let bytes = proxy.forward_request(request)?;
+                streaming_body(ConnectionPool::open(
+                    &self.pool,
+                    &proxy.host().serialize(),
+                    proxy.port(),
+                    &request.method(),
+                    &bytes,
+                    || connect(Some(proxy.host().clone()), proxy.port()),
+                ))
+            }
+            ("https", proxy) => {
+                let connect_tls = || {
+                    let connection = match &proxy {
+                        Some(proxy) => {
+                            let mut connection = connect(Some(proxy.host().clone()), proxy.port())?;
+                            proxy.establish_tunnel(&mut connection, &host, port)?;
+                            connection
+                        }
+                        None => connect(url.host_kind(), port)?,
+                    };
+                    let server_name = url.host_kind().unwrap_or(Host::Empty);
+                    TlsConnection::connect(connection, self.tls_config(), &server_name)
+                };
+                let connect_h2 = || {
+                    let connection = connect_tls()?;
+                    if connection.alpn_protocol() != Some(ALPN_PROTOCOL) {
+                        return Err(Error::Network(format!(
+                            "server no longer supports HTTP/2: {}",
+                            url.href()
+                        )));
+                    }
+                    H2Connection::connect(connection)
+                };
+
+                // どちらのプロトコルを使うかは、新しく接続したときのALPNで決まる
+                if !self.h2_pool.borrow().contains(&host, port)
+                    && !self.tls_pool.borrow().contains(&host, port)
+                {
+                    let connection = connect_tls()?;
+                    if connection.alpn_protocol() == Some(ALPN_PROTOCOL) {
+                        let session = H2Connection::connect(connection)?;
+                        self.h2_pool.borrow_mut().insert(&host, port, session);
+                    } else {
+                        self.tls_pool.borrow_mut().put(&host, port, connection);
+                    }
+                }
+
+                if self.h2_pool.borrow().contains(&host, port) {
+                    return complete_body(
+                        self.h2_pool
+                            .borrow_mut()
+                            .send(&host, port, request, connect_h2),
+                    );
+                }
+                let bytes = request.serialize()?;
+                streaming_body(ConnectionPool::open(
+                    &self.tls_pool,
+                    &host,
+                    port,
+                    &request.method(),
+                    &bytes,
+                    || {
+                        let connection = connect_tls()?;
+                        if connection.alpn_protocol() == Some(ALPN_PROTOCOL) {
+                            return Err(Error::Network(format!(
+                                "server switched to HTTP/2: {}",
+                                url.href()
+                            )));
+                        }
+                        Ok(connection)
+                    },
+                ))
+            }
+            _ => Err(Error::Network(format!(
+                "only http and https schemes are supported: {}",
+                url.href()
+            ))),
+        }
+    }
+
+    /// 古い設定で張った接続は使わない
+    fn set_proxy(&self, proxy: Option<ProxyConfig>) {
+        *self.proxy.borrow_mut() = proxy;
+        self.pool.borrow_mut().clear();
+        self.tls_pool.borrow_mut().clear();
+        self.h2_pool.borrow_mut().clear();
+        self.h2c_pool.borrow_mut().clear();
+    }
+}
+
+/// すべて受信したレスポンスのボディを、BodyReaderから読み出せるようにする
+fn complete_body(
+    response: Result<HttpResponse, Error>,
+) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+    let mut response = response?;
+    let body = response.take_body();
+    Ok((response, Box::new(CompleteBody::new(body))))
+}
+
+fn streaming_body<C: Connection + 'static>(
+    opened: Result<(HttpResponse, PooledBody<C>), Error>,
+) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+    let (response, body) = opened?;
+    Ok((response, Box::new(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saba_core::resolver::HostsTable;
+    use saba_core::url::Url;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::process::Child;
+    use std::process::Command;
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::thread::JoinHandle;
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../saba_core/testdata/tls/");
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::get(
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    /// リクエストのヘッダの終わりまで読む
+    fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let bytes = stream.read(&mut buf).expect("failed to read");
+            if bytes == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..bytes]);
+        }
+        request
+    }
+
+    /// 1つの接続で、受け取ったリクエストごとにレスポンスを順に返すサーバー
+    fn serve(listener: TcpListener, responses: Vec<&'static str>) -> JoinHandle<Vec<Vec<u8>>> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("failed to accept");
+            let mut requests = Vec::new();
+            for response in responses {
+                requests.push(read_request(&mut stream));
+                stream
+                    .write_all(response.as_bytes())
+                    .expect("failed to write");
+            }
+            requests
+        })
+    }
+
+    #[test]
+    fn test_get() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        let server = serve(
+            listener,
+            vec!["HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nsaba"],
+        );
+
+        let client = HttpClient::new();
+        let response = client
+            .send(&get(&format!("http://127.0.0.1:{}/index.html", port)))
+            .expect("failed to send");
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"saba".to_vec(), response.body());
+
+        let requests = server.join().expect("server panicked");
+        assert!(requests[0].starts_with(b"GET /index.html HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_reuse_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        // 1つの接続しか受け付けないので、2つ目のリクエストは同じ接続で送られる必要がある
+        let server = serve(
+            listener,
+            vec![
+                "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na",
+                "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nb",
+            ],
+        );
+
+        let client = HttpClient::new();
+        let url = format!("http://localhost:{}/", port);
+        let first = client.send(&get(&url)).expect("failed to send");
+        assert_eq!(1, client.idle_connections());
+        let second = client.send(&get(&url)).expect("failed to send");
+        assert_eq!(b"a".to_vec(), first.body());
+        assert_eq!(b"b".to_vec(), second.body());
+
+        assert_eq!(2, server.join().expect("server panicked").len());
+    }
+
+    #[test]
+    fn test_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        let (sender, receiver) = mpsc::channel();
+        // ボディの続きは、クライアントが最初の部分を読んでから送る
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("failed to accept");
+            read_request(&mut stream);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nfirst")
+                .expect("failed to write");
+            receiver.recv().expect("failed to receive");
+            stream.write_all(b" last").expect("failed to write");
+        });
+
+        let client = HttpClient::new();
+        let (response, mut body) = client
+            .open(&get(&format!("http://127.0.0.1:{}/", port)))
+            .expect("failed to open");
+        assert_eq!(200, response.status_code());
+        assert_eq!(
+            Some(b"first".to_vec()),
+            body.read().expect("failed to read")
+        );
+        sender.send(()).expect("failed to send");
+        assert_eq!(
+            Some(b" last".to_vec()),
+            body.read().expect("failed to read")
+        );
+        assert_eq!(None, body.read().expect("failed to read"));
+
+        // ボディを読み終えた接続は次のリクエストで使う
+        assert_eq!(1, client.idle_connections());
+        server.join().expect("server panicked");
+    }
+
+    #[test]
+    fn test_ipv6() {
+        // IPv6が使えない環境では確認しない
+        let listener = match TcpListener::bind("[::1]:0") {
+            Ok(listener) => listener,
+            Err(_) => return,
+        };
+        let port = listener.local_addr().expect("no address").port();
+        let server = serve(
+            listener,
+            vec!["HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nv6"],
+        );
+
+        let response = HttpClient::new()
+            .send(&get(&format!("http://[::1]:{}/", port)))
+            .expect("failed to send");
+        assert_eq!(b"v6".to_vec(), response.body());
+
+        let requests = server.join().expect("server panicked");
+        let request = String::from_utf8_lossy(&requests[0]).to_string();
+        assert!(request.contains(&format!("Host: [::1]:{}\r\n", port)));
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        // リクエストを受け取っても、レスポンスを返さない
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("failed to accept");
+            read_request(&mut stream);
+            thread::sleep(Duration::from_millis(500));
+        });
+
+        let client = HttpClient::new().with_read_timeout(Duration::from_millis(50));
+        match client.send(&get(&format!("http://127.0.0.1:{}/", port))) {
+            Err(Error::Timeout(message)) => assert!(message.contains("timed out"), "{}", message),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        server.join().expect("server panicked");
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        // 接続を受け付けていないポートを用意する
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("failed to bind")
+            .port();
+        assert!(matches!(
+            HttpClient::new().send(&get(&format!("http://127.0.0.1:{}/", port))),
+            Err(Error::Connect(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_scheme() {
+        assert!(HttpClient::new().send(&get("ftp://example.com/")).is_err());
+    }
+
+    /// テスト用の証明書を使うopenssl s_server
+    struct TlsServer {
+        child: Child,
+        port: u16,
+    }
+
+    impl Drop for TlsServer {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// -wwwを指定したs_serverを起動する。opensslがない環境ではNoneを返す
+    fn tls_server(args: &[&str]) -> Option<TlsServer> {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("failed to bind")
+            .port();
+        let mut child = Command::new("openssl")
+            .arg("s_server")
+            .arg("-accept")
+            .arg(format!("127.0.0.1:{}", port))
+            .arg("-www")
+            .args(args)
+            .current_dir(TESTDATA)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        // 接続を受け付けられるようになるまで待つ
+        let stdout = child.stdout.take().expect("no stdout");
+        let mut lines = BufReader::new(stdout).lines();
+        let server = TlsServer { child, port };
+        lines
+            .find(|line| matches!(line, Ok(line) if line == "ACCEPT"))
+            .map(|_| server)
+    }
+
+    fn test_client() -> HttpClient {
+        let mut trust_store = TrustStore::new();
+        trust_store
+            .add_pem(include_str!("../../../saba_core/testdata/tls/ca.pem"))
+            .expect("failed to add a certificate");
+        HttpClient::new().with_trust_store(trust_store)
+    }
+
+    /// s_serverのページに表示される、ネゴシエーションの結果を含むレスポンスを受け取る
+    fn fetch_status(server: &TlsServer, host: &str) -> Result<String, Error> {
+        let response = test_client().send(&get(&format!("https://{}:{}/", host, server.port)))?;
+        assert_eq!(200, response.status_code());
+        Ok(String::from_utf8_lossy(&response.body()).to_string())
+    }
+
+    fn connect_tls(server: &TlsServer, host: Host) -> Result<TlsConnection<TcpConnection>, Error> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], server.port));
+        let connection =
+            TcpConnection::connect(&[addr], DEFAULT_CONNECT_TIMEOUT, DEFAULT_READ_TIMEOUT)?;
+        TlsConnection::connect(connection, test_client().tls_config(), &host)
+    }
+
+    #[test]
+    fn test_https_tls13() {
+        let server = match tls_server(&[
+            "-tls1_3",
+            "-cert",
+            "rsa.pem",
+            "-key",
+            "rsa.key",
+            "-cert_chain",
+            "intermediate.pem",
+        ]) {
+            Some(server) => server,
+            None => return,
+        };
+        let status = fetch_status(&server, "localhost").expect("failed to fetch");
+        assert!(status.contains("New, TLSv1.3"), "{}", status);
+    }
+
+    #[test]
+    fn test_https_tls13_hello_retry_request() {
+        // X25519の鍵しか送らないので、P-256を要求するHelloRetryRequestが返される
+        let server = match tls_server(&[
+            "-tls1_3",
+            "-cert",
+            "ecdsa.pem",
+            "-key",
+            "ecdsa.key",
+            "-groups",
+            "P-256",
+            "-ciphersuites",
+            "TLS_CHACHA20_POLY1305_SHA256",
+        ]) {
+            Some(server) => server,
+            None => return,
+        };
+        let status = fetch_status(&server, "127.0.0.1").expect("failed to fetch");
+        assert!(
+            status.contains("TLS_CHACHA20_POLY1305_SHA256"),
+            "{}",
+            status
+        );
+    }
+
+    #[test]
+    fn test_https_tls12() {
+        for (cert, key, cipher) in [
+            ("ecdsa.pem", "ecdsa.key", "ECDHE-ECDSA-AES256-GCM-SHA384"),
+            ("rsa.pem", "rsa.key", "ECDHE-RSA-AES128-GCM-SHA256"),
+            ("rsa.pem", "rsa.key", "ECDHE-RSA-CHACHA20-POLY1305"),
+        ] {
+            let server = match tls_server(&[
+                "-tls1_2",
+                "-cert",
+                cert,
+                "-key",
+                key,
+                "-cert_chain",
+                "intermediate.pem",
+                "-cipher",
+                cipher,
+            ]) {
+                Some(server) => server,
+                None => return,
+            };
+            let status = fetch_status(&server, "localhost").expect("failed to fetch");
+            let negotiated = format!("New, TLSv1.2, Cipher is {}", cipher);
+            assert!(status.contains(&negotiated), "{}", status);
+        }
+    }
+
+    #[test]
+    fn test_alpn() {
+        let server = match tls_server(&[
+            "-cert",
+            "rsa.pem",
+            "-key",
+            "rsa.key",
+            "-cert_chain",
+            "intermediate.pem",
+            "-alpn",
+            "h2,http/1.1",
+        ]) {
+            Some(server) => server,
+            None => return,
+        };
+        let connection = connect_tls(&server, Host::Domain("www.saba.test".to_string()))
+            .expect("failed to connect");
+        assert_eq!(Some("h2"), connection.alpn_protocol());
+        assert_eq!(Some(0x0304), connection.client().version());
+    }
+
+    #[test]
+    fn test_untrusted_certificate() {
+        let server = match tls_server(&["-cert", "untrusted.pem", "-key", "untrusted.key"]) {
+            Some(server) => server,
+            None => return,
+        };
+        match fetch_status(&server, "localhost") {
+            Err(Error::Tls(message)) => {
+                assert!(message.contains("unknown issuer"), "{}", message)
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_wrong_host() {
+        let server = match tls_server(&["-cert", "ecdsa.pem", "-key", "ecdsa.key"]) {
+            Some(server) => server,
+            None => return,
+        };
+        match connect_tls(&server, Host::Domain("example.com".to_string())) {
+            Err(Error::Tls(message)) => {
+                assert!(message.contains("not valid for example.com"), "{}", message)
+            }
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_http_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        let proxy = serve(
+            listener,
+            vec!["HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nproxy"],
+        );
+
+        // 解決できないホストでも、プロキシが代わりに接続する
+        let client = HttpClient::new().with_proxy(
+            ProxyConfig::new(Host::Ipv4(0x7f000001), port).with_credentials("saba", "book"),
+        );
+        let response = client
+            .send(&get("http://saba.invalid/index.html"))
+            .expect("failed to send");
+        assert_eq!(b"proxy".to_vec(), response.body());
+
+        let requests = proxy.join().expect("proxy panicked");
+        let request = String::from_utf8_lossy(&requests[0]).to_string();
+        assert!(
+            request.starts_with("GET http://saba.invalid/index.html HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+        assert!(
+            request.contains("Proxy-Authorization: Basic c2FiYTpib29r\r\n"),
+            "{}",
+            request
+        );
+    }
+
+    #[test]
+    fn test_no_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        let server = serve(
+            listener,
+            vec!["HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\ndirect"],
+        );
+        // 接続を受け付けていないプロキシ
+        let proxy_port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("failed to bind")
+            .port();
+
+        let client = HttpClient::new().with_proxy(
+            ProxyConfig::new(Host::Ipv4(0x7f000001), proxy_port).with_no_proxy("127.0.0.1"),
+        );
+        let response = client
+            .send(&get(&format!("http://127.0.0.1:{}/", port)))
+            .expect("failed to send");
+        assert_eq!(b"direct".to_vec(), response.body());
+        server.join().expect("server panicked");
+    }
+
+    /// CONNECTを受け付けて、指定したポートとの間でバイト列を中継するプロキシ
+    fn serve_tunnel(listener: TcpListener, target_port: u16) -> JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let (mut client, _) = listener.accept().expect("failed to accept");
+            let request = read_request(&mut client);
+            let mut server =
+                TcpStream::connect(("127.0.0.1", target_port)).expect("failed to connect");
+            client
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .expect("failed to write");
+
+            let mut client_reader = client.try_clone().expect("failed to clone");
+            let mut server_writer = server.try_clone().expect("failed to clone");
+            let upstream = thread::spawn(move || {
+                let _ = std::io::copy(&mut client_reader, &mut server_writer);
+                let _ = server_writer.shutdown(std::net::Shutdown::Write);
+            });
+            let _ = std::io::copy(&mut server, &mut client);
+            let _ = client.shutdown(std::net::Shutdown::Write);
+            let _ = upstream.join();
+            request
+        })
+    }
+
+    #[test]
+    fn test_https_proxy() {
+        let server = match tls_server(&[
+            "-cert",
+            "rsa.pem",
+            "-key",
+            "rsa.key",
+            "-cert_chain",
+            "intermediate.pem",
+        ]) {
+            Some(server) => server,
+            None => return,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let proxy_port = listener.local_addr().expect("no address").port();
+        let proxy = serve_tunnel(listener, server.port);
+
+        let client = test_client().with_proxy(
+            ProxyConfig::new(Host::Ipv4(0x7f000001), proxy_port).with_credentials("saba", "book"),
+        );
+        let server_port = server.port;
+        let response = client
+            .send(&get(&format!("https://localhost:{}/", server_port)))
+            .expect("failed to send");
+        assert_eq!(200, response.status_code());
+        // s_serverを止めて、中継を終わらせる
+        drop(server);
+
+        let request = String::from_utf8_lossy(&proxy.join().expect("proxy panicked")).to_string();
+        assert!(
+            request.starts_with(&format!("CONNECT localhost:{} HTTP/1.1\r\n", server_port)),
+            "{}",
+            request
+        );
+        assert!(
+            request.contains("Proxy-Authorization: Basic c2FiYTpib29r\r\n"),
+            "{}",
+            request
+        );
+    }
+
+    #[test]
+    fn test_https_proxy_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        let proxy = serve(
+            listener,
+            vec!["HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n"],
+        );
+
+        let client = HttpClient::new().with_proxy(ProxyConfig::new(Host::Ipv4(0x7f000001), port));
+        match client.send(&get("https://saba.invalid/")) {
+            Err(Error::Network(message)) => assert_eq!(
+                "proxy authentication required for saba.invalid:443",
+                message
+            ),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        proxy.join().expect("proxy panicked");
+    }
+
+    #[test]
+    fn test_hosts_override() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no address").port();
+        let server = serve(
+            listener,
+            vec!["HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nhost"],
+        );
+
+        let resolver = Resolver::new(Rc::new(SystemLookup::new()))
+            .with_hosts(HostsTable::parse("127.0.0.1 host.test"));
+        let client = HttpClient::new().with_resolver(resolver);
+        let response = client
+            .send(&get(&format!("http://host.test:{}/", port)))
+            .expect("failed to send");
+        assert_eq!(b"host".to_vec(), response.body());
+
+        let requests = server.join().expect("server panicked");
+        let request = String::from_utf8_lossy(&requests[0]).to_string();
+        assert!(request.contains(&format!("Host: host.test:{}\r\n", port)));
+    }
+
+    #[test]
+    fn test_unknown_host() {
+        match HttpClient::new().send(&get("http://saba.invalid/")) {
+            Err(Error::Dns(message)) => assert!(
+                message == "could not resolve host: saba.invalid"
+                    || message == "timed out resolving host: saba.invalid",
+                "{}",
+                message
+            ),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    /// テスト用のファイルを置いたnghttpd
+    struct H2Server {
+        child: Child,
+        port: u16,
+        htdocs: PathBuf,
+    }
+
+    impl Drop for H2Server {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = fs::remove_dir_all(&self.htdocs);
+        }
+    }
+
+    fn large_body() -> Vec<u8> {
+        (0..300_000).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// nghttpdを起動する。tlsがtrueの場合はテスト用の証明書を使い、ALPNでh2を選ぶ。nghttpdが
+    /// ない環境ではNoneを返す
+    fn h2_server(args: &[&str], tls: bool) -> Option<H2Server> {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("failed to bind")
+            .port();
+        let htdocs = env::temp_dir().join(format!("saba-h2-{}", port));
+        fs::create_dir_all(&htdocs).expect("failed to create htdocs");
+        fs::write(htdocs.join("index.html"), "<p>saba</p>").expect("failed to write");
+        fs::write(htdocs.join("large.bin"), large_body()).expect("failed to write");
+
+        let mut command = Command::new("nghttpd");
+        command
+            .arg("-d")
+            .arg(&htdocs)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if tls {
+            let chain = [
+                fs::read_to_string(format!("{}rsa.pem", TESTDATA)),
+                fs::read_to_string(format!("{}intermediate.pem", TESTDATA)),
+            ]
+            .into_iter()
+            .collect::<Result<String, _>>()
+            .expect("failed to read certificates");
+            fs::write(htdocs.join("chain.pem"), chain).expect("failed to write");
+            command
+                .arg(port.to_string())
+                .arg(format!("{}rsa.key", TESTDATA))
+                .arg(htdocs.join("chain.pem"));
+        } else {
+            command.arg("--no-tls").arg(port.to_string());
+        }
+        let server = H2Server {
+            child: command.spawn().ok()?,
+            port,
+            htdocs,
+        };
+
+        // nghttpdは準備ができても何も出力しないので、接続できるまで待つ
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        for _ in 0..50 {
+            if TcpStream::connect(addr).is_ok() {
+                return Some(server);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        None
+    }
+
+    #[test]
+    fn test_h2c() {
+        let server = match h2_server(&["--padding=20", "--trailer=x-saba: done"], false) {
+            Some(server) => server,
+            None => return,
+        };
+        let client = HttpClient::new().with_http2_prior_knowledge();
+        let response = client
+            .send(&get(&format!("http://127.0.0.1:{}/", server.port)))
+            .expect("failed to send");
+        assert_eq!("HTTP/2", response.version());
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"<p>saba</p>".to_vec(), response.body());
+        assert_eq!("x-saba", response.trailers()[0].name());
+
+        let response = client
+            .send(&get(&format!("http://127.0.0.1:{}/saba", server.port)))
+            .expect("failed to send");
+        assert_eq!(404, response.status_code());
+        assert_eq!(1, client.idle_connections());
+    }
+
+    #[test]
+    fn test_h2c_flow_control() {
+        // サーバーのウィンドウを小さくし、WINDOW_UPDATEを待ちながら送る
+        let server = match h2_server(&["--echo-upload", "-w", "10", "-W", "12"], false) {
+            Some(server) => server,
+            None => return,
+        };
+        let client = HttpClient::new().with_http2_prior_knowledge();
+        let response = client
+            .send(&get(&format!("http://127.0.0.1:{}/large.bin", server.port)))
+            .expect("failed to send");
+        assert_eq!(large_body(), response.body());
+
+        let url = Url::new(format!("http://127.0.0.1:{}/", server.port))
+            .parse()
+            .expect("failed to parse url");
+        let request = HttpRequest::new("POST", url).with_body(large_body());
+        let response = client.send(&request).expect("failed to send");
+        assert_eq!(200, response.status_code());
+        assert_eq!(large_body(), response.body());
+    }
+
+    #[test]
+    fn test_h2c_multiplexing() {
+        let server = match h2_server(&["--max-concurrent-streams=2"], false) {
+            Some(server) => server,
+            None => return,
+        };
+        let addr = SocketAddr::from(([127, 0, 0, 1], server.port));
+        let connection =
+            TcpConnection::connect(&[addr], DEFAULT_CONNECT_TIMEOUT, DEFAULT_READ_TIMEOUT)
+                .expect("failed to connect");
+        let mut connection = H2Connection::connect(connection).expect("failed to connect");
+
+        // 同時に処理できるストリームは2つなので、3つ目は1つ目の後に送られる
+        let paths = ["/large.bin", "/index.html", "/saba"];
+        let stream_ids: Vec<u32> = paths
+            .iter()
+            .map(|path| {
+                connection
+                    .send_request(&get(&format!("http://127.0.0.1:{}{}", server.port, path)))
+                    .expect("failed to send")
+            })
+            .collect();
+        assert_eq!(vec![1, 3, 5], stream_ids);
+
+        let statuses: Vec<u16> = stream_ids
+            .iter()
+            .rev()
+            .map(|stream_id| {
+                connection
+                    .response(*stream_id)
+                    .expect("failed to receive")
+                    .status_code()
+            })
+            .collect();
+        assert_eq!(vec![404, 200, 200], statuses);
+        assert_eq!(0, connection.active_streams());
+        assert!(connection.is_usable());
+    }
+
+    #[test]
+    fn test_https_h2() {
+        let server = match h2_server(&[], true) {
+            Some(server) => server,
+            None => return,
+        };
+        let client = test_client();
+        for _ in 0..2 {
+            let response = client
+                .send(&get(&format!("https://localhost:{}/", server.port)))
+                .expect("failed to send");
+            assert_eq!("HTTP/2", response.version());
+            assert_eq!(b"<p>saba</p>".to_vec(), response.body());
+        }
+        assert_eq!(1, client.idle_connections());
+    }
+}
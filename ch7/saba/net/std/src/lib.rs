@@ -0,0 +1,3 @@
+pub mod fs;
+pub mod http;
+pub mod resolver;
@@ -0,0 +1,91 @@
+use saba_core::resolver::IpAddress;
+use saba_core::resolver::NameLookup;
+use saba_core::resolver::ResolveError;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::net::ToSocketAddrs;
+
+/// getaddrinfoのエラーはio::ErrorKindで区別できないので、gai_strerrorのメッセージで区別する
+const NOT_FOUND_MESSAGES: [&str; 4] = [
+    // glibcのEAI_NONAMEとEAI_NODATA
+    "Name or service not known",
+    "No address associated with hostname",
+    // musl
+    "Name does not resolve",
+    // macOS
+    "nodename nor servname provided",
+];
+const TIMEOUT_MESSAGES: [&str; 1] = [
+    // EAI_AGAIN。DNSサーバーから時間内に応答がなかった場合に返される
+    "Temporary failure in name resolution",
+];
+
+/// OSのリゾルバ(getaddrinfo)を使う名前解決。/etc/hostsも使われる
+#[derive(Debug, Clone, Default)]
+pub struct SystemLookup;
+
+impl SystemLookup {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl NameLookup for SystemLookup {
+    fn lookup(&self, domain: &str) -> Result<Vec<IpAddress>, ResolveError> {
+        let addrs = match (domain, 0).to_socket_addrs() {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                let message = e.to_string();
+                if e.kind() == ErrorKind::TimedOut
+                    || TIMEOUT_MESSAGES.iter().any(|m| message.contains(m))
+                {
+                    return Err(ResolveError::Timeout(domain.to_string()));
+                }
+                if NOT_FOUND_MESSAGES.iter().any(|m| message.contains(m)) {
+                    return Err(ResolveError::NotFound(domain.to_string()));
+                }
+                return Err(ResolveError::Other(format!("{}: {}", domain, message)));
+            }
+        };
+
+        let mut addresses = Vec::new();
+        for addr in addrs {
+            let address = match addr.ip() {
+                IpAddr::V4(ip) => IpAddress::V4(ip.octets()),
+                IpAddr::V6(ip) => IpAddress::V6(ip.segments()),
+            };
+            // getaddrinfoはソケットの種類ごとに同じアドレスを返すことがある
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        Ok(addresses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_localhost() {
+        let addresses = SystemLookup::new()
+            .lookup("localhost")
+            .expect("failed to resolve localhost");
+        assert!(addresses
+            .iter()
+            .all(|a| *a == IpAddress::LOCALHOST_V4 || *a == IpAddress::LOCALHOST_V6));
+    }
+
+    #[test]
+    fn test_lookup_not_found() {
+        // .invalidは存在しないことが保証されている
+        // https://www.rfc-editor.org/rfc/rfc6761#section-6.4
+        match SystemLookup::new().lookup("saba.invalid") {
+            Err(ResolveError::NotFound(name)) => assert_eq!("saba.invalid", name),
+            // DNSサーバーに届かない環境では確認しない
+            Err(ResolveError::Timeout(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
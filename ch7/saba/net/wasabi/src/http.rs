@@ -1,88 +1,176 @@
 extern crate alloc;
 use crate::http::alloc::string::ToString;
+use alloc::boxed::Box;
 use alloc::format;
-use alloc::string::String;
+use alloc::rc::Rc;
 use alloc::vec::Vec;
+use core::cell::RefCell;
 use noli::net::lookup_host;
+use noli::net::IpV4Addr;
 use noli::net::SocketAddr;
 use noli::net::TcpStream;
 use saba_core::error::Error;
+use saba_core::http::client::BodyReader;
+use saba_core::http::client::NetworkClient;
+use saba_core::http::pool::Connection;
+use saba_core::http::pool::ConnectionPool;
+use saba_core::http::proxy::ProxyConfig;
+use saba_core::http::request::HttpRequest;
 use saba_core::http::HttpResponse;
+use saba_core::resolver::IpAddress;
+use saba_core::resolver::NameLookup;
+use saba_core::resolver::ResolveError;
+use saba_core::resolver::Resolver;
+use saba_core::url::host::Host;
 
-pub struct HttpClient {}
+/// noliのlookup_hostを使う名前解決。IPv4のアドレスだけを返す
+#[derive(Debug, Clone, Default)]
+pub struct WasabiLookup;
 
-impl HttpClient {
-    pub fn new() -> Self {
-        Self {}
+impl NameLookup for WasabiLookup {
+    /// noliのエラーからはタイムアウトを区別できない
+    fn lookup(&self, domain: &str) -> Result<Vec<IpAddress>, ResolveError> {
+        match lookup_host(&domain.to_string()) {
+            Ok(ips) => Ok(ips.iter().map(|ip| IpAddress::V4(ip.bytes())).collect()),
+            Err(e) => Err(ResolveError::Other(format!("{}: {:?}", domain, e))),
+        }
     }
+}
 
-    pub fn get(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
-        let ips = match lookup_host(&host) {
-            Ok(ips) => ips,
-            Err(e) => {
-                return Err(Error::Network(format!(
-                    "Failed to find IP addresses: {:#?}",
-                    e
-                )))
-            }
-        };
+struct TcpConnection {
+    stream: TcpStream,
+}
 
-        if ips.len() < 1 {
-            return Err(Error::Network("Failed to find IP addresses".to_string()));
+impl TcpConnection {
+    /// IPv4のアドレスを順に試し、最初に接続できたものを使う
+    fn connect(addresses: &[IpAddress], port: u16) -> Result<Self, Error> {
+        for address in addresses {
+            if let IpAddress::V4(octets) = address {
+                let socket_addr: SocketAddr = (IpV4Addr::new(*octets), port).into();
+                if let Ok(stream) = TcpStream::connect(socket_addr) {
+                    return Ok(Self { stream });
+                }
+            }
         }
+        Err(Error::Connect(
+            "Failed to connect to TCP stream".to_string(),
+        ))
+    }
+}
 
-        let socket_addr: SocketAddr = (ips[0], port).into();
+impl Connection for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self.stream.write(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to send a request to TCP stream".to_string(),
+            )),
+        }
+    }
 
-        let mut stream = match TcpStream::connect(socket_addr) {
-            Ok(stream) => stream,
-            Err(_) => {
-                return Err(Error::Network(
-                    "Failed to connect to TCP stream".to_string(),
-                ))
-            }
-        };
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.stream.read(buf) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(Error::Network(
+                "Failed to receive a request from TCP stream".to_string(),
+            )),
+        }
+    }
+}
 
-        let mut request = String::from("GET /");
-        request.push_str(&path);
-        request.push_str(" HTTP/1.1\n");
+#[derive(Debug)]
+pub struct HttpClient {
+    // 使い終わった接続は閉じずに、同じホストへの次のリクエストで使う
+    pool: Rc<RefCell<ConnectionPool<TcpConnection>>>,
+    proxy: RefCell<Option<ProxyConfig>>,
+    resolver: Resolver,
+}
 
-        // ヘッダの追加
-        request.push_str("Host: ");
-        request.push_str(&host);
-        request.push('\n');
-        request.push_str("Accept: text/html\n");
-        request.push_str("Connection: close\n");
-        request.push('\n');
+impl HttpClient {
+    pub fn new() -> Self {
+        Self {
+            pool: Rc::new(RefCell::new(ConnectionPool::new())),
+            proxy: RefCell::new(None),
+            // noliからは時刻を取得できないので、時計を渡さない。そのため名前解決の結果は
+            // キャッシュされず、毎回問い合わせる
+            resolver: Resolver::new(Rc::new(WasabiLookup)),
+        }
+    }
 
-        let _bytes_written = match stream.write(request.as_bytes()) {
-            Ok(bytes) => bytes,
-            Err(_) => {
-                return Err(Error::Network(
-                    "Failed to send a request to TCP stream".to_string(),
-                ))
-            }
-        };
+    /// hostsの表などを設定したリゾルバで、接続先のホストとプロキシを解決する
+    pub fn with_resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = resolver;
+        self
+    }
+}
 
-        let mut received = Vec::new();
-        loop {
-            let mut buf = [0u8; 4096];
-            let bytes_read = match stream.read(&mut buf) {
-                Ok(bytes) => bytes,
-                Err(_) => {
-                    return Err(Error::Network(
-                        "Failed to receive a request from TCP stream".to_string(),
-                    ))
-                }
-            };
-            if bytes_read == 0 {
-                break;
-            }
-            received.extend_from_slice(&buf[..bytes_read]);
+impl NetworkClient for HttpClient {
+    /// POSTやHEADなど、任意のメソッドとヘッダのリクエストを送信する
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        let (mut response, mut body) = self.open(request)?;
+        while let Some(bytes) = body.read()? {
+            response.extend_body(&bytes);
         }
+        Ok(response)
+    }
 
-        match core::str::from_utf8(&received) {
-            Ok(response) => HttpResponse::new(response.to_string()),
-            Err(e) => Err(Error::Network(format!("Invalid received response: {}", e))),
+    /// ボディは受信した分ずつ返し、読み終えた接続はプールに戻す
+    fn open(&self, request: &HttpRequest) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let url = request.url();
+        // WasabiOSには暗号学的に安全な乱数の源がなく、TLSの鍵を作れないので、https:は扱えない
+        if url.scheme() == "https" {
+            return Err(Error::Network(format!(
+                "https is not supported on WasabiOS: {}",
+                url.href()
+            )));
+        }
+        if url.scheme() != "http" {
+            return Err(Error::Network(format!(
+                "only http scheme is supported: {}",
+                url.href()
+            )));
         }
+        let proxy = self
+            .proxy
+            .borrow()
+            .clone()
+            .filter(|proxy| !proxy.is_bypassed(&url));
+        let (host, port, bytes) = match proxy {
+            Some(proxy) => (
+                proxy.host().clone(),
+                proxy.port(),
+                proxy.forward_request(request)?,
+            ),
+            None => (
+                url.host_kind().unwrap_or(Host::Empty),
+                url.port(),
+                request.serialize()?,
+            ),
+        };
+
+        let (response, body) = ConnectionPool::open(
+            &self.pool,
+            &host.serialize(),
+            port,
+            &request.method(),
+            &bytes,
+            || {
+                let addresses = self.resolver.resolve_host(&host)?;
+                TcpConnection::connect(&addresses, port)
+            },
+        )?;
+        Ok((response, Box::new(body)))
+    }
+
+    /// 古い設定で張った接続は使わない
+    fn set_proxy(&self, proxy: Option<ProxyConfig>) {
+        *self.proxy.borrow_mut() = proxy;
+        self.pool.borrow_mut().clear();
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,122 @@
+use crate::error::Error;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn decode_char(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// 空白文字を無視し、末尾の"="を省略できるBase64のデコード
+/// https://infra.spec.whatwg.org/#forgiving-base64-decode
+pub fn forgiving_decode(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut data: Vec<u8> = input
+        .iter()
+        .filter(|b| !b.is_ascii_whitespace())
+        .copied()
+        .collect();
+
+    if data.len() % 4 == 0 {
+        if data.ends_with(b"==") {
+            data.truncate(data.len() - 2);
+        } else if data.ends_with(b"=") {
+            data.truncate(data.len() - 1);
+        }
+    }
+
+    if data.len() % 4 == 1 {
+        return Err(Error::UnexpectedInput(format!(
+            "invalid base64 length: {}",
+            data.len()
+        )));
+    }
+
+    let mut output = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in data {
+        let value = match decode_char(c) {
+            Some(v) => v,
+            None => {
+                return Err(Error::UnexpectedInput(format!(
+                    "invalid base64 character: {:?}",
+                    c as char
+                )))
+            }
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(output)
+}
+
+/// 末尾を"="で埋めるBase64のエンコード
+/// https://www.rfc-editor.org/rfc/rfc4648#section-4
+pub fn encode(input: &[u8]) -> String {
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let buffer = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(buffer >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("", encode(b""));
+        assert_eq!("cw==", encode(b"s"));
+        assert_eq!("c2E=", encode(b"sa"));
+        assert_eq!("c2FiYQ==", encode(b"saba"));
+        assert_eq!("c2FiYWJvb2s=", encode(b"sababook"));
+        assert_eq!(
+            Ok(b"\xff\x00\x80".to_vec()),
+            forgiving_decode(encode(b"\xff\x00\x80").as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Ok(b"saba".to_vec()), forgiving_decode(b"c2FiYQ=="));
+        assert_eq!(Ok(b"saba".to_vec()), forgiving_decode(b"c2FiYQ"));
+        assert_eq!(
+            Ok(b"sababook".to_vec()),
+            forgiving_decode(b"c2Fi YWJv\nb2s=")
+        );
+        assert_eq!(Ok(Vec::new()), forgiving_decode(b""));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(forgiving_decode(b"c2FiY").is_err());
+        assert!(forgiving_decode(b"c2F*YQ==").is_err());
+        assert!(forgiving_decode(b"c2FiYQ===").is_err());
+    }
+}
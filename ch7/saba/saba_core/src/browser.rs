@@ -1,5 +1,33 @@
+use crate::download::Download;
+use crate::download::DownloadManager;
+use crate::download::DownloadStorage;
+use crate::download::MemoryStorage;
+use crate::download::ProgressListener;
+use crate::error::Error;
+use crate::http::cache::CacheMode;
+use crate::http::cache::HttpCache;
+use crate::http::cache::DEFAULT_CAPACITY;
+use crate::http::client::BodyReader;
+use crate::http::client::NetworkClient;
+use crate::http::cookie::CookieJar;
+use crate::http::proxy::ProxyConfig;
+use crate::http::settings::Destination;
+use crate::http::settings::RequestSettings;
+use crate::http::HttpResponse;
+use crate::mime::sniff::RESOURCE_HEADER_LENGTH;
+use crate::renderer::error_page::ErrorPage;
+use crate::renderer::page::DocumentKind;
 use crate::renderer::page::Page;
+use crate::scheme::about::AboutHandler;
+use crate::scheme::data::DataHandler;
+use crate::scheme::handler::SchemeHandler;
+use crate::scheme::handler::SchemeRegistry;
+use crate::scheme::http::HttpHandler;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::format;
 use alloc::rc::Rc;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
@@ -7,15 +35,65 @@ use core::cell::RefCell;
 pub struct Browser {
     active_page_index: usize,
     pages: Vec<Rc<RefCell<Page>>>,
+    scheme_registry: SchemeRegistry,
+    /// すべてのページとHTTPリクエストで共有する
+    cookie_jar: Rc<RefCell<CookieJar>>,
+    http_cache: Rc<RefCell<HttpCache>>,
+    request_settings: Rc<RefCell<RequestSettings>>,
+    http_handler: Option<Rc<HttpHandler>>,
+    proxy: Option<ProxyConfig>,
+    /// ページとして表示しないレスポンスを保存する
+    downloads: Rc<RefCell<DownloadManager>>,
 }
 
 impl Browser {
+    /// 時刻を取得できない環境のためのブラウザ。キャッシュしたレスポンスは、使う前に毎回
+    /// 再検証する。時刻を取得できる環境ではwith_clockを使う
     pub fn new() -> Rc<RefCell<Self>> {
+        Self::build(None)
+    }
+
+    /// clockは現在のUNIX時間の秒数を返す。キャッシュしたレスポンスの鮮度と、Cookieの期限の
+    /// 計算に使う
+    pub fn with_clock(clock: Rc<dyn Fn() -> u64>) -> Rc<RefCell<Self>> {
+        Self::build(Some(clock))
+    }
+
+    fn build(clock: Option<Rc<dyn Fn() -> u64>>) -> Rc<RefCell<Self>> {
         let mut page = Page::new();
 
+        let downloads = Rc::new(RefCell::new(DownloadManager::new(Rc::new(
+            MemoryStorage::new(),
+        ))));
+        let mut about = AboutHandler::new();
+        let manager = downloads.clone();
+        about.register_page(
+            "downloads",
+            Rc::new(move || manager.borrow().downloads_page()),
+        );
+
+        let mut scheme_registry = SchemeRegistry::new();
+        scheme_registry.register("about", Rc::new(about));
+        scheme_registry.register("data", Rc::new(DataHandler::new()));
+
+        let (cookie_jar, http_cache) = match clock {
+            Some(clock) => (
+                CookieJar::with_clock(clock.clone()),
+                HttpCache::with_clock(DEFAULT_CAPACITY, clock),
+            ),
+            None => (CookieJar::new(), HttpCache::new()),
+        };
+
         let browser = Rc::new(RefCell::new(Self {
             active_page_index: 0,
             pages: Vec::new(),
+            scheme_registry,
+            cookie_jar: Rc::new(RefCell::new(cookie_jar)),
+            http_cache: Rc::new(RefCell::new(http_cache)),
+            request_settings: Rc::new(RefCell::new(RequestSettings::new())),
+            http_handler: None,
+            proxy: None,
+            downloads,
         }));
 
         page.set_browser(Rc::downgrade(&browser));
@@ -27,4 +105,777 @@ impl Browser {
     pub fn current_page(&self) -> Rc<RefCell<Page>> {
         self.pages[self.active_page_index].clone()
     }
+
+    pub fn cookie_jar(&self) -> Rc<RefCell<CookieJar>> {
+        self.cookie_jar.clone()
+    }
+
+    pub fn http_cache(&self) -> Rc<RefCell<HttpCache>> {
+        self.http_cache.clone()
+    }
+
+    /// http:やfile:など、OSに依存するスキームのハンドラを登録する
+    pub fn register_scheme_handler(&mut self, scheme: &str, handler: Rc<dyn SchemeHandler>) {
+        self.scheme_registry.register(scheme, handler);
+    }
+
+    /// http:とhttps:のリソースを、指定したクライアントを使って取得する。Cookieとキャッシュは
+    /// ブラウザのものを使う
+    pub fn set_network_client(&mut self, client: Rc<dyn NetworkClient>) {
+        client.set_proxy(self.proxy.clone());
+        let handler = Rc::new(HttpHandler::new(
+            client.clone(),
+            self.cookie_jar(),
+            self.http_cache(),
+            self.request_settings.clone(),
+        ));
+        self.scheme_registry.register("http", handler.clone());
+        self.scheme_registry.register("https", handler.clone());
+        self.http_handler = Some(handler);
+    }
+
+    pub fn request_settings(&self) -> RequestSettings {
+        self.request_settings.borrow().clone()
+    }
+
+    /// User-AgentやAccept-Languageなど、HTTPリクエストに付けるヘッダを設定する
+    pub fn set_request_settings(&mut self, settings: RequestSettings) {
+        *self.request_settings.borrow_mut() = settings;
+    }
+
+    pub fn proxy(&self) -> Option<ProxyConfig> {
+        self.proxy.clone()
+    }
+
+    /// すべてのHTTPリクエストで使うプロキシを設定する。後から設定したクライアントにも引き継ぐ
+    pub fn set_proxy(&mut self, proxy: Option<ProxyConfig>) {
+        if let Some(handler) = &self.http_handler {
+            handler.client().set_proxy(proxy.clone());
+        }
+        self.proxy = proxy;
+    }
+
+    /// URLのスキームに対応するハンドラを使って、ページとして表示するリソースを読み込む
+    pub fn load(&self, url: &str) -> Result<HttpResponse, Error> {
+        self.load_with_destination(url, Destination::Document)
+    }
+
+    /// スタイルシートやスクリプトなど、ページから参照されるリソースを読み込む
+    pub fn load_with_destination(
+        &self,
+        url: &str,
+        destination: Destination,
+    ) -> Result<HttpResponse, Error> {
+        let url = match Url::new(url.to_string()).parse() {
+            Ok(url) => url,
+            Err(e) => return Err(Error::UnexpectedInput(format!("invalid url: {}", e))),
+        };
+        self.scheme_registry
+            .load_with_destination(&url, destination)
+    }
+
+    /// ヘッダを受信した時点でレスポンスを返す。ボディは返したBodyReaderから受信した分ずつ読む
+    pub fn open(
+        &self,
+        url: &str,
+        destination: Destination,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let url = match Url::new(url.to_string()).parse() {
+            Ok(url) => url,
+            Err(e) => return Err(Error::UnexpectedInput(format!("invalid url: {}", e))),
+        };
+        self.scheme_registry.open(&url, destination)
+    }
+
+    /// 読み込んだレスポンスを、MIMEタイプに合わせて現在のページに表示する。表示できない
+    /// レスポンスはダウンロードを始める
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#loading-a-document
+    pub fn receive_response(&self, response: HttpResponse) -> DocumentKind {
+        let kind = DocumentKind::for_response(&response);
+        if kind == DocumentKind::Download {
+            self.downloads.borrow_mut().start(response);
+        } else {
+            self.current_page().borrow_mut().receive_response(response);
+        }
+        kind
+    }
+
+    /// receive_responseと同じように表示する。responseのボディには受信済みの部分を入れ、
+    /// 続きはbodyから読む。文書は受信済みの部分だけを表示し、続きはprocess_loadingで、
+    /// ダウンロードの続きはprocess_downloadsで、受信した分ずつ処理する
+    fn begin_response(&self, response: HttpResponse, body: Box<dyn BodyReader>) -> DocumentKind {
+        let kind = DocumentKind::for_response(&response);
+        if kind == DocumentKind::Download {
+            self.downloads.borrow_mut().start_streaming(response, body);
+        } else {
+            self.current_page()
+                .borrow_mut()
+                .begin_response(response, body);
+        }
+        kind
+    }
+
+    /// 読み込み中のページの続きを解析して描画し直す。描画し直した場合はtrueを返す
+    pub fn process_loading(&self) -> bool {
+        self.current_page().borrow_mut().process_loading()
+    }
+
+    /// URLを読み込んで現在のページに表示する。読み込めなかった場合や、サーバーがエラーの
+    /// ステータスコードだけを返した場合は、代わりにエラーページを表示する
+    pub fn navigate(&self, url: &str, mode: CacheMode) -> DocumentKind {
+        let opened = self.with_cache_mode(mode, || self.open(url, Destination::Document));
+        let (mut response, mut body) = match opened {
+            Ok(opened) => opened,
+            Err(e) => return self.receive_response(ErrorPage::for_error(url, &e).response()),
+        };
+
+        // MIMEタイプを判定できるだけのボディを受信してから、表示の仕方を決める
+        // https://mimesniff.spec.whatwg.org/#reading-the-resource-header
+        let mut complete = false;
+        while response.body_len() < RESOURCE_HEADER_LENGTH {
+            match body.read() {
+                Ok(Some(bytes)) => response.extend_body(&bytes),
+                Ok(None) => {
+                    complete = true;
+                    break;
+                }
+                Err(e) => return self.receive_response(ErrorPage::for_error(url, &e).response()),
+            }
+        }
+
+        match ErrorPage::for_response(&response) {
+            Some(error_page) => self.receive_response(error_page.response()),
+            // ボディ全体を受信済みの場合は、すぐに最後まで表示する
+            None if complete => self.receive_response(response),
+            None => self.begin_response(response, body),
+        }
+    }
+
+    /// これから始めるダウンロードを保存するストレージを設定する。デフォルトではメモリ上に保存する
+    pub fn set_download_storage(&mut self, storage: Rc<dyn DownloadStorage>) {
+        self.downloads.borrow_mut().set_storage(storage);
+    }
+
+    /// ダウンロードの状態や進み具合が変わるたびに呼ばれる関数を設定する
+    pub fn set_download_listener(&mut self, listener: ProgressListener) {
+        self.downloads.borrow_mut().set_progress_listener(listener);
+    }
+
+    pub fn downloads(&self) -> Vec<Download> {
+        self.downloads.borrow().downloads()
+    }
+
+    /// 保存中のダウンロードを少しずつ進める。まだ保存中のものが残っている場合はtrueを返す
+    pub fn process_downloads(&self) -> bool {
+        self.downloads.borrow_mut().process()
+    }
+
+    pub fn cancel_download(&self, id: usize) -> bool {
+        self.downloads.borrow_mut().cancel(id)
+    }
+
+    /// 中断したダウンロードを、RangeとIf-Rangeを付けたリクエストで再開する
+    pub fn resume_download(&self, id: usize) -> Result<(), Error> {
+        let handler = match &self.http_handler {
+            Some(handler) => handler.clone(),
+            None => return Err(Error::Network("no network client".to_string())),
+        };
+        self.downloads.borrow_mut().resume(id, |request| {
+            handler.fetch_streaming(
+                request.clone(),
+                Destination::Document,
+                /*use_cache=*/ false,
+            )
+        })
+    }
+
+    /// キャッシュの使い方を指定して読み込む。再読み込みではCacheMode::NoCacheを、
+    /// キャッシュを迂回する再読み込みではCacheMode::Reloadを使う
+    pub fn load_with_cache_mode(&self, url: &str, mode: CacheMode) -> Result<HttpResponse, Error> {
+        self.with_cache_mode(mode, || self.load(url))
+    }
+
+    /// fの間だけキャッシュの使い方を変える
+    fn with_cache_mode<T>(&self, mode: CacheMode, f: impl FnOnce() -> T) -> T {
+        let previous = self.http_cache.borrow().mode();
+        self.http_cache.borrow_mut().set_mode(mode);
+        let result = f();
+        self.http_cache.borrow_mut().set_mode(previous);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_item::DisplayItem;
+    use crate::download::DownloadState;
+    use crate::http::client::MockNetworkClient;
+    use crate::http::Header;
+    use alloc::string::String;
+    use alloc::vec;
+    use core::cell::Cell;
+
+    fn response(status_code: u16, headers: &[(&str, &str)], body: &str) -> HttpResponse {
+        HttpResponse::from_parts(
+            status_code,
+            "",
+            headers
+                .iter()
+                .map(|(name, value)| Header::new(name.to_string(), value.to_string()))
+                .collect(),
+            body.as_bytes().to_vec(),
+        )
+    }
+
+    fn browser_with_mock() -> (Rc<RefCell<Browser>>, Rc<MockNetworkClient>) {
+        let browser = Browser::new();
+        let client = Rc::new(MockNetworkClient::new());
+        browser.borrow_mut().set_network_client(client.clone());
+        (browser, client)
+    }
+
+    /// 読み込まれたときのキャッシュの使い方を記録する
+    #[derive(Debug)]
+    struct CacheModeRecorder {
+        http_cache: Rc<RefCell<HttpCache>>,
+        modes: RefCell<Vec<CacheMode>>,
+    }
+
+    impl SchemeHandler for CacheModeRecorder {
+        fn load(&self, _url: &Url) -> Result<HttpResponse, Error> {
+            self.modes
+                .borrow_mut()
+                .push(self.http_cache.borrow().mode());
+            Ok(HttpResponse::from_parts(200, "OK", Vec::new(), Vec::new()))
+        }
+    }
+
+    #[test]
+    fn test_load_builtin_schemes() {
+        let browser = Browser::new();
+
+        let response = browser
+            .borrow()
+            .load("data:text/html,<p>saba</p>")
+            .expect("failed to load");
+        assert_eq!(b"<p>saba</p>".to_vec(), response.body());
+
+        let response = browser
+            .borrow()
+            .load("about:blank")
+            .expect("failed to load");
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn test_load_unsupported() {
+        let browser = Browser::new();
+        assert!(browser.borrow().load("http://example.com/").is_err());
+        assert!(browser.borrow().load("not a url").is_err());
+    }
+
+    #[test]
+    fn test_load_with_cache_mode() {
+        let browser = Browser::new();
+        let recorder = Rc::new(CacheModeRecorder {
+            http_cache: browser.borrow().http_cache(),
+            modes: RefCell::new(Vec::new()),
+        });
+        browser
+            .borrow_mut()
+            .register_scheme_handler("http", recorder.clone());
+
+        let url = "http://example.com/";
+        browser.borrow().load(url).expect("failed to load");
+        browser
+            .borrow()
+            .load_with_cache_mode(url, CacheMode::Reload)
+            .expect("failed to load");
+        browser.borrow().load(url).expect("failed to load");
+        assert_eq!(
+            vec![CacheMode::Default, CacheMode::Reload, CacheMode::Default],
+            *recorder.modes.borrow()
+        );
+    }
+
+    #[test]
+    fn test_navigate_with_network_client() {
+        let (browser, client) = browser_with_mock();
+        client.add_response(
+            "http://example.com/index.html",
+            response(200, &[("Content-Type", "text/html")], "<p>hello</p>"),
+        );
+
+        let response = browser
+            .borrow()
+            .load("http://example.com/index.html")
+            .expect("failed to load");
+        let page = browser.borrow().current_page();
+        page.borrow_mut().receive_response(response);
+
+        assert_eq!(
+            Some("http://example.com/index.html".to_string()),
+            page.borrow().url().map(|url| url.href())
+        );
+        let texts: Vec<String> = page
+            .borrow()
+            .display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec!["hello".to_string()], texts);
+
+        let requests = client.requests();
+        assert_eq!(1, requests.len());
+        let settings = RequestSettings::new();
+        assert_eq!(
+            Some(settings.accept(Destination::Document).to_string()),
+            requests[0].header_value("Accept")
+        );
+        assert_eq!(
+            Some(settings.user_agent().to_string()),
+            requests[0].header_value("User-Agent")
+        );
+    }
+
+    #[test]
+    fn test_request_settings() {
+        let (browser, client) = browser_with_mock();
+        browser.borrow_mut().set_request_settings(
+            RequestSettings::new()
+                .with_user_agent("saba-test")
+                .with_accept_languages(&["en", "ja"]),
+        );
+        client.add_response("http://example.com/style.css", response(200, &[], ""));
+
+        browser
+            .borrow()
+            .load_with_destination("http://example.com/style.css", Destination::Style)
+            .expect("failed to load");
+        let requests = client.requests();
+        assert_eq!(
+            Some("text/css,*/*;q=0.1".to_string()),
+            requests[0].header_value("Accept")
+        );
+        assert_eq!(
+            Some("saba-test".to_string()),
+            requests[0].header_value("User-Agent")
+        );
+        assert_eq!(
+            Some("en,ja;q=0.9".to_string()),
+            requests[0].header_value("Accept-Language")
+        );
+    }
+
+    #[test]
+    fn test_redirect_with_cookies() {
+        let (browser, client) = browser_with_mock();
+        client.add_response(
+            "http://example.com/login",
+            response(
+                302,
+                &[
+                    ("Location", "/home"),
+                    ("Set-Cookie", "session=saba; Path=/"),
+                ],
+                "",
+            ),
+        );
+        client.add_response("http://example.com/home", response(200, &[], "home"));
+
+        let response = browser
+            .borrow()
+            .load("http://example.com/login")
+            .expect("failed to load");
+        assert_eq!(b"home".to_vec(), response.body());
+        assert_eq!(
+            Some("http://example.com/home".to_string()),
+            response.url().map(|url| url.href())
+        );
+
+        // リダイレクトのレスポンスで受け取ったCookieを、転送先へのリクエストに付ける
+        let requests = client.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!(None, requests[0].header_value("Cookie"));
+        assert_eq!(
+            Some("session=saba".to_string()),
+            requests[1].header_value("Cookie")
+        );
+    }
+
+    #[test]
+    fn test_delete_cookie_with_past_expires() {
+        // 2023-11-14T22:13:20Z
+        let browser = Browser::with_clock(Rc::new(|| 1_700_000_000));
+        let client = Rc::new(MockNetworkClient::new());
+        browser.borrow_mut().set_network_client(client.clone());
+        client.add_response(
+            "http://example.com/login",
+            response(200, &[("Set-Cookie", "sid=abc; Max-Age=3600")], ""),
+        );
+        client.add_response(
+            "http://example.com/logout",
+            response(
+                200,
+                &[("Set-Cookie", "sid=; Expires=Thu, 01 Jan 2015 00:00:00 GMT")],
+                "",
+            ),
+        );
+        client.add_response("http://example.com/", response(200, &[], ""));
+
+        for url in [
+            "http://example.com/login",
+            "http://example.com/",
+            "http://example.com/logout",
+            "http://example.com/",
+        ] {
+            browser.borrow().load(url).expect("failed to load");
+        }
+
+        let requests = client.requests();
+        assert_eq!(
+            Some("sid=abc".to_string()),
+            requests[1].header_value("Cookie")
+        );
+        // 過去のExpiresで上書きされたCookieは削除される
+        assert_eq!(None, requests[3].header_value("Cookie"));
+        assert!(browser.borrow().cookie_jar().borrow().cookies().is_empty());
+    }
+
+    #[test]
+    fn test_cache_with_network_client() {
+        let browser = Browser::with_clock(Rc::new(|| 1_700_000_000));
+        let client = Rc::new(MockNetworkClient::new());
+        browser.borrow_mut().set_network_client(client.clone());
+        client.add_response(
+            "http://example.com/",
+            response(200, &[("Cache-Control", "max-age=60")], "cached"),
+        );
+
+        let url = "http://example.com/";
+        browser.borrow().load(url).expect("failed to load");
+        let cached = browser.borrow().load(url).expect("failed to load");
+        assert_eq!(b"cached".to_vec(), cached.body());
+        assert_eq!(1, client.requests().len());
+
+        browser
+            .borrow()
+            .load_with_cache_mode(url, CacheMode::Reload)
+            .expect("failed to load");
+        assert_eq!(2, client.requests().len());
+
+        // 時計がない場合は、新鮮かどうかを判断できないので取得し直す
+        let (browser, client) = browser_with_mock();
+        client.add_response(
+            "http://example.com/",
+            response(200, &[("Cache-Control", "max-age=60")], "cached"),
+        );
+        browser.borrow().load(url).expect("failed to load");
+        browser.borrow().load(url).expect("failed to load");
+        assert_eq!(2, client.requests().len());
+    }
+
+    #[test]
+    fn test_cache_expires_with_clock() {
+        let now = Rc::new(Cell::new(1_700_000_000));
+        let clock = now.clone();
+        let browser = Browser::with_clock(Rc::new(move || clock.get()));
+        let client = Rc::new(MockNetworkClient::new());
+        browser.borrow_mut().set_network_client(client.clone());
+        client.add_response(
+            "http://example.com/",
+            response(200, &[("Cache-Control", "max-age=5")], "cached"),
+        );
+
+        let url = "http://example.com/";
+        browser.borrow().load(url).expect("failed to load");
+        now.set(now.get() + 4);
+        browser.borrow().load(url).expect("failed to load");
+        assert_eq!(1, client.requests().len());
+
+        // max-ageを過ぎたら、もう一度サーバーから取得する
+        now.set(now.get() + 2);
+        browser.borrow().load(url).expect("failed to load");
+        assert_eq!(2, client.requests().len());
+    }
+
+    #[test]
+    fn test_load_https() {
+        let (browser, client) = browser_with_mock();
+        client.add_response(
+            "https://example.com/",
+            response(200, &[("Content-Type", "text/html")], "<p>secure</p>"),
+        );
+        let response = browser
+            .borrow()
+            .load("https://example.com/")
+            .expect("failed to load");
+        assert_eq!(b"<p>secure</p>".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_set_proxy() {
+        let proxy = ProxyConfig::parse("http://proxy.example:3128").expect("invalid proxy");
+        let (browser, client) = browser_with_mock();
+        browser.borrow_mut().set_proxy(Some(proxy.clone()));
+        assert_eq!(Some(proxy.clone()), client.proxy());
+
+        // 後から設定したクライアントにも使われる
+        let other = Rc::new(MockNetworkClient::new());
+        browser.borrow_mut().set_network_client(other.clone());
+        assert_eq!(Some(proxy), other.proxy());
+
+        browser.borrow_mut().set_proxy(None);
+        assert_eq!(None, other.proxy());
+    }
+
+    #[test]
+    fn test_network_error() {
+        let (browser, _client) = browser_with_mock();
+        assert!(browser.borrow().load("http://example.com/").is_err());
+    }
+
+    #[test]
+    fn test_receive_response() {
+        let (browser, client) = browser_with_mock();
+        client.add_response(
+            "http://example.com/a.txt",
+            response(200, &[("Content-Type", "text/plain")], "<p>text</p>"),
+        );
+        client.add_response(
+            "http://example.com/a.bin",
+            response(200, &[("Content-Type", "application/octet-stream")], "bin"),
+        );
+
+        let response = browser
+            .borrow()
+            .load("http://example.com/a.txt")
+            .expect("failed to load");
+        assert_eq!(
+            DocumentKind::Text,
+            browser.borrow().receive_response(response)
+        );
+        assert!(browser.borrow().downloads().is_empty());
+
+        let response = browser
+            .borrow()
+            .load("http://example.com/a.bin")
+            .expect("failed to load");
+        assert_eq!(
+            DocumentKind::Download,
+            browser.borrow().receive_response(response)
+        );
+        let downloads = browser.borrow().downloads();
+        assert_eq!(1, downloads.len());
+        assert_eq!("a.bin".to_string(), downloads[0].file_name());
+
+        // ダウンロードの場合は、表示中のページを変更しない
+        assert_eq!(
+            Some("http://example.com/a.txt".to_string()),
+            browser
+                .borrow()
+                .current_page()
+                .borrow()
+                .url()
+                .map(|url| url.href())
+        );
+    }
+
+    #[test]
+    fn test_navigate_download_while_receiving() {
+        let (browser, client) = browser_with_mock();
+        let storage = Rc::new(MemoryStorage::new());
+        browser.borrow_mut().set_download_storage(storage.clone());
+        let body = "z".repeat(10000);
+        client.add_response(
+            "http://example.com/a.zip",
+            response(
+                200,
+                &[
+                    ("Content-Type", "application/zip"),
+                    ("Content-Length", "10000"),
+                ],
+                &body,
+            ),
+        );
+        client.set_chunk_size(2000);
+
+        // MIMEタイプを判定できるだけ受信した時点でダウンロードを始め、続きは受信した分ずつ保存する
+        let kind = browser
+            .borrow()
+            .navigate("http://example.com/a.zip", CacheMode::Default);
+        assert_eq!(DocumentKind::Download, kind);
+        let mut received = Vec::new();
+        while browser.borrow().process_downloads() {
+            received.push(browser.borrow().downloads()[0].received());
+        }
+        assert_eq!(vec![4000, 6000, 8000, 10000], received);
+        let download = browser.borrow().downloads()[0].clone();
+        assert_eq!(Some(10000), download.total());
+        assert_eq!(DownloadState::Completed, download.state());
+        assert_eq!(Some(body.into_bytes()), storage.read("a.zip"));
+    }
+
+    #[test]
+    fn test_download() {
+        let (browser, client) = browser_with_mock();
+        let storage = Rc::new(MemoryStorage::new());
+        browser.borrow_mut().set_download_storage(storage.clone());
+        let body = "z".repeat(40 * 1024);
+        client.add_response(
+            "http://example.com/a.zip",
+            response(
+                200,
+                &[
+                    ("Content-Type", "application/zip"),
+                    ("Content-Disposition", "attachment; filename=saba.zip"),
+                    ("ETag", "\"v1\""),
+                ],
+                &body,
+            ),
+        );
+
+        let zip = browser
+            .borrow()
+            .load("http://example.com/a.zip")
+            .expect("failed to load");
+        browser.borrow().receive_response(zip);
+        let id = browser.borrow().downloads()[0].id();
+        assert!(browser.borrow().process_downloads());
+        assert!(browser.borrow().cancel_download(id));
+
+        let page = browser
+            .borrow()
+            .load("about:downloads")
+            .expect("failed to load")
+            .text();
+        assert!(page.contains("saba.zip: cancelled, 16384/40960 bytes"));
+
+        // 保存済みの続きから再開する
+        client.add_response(
+            "http://example.com/a.zip",
+            response(
+                206,
+                &[("Content-Range", "bytes 16384-40959/40960")],
+                &body[16384..],
+            ),
+        );
+        browser
+            .borrow()
+            .resume_download(id)
+            .expect("failed to resume");
+        let request = client.requests().pop().expect("no request");
+        assert_eq!(
+            Some("bytes=16384-".to_string()),
+            request.header_value("Range")
+        );
+        assert_eq!(Some("\"v1\"".to_string()), request.header_value("If-Range"));
+
+        while browser.borrow().process_downloads() {}
+        assert_eq!(Some(body.into_bytes()), storage.read("saba.zip"));
+        let page = browser
+            .borrow()
+            .load("about:downloads")
+            .expect("failed to load")
+            .text();
+        assert!(page.contains("saba.zip: completed, 40960/40960 bytes"));
+    }
+
+    fn page_texts(browser: &Rc<RefCell<Browser>>) -> Vec<String> {
+        browser
+            .borrow()
+            .current_page()
+            .borrow()
+            .display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_navigate_while_receiving() {
+        let (browser, client) = browser_with_mock();
+        let html = format!(
+            "<html><body><p>first</p>{}<p>last</p></body></html>",
+            "<p>日本語です</p>".repeat(100)
+        );
+        client.add_response(
+            "http://example.com/",
+            response(200, &[("Content-Type", "text/html; charset=utf-8")], &html),
+        );
+        client.set_chunk_size(500);
+
+        // MIMEタイプを判定できるだけ受信した時点で、受信済みの部分を表示する
+        let kind = browser
+            .borrow()
+            .navigate("http://example.com/", CacheMode::Default);
+        assert_eq!(DocumentKind::Html, kind);
+        assert!(browser.borrow().current_page().borrow().is_loading());
+        let texts = page_texts(&browser);
+        assert_eq!(Some(&"first".to_string()), texts.first());
+        assert!(!texts.contains(&"last".to_string()));
+
+        while browser.borrow().process_loading() {}
+        let texts = page_texts(&browser);
+        assert_eq!(102, texts.len());
+        assert_eq!(Some(&"last".to_string()), texts.last());
+    }
+
+    #[test]
+    fn test_navigate_error_page() {
+        let (browser, client) = browser_with_mock();
+        client.add_response("http://example.com/missing", response(404, &[], ""));
+        client.add_response(
+            "http://example.com/custom",
+            response(404, &[("Content-Type", "text/html")], "<p>custom 404</p>"),
+        );
+
+        // 接続できない場合は、エラーページを表示してURLを残す
+        let kind = browser
+            .borrow()
+            .navigate("http://example.com/", CacheMode::Default);
+        assert_eq!(DocumentKind::Html, kind);
+        let texts = page_texts(&browser);
+        assert!(
+            texts.contains(&"Error: Protocol".to_string()),
+            "{:?}",
+            texts
+        );
+        assert!(texts.contains(&"Retry".to_string()), "{:?}", texts);
+        assert_eq!(
+            Some("http://example.com/".to_string()),
+            browser
+                .borrow()
+                .current_page()
+                .borrow()
+                .url()
+                .map(|url| url.href())
+        );
+
+        browser
+            .borrow()
+            .navigate("http://example.com/missing", CacheMode::Default);
+        assert!(page_texts(&browser).contains(&"Error: HTTP 404".to_string()));
+
+        // サーバーが用意したエラーのページはそのまま表示する
+        browser
+            .borrow()
+            .navigate("http://example.com/custom", CacheMode::Default);
+        assert_eq!(vec!["custom 404".to_string()], page_texts(&browser));
+
+        browser
+            .borrow()
+            .navigate("http://example.com:port/<p>", CacheMode::Default);
+        let texts = page_texts(&browser);
+        assert!(texts.contains(&"Error: URL".to_string()));
+        // URLの中の"<"や">"は、そのままの文字で表示する
+        assert!(texts.contains(&"http://example.com:port/<p>".to_string()));
+    }
 }
@@ -0,0 +1,112 @@
+/// https://www.rfc-editor.org/rfc/rfc1952#section-8
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+/// gzipで使うCRC-32。少しずつ入力を与えられる
+/// https://www.rfc-editor.org/rfc/rfc1952#section-8
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    value: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { value: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.value =
+                CRC32_TABLE[((self.value ^ *b as u32) & 0xFF) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        !self.value
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// zlibで使うAdler-32。少しずつ入力を与えられる
+/// https://www.rfc-editor.org/rfc/rfc1950#section-9
+#[derive(Debug, Clone, Copy)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+const ADLER32_MODULUS: u32 = 65521;
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        // 5552バイトまではu32があふれないので、まとめてから剰余を取る
+        for chunk in bytes.chunks(5552) {
+            for b in chunk {
+                self.a += *b as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER32_MODULUS;
+            self.b %= ADLER32_MODULUS;
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        let mut crc = Crc32::new();
+        assert_eq!(0, crc.value());
+        crc.update(b"123456789");
+        assert_eq!(0xCBF4_3926, crc.value());
+    }
+
+    #[test]
+    fn test_adler32() {
+        let mut adler = Adler32::new();
+        assert_eq!(1, adler.value());
+        adler.update(b"Wiki");
+        adler.update(b"pedia");
+        assert_eq!(0x11E6_0398, adler.value());
+    }
+}
@@ -0,0 +1,557 @@
+use crate::compression::Decoder;
+use crate::error::Error;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 後方参照できる距離の最大値
+/// https://www.rfc-editor.org/rfc/rfc1951#section-2
+const WINDOW_SIZE: usize = 32 * 1024;
+
+const MAX_CODE_LENGTH: usize = 15;
+
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.5
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// 符号長の符号長が並ぶ順番
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.7
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(message: &str) -> Error {
+    Error::Network(format!("invalid deflate stream: {}", message))
+}
+
+/// 符号長から作るカノニカルハフマン符号
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.2
+#[derive(Debug, Clone)]
+struct Huffman {
+    /// 符号長ごとの符号の数
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    /// 符号の順に並べたシンボル
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+
+        // 割り当てられる符号より多くの符号がある場合は不正
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_CODE_LENGTH + 1] as usize];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.6
+    fn fixed() -> (Self, Self) {
+        let mut lengths = [0u8; 288];
+        for (symbol, length) in lengths.iter_mut().enumerate() {
+            *length = match symbol {
+                0..=143 => 8,
+                144..=255 => 9,
+                256..=279 => 7,
+                _ => 8,
+            };
+        }
+        let literal = Self::new(&lengths).expect("fixed literal code is valid");
+        let distance = Self::new(&[5; 30]).expect("fixed distance code is valid");
+        (literal, distance)
+    }
+}
+
+/// 入力をビット単位で読む。入力が足りない場合はNoneを返すので、呼び出し側は読み始めた
+/// 位置に戻ってから次の入力を待つ
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.1.1
+#[derive(Debug, Clone)]
+struct BitReader {
+    bytes: Vec<u8>,
+    /// 次に読むビットの位置
+    position: usize,
+}
+
+impl BitReader {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        // 読み終わったバイトを捨ててから追加する
+        let consumed = self.position / 8;
+        self.bytes.drain(..consumed);
+        self.position -= consumed * 8;
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn bits(&mut self, n: usize) -> Option<u32> {
+        if self.position + n > self.bytes.len() * 8 {
+            return None;
+        }
+        let mut value = 0;
+        for i in 0..n {
+            let p = self.position + i;
+            let bit = (self.bytes[p / 8] >> (p % 8)) & 1;
+            value |= (bit as u32) << i;
+        }
+        self.position += n;
+        Some(value)
+    }
+
+    /// ハフマン符号は最上位ビットから詰められている
+    fn decode(&mut self, huffman: &Huffman) -> Result<Option<u16>, Error> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_CODE_LENGTH {
+            code |= match self.bits(1) {
+                Some(bit) => bit as i32,
+                None => return Ok(None),
+            };
+            let count = huffman.counts[length] as i32;
+            if code - count < first {
+                return Ok(Some(huffman.symbols[(index + code - first) as usize]));
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(invalid("unknown huffman code"))
+    }
+
+    fn align_to_byte(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+
+    /// バイト境界に揃っているときに、読める残りのバイト列
+    fn remaining_bytes(&self) -> &[u8] {
+        &self.bytes[self.position.div_ceil(8)..]
+    }
+
+    fn skip_bytes(&mut self, n: usize) {
+        self.position += n * 8;
+    }
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    BlockHeader,
+    Stored { remaining: usize },
+    Huffman { literal: Huffman, distance: Huffman },
+    Done,
+}
+
+enum Step {
+    Continue,
+    NeedInput,
+    EndOfBlock,
+}
+
+/// 入力が足りない場合は、このステップを中断して次の入力を待つ
+macro_rules! need {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None => return Ok(Step::NeedInput),
+        }
+    };
+}
+
+/// 生のdeflateストリームを展開する。入力を少しずつ与えられ、展開したバイト列はすぐに
+/// 出力するので、保持するのは後方参照のための直近32KiBだけになる
+/// https://www.rfc-editor.org/rfc/rfc1951
+#[derive(Debug, Clone)]
+pub struct Inflater {
+    reader: BitReader,
+    state: State,
+    final_block: bool,
+    /// 直近に出力したバイト列
+    window: Vec<u8>,
+}
+
+impl Inflater {
+    pub fn new() -> Self {
+        Self {
+            reader: BitReader::new(),
+            state: State::BlockHeader,
+            final_block: false,
+            window: Vec::new(),
+        }
+    }
+
+    /// 最後のブロックまで展開し終えたか
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// deflateストリームの後ろに続いていた入力を取り出す。gzipやzlibのトレーラーに使う
+    pub fn take_remaining(&mut self) -> Vec<u8> {
+        let remaining = self.reader.remaining_bytes().to_vec();
+        self.reader = BitReader::new();
+        remaining
+    }
+
+    fn emit(&mut self, bytes: &[u8], output: &mut Vec<u8>) {
+        output.extend_from_slice(bytes);
+        self.window.extend_from_slice(bytes);
+        if self.window.len() > 2 * WINDOW_SIZE {
+            self.window.drain(..self.window.len() - WINDOW_SIZE);
+        }
+    }
+
+    fn end_block(&mut self) {
+        self.state = if self.final_block {
+            State::Done
+        } else {
+            State::BlockHeader
+        };
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.3
+    fn read_block_header(&mut self) -> Result<Step, Error> {
+        let final_block = need!(self.reader.bits(1)) == 1;
+        let state = match need!(self.reader.bits(2)) {
+            0 => {
+                // https://www.rfc-editor.org/rfc/rfc1951#section-3.2.4
+                self.reader.align_to_byte();
+                let length = need!(self.reader.bits(16));
+                let complement = need!(self.reader.bits(16));
+                if length != !complement & 0xFFFF {
+                    return Err(invalid("stored block length does not match its complement"));
+                }
+                State::Stored {
+                    remaining: length as usize,
+                }
+            }
+            1 => {
+                let (literal, distance) = Huffman::fixed();
+                State::Huffman { literal, distance }
+            }
+            2 => match self.read_dynamic_tables()? {
+                Some((literal, distance)) => State::Huffman { literal, distance },
+                None => return Ok(Step::NeedInput),
+            },
+            _ => return Err(invalid("reserved block type")),
+        };
+        self.final_block = final_block;
+        self.state = state;
+        Ok(Step::Continue)
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.7
+    fn read_dynamic_tables(&mut self) -> Result<Option<(Huffman, Huffman)>, Error> {
+        macro_rules! need {
+            ($e:expr) => {
+                match $e {
+                    Some(v) => v,
+                    None => return Ok(None),
+                }
+            };
+        }
+
+        let literal_count = need!(self.reader.bits(5)) as usize + 257;
+        let distance_count = need!(self.reader.bits(5)) as usize + 1;
+        let code_length_count = need!(self.reader.bits(4)) as usize + 4;
+        if literal_count > 286 || distance_count > 30 {
+            return Err(invalid("too many length or distance codes"));
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+            code_lengths[*i] = need!(self.reader.bits(3)) as u8;
+        }
+        let code_length_huffman = Huffman::new(&code_lengths)?;
+
+        let mut lengths = Vec::with_capacity(literal_count + distance_count);
+        while lengths.len() < literal_count + distance_count {
+            let symbol = need!(self.reader.decode(&code_length_huffman)?);
+            let (length, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => match lengths.last() {
+                    Some(previous) => (*previous, 3 + need!(self.reader.bits(2)) as usize),
+                    None => return Err(invalid("repeat with no previous length")),
+                },
+                17 => (0, 3 + need!(self.reader.bits(3)) as usize),
+                _ => (0, 11 + need!(self.reader.bits(7)) as usize),
+            };
+            if lengths.len() + repeat > literal_count + distance_count {
+                return Err(invalid("too many code lengths"));
+            }
+            lengths.extend(core::iter::repeat(length).take(repeat));
+        }
+
+        if lengths[256] == 0 {
+            return Err(invalid("missing end-of-block code"));
+        }
+        let literal = Huffman::new(&lengths[..literal_count])?;
+        let distance = Huffman::new(&lengths[literal_count..])?;
+        Ok(Some((literal, distance)))
+    }
+
+    fn read_stored(&mut self, remaining: usize, output: &mut Vec<u8>) -> Result<Step, Error> {
+        if remaining == 0 {
+            self.end_block();
+            return Ok(Step::Continue);
+        }
+        let available = self.reader.remaining_bytes();
+        let n = remaining.min(available.len());
+        if n == 0 {
+            return Ok(Step::NeedInput);
+        }
+        let bytes = available[..n].to_vec();
+        self.reader.skip_bytes(n);
+        self.emit(&bytes, output);
+        self.state = State::Stored {
+            remaining: remaining - n,
+        };
+        Ok(Step::Continue)
+    }
+
+    /// リテラルを1つ、または長さと距離の組を1つ読む
+    /// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.5
+    fn read_symbol(
+        &mut self,
+        literal: &Huffman,
+        distance: &Huffman,
+        output: &mut Vec<u8>,
+    ) -> Result<Step, Error> {
+        let symbol = need!(self.reader.decode(literal)?) as usize;
+        if symbol < 256 {
+            self.emit(&[symbol as u8], output);
+            return Ok(Step::Continue);
+        }
+        if symbol == 256 {
+            return Ok(Step::EndOfBlock);
+        }
+
+        let index = symbol - 257;
+        if index >= LENGTH_BASE.len() {
+            return Err(invalid("invalid length code"));
+        }
+        let length = LENGTH_BASE[index] as usize
+            + need!(self.reader.bits(LENGTH_EXTRA[index] as usize)) as usize;
+
+        let index = need!(self.reader.decode(distance)?) as usize;
+        if index >= DISTANCE_BASE.len() {
+            return Err(invalid("invalid distance code"));
+        }
+        let back = DISTANCE_BASE[index] as usize
+            + need!(self.reader.bits(DISTANCE_EXTRA[index] as usize)) as usize;
+        if back > self.window.len() {
+            return Err(invalid("distance too far back"));
+        }
+
+        // 長さが距離より長い場合は、コピーしたバイトをさらにコピーする
+        let mut bytes = Vec::with_capacity(length);
+        let start = self.window.len() - back;
+        for i in 0..length {
+            let b = if i < back {
+                self.window[start + i]
+            } else {
+                bytes[i - back]
+            };
+            bytes.push(b);
+        }
+        self.emit(&bytes, output);
+        Ok(Step::Continue)
+    }
+
+    fn step(&mut self, output: &mut Vec<u8>) -> Result<Step, Error> {
+        match &self.state {
+            State::BlockHeader => self.read_block_header(),
+            State::Stored { remaining } => self.read_stored(*remaining, output),
+            State::Huffman { .. } => {
+                // 符号表を読みながら出力するので、借用を外すために状態を一時的に取り出す
+                let state = core::mem::replace(&mut self.state, State::BlockHeader);
+                let step = match &state {
+                    State::Huffman { literal, distance } => {
+                        self.read_symbol(literal, distance, output)
+                    }
+                    _ => unreachable!(),
+                };
+                self.state = state;
+                match step? {
+                    Step::EndOfBlock => {
+                        self.end_block();
+                        Ok(Step::Continue)
+                    }
+                    step => Ok(step),
+                }
+            }
+            State::Done => Ok(Step::NeedInput),
+        }
+    }
+}
+
+impl Default for Inflater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for Inflater {
+    fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        self.reader.push(input);
+        while !self.is_done() {
+            let checkpoint = self.reader.position;
+            match self.step(output)? {
+                Step::Continue | Step::EndOfBlock => {}
+                Step::NeedInput => {
+                    // 読みかけのビットは、次の入力が届いてから読み直す
+                    self.reader.position = checkpoint;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> Result<(), Error> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err(Error::Network(
+                "deflate stream ended before the final block".to_string(),
+            ))
+        }
+    }
+}
+
+/// 生のdeflateストリームをまとめて展開する
+pub fn inflate(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut inflater = Inflater::new();
+    let mut output = Vec::new();
+    inflater.push(input, &mut output)?;
+    inflater.finish(&mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::decode_in_pieces;
+    use crate::compression::from_hex;
+
+    fn decode(hex: &str) -> Result<Vec<u8>, Error> {
+        decode_in_pieces(Inflater::new, &from_hex(hex))
+    }
+
+    fn html() -> Vec<u8> {
+        let mut html = b"<html><body>".to_vec();
+        for i in 0..40 {
+            html.extend_from_slice(format!("<p>saba {}</p>", i).as_bytes());
+        }
+        html.extend_from_slice(b"</body></html>");
+        html
+    }
+
+    #[test]
+    fn test_stored_block() {
+        assert_eq!(
+            b"hello, saba!".to_vec(),
+            decode("010c00f3ff68656c6c6f2c207361626121").expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_fixed_huffman() {
+        assert_eq!(
+            b"hello, saba! hello, saba!".to_vec(),
+            decode("cb48cdc9c9d751284e4c4a5454c840e20000").expect("failed to decode")
+        );
+        assert_eq!(
+            html(),
+            decode("b3c928c9cdb1b349ca4fa9b4b329b02b4e4c4a5430b0d12f80730c913946c81c63648e0932c714996386cc3147e65820732c512c4575028a1b0c511c6188e20a43146718a2b8c310c52186282e3144718a218a5b8c50dc62841a1e286e3142718b118a5b8c50dc6284e2162314b718a1b8c508c52dc6286e3146718b316ae4a0b8c518c52dc6286e3146718b318a5b8c51dc620c718b3e24b1e883530e00")
+                .expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_dynamic_huffman() {
+        assert_eq!(
+            html(),
+            decode("55d24d0a02410c44e1ab7883b6aafc85a6ef32c32c5c280ee8c6db0b0e48def2adf291a4dfde8ffbe8f373f98cbe8ed7344fbb7d6feb3f54c33552e350e358e354e35ce352e38aa124c020200485c0101c0244900814c16258cc7dc062580c8b61312c86c5b01896c01258c2e3c01258024b60092c81259ba56dcfd27e9ff305")
+                .expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_long_stored_stream() {
+        // 32KiBを超える出力を後方参照しても、窓から正しく読めることを確かめる
+        let mut input = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..3u8 {
+            let block = vec![b'a' + i; 20000];
+            input.push(0);
+            input.extend_from_slice(&(20000u16).to_le_bytes());
+            input.extend_from_slice(&(!20000u16).to_le_bytes());
+            input.extend_from_slice(&block);
+            expected.extend_from_slice(&block);
+        }
+        // 最後のブロックは固定ハフマン符号で、距離32768から長さ258をコピーする
+        input.extend_from_slice(&from_hex("1bbdff1f00"));
+        let start = expected.len() - 32768;
+        let copied = expected[start..start + 258].to_vec();
+        expected.extend_from_slice(&copied);
+        assert_eq!(expected, inflate(&input).expect("failed to decode"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let cases = [
+            // 予約されたブロックタイプ
+            "07",
+            // 長さと補数が合わない
+            "010c00f3fe68656c6c6f2c207361626121",
+            // 出力より前を参照する
+            "030200",
+            // 最後のブロックで終わっていない
+            "cb48cdc9c9d751284e4c4a",
+            "",
+        ];
+        for hex in cases {
+            assert!(decode(hex).is_err(), "{}", hex);
+        }
+    }
+}
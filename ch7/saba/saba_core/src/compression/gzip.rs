@@ -0,0 +1,254 @@
+use crate::compression::checksum::Crc32;
+use crate::compression::deflate::Inflater;
+use crate::compression::Decoder;
+use crate::error::Error;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+const MAGIC: [u8; 2] = [0x1F, 0x8B];
+const METHOD_DEFLATE: u8 = 8;
+
+/// https://www.rfc-editor.org/rfc/rfc1952#section-2.3.1
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+const FLAG_RESERVED: u8 = 0xE0;
+
+fn invalid(message: &str) -> Error {
+    Error::Network(format!("invalid gzip stream: {}", message))
+}
+
+/// ヘッダの長さを返す。まだ揃っていなければNone
+/// https://www.rfc-editor.org/rfc/rfc1952#section-2.3
+fn parse_header(bytes: &[u8]) -> Result<Option<usize>, Error> {
+    if !MAGIC.starts_with(&bytes[..bytes.len().min(2)]) {
+        return Err(invalid("wrong magic number"));
+    }
+    if bytes.len() < 10 {
+        return Ok(None);
+    }
+    if bytes[2] != METHOD_DEFLATE {
+        return Err(invalid("unknown compression method"));
+    }
+    let flags = bytes[3];
+    if flags & FLAG_RESERVED != 0 {
+        return Err(invalid("reserved flags are set"));
+    }
+
+    let mut position = 10;
+    if flags & FLAG_EXTRA != 0 {
+        if bytes.len() < position + 2 {
+            return Ok(None);
+        }
+        let length = u16::from_le_bytes([bytes[position], bytes[position + 1]]) as usize;
+        position += 2 + length;
+    }
+    // ファイル名とコメントは0で終わる
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            match bytes
+                .get(position..)
+                .and_then(|b| b.iter().position(|b| *b == 0))
+            {
+                Some(end) => position += end + 1,
+                None => return Ok(None),
+            }
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        if bytes.len() < position + 2 {
+            return Ok(None);
+        }
+        let mut crc = Crc32::new();
+        crc.update(&bytes[..position]);
+        let expected = u16::from_le_bytes([bytes[position], bytes[position + 1]]);
+        if crc.value() as u16 != expected {
+            return Err(invalid("header checksum mismatch"));
+        }
+        position += 2;
+    }
+
+    if bytes.len() < position {
+        return Ok(None);
+    }
+    Ok(Some(position))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    Body,
+    Trailer,
+    Done,
+}
+
+/// gzip形式を展開する。複数のメンバーが連結されている場合は、続けて展開する
+/// https://www.rfc-editor.org/rfc/rfc1952
+#[derive(Debug, Clone)]
+pub struct GzipDecoder {
+    state: State,
+    /// ヘッダとトレーラーを読むために溜めておくバイト列
+    pending: Vec<u8>,
+    inflater: Inflater,
+    crc: Crc32,
+    /// 展開したバイト数の下位32ビット
+    size: u32,
+}
+
+impl GzipDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Header,
+            pending: Vec::new(),
+            inflater: Inflater::new(),
+            crc: Crc32::new(),
+            size: 0,
+        }
+    }
+}
+
+impl Default for GzipDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for GzipDecoder {
+    fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        self.pending.extend_from_slice(input);
+        loop {
+            match self.state {
+                State::Header => match parse_header(&self.pending)? {
+                    Some(length) => {
+                        self.pending.drain(..length);
+                        self.inflater = Inflater::new();
+                        self.crc = Crc32::new();
+                        self.size = 0;
+                        self.state = State::Body;
+                    }
+                    None => return Ok(()),
+                },
+                State::Body => {
+                    let data = core::mem::take(&mut self.pending);
+                    let start = output.len();
+                    self.inflater.push(&data, output)?;
+                    self.crc.update(&output[start..]);
+                    self.size = self.size.wrapping_add((output.len() - start) as u32);
+                    if !self.inflater.is_done() {
+                        return Ok(());
+                    }
+                    self.pending = self.inflater.take_remaining();
+                    self.state = State::Trailer;
+                }
+                // https://www.rfc-editor.org/rfc/rfc1952#section-2.3.1
+                State::Trailer => {
+                    if self.pending.len() < 8 {
+                        return Ok(());
+                    }
+                    let trailer: Vec<u8> = self.pending.drain(..8).collect();
+                    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+                    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+                    if crc != self.crc.value() {
+                        return Err(invalid("checksum mismatch"));
+                    }
+                    if size != self.size {
+                        return Err(invalid("size mismatch"));
+                    }
+                    self.state = State::Done;
+                }
+                State::Done => {
+                    // 次のメンバーが続いていなければ、残りは読み捨てる
+                    if self.pending.first() == Some(&MAGIC[0]) {
+                        self.state = State::Header;
+                    } else {
+                        self.pending.clear();
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> Result<(), Error> {
+        if self.state == State::Done {
+            Ok(())
+        } else {
+            Err(Error::Network(
+                "gzip stream ended before the trailer".to_string(),
+            ))
+        }
+    }
+}
+
+/// gzip形式をまとめて展開する
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decoder = GzipDecoder::new();
+    let mut output = Vec::new();
+    decoder.push(input, &mut output)?;
+    decoder.finish(&mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::decode_in_pieces;
+    use crate::compression::from_hex;
+
+    fn decode(hex: &str) -> Result<Vec<u8>, Error> {
+        decode_in_pieces(GzipDecoder::new, &from_hex(hex))
+    }
+
+    #[test]
+    fn test_with_file_name() {
+        assert_eq!(
+            b"hello, saba!".to_vec(),
+            decode("1f8b08080000000002ff696e6465782e68746d6c00cb48cdc9c9d751284e4c4a540400ebcbca450c000000")
+                .expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_with_optional_fields() {
+        // FEXTRA、FCOMMENT、FHCRC
+        assert_eq!(
+            b"hello, saba!".to_vec(),
+            decode("1f8b08160000000000ff03006162636e6f7465002f5acb48cdc9c9d751284e4c4a540400ebcbca450c000000")
+                .expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_multiple_members() {
+        assert_eq!(
+            b"hello, saba!world".to_vec(),
+            decode(
+                "1f8b08080000000002ff696e6465782e68746d6c00cb48cdc9c9d751284e4c4a540400ebcbca450c000000\
+                 1f8b08000000000002032bcf2fca4901004311773a05000000"
+            )
+            .expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let cases = [
+            // チェックサムが違う
+            "1f8b08000000000002032bcf2fca4901004311773b05000000",
+            // 長さが違う
+            "1f8b08000000000002032bcf2fca4901004311773a06000000",
+            // マジックナンバーが違う
+            "1f8c08000000000002032bcf2fca4901004311773a05000000",
+            // トレーラーがない
+            "1f8b08000000000002032bcf2fca490100",
+            // ヘッダのチェックサムが違う
+            "1f8b08160000000000ff03006162636e6f7465002f5bcb48cdc9c9d751284e4c4a540400ebcbca450c000000",
+            "",
+        ];
+        for hex in cases {
+            assert!(decode(hex).is_err(), "{}", hex);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+pub mod checksum;
+pub mod deflate;
+pub mod gzip;
+pub mod zlib;
+
+use crate::error::Error;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// 圧縮されたバイト列を少しずつ展開する
+pub trait Decoder: Debug {
+    /// 受け取った入力から展開できた分を、outputの末尾に追加する
+    fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// 入力の終わりで呼び、ストリームが途中で切れていないか確かめる
+    fn finish(&mut self, output: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+#[cfg(test)]
+pub(crate) fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("invalid hex"))
+        .collect()
+}
+
+/// 1バイトずつ与えた場合も、まとめて与えた場合も同じ結果になることを確かめる
+#[cfg(test)]
+pub(crate) fn decode_in_pieces<D: Decoder>(
+    new: impl Fn() -> D,
+    input: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut decoder = new();
+    let mut whole = Vec::new();
+    let expected = decoder
+        .push(input, &mut whole)
+        .and_then(|_| decoder.finish(&mut whole))
+        .map(|_| whole);
+
+    let mut decoder = new();
+    let mut output = Vec::new();
+    let actual = input
+        .iter()
+        .try_for_each(|b| decoder.push(&[*b], &mut output))
+        .and_then(|_| decoder.finish(&mut output))
+        .map(|_| output);
+
+    assert_eq!(
+        alloc::format!("{:?}", expected),
+        alloc::format!("{:?}", actual)
+    );
+    expected
+}
@@ -0,0 +1,171 @@
+use crate::compression::checksum::Adler32;
+use crate::compression::deflate::Inflater;
+use crate::compression::Decoder;
+use crate::error::Error;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+const METHOD_DEFLATE: u8 = 8;
+const FLAG_DICT: u8 = 0x20;
+
+fn invalid(message: &str) -> Error {
+    Error::Network(format!("invalid zlib stream: {}", message))
+}
+
+/// 圧縮方式がdeflateで、ヘッダのチェックビットが正しいか
+/// https://www.rfc-editor.org/rfc/rfc1950#section-2.2
+pub fn is_zlib_header(cmf: u8, flg: u8) -> bool {
+    cmf & 0x0F == METHOD_DEFLATE && cmf >> 4 <= 7 && (cmf as u16 * 256 + flg as u16) % 31 == 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    Body,
+    Trailer,
+    Done,
+}
+
+/// zlib形式を展開する
+/// https://www.rfc-editor.org/rfc/rfc1950
+#[derive(Debug, Clone)]
+pub struct ZlibDecoder {
+    state: State,
+    /// ヘッダとトレーラーを読むために溜めておくバイト列
+    pending: Vec<u8>,
+    inflater: Inflater,
+    adler: Adler32,
+}
+
+impl ZlibDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Header,
+            pending: Vec::new(),
+            inflater: Inflater::new(),
+            adler: Adler32::new(),
+        }
+    }
+}
+
+impl Default for ZlibDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for ZlibDecoder {
+    fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        self.pending.extend_from_slice(input);
+        loop {
+            match self.state {
+                State::Header => {
+                    if self.pending.len() < 2 {
+                        return Ok(());
+                    }
+                    let (cmf, flg) = (self.pending[0], self.pending[1]);
+                    if !is_zlib_header(cmf, flg) {
+                        return Err(invalid("wrong header"));
+                    }
+                    // 事前に共有した辞書は使えない
+                    if flg & FLAG_DICT != 0 {
+                        return Err(invalid("preset dictionary is not supported"));
+                    }
+                    self.pending.drain(..2);
+                    self.state = State::Body;
+                }
+                State::Body => {
+                    let data = core::mem::take(&mut self.pending);
+                    let start = output.len();
+                    self.inflater.push(&data, output)?;
+                    self.adler.update(&output[start..]);
+                    if !self.inflater.is_done() {
+                        return Ok(());
+                    }
+                    self.pending = self.inflater.take_remaining();
+                    self.state = State::Trailer;
+                }
+                State::Trailer => {
+                    if self.pending.len() < 4 {
+                        return Ok(());
+                    }
+                    let trailer: Vec<u8> = self.pending.drain(..4).collect();
+                    let adler =
+                        u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+                    if adler != self.adler.value() {
+                        return Err(invalid("checksum mismatch"));
+                    }
+                    self.state = State::Done;
+                }
+                State::Done => {
+                    self.pending.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> Result<(), Error> {
+        if self.state == State::Done {
+            Ok(())
+        } else {
+            Err(Error::Network(
+                "zlib stream ended before the trailer".to_string(),
+            ))
+        }
+    }
+}
+
+/// zlib形式をまとめて展開する
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decoder = ZlibDecoder::new();
+    let mut output = Vec::new();
+    decoder.push(input, &mut output)?;
+    decoder.finish(&mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::decode_in_pieces;
+    use crate::compression::from_hex;
+
+    const HTML_ZLIB: &str = "78da55d24d0a02410c44e1ab7883b6aafc85a6ef32c32c5c280ee8c6db0b0e48def2adf291a4dfde8ffbe8f373f98cbe8ed7344fbb7d6feb3f54c33552e350e358e354e35ce352e38aa124c020200485c0101c0244900814c16258cc7dc062580c8b61312c86c5b01896c01258c2e3c01258024b60092c81259ba56dcfd27e9ff3054d12ac2d";
+
+    fn html() -> Vec<u8> {
+        let mut html = b"<html><body>".to_vec();
+        for i in 0..40 {
+            html.extend_from_slice(format!("<p>saba {}</p>", i).as_bytes());
+        }
+        html.extend_from_slice(b"</body></html>");
+        html
+    }
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(
+            html(),
+            decode_in_pieces(ZlibDecoder::new, &from_hex(HTML_ZLIB)).expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut input = from_hex(HTML_ZLIB);
+        let last = input.len() - 1;
+        input[last] ^= 1;
+        assert!(decode_in_pieces(ZlibDecoder::new, &input).is_err());
+    }
+
+    #[test]
+    fn test_is_zlib_header() {
+        assert!(is_zlib_header(0x78, 0x9C));
+        assert!(is_zlib_header(0x78, 0xDA));
+        assert!(is_zlib_header(0x78, 0x01));
+        assert!(!is_zlib_header(0x78, 0x9D));
+        // 生のdeflateストリームの先頭
+        assert!(!is_zlib_header(0xCB, 0x48));
+    }
+}
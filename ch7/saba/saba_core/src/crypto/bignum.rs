@@ -0,0 +1,384 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// 符号なしの多倍長整数。下位のリムから順に64ビットずつ持ち、上位の0のリムは持たない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self { limbs: Vec::new() }
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        Self::from_limbs(vec![value])
+    }
+
+    fn from_limbs(limbs: Vec<u64>) -> Self {
+        let mut n = Self { limbs };
+        n.normalize();
+        n
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn from_bytes_be(bytes: &[u8]) -> Self {
+        let limbs = bytes
+            .rchunks(8)
+            .map(|chunk| {
+                let mut word = [0u8; 8];
+                word[8 - chunk.len()..].copy_from_slice(chunk);
+                u64::from_be_bytes(word)
+            })
+            .collect();
+        Self::from_limbs(limbs)
+    }
+
+    /// lenバイトになるように、先頭を0で埋める。収まらない場合は下位のlenバイトを返す
+    pub fn to_bytes_be(&self, len: usize) -> Vec<u8> {
+        let mut output = vec![0u8; len];
+        for (i, limb) in self.limbs.iter().enumerate() {
+            for (j, b) in limb.to_le_bytes().iter().enumerate() {
+                let position = i * 8 + j;
+                if position < len {
+                    output[len - 1 - position] = *b;
+                }
+            }
+        }
+        output
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() * 64 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        self.limbs
+            .get(i / 64)
+            .map(|limb| (limb >> (i % 64)) & 1 == 1)
+            .unwrap_or(false)
+    }
+
+    fn limb(&self, i: usize) -> u64 {
+        self.limbs.get(i).copied().unwrap_or(0)
+    }
+
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let len = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u128;
+        for i in 0..len {
+            let sum = self.limb(i) as u128 + other.limb(i) as u128 + carry;
+            limbs.push(sum as u64);
+            carry = sum >> 64;
+        }
+        limbs.push(carry as u64);
+        Self::from_limbs(limbs)
+    }
+
+    /// self >= otherでなければならない
+    pub fn sub(&self, other: &BigUint) -> BigUint {
+        debug_assert!(*self >= *other);
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0u64;
+        for i in 0..self.limbs.len() {
+            let (d1, b1) = self.limbs[i].overflowing_sub(other.limb(i));
+            let (d2, b2) = d1.overflowing_sub(borrow);
+            limbs.push(d2);
+            borrow = (b1 || b2) as u64;
+        }
+        Self::from_limbs(limbs)
+    }
+
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let t = *a as u128 * *b as u128 + limbs[i + j] as u128 + carry;
+                limbs[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+        Self::from_limbs(limbs)
+    }
+
+    pub fn shl(&self, bits: usize) -> BigUint {
+        let words = bits / 64;
+        let shift = bits % 64;
+        let mut limbs = vec![0u64; words];
+        let mut carry = 0u64;
+        for limb in &self.limbs {
+            if shift == 0 {
+                limbs.push(*limb);
+            } else {
+                limbs.push((limb << shift) | carry);
+                carry = limb >> (64 - shift);
+            }
+        }
+        limbs.push(carry);
+        Self::from_limbs(limbs)
+    }
+
+    pub fn shr(&self, bits: usize) -> BigUint {
+        let words = bits / 64;
+        let shift = bits % 64;
+        let limbs = (words..self.limbs.len())
+            .map(|i| {
+                if shift == 0 {
+                    self.limbs[i]
+                } else {
+                    (self.limbs[i] >> shift) | (self.limb(i + 1) << (64 - shift))
+                }
+            })
+            .collect();
+        Self::from_limbs(limbs)
+    }
+
+    /// ビットごとの筆算で余りを求める。鍵の準備など、まれにしか使わない
+    pub fn rem(&self, modulus: &BigUint) -> BigUint {
+        assert!(!modulus.is_zero(), "division by zero");
+        if *self < *modulus {
+            return self.clone();
+        }
+        let mut remainder = BigUint::zero();
+        for i in (0..self.bits()).rev() {
+            remainder = remainder.shl(1);
+            if self.bit(i) {
+                remainder = remainder.add(&BigUint::from_u64(1));
+            }
+            if remainder >= *modulus {
+                remainder = remainder.sub(modulus);
+            }
+        }
+        remainder
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+/// 奇数の法でのモンゴメリ乗算。R = 2^(64 * リムの数)
+#[derive(Debug, Clone)]
+pub struct Modulus {
+    m: BigUint,
+    /// -m^(-1) mod 2^64
+    m_inv: u64,
+    /// R^2 mod m
+    r2: BigUint,
+}
+
+impl Modulus {
+    /// 法が偶数か1以下の場合はNoneを返す
+    pub fn new(m: BigUint) -> Option<Self> {
+        if !m.bit(0) || m.bits() < 2 {
+            return None;
+        }
+        // ニュートン法で、2^64を法とするm[0]の逆数を求める
+        let m0 = m.limbs[0];
+        let mut inverse = 1u64;
+        for _ in 0..6 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(m0.wrapping_mul(inverse)));
+        }
+        let r2 = BigUint::from_u64(1).shl(128 * m.limbs.len()).rem(&m);
+        Some(Self {
+            m_inv: inverse.wrapping_neg(),
+            m,
+            r2,
+        })
+    }
+
+    pub fn value(&self) -> &BigUint {
+        &self.m
+    }
+
+    /// a * b * R^(-1) mod mを求める。aとbはmより小さくなければならない
+    /// https://www.microsoft.com/en-us/research/wp-content/uploads/1996/01/j37acmon.pdf (CIOS)
+    pub fn mont_mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let n = self.m.limbs.len();
+        let mut t = vec![0u64; n + 2];
+        for i in 0..n {
+            let bi = b.limb(i) as u128;
+            let mut carry = 0u128;
+            for (j, tj) in t.iter_mut().enumerate().take(n) {
+                let sum = *tj as u128 + a.limb(j) as u128 * bi + carry;
+                *tj = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = t[n] as u128 + carry;
+            t[n] = sum as u64;
+            t[n + 1] = (sum >> 64) as u64;
+
+            let factor = t[0].wrapping_mul(self.m_inv) as u128;
+            let mut carry = (t[0] as u128 + factor * self.m.limbs[0] as u128) >> 64;
+            for j in 1..n {
+                let sum = t[j] as u128 + factor * self.m.limbs[j] as u128 + carry;
+                t[j - 1] = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = t[n] as u128 + carry;
+            t[n - 1] = sum as u64;
+            t[n] = t[n + 1] + (sum >> 64) as u64;
+            t[n + 1] = 0;
+        }
+        let result = BigUint::from_limbs(t);
+        if result >= self.m {
+            result.sub(&self.m)
+        } else {
+            result
+        }
+    }
+
+    /// モンゴメリ表現に変換する
+    pub fn to_mont(&self, a: &BigUint) -> BigUint {
+        self.mont_mul(&a.rem(&self.m), &self.r2)
+    }
+
+    pub fn from_mont(&self, a: &BigUint) -> BigUint {
+        self.mont_mul(a, &BigUint::from_u64(1))
+    }
+
+    /// モンゴメリ表現での1
+    pub fn one(&self) -> BigUint {
+        self.from_mont(&self.r2)
+    }
+
+    pub fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let sum = a.add(b);
+        if sum >= self.m {
+            sum.sub(&self.m)
+        } else {
+            sum
+        }
+    }
+
+    pub fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b {
+            a.sub(b)
+        } else {
+            a.add(&self.m).sub(b)
+        }
+    }
+
+    /// モンゴメリ表現のままべき乗する
+    pub fn mont_pow(&self, base: &BigUint, exponent: &BigUint) -> BigUint {
+        let mut result = self.one();
+        for i in (0..exponent.bits()).rev() {
+            result = self.mont_mul(&result, &result);
+            if exponent.bit(i) {
+                result = self.mont_mul(&result, base);
+            }
+        }
+        result
+    }
+
+    /// base^exponent mod mを、通常の表現で求める
+    pub fn pow(&self, base: &BigUint, exponent: &BigUint) -> BigUint {
+        self.from_mont(&self.mont_pow(&self.to_mont(base), exponent))
+    }
+
+    /// 法が素数の場合に、フェルマーの小定理で逆数を求める。モンゴメリ表現のまま扱う
+    pub fn mont_invert(&self, a: &BigUint) -> BigUint {
+        let exponent = self.m.sub(&BigUint::from_u64(2));
+        self.mont_pow(a, &exponent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    fn n(hex: &str) -> BigUint {
+        BigUint::from_bytes_be(&from_hex(hex))
+    }
+
+    #[test]
+    fn test_bytes() {
+        let value = n("0102030405060708090a");
+        assert_eq!(from_hex("0102030405060708090a"), value.to_bytes_be(10));
+        assert_eq!(from_hex("000102030405060708090a"), value.to_bytes_be(11));
+        assert_eq!(73, value.bits());
+        assert_eq!(BigUint::zero(), n("0000"));
+        assert!(n("0000").is_zero());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = n("ffffffffffffffffffffffffffffffff");
+        let b = n("01");
+        assert_eq!(n("0100000000000000000000000000000000"), a.add(&b));
+        assert_eq!(a, a.add(&b).sub(&b));
+        assert_eq!(
+            n("fffffffffffffffffffffffffffffffe00000000000000000000000000000001"),
+            a.mul(&a)
+        );
+        assert_eq!(n("0200"), n("01").shl(9));
+        assert_eq!(n("01"), n("0200").shr(9));
+        assert_eq!(
+            n("123456789abcdef0"),
+            n("123456789abcdef0123456789abcdef0").shr(64)
+        );
+        assert_eq!(
+            n("091a2b3c4d5e6f78"),
+            n("123456789abcdef0123456789abcdef0").shr(65)
+        );
+        assert_eq!(BigUint::from_u64(1), n("64").rem(&BigUint::from_u64(9)));
+        assert!(n("0100") > n("ff"));
+    }
+
+    #[test]
+    fn test_pow() {
+        let modulus = Modulus::new(BigUint::from_u64(1_000_000_007)).expect("invalid modulus");
+        // 2^100 mod 1000000007
+        assert_eq!(
+            BigUint::from_u64(976_371_285),
+            modulus.pow(&BigUint::from_u64(2), &BigUint::from_u64(100))
+        );
+
+        // 複数のリムを持つ法
+        let modulus = Modulus::new(n(
+            "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+        ))
+        .expect("invalid modulus");
+        let a = n("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296");
+        let inverse = modulus.mont_invert(&modulus.to_mont(&a));
+        assert_eq!(
+            BigUint::from_u64(1),
+            modulus.from_mont(&modulus.mont_mul(&inverse, &modulus.to_mont(&a)))
+        );
+    }
+
+    #[test]
+    fn test_invalid_modulus() {
+        assert!(Modulus::new(BigUint::from_u64(10)).is_none());
+        assert!(Modulus::new(BigUint::from_u64(1)).is_none());
+    }
+}
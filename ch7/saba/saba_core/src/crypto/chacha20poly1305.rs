@@ -0,0 +1,288 @@
+use crate::crypto::hash::constant_time_eq;
+use alloc::vec::Vec;
+
+pub const KEY_LEN: usize = 32;
+pub const TAG_LEN: usize = 16;
+pub const NONCE_LEN: usize = 12;
+
+/// https://www.rfc-editor.org/rfc/rfc8439#section-2.1
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// https://www.rfc-editor.org/rfc/rfc8439#section-2.3
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for i in 0..8 {
+        initial[4 + i] = le_u32(&key[i * 4..]);
+    }
+    initial[12] = counter;
+    for i in 0..3 {
+        initial[13 + i] = le_u32(&nonce[i * 4..]);
+    }
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut output = [0u8; 64];
+    for i in 0..16 {
+        output[i * 4..i * 4 + 4].copy_from_slice(&state[i].wrapping_add(initial[i]).to_le_bytes());
+    }
+    output
+}
+
+/// https://www.rfc-editor.org/rfc/rfc8439#section-2.4
+fn chacha20_xor(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let keystream = chacha20_block(key, counter.wrapping_add(i as u32), nonce);
+        for (b, k) in chunk.iter_mut().zip(keystream) {
+            *b ^= k;
+        }
+    }
+}
+
+/// 2^130-5を法とする計算を、26ビットずつ5つに分けて行う
+/// https://www.rfc-editor.org/rfc/rfc8439#section-2.5
+fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; 16] {
+    // rの一部のビットを0にする
+    let r0 = le_u32(&key[0..]) & 0x3ffffff;
+    let r1 = (le_u32(&key[3..]) >> 2) & 0x3ffff03;
+    let r2 = (le_u32(&key[6..]) >> 4) & 0x3ffc0ff;
+    let r3 = (le_u32(&key[9..]) >> 6) & 0x3f03fff;
+    let r4 = (le_u32(&key[12..]) >> 8) & 0x00fffff;
+    let [s1, s2, s3, s4] = [r1 * 5, r2 * 5, r3 * 5, r4 * 5];
+
+    let mut h = [0u32; 5];
+    for chunk in message.chunks(16) {
+        let mut block = [0u8; 17];
+        block[..chunk.len()].copy_from_slice(chunk);
+        block[chunk.len()] = 1;
+
+        h[0] += le_u32(&block[0..]) & 0x3ffffff;
+        h[1] += (le_u32(&block[3..]) >> 2) & 0x3ffffff;
+        h[2] += (le_u32(&block[6..]) >> 4) & 0x3ffffff;
+        h[3] += (le_u32(&block[9..]) >> 6) & 0x3ffffff;
+        h[4] += (le_u32(&block[12..]) >> 8) | ((block[16] as u32) << 24);
+
+        let [h0, h1, h2, h3, h4] = h.map(|v| v as u64);
+        let [r0, r1, r2, r3, r4] = [r0, r1, r2, r3, r4].map(|v| v as u64);
+        let [s1, s2, s3, s4] = [s1, s2, s3, s4].map(|v| v as u64);
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        let mut carry = d0 >> 26;
+        h[0] = (d0 & 0x3ffffff) as u32;
+        d1 += carry;
+        carry = d1 >> 26;
+        h[1] = (d1 & 0x3ffffff) as u32;
+        d2 += carry;
+        carry = d2 >> 26;
+        h[2] = (d2 & 0x3ffffff) as u32;
+        d3 += carry;
+        carry = d3 >> 26;
+        h[3] = (d3 & 0x3ffffff) as u32;
+        d4 += carry;
+        carry = d4 >> 26;
+        h[4] = (d4 & 0x3ffffff) as u32;
+        h[0] += (carry * 5) as u32;
+        h[1] += h[0] >> 26;
+        h[0] &= 0x3ffffff;
+    }
+
+    // 繰り上がりを伝えきってから、h - pが負でなければそちらを使う
+    let mut carry = h[1] >> 26;
+    h[1] &= 0x3ffffff;
+    for limb in h.iter_mut().skip(2) {
+        *limb += carry;
+        carry = *limb >> 26;
+        *limb &= 0x3ffffff;
+    }
+    h[0] += carry * 5;
+    carry = h[0] >> 26;
+    h[0] &= 0x3ffffff;
+    h[1] += carry;
+
+    let mut g = [0u32; 5];
+    let mut carry = 5;
+    for i in 0..4 {
+        g[i] = h[i] + carry;
+        carry = g[i] >> 26;
+        g[i] &= 0x3ffffff;
+    }
+    g[4] = (h[4] + carry).wrapping_sub(1 << 26);
+    // g[4]の最上位ビットが立っていなければ、h >= pだった
+    let mask = (g[4] >> 31).wrapping_sub(1);
+    for i in 0..5 {
+        h[i] = (h[i] & !mask) | (g[i] & mask);
+    }
+
+    let h0 = h[0] | (h[1] << 26);
+    let h1 = (h[1] >> 6) | (h[2] << 20);
+    let h2 = (h[2] >> 12) | (h[3] << 14);
+    let h3 = (h[3] >> 18) | (h[4] << 8);
+
+    let mut tag = [0u8; 16];
+    let mut carry = 0u64;
+    for (i, word) in [h0, h1, h2, h3].iter().enumerate() {
+        let sum = *word as u64 + le_u32(&key[16 + i * 4..]) as u64 + carry;
+        tag[i * 4..i * 4 + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+        carry = sum >> 32;
+    }
+    tag
+}
+
+/// https://www.rfc-editor.org/rfc/rfc8439#section-2.8
+#[derive(Debug, Clone)]
+pub struct ChaCha20Poly1305 {
+    key: [u8; KEY_LEN],
+}
+
+impl ChaCha20Poly1305 {
+    pub fn new(key: &[u8]) -> Option<Self> {
+        if key.len() != KEY_LEN {
+            return None;
+        }
+        let mut k = [0u8; KEY_LEN];
+        k.copy_from_slice(key);
+        Some(Self { key: k })
+    }
+
+    fn tag(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        let block = chacha20_block(&self.key, 0, nonce);
+        let mut one_time_key = [0u8; 32];
+        one_time_key.copy_from_slice(&block[..32]);
+
+        let mut data = Vec::new();
+        for part in [aad, ciphertext] {
+            data.extend_from_slice(part);
+            data.resize(data.len().div_ceil(16) * 16, 0);
+        }
+        data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+        data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+        poly1305(&one_time_key, &data)
+    }
+
+    /// 暗号文の後ろに認証タグを付けて返す
+    pub fn seal(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut output = plaintext.to_vec();
+        chacha20_xor(&self.key, 1, nonce, &mut output);
+        let tag = self.tag(nonce, aad, &output);
+        output.extend_from_slice(&tag);
+        output
+    }
+
+    /// 認証タグが一致しない場合はNoneを返す
+    pub fn open(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+        if input.len() < TAG_LEN {
+            return None;
+        }
+        let (ciphertext, tag) = input.split_at(input.len() - TAG_LEN);
+        if !constant_time_eq(&self.tag(nonce, aad, ciphertext), tag) {
+            return None;
+        }
+        let mut output = ciphertext.to_vec();
+        chacha20_xor(&self.key, 1, nonce, &mut output);
+        Some(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    #[test]
+    fn test_poly1305() {
+        // https://www.rfc-editor.org/rfc/rfc8439#section-2.5.2
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&from_hex(
+            "85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b",
+        ));
+        assert_eq!(
+            from_hex("a8061dc1305136c6c22b8baf0c0127a9"),
+            poly1305(&key, b"Cryptographic Forum Research Group").to_vec()
+        );
+    }
+
+    #[test]
+    fn test_poly1305_wrap() {
+        // hがpを超える場合
+        // https://www.rfc-editor.org/rfc/rfc8439#appendix-A.3 (Test Vector #5)
+        let mut key = [0u8; 32];
+        key[0] = 2;
+        assert_eq!(
+            from_hex("03000000000000000000000000000000"),
+            poly1305(&key, &[0xff; 16]).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_chacha20_block() {
+        // https://www.rfc-editor.org/rfc/rfc8439#section-2.3.2
+        let mut key = [0u8; KEY_LEN];
+        key.copy_from_slice(&from_hex(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        ));
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&from_hex("000000090000004a00000000"));
+        assert_eq!(
+            from_hex(
+                "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e\
+                 d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+            ),
+            chacha20_block(&key, 1, &nonce).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        // https://www.rfc-editor.org/rfc/rfc8439#section-2.8.2
+        let aead = ChaCha20Poly1305::new(&from_hex(
+            "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f",
+        ))
+        .expect("invalid key");
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&from_hex("070000004041424344454647"));
+        let aad = from_hex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+        let sealed = aead.seal(&nonce, &aad, plaintext);
+        let mut expected = from_hex(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b\
+             1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+             3ff4def08e4b7a9de576d26586cec64b6116",
+        );
+        // 認証タグ
+        expected.extend_from_slice(&from_hex("1ae10b594f09e26a7e902ecbd0600691"));
+        assert_eq!(expected, sealed);
+        assert_eq!(Some(plaintext.to_vec()), aead.open(&nonce, &aad, &sealed));
+
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert!(aead.open(&nonce, &aad, &tampered).is_none());
+    }
+}
@@ -0,0 +1,401 @@
+use crate::crypto::bignum::BigUint;
+use crate::crypto::bignum::Modulus;
+use alloc::vec::Vec;
+
+/// a = -3の短いワイエルシュトラス曲線 y^2 = x^3 - 3x + b
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-186.pdf (3.2.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamedCurve {
+    P256,
+    P384,
+}
+
+struct CurveParameters {
+    p: &'static str,
+    b: &'static str,
+    n: &'static str,
+    gx: &'static str,
+    gy: &'static str,
+}
+
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-186.pdf (3.2.1.3)
+const P256: CurveParameters = CurveParameters {
+    p: "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+    b: "5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b",
+    n: "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+    gx: "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296",
+    gy: "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5",
+};
+
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-186.pdf (3.2.1.4)
+const P384: CurveParameters = CurveParameters {
+    p: "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffffff0000000000000000ffffffff",
+    b: "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875ac656398d8a2ed19d2a85c8edd3ec2aef",
+    n: "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf581a0db248b0a77aecec196accc52973",
+    gx: "aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a385502f25dbf55296c3a545e3872760ab7",
+    gy: "3617de4a96262c6f5d9e98bf9292dc29f8f41dbd289a147ce9da3113b5f0b8c00a60b1ce1d7e819d7a431d7c90ea0e5f",
+};
+
+fn number(hex: &str) -> BigUint {
+    let bytes: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("invalid hex"))
+        .collect();
+    BigUint::from_bytes_be(&bytes)
+}
+
+/// ヤコビアン座標の点。Zが0の点は無限遠点を表す。座標はモンゴメリ表現で持つ
+#[derive(Debug, Clone)]
+struct Point {
+    x: BigUint,
+    y: BigUint,
+    z: BigUint,
+}
+
+/// 楕円曲線の計算に使う値
+#[derive(Debug, Clone)]
+pub struct Curve {
+    field: Modulus,
+    order: Modulus,
+    b: BigUint,
+    g: Point,
+    /// 座標1つ分のバイト数
+    len: usize,
+}
+
+impl Curve {
+    pub fn new(curve: NamedCurve) -> Self {
+        let parameters = match curve {
+            NamedCurve::P256 => &P256,
+            NamedCurve::P384 => &P384,
+        };
+        let field = Modulus::new(number(parameters.p)).expect("invalid field");
+        let order = Modulus::new(number(parameters.n)).expect("invalid order");
+        let g = Point {
+            x: field.to_mont(&number(parameters.gx)),
+            y: field.to_mont(&number(parameters.gy)),
+            z: field.one(),
+        };
+        Self {
+            b: field.to_mont(&number(parameters.b)),
+            len: parameters.p.len() / 2,
+            field,
+            order,
+            g,
+        }
+    }
+
+    /// 座標1つ分のバイト数
+    pub fn coordinate_len(&self) -> usize {
+        self.len
+    }
+
+    fn infinity(&self) -> Point {
+        Point {
+            x: self.field.one(),
+            y: self.field.one(),
+            z: BigUint::zero(),
+        }
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.field.mont_mul(a, b)
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.field.add(a, b)
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.field.sub(a, b)
+    }
+
+    /// https://hyperelliptic.org/EFD/g1p/auto-shortw-jacobian-3.html#doubling-dbl-2001-b
+    fn double(&self, point: &Point) -> Point {
+        if point.z.is_zero() || point.y.is_zero() {
+            return self.infinity();
+        }
+        let delta = self.mul(&point.z, &point.z);
+        let gamma = self.mul(&point.y, &point.y);
+        let beta = self.mul(&point.x, &gamma);
+        let t = self.mul(&self.sub(&point.x, &delta), &self.add(&point.x, &delta));
+        let alpha = self.add(&self.add(&t, &t), &t);
+        let beta4 = self.add(&beta, &beta);
+        let beta4 = self.add(&beta4, &beta4);
+        let x = self.sub(&self.mul(&alpha, &alpha), &self.add(&beta4, &beta4));
+        let yz = self.add(&point.y, &point.z);
+        let z = self.sub(&self.sub(&self.mul(&yz, &yz), &gamma), &delta);
+        let gamma2 = self.mul(&gamma, &gamma);
+        let gamma8 = self.add(&gamma2, &gamma2);
+        let gamma8 = self.add(&gamma8, &gamma8);
+        let gamma8 = self.add(&gamma8, &gamma8);
+        let y = self.sub(&self.mul(&alpha, &self.sub(&beta4, &x)), &gamma8);
+        Point { x, y, z }
+    }
+
+    /// https://hyperelliptic.org/EFD/g1p/auto-shortw-jacobian-3.html#addition-add-2007-bl
+    fn add_points(&self, p: &Point, q: &Point) -> Point {
+        if p.z.is_zero() {
+            return q.clone();
+        }
+        if q.z.is_zero() {
+            return p.clone();
+        }
+        let z1z1 = self.mul(&p.z, &p.z);
+        let z2z2 = self.mul(&q.z, &q.z);
+        let u1 = self.mul(&p.x, &z2z2);
+        let u2 = self.mul(&q.x, &z1z1);
+        let s1 = self.mul(&self.mul(&p.y, &q.z), &z2z2);
+        let s2 = self.mul(&self.mul(&q.y, &p.z), &z1z1);
+        let h = self.sub(&u2, &u1);
+        let r = self.sub(&s2, &s1);
+        if h.is_zero() {
+            // 同じ点なら2倍、逆元なら無限遠点になる
+            return if r.is_zero() {
+                self.double(p)
+            } else {
+                self.infinity()
+            };
+        }
+        let h2 = self.add(&h, &h);
+        let i = self.mul(&h2, &h2);
+        let j = self.mul(&h, &i);
+        let r = self.add(&r, &r);
+        let v = self.mul(&u1, &i);
+        let x = self.sub(&self.sub(&self.mul(&r, &r), &j), &self.add(&v, &v));
+        let s1j = self.mul(&s1, &j);
+        let y = self.sub(&self.mul(&r, &self.sub(&v, &x)), &self.add(&s1j, &s1j));
+        let zz = self.add(&p.z, &q.z);
+        let z = self.mul(&self.sub(&self.sub(&self.mul(&zz, &zz), &z1z1), &z2z2), &h);
+        Point { x, y, z }
+    }
+
+    fn scalar_mul(&self, scalar: &BigUint, point: &Point) -> Point {
+        let mut result = self.infinity();
+        for i in (0..scalar.bits()).rev() {
+            result = self.double(&result);
+            if scalar.bit(i) {
+                result = self.add_points(&result, point);
+            }
+        }
+        result
+    }
+
+    /// アフィン座標(通常の表現)にする。無限遠点の場合はNone
+    fn to_affine(&self, point: &Point) -> Option<(BigUint, BigUint)> {
+        if point.z.is_zero() {
+            return None;
+        }
+        let z_inv = self.field.mont_invert(&point.z);
+        let z_inv2 = self.mul(&z_inv, &z_inv);
+        let x = self.mul(&point.x, &z_inv2);
+        let y = self.mul(&point.y, &self.mul(&z_inv2, &z_inv));
+        Some((self.field.from_mont(&x), self.field.from_mont(&y)))
+    }
+
+    /// 圧縮していない形式(0x04 || X || Y)の点を読む。曲線上にない点は受け付けない
+    /// https://www.secg.org/sec1-v2.pdf (2.3.4)
+    fn decode_point(&self, bytes: &[u8]) -> Option<Point> {
+        if bytes.len() != 1 + 2 * self.len || bytes[0] != 0x04 {
+            return None;
+        }
+        let x = BigUint::from_bytes_be(&bytes[1..1 + self.len]);
+        let y = BigUint::from_bytes_be(&bytes[1 + self.len..]);
+        if x >= *self.field.value() || y >= *self.field.value() {
+            return None;
+        }
+        let x = self.field.to_mont(&x);
+        let y = self.field.to_mont(&y);
+
+        // y^2 = x^3 - 3x + b
+        let x3 = self.mul(&self.mul(&x, &x), &x);
+        let x3_3x = self.sub(&x3, &self.add(&self.add(&x, &x), &x));
+        if self.mul(&y, &y) != self.add(&x3_3x, &self.b) {
+            return None;
+        }
+        Some(Point {
+            x,
+            y,
+            z: self.field.one(),
+        })
+    }
+
+    fn encode_point(&self, point: &Point) -> Option<Vec<u8>> {
+        let (x, y) = self.to_affine(point)?;
+        let mut output = alloc::vec![0x04];
+        output.extend_from_slice(&x.to_bytes_be(self.len));
+        output.extend_from_slice(&y.to_bytes_be(self.len));
+        Some(output)
+    }
+
+    /// 1以上n未満の秘密鍵なら、対応する公開鍵を返す
+    pub fn public_key(&self, private_key: &[u8]) -> Option<Vec<u8>> {
+        let d = BigUint::from_bytes_be(private_key);
+        if d.is_zero() || d >= *self.order.value() {
+            return None;
+        }
+        self.encode_point(&self.scalar_mul(&d, &self.g))
+    }
+
+    /// ECDHの共有秘密(x座標)を求める
+    /// https://www.secg.org/sec1-v2.pdf (3.3.1)
+    pub fn shared_secret(&self, private_key: &[u8], peer_public_key: &[u8]) -> Option<Vec<u8>> {
+        let d = BigUint::from_bytes_be(private_key);
+        if d.is_zero() || d >= *self.order.value() {
+            return None;
+        }
+        let q = self.decode_point(peer_public_key)?;
+        let (x, _) = self.to_affine(&self.scalar_mul(&d, &q))?;
+        Some(x.to_bytes_be(self.len))
+    }
+
+    /// ECDSAの署名を検証する。hashはメッセージのハッシュ値で、rとsは署名の値
+    /// https://www.secg.org/sec1-v2.pdf (4.1.4)
+    pub fn verify(&self, public_key: &[u8], hash: &[u8], r: &BigUint, s: &BigUint) -> bool {
+        let n = self.order.value();
+        if r.is_zero() || s.is_zero() || r >= n || s >= n {
+            return false;
+        }
+        let q = match self.decode_point(public_key) {
+            Some(q) => q,
+            None => return false,
+        };
+
+        // ハッシュ値がnより長い場合は、上位のビットだけを使う
+        let mut e = BigUint::from_bytes_be(hash);
+        let n_bits = n.bits();
+        if hash.len() * 8 > n_bits {
+            e = BigUint::from_bytes_be(&hash[..n_bits.div_ceil(8)]);
+            if n_bits % 8 != 0 {
+                e = e.shr(8 - n_bits % 8);
+            }
+        }
+
+        let w = self.order.mont_invert(&self.order.to_mont(s));
+        let u1 = self
+            .order
+            .from_mont(&self.order.mont_mul(&self.order.to_mont(&e), &w));
+        let u2 = self
+            .order
+            .from_mont(&self.order.mont_mul(&self.order.to_mont(r), &w));
+        let point = self.add_points(&self.scalar_mul(&u1, &self.g), &self.scalar_mul(&u2, &q));
+        match self.to_affine(&point) {
+            Some((x, _)) => x.rem(n) == *r,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    #[test]
+    fn test_public_key() {
+        let curve = Curve::new(NamedCurve::P256);
+        // 秘密鍵が1なら、公開鍵は生成元になる
+        let mut one = alloc::vec![0u8; 32];
+        one[31] = 1;
+        assert_eq!(
+            from_hex(&alloc::format!("04{}{}", P256.gx, P256.gy)),
+            curve.public_key(&one).expect("invalid key")
+        );
+        assert!(curve.public_key(&[0; 32]).is_none());
+        assert!(curve.public_key(&from_hex(P256.n)).is_none());
+    }
+
+    #[test]
+    fn test_ecdh_p256() {
+        // https://datatracker.ietf.org/doc/html/rfc5903#section-8.1
+        let curve = Curve::new(NamedCurve::P256);
+        let i = from_hex("c88f01f510d9ac3f70a292daa2316de544e9aab8afe84049c62a9c57862d1433");
+        let r = from_hex("c6ef9c5d78ae012a011164acb397ce2088685d8f06bf9be0b283ab46476bee53");
+        let gi = curve.public_key(&i).expect("invalid key");
+        let gr = curve.public_key(&r).expect("invalid key");
+        assert_eq!(
+            from_hex(
+                "04dad0b65394221cf9b051e1feca5787d098dfe637fc90b9ef945d0c37725811805271a0461cdb8252d61f1c456fa3e59ab1f45b33accf5f58389e0577b8990bb3"
+            ),
+            gi
+        );
+        let expected = from_hex("d6840f6b42f6edafd13116e0e12565202fef8e9ece7dce03812464d04b9442de");
+        assert_eq!(Some(expected.clone()), curve.shared_secret(&i, &gr));
+        assert_eq!(Some(expected), curve.shared_secret(&r, &gi));
+    }
+
+    #[test]
+    fn test_ecdh_p384() {
+        // https://datatracker.ietf.org/doc/html/rfc5903#section-8.2
+        let curve = Curve::new(NamedCurve::P384);
+        let i = from_hex("099f3c7034d4a2c699884d73a375a67f7624ef7c6b3c0f160647b67414dce655e35b538041e649ee3faef896783ab194");
+        let r = from_hex("41cb0779b4bdb85d47846725fbec3c9430fab46cc8dc5060855cc9bda0aa2942e0308312916b8ed2960e4bd55a7448fc");
+        let gr = curve.public_key(&r).expect("invalid key");
+        assert_eq!(
+            Some(from_hex("11187331c279962d93d604243fd592cb9d0a926f422e47187521287e7156c5c4d603135569b9e9d09cf5d4a270f59746")),
+            curve.shared_secret(&i, &gr)
+        );
+    }
+
+    #[test]
+    fn test_invalid_point() {
+        let curve = Curve::new(NamedCurve::P256);
+        let mut one = alloc::vec![0u8; 32];
+        one[31] = 1;
+        let mut point = curve.public_key(&one).expect("invalid key");
+        point[40] ^= 1;
+        assert!(curve.shared_secret(&one, &point).is_none());
+        assert!(curve.shared_secret(&one, &point[..64]).is_none());
+    }
+
+    fn verify(curve: NamedCurve, public_key: &str, hash: &str, r: &str, s: &str) -> bool {
+        Curve::new(curve).verify(
+            &from_hex(public_key),
+            &from_hex(hash),
+            &BigUint::from_bytes_be(&from_hex(r)),
+            &BigUint::from_bytes_be(&from_hex(s)),
+        )
+    }
+
+    #[test]
+    fn test_ecdsa_p256() {
+        let public_key = "04556fd6d8743699578ffdd525f177318b352d4ef704aee7e87a41fdeb95a14ade5eb919b823174fda1320f1eb334ed3a0887e74be786e2f12bbe20af18181748e";
+        // SHA-256("saba")
+        let hash = "318f82cf849d0667b848f68eff45969f7c50c2f46fc4dad87f999d748205e52e";
+        let r = "120f1d88e610dd95f07b5ab03f0fc35d97c7691d9c12de5fe0705ca37ddd8263";
+        let s = "4362984884fe6b6552ea85cf8842235e77c3f0d8144fd0be0d24ba68b466ea48";
+        assert!(verify(NamedCurve::P256, public_key, hash, r, s));
+        assert!(!verify(NamedCurve::P256, public_key, hash, s, r));
+        assert!(!verify(
+            NamedCurve::P256,
+            public_key,
+            "318f82cf849d0667b848f68eff45969f7c50c2f46fc4dad87f999d748205e52f",
+            r,
+            s
+        ));
+        assert!(!verify(NamedCurve::P256, public_key, hash, "00", s));
+        assert!(!verify(NamedCurve::P256, public_key, hash, P256.n, s));
+    }
+
+    #[test]
+    fn test_ecdsa_p384() {
+        let public_key = "04dffbdbc665ba7273cbe10d3f1f57f8130c0b8e2f264ced3ae22544f11eed1c2fc58f200e87491b403cb6e44872f7a229568c428b473e25671778ffc549dfa95f0e800824e726a9e3fd59ed1c2a1a110fd7523d7538f3b80e387760e89807acc3";
+        // SHA-384("saba")
+        let hash = "268c5faa9086e0c5c9f3d1b108352f43d8b8fd6f7a2173b58f641bc03b452e9e22bc2a0d05c09bac69c1457b96939b08";
+        let r = "968ecf3216a167f09c0897e370210230c6f6fb8cc94d3e80f98e549515114c938bc4e657964979649548bd818599b728";
+        let s = "3314ad6496d59a545c92ce7f0cd448fc3bdf985c028af66874d7d2560fb35c049b37243129108ad0349b688bf0159f6b";
+        assert!(verify(NamedCurve::P384, public_key, hash, r, s));
+        assert!(!verify(NamedCurve::P384, public_key, hash, s, r));
+    }
+
+    #[test]
+    fn test_ecdsa_long_hash() {
+        // P-256の鍵でSHA-512を使う場合は、ハッシュ値の上位256ビットを使う
+        let public_key = "041e71c2bcfb7f7956b1259f5ebe2c56ddad863aa39a89e956c3aec6004d7e8a88fd04c4cd8682a8d19240278e99731d4048307cd3ef935ac045603ad91a08a055";
+        // SHA-512("saba")
+        let hash = "4872c954783d7793692433fda7e124789e4bd8e5e7937dc903ec36f15676eb924c096e8cc27d3419f37526ff42f5edb1ef1ddffc362b8e04882b0101a3d5f653";
+        let r = "0a458064462f4af03872af01c8e106a3149d042ceae10eadf3f14030f2b5c204";
+        let s = "ebef7aa77ea98a77d094fcdfe4c85142bdde34fa9cee9b76facb744266ef3d59";
+        assert!(verify(NamedCurve::P256, public_key, hash, r, s));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::crypto::aes::Aes;
+use crate::crypto::hash::constant_time_eq;
+use alloc::vec::Vec;
+
+pub const TAG_LEN: usize = 16;
+pub const NONCE_LEN: usize = 12;
+
+/// GF(2^128)での掛け算。ビットの並びはGCMの仕様に従い、最上位ビットがx^0の係数になる
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38d.pdf (6.3)
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut z = 0;
+    let mut v = y;
+    for i in 0..128 {
+        if (x >> (127 - i)) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    z
+}
+
+fn block_to_u128(bytes: &[u8]) -> u128 {
+    let mut block = [0u8; 16];
+    block[..bytes.len()].copy_from_slice(bytes);
+    u128::from_be_bytes(block)
+}
+
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38d.pdf
+#[derive(Debug, Clone)]
+pub struct AesGcm {
+    aes: Aes,
+    h: u128,
+}
+
+impl AesGcm {
+    pub fn new(key: &[u8]) -> Option<Self> {
+        let aes = Aes::new(key)?;
+        let mut h = [0u8; 16];
+        aes.encrypt_block(&mut h);
+        Some(Self {
+            aes,
+            h: u128::from_be_bytes(h),
+        })
+    }
+
+    /// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38d.pdf (6.4)
+    fn ghash(&self, aad: &[u8], ciphertext: &[u8]) -> u128 {
+        let mut y = 0;
+        for data in [aad, ciphertext] {
+            for block in data.chunks(16) {
+                y = gf_mul(y ^ block_to_u128(block), self.h);
+            }
+        }
+        let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+        gf_mul(y ^ lengths, self.h)
+    }
+
+    /// カウンターの初期値J0から数えて、inc32を適用しながら鍵ストリームをXORする
+    fn ctr(&self, j0: u128, data: &mut [u8]) {
+        let mut counter = j0;
+        for chunk in data.chunks_mut(16) {
+            counter = (counter & !0xffff_ffff) | ((counter as u32).wrapping_add(1) as u128);
+            let mut keystream = counter.to_be_bytes();
+            self.aes.encrypt_block(&mut keystream);
+            for (b, k) in chunk.iter_mut().zip(keystream) {
+                *b ^= k;
+            }
+        }
+    }
+
+    fn tag(&self, j0: u128, aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+        let mut mask = j0.to_be_bytes();
+        self.aes.encrypt_block(&mut mask);
+        (self.ghash(aad, ciphertext) ^ u128::from_be_bytes(mask)).to_be_bytes()
+    }
+
+    /// 暗号文の後ろに認証タグを付けて返す
+    pub fn seal(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let j0 = (block_to_u128(nonce) & !0xffff_ffff) | 1;
+        let mut output = plaintext.to_vec();
+        self.ctr(j0, &mut output);
+        let tag = self.tag(j0, aad, &output);
+        output.extend_from_slice(&tag);
+        output
+    }
+
+    /// 認証タグが一致しない場合はNoneを返す
+    pub fn open(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+        if input.len() < TAG_LEN {
+            return None;
+        }
+        let (ciphertext, tag) = input.split_at(input.len() - TAG_LEN);
+        let j0 = (block_to_u128(nonce) & !0xffff_ffff) | 1;
+        if !constant_time_eq(&self.tag(j0, aad, ciphertext), tag) {
+            return None;
+        }
+        let mut output = ciphertext.to_vec();
+        self.ctr(j0, &mut output);
+        Some(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    fn nonce(hex: &str) -> [u8; NONCE_LEN] {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&from_hex(hex));
+        nonce
+    }
+
+    #[test]
+    fn test_empty() {
+        // https://csrc.nist.rip/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf (Test Case 1, 2)
+        let gcm = AesGcm::new(&[0; 16]).expect("invalid key");
+        assert_eq!(
+            from_hex("58e2fccefa7e3061367f1d57a4e7455a"),
+            gcm.seal(&[0; 12], &[], &[])
+        );
+        assert_eq!(
+            from_hex("0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf"),
+            gcm.seal(&[0; 12], &[], &[0; 16])
+        );
+    }
+
+    #[test]
+    fn test_with_aad() {
+        // https://csrc.nist.rip/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf (Test Case 4)
+        let gcm = AesGcm::new(&from_hex("feffe9928665731c6d6a8f9467308308")).expect("invalid key");
+        let plaintext = from_hex(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        );
+        let aad = from_hex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let expected = from_hex(
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091\
+             5bc94fbc3221a5db94fae95ae7121a47",
+        );
+        let sealed = gcm.seal(&nonce("cafebabefacedbaddecaf888"), &aad, &plaintext);
+        assert_eq!(expected, sealed);
+        assert_eq!(
+            Some(plaintext),
+            gcm.open(&nonce("cafebabefacedbaddecaf888"), &aad, &sealed)
+        );
+    }
+
+    #[test]
+    fn test_aes256() {
+        // https://csrc.nist.rip/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf (Test Case 16)
+        let gcm = AesGcm::new(&from_hex(
+            "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+        ))
+        .expect("invalid key");
+        let plaintext = from_hex(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        );
+        let aad = from_hex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let expected = from_hex(
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662\
+             76fc6ece0f4e1768cddf8853bb2d551b",
+        );
+        assert_eq!(
+            expected,
+            gcm.seal(&nonce("cafebabefacedbaddecaf888"), &aad, &plaintext)
+        );
+    }
+
+    #[test]
+    fn test_tampered() {
+        let gcm = AesGcm::new(&[7; 16]).expect("invalid key");
+        let mut sealed = gcm.seal(&[1; 12], b"aad", b"saba");
+        assert!(gcm.open(&[1; 12], b"aad", &sealed).is_some());
+        assert!(gcm.open(&[1; 12], b"aae", &sealed).is_none());
+        assert!(gcm.open(&[2; 12], b"aad", &sealed).is_none());
+        sealed[0] ^= 1;
+        assert!(gcm.open(&[1; 12], b"aad", &sealed).is_none());
+        assert!(gcm.open(&[1; 12], b"aad", &sealed[..10]).is_none());
+    }
+}
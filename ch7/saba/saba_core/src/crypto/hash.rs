@@ -0,0 +1,180 @@
+use crate::crypto::sha2::Sha256;
+use crate::crypto::sha2::Sha512;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// TLSの暗号スイートや署名アルゴリズムによって使い分けるハッシュ関数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+
+    pub fn block_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::BLOCK_LEN,
+            HashAlgorithm::Sha384 | HashAlgorithm::Sha512 => Sha512::BLOCK_LEN,
+        }
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha384 => Hasher::Sha512(Sha512::new_384()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    pub fn digest(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        hasher.update(bytes);
+        hasher.finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Sha512(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(hasher) => hasher.finish(),
+            Hasher::Sha512(hasher) => hasher.finish(),
+        }
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc2104
+pub fn hmac(algorithm: HashAlgorithm, key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut key = if key.len() > algorithm.block_len() {
+        algorithm.digest(key)
+    } else {
+        key.to_vec()
+    };
+    key.resize(algorithm.block_len(), 0);
+
+    let mut inner = algorithm.hasher();
+    inner.update(&key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.update(message);
+
+    let mut outer = algorithm.hasher();
+    outer.update(&key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.update(&inner.finish());
+    outer.finish()
+}
+
+/// https://www.rfc-editor.org/rfc/rfc5869#section-2.2
+pub fn hkdf_extract(algorithm: HashAlgorithm, salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+    if salt.is_empty() {
+        hmac(algorithm, &vec![0; algorithm.output_len()], ikm)
+    } else {
+        hmac(algorithm, salt, ikm)
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc5869#section-2.3
+pub fn hkdf_expand(algorithm: HashAlgorithm, prk: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut previous = Vec::new();
+    let mut counter = 1u8;
+    while output.len() < length {
+        let mut message = previous.clone();
+        message.extend_from_slice(info);
+        message.push(counter);
+        previous = hmac(algorithm, prk, &message);
+        output.extend_from_slice(&previous);
+        counter = counter.wrapping_add(1);
+    }
+    output.truncate(length);
+    output
+}
+
+/// 秘密の値を比べるときに、一致しない位置によって時間が変わらないようにする
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    #[test]
+    fn test_hmac() {
+        // https://www.rfc-editor.org/rfc/rfc4231#section-4.2
+        let key = vec![0x0b; 20];
+        assert_eq!(
+            from_hex("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            hmac(HashAlgorithm::Sha256, &key, b"Hi There")
+        );
+        assert_eq!(
+            from_hex("afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6"),
+            hmac(HashAlgorithm::Sha384, &key, b"Hi There")
+        );
+        // ブロック長より長い鍵
+        // https://www.rfc-editor.org/rfc/rfc4231#section-4.7
+        let key = vec![0xaa; 131];
+        assert_eq!(
+            from_hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+            hmac(
+                HashAlgorithm::Sha256,
+                &key,
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )
+        );
+    }
+
+    #[test]
+    fn test_hkdf() {
+        // https://www.rfc-editor.org/rfc/rfc5869#appendix-A.1
+        let ikm = vec![0x0b; 22];
+        let salt = from_hex("000102030405060708090a0b0c");
+        let info = from_hex("f0f1f2f3f4f5f6f7f8f9");
+        let prk = hkdf_extract(HashAlgorithm::Sha256, &salt, &ikm);
+        assert_eq!(
+            from_hex("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5"),
+            prk
+        );
+        assert_eq!(
+            from_hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"),
+            hkdf_expand(HashAlgorithm::Sha256, &prk, &info, 42)
+        );
+
+        // https://www.rfc-editor.org/rfc/rfc5869#appendix-A.3
+        let prk = hkdf_extract(HashAlgorithm::Sha256, &[], &ikm);
+        assert_eq!(
+            from_hex("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"),
+            hkdf_expand(HashAlgorithm::Sha256, &prk, &[], 42)
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"saba", b"saba"));
+        assert!(!constant_time_eq(b"saba", b"sabb"));
+        assert!(!constant_time_eq(b"saba", b"sab"));
+    }
+}
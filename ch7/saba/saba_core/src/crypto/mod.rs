@@ -0,0 +1,9 @@
+pub mod aes;
+pub mod bignum;
+pub mod chacha20poly1305;
+pub mod ec;
+pub mod gcm;
+pub mod hash;
+pub mod rsa;
+pub mod sha2;
+pub mod x25519;
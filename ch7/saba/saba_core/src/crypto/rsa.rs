@@ -0,0 +1,246 @@
+use crate::crypto::bignum::BigUint;
+use crate::crypto::bignum::Modulus;
+use crate::crypto::hash::constant_time_eq;
+use crate::crypto::hash::HashAlgorithm;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// これより短い鍵は、署名を偽造できるおそれがあるので受け付けない
+const MIN_MODULUS_BITS: usize = 1024;
+const MAX_MODULUS_BITS: usize = 8192;
+
+/// 署名に含めるDigestInfoのうち、ハッシュ値の前に付くDERの部分
+/// https://www.rfc-editor.org/rfc/rfc8017#section-9.2
+fn digest_info_prefix(algorithm: HashAlgorithm) -> &'static [u8] {
+    match algorithm {
+        HashAlgorithm::Sha256 => &[
+            0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x01, 0x05, 0x00, 0x04, 0x20,
+        ],
+        HashAlgorithm::Sha384 => &[
+            0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x02, 0x05, 0x00, 0x04, 0x30,
+        ],
+        HashAlgorithm::Sha512 => &[
+            0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x03, 0x05, 0x00, 0x04, 0x40,
+        ],
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc8017#appendix-B.2.1
+fn mgf1(algorithm: HashAlgorithm, seed: &[u8], length: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut counter = 0u32;
+    while output.len() < length {
+        let mut hasher = algorithm.hasher();
+        hasher.update(seed);
+        hasher.update(&counter.to_be_bytes());
+        output.extend_from_slice(&hasher.finish());
+        counter += 1;
+    }
+    output.truncate(length);
+    output
+}
+
+/// 署名の検証だけに使うRSAの公開鍵
+/// https://www.rfc-editor.org/rfc/rfc8017
+#[derive(Debug, Clone)]
+pub struct RsaPublicKey {
+    modulus: Modulus,
+    exponent: BigUint,
+}
+
+impl RsaPublicKey {
+    /// nとeはビッグエンディアンの整数
+    pub fn new(n: &[u8], e: &[u8]) -> Option<Self> {
+        let n = BigUint::from_bytes_be(n);
+        let exponent = BigUint::from_bytes_be(e);
+        if !(MIN_MODULUS_BITS..=MAX_MODULUS_BITS).contains(&n.bits())
+            || exponent.bits() < 2
+            || !exponent.bit(0)
+        {
+            return None;
+        }
+        Some(Self {
+            modulus: Modulus::new(n)?,
+            exponent,
+        })
+    }
+
+    pub fn modulus_bits(&self) -> usize {
+        self.modulus.value().bits()
+    }
+
+    fn len(&self) -> usize {
+        self.modulus_bits().div_ceil(8)
+    }
+
+    /// 署名を公開鍵で変換して、符号化されたメッセージに戻す
+    /// https://www.rfc-editor.org/rfc/rfc8017#section-5.2.2
+    fn recover(&self, signature: &[u8]) -> Option<Vec<u8>> {
+        if signature.len() != self.len() {
+            return None;
+        }
+        let s = BigUint::from_bytes_be(signature);
+        if s >= *self.modulus.value() {
+            return None;
+        }
+        Some(self.modulus.pow(&s, &self.exponent).to_bytes_be(self.len()))
+    }
+
+    /// RSASSA-PKCS1-v1_5の署名を検証する
+    /// https://www.rfc-editor.org/rfc/rfc8017#section-8.2.2
+    pub fn verify_pkcs1(&self, algorithm: HashAlgorithm, message: &[u8], signature: &[u8]) -> bool {
+        let em = match self.recover(signature) {
+            Some(em) => em,
+            None => return false,
+        };
+
+        let prefix = digest_info_prefix(algorithm);
+        let t_len = prefix.len() + algorithm.output_len();
+        if em.len() < t_len + 11 {
+            return false;
+        }
+        let mut expected = vec![0x00, 0x01];
+        expected.resize(em.len() - t_len - 1, 0xff);
+        expected.push(0x00);
+        expected.extend_from_slice(prefix);
+        expected.extend_from_slice(&algorithm.digest(message));
+        constant_time_eq(&em, &expected)
+    }
+
+    /// RSASSA-PSSの署名を検証する。TLSと同じく、ソルトはハッシュ値と同じ長さとする
+    /// https://www.rfc-editor.org/rfc/rfc8017#section-8.1.2
+    pub fn verify_pss(&self, algorithm: HashAlgorithm, message: &[u8], signature: &[u8]) -> bool {
+        let em = match self.recover(signature) {
+            Some(em) => em,
+            None => return false,
+        };
+
+        // https://www.rfc-editor.org/rfc/rfc8017#section-9.1.2
+        let em_bits = self.modulus_bits() - 1;
+        let em_len = em_bits.div_ceil(8);
+        // emBitsが8の倍数なら、先頭の1バイトは0でなければならない
+        let em = if em_len < em.len() {
+            if em[0] != 0 {
+                return false;
+            }
+            &em[1..]
+        } else {
+            &em[..]
+        };
+
+        let h_len = algorithm.output_len();
+        let s_len = h_len;
+        if em_len < h_len + s_len + 2 || em[em_len - 1] != 0xbc {
+            return false;
+        }
+        let (masked_db, rest) = em.split_at(em_len - h_len - 1);
+        let h = &rest[..h_len];
+
+        let unused_bits = 8 * em_len - em_bits;
+        let top_mask = 0xffu8.checked_shr(unused_bits as u32).unwrap_or(0);
+        if masked_db[0] & !top_mask != 0 {
+            return false;
+        }
+        let mut db: Vec<u8> = masked_db
+            .iter()
+            .zip(mgf1(algorithm, h, masked_db.len()))
+            .map(|(a, b)| a ^ b)
+            .collect();
+        db[0] &= top_mask;
+
+        let padding_len = em_len - h_len - s_len - 2;
+        if db[..padding_len].iter().any(|b| *b != 0) || db[padding_len] != 0x01 {
+            return false;
+        }
+        let salt = &db[db.len() - s_len..];
+
+        let mut hasher = algorithm.hasher();
+        hasher.update(&[0; 8]);
+        hasher.update(&algorithm.digest(message));
+        hasher.update(salt);
+        constant_time_eq(&hasher.finish(), h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    // 2048ビットの鍵と、"saba"への署名
+    const MODULUS: &str = "dd668ebc78ff20385bb7c8f9e6dbf0a8a36e58820e6d5909e4fe5dee7260d2409b81f2f850f4762b0f9686923def0ac5\
+         9e4b70cfab62e808520ab2b020cbb3f3b4746b558d37dd9f272ccd90fe51f3f27868dffb8cf776924b29244b29de7d8e\
+         5ad6903aa53a716e9c983a38f57743634a37fac009ff50f70182fa3725b8dc8c884532be85ccf0e087f390e396673458\
+         510befb3fd07a732b0b15a082f53a1478aaafe84cc4c2df495c569679523093064dddcc8913534855a706ea250d64cec\
+         a9335fe032fba24b8b00a160c79e137ebd8b9c92f31183cb102d22c6f299fbda25e452544c30de940c7c72bef6163062\
+         d9528279b901c2a72d71849ef8641bdb";
+
+    const PKCS1_SHA256: &str = "9d636bd5f2347b0ed40c6a3376284e7dc64968daae3ba1c84e3163d2cf022a70b11803e10ddb9310ab9653e4f5da2af7\
+         17664eb71dc7f6385aaf75f9f0ae5d8c757bd4238eb65ea34f14e32db1c0aa8bfbcc913c04490fd9c2484f7df5a42094\
+         220de8e7af658ef87c6d7686ddf082ef6556b4552f56ad6613da1a80f4638d3af8b1936565c101ece04756c93ec3600b\
+         1aca665373b06f26e23a5e613289a85e6bf1543f773b95cc483e92449dd659a844711590f2aa958b0f09cd2c934d50dc\
+         19d4e7148ae6226f108b92ed0b5d7967d3a2726ae458374a40b984d40d1d3ec14fc6d6e1aee05d0f221cd728e67d0514\
+         310b2df582dce957a7af81a374399bcb";
+
+    const PKCS1_SHA512: &str = "5aaf34e17f49da3dc6cc726962c0401aabc47b0f30bec14192ef1e589fd95f9f882b0da43a5dbb4757f2cde145a753d8\
+         2b385116bc52863dc5fc107550f12207c818986c9c902a1115e419dc54ddcd63471a38c3884130935b924cc7756f5965\
+         c70fde2f67d177d58bcaaabb50e27ddc5ceacd8a77c9f401d791b9017f2feaa656c605324ba4d0d07a7111c8c27b5f96\
+         34f0983173e596c73edfad7c89a84cba592a79a948a89774478e5b3acd08e5ff5b1f71ea2f4af2a9c70ced74e5455c09\
+         0d4a04e85cbb107c6bb2b5aa1472251ed333a5def04d2e0799bcf7ba1e41d411a1c0b5e7228d661667da55cd6937bc0a\
+         cfa8f9ec30622be055f9aaa4209a98a2";
+
+    const PSS_SHA256: &str = "10acb2f872566b0dfe07962600a323018cade7d0f181a0d955eb9092e18d16461d60f75cc42687931b23bcfd3c99eacc\
+         b64e9dc99b901879ad7eb20e056a2f502b7278dcd86fdd9c50da4354df97e0ea2ca5c0bc0da742549f12ba319ea693ff\
+         5584a2af7d3dfb82745a205f604960b43195ec079e816b1f3d7a3843fa16aa1c1fe1612b6dceb913e7b9acf6927df74e\
+         0a3056b44ac907125ac2540be6792ea3e7c47baf472a6e40de92d98a67f19473e4f765ac202bb4d8bba1b52319a625ab\
+         69b35e670e008763bb670053f8e918206e314dbe074f210bc28a2af52d99d9de7d8b7e2777f8df61e5686bb26332bcd6\
+         03c5dc12c50fbd63d51eb950b83ea8e0";
+
+    const PSS_SHA384: &str = "dc58ab91844b85e6a8dc75ba14ee5fcc1a52d1083e7de804ea4523268af4feba0cf3c59442407872e3a1599e5033d2e2\
+         8b04913676d082574e42918be8e1626f1bf1619162de35b39162c48d70f1bdcfed2ed945831939527f349a92ed715fad\
+         5d77471f09bfa257a5cb2ae16738bd5f5f9711747e577c7018bee728a92f858b4b601e75d96c1acd233f3249ba29213b\
+         fe050431a67fb3cfe42c6db6a7c7ab8b8d6e395ccb46052abfe3de2f0a048d28350a025c32fd769092111680fd4fb888\
+         19757eb8e0ea657bf963f8d35ac0dabb869887594938fdc0e87e6e3c78334149f76cb8f2f9b24d8e13837956d1378190\
+         6e2e3661fbd12a565094d32e649332a7";
+
+    fn key() -> RsaPublicKey {
+        RsaPublicKey::new(&from_hex(MODULUS), &[0x01, 0x00, 0x01]).expect("invalid key")
+    }
+
+    #[test]
+    fn test_pkcs1() {
+        let key = key();
+        assert_eq!(2048, key.modulus_bits());
+        assert!(key.verify_pkcs1(HashAlgorithm::Sha256, b"saba", &from_hex(PKCS1_SHA256)));
+        assert!(key.verify_pkcs1(HashAlgorithm::Sha512, b"saba", &from_hex(PKCS1_SHA512)));
+        assert!(!key.verify_pkcs1(HashAlgorithm::Sha256, b"sabb", &from_hex(PKCS1_SHA256)));
+        assert!(!key.verify_pkcs1(HashAlgorithm::Sha384, b"saba", &from_hex(PKCS1_SHA256)));
+        assert!(!key.verify_pkcs1(HashAlgorithm::Sha256, b"saba", &from_hex(PSS_SHA256)));
+    }
+
+    #[test]
+    fn test_pss() {
+        let key = key();
+        assert!(key.verify_pss(HashAlgorithm::Sha256, b"saba", &from_hex(PSS_SHA256)));
+        assert!(key.verify_pss(HashAlgorithm::Sha384, b"saba", &from_hex(PSS_SHA384)));
+        assert!(!key.verify_pss(HashAlgorithm::Sha256, b"sabb", &from_hex(PSS_SHA256)));
+        assert!(!key.verify_pss(HashAlgorithm::Sha384, b"saba", &from_hex(PSS_SHA256)));
+        assert!(!key.verify_pss(HashAlgorithm::Sha256, b"saba", &from_hex(PKCS1_SHA256)));
+
+        let mut tampered = from_hex(PSS_SHA256);
+        tampered[100] ^= 1;
+        assert!(!key.verify_pss(HashAlgorithm::Sha256, b"saba", &tampered));
+        assert!(!key.verify_pss(HashAlgorithm::Sha256, b"saba", &tampered[1..]));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        // 短すぎる鍵
+        assert!(RsaPublicKey::new(&[0xff; 64], &[0x01, 0x00, 0x01]).is_none());
+        // 偶数の指数
+        assert!(RsaPublicKey::new(&from_hex(MODULUS), &[0x02]).is_none());
+    }
+}
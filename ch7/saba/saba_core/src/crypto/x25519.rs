@@ -0,0 +1,253 @@
+/// 2^255-19を法とする整数を、51ビットずつ5つに分けて持つ
+#[derive(Debug, Clone, Copy)]
+struct Fe([u64; 5]);
+
+const MASK51: u64 = (1 << 51) - 1;
+
+impl Fe {
+    const ZERO: Fe = Fe([0; 5]);
+    const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+
+    fn from_bytes(bytes: &[u8; 32]) -> Fe {
+        let load = |i: usize| {
+            let mut word = [0u8; 8];
+            let end = (i + 8).min(32);
+            word[..end - i].copy_from_slice(&bytes[i..end]);
+            u64::from_le_bytes(word)
+        };
+        Fe([
+            load(0) & MASK51,
+            (load(6) >> 3) & MASK51,
+            (load(12) >> 6) & MASK51,
+            (load(19) >> 1) & MASK51,
+            // 最上位ビットは無視する
+            (load(24) >> 12) & MASK51,
+        ])
+    }
+
+    fn to_bytes(self) -> [u8; 32] {
+        let mut h = self.carry().0;
+        // h - pが負でなければhをpで割った余りにする
+        let mut q = (h[0] + 19) >> 51;
+        q = (h[1] + q) >> 51;
+        q = (h[2] + q) >> 51;
+        q = (h[3] + q) >> 51;
+        q = (h[4] + q) >> 51;
+        h[0] += 19 * q;
+        for i in 0..4 {
+            h[i + 1] += h[i] >> 51;
+            h[i] &= MASK51;
+        }
+        h[4] &= MASK51;
+
+        let words = [
+            h[0] | (h[1] << 51),
+            (h[1] >> 13) | (h[2] << 38),
+            (h[2] >> 26) | (h[3] << 25),
+            (h[3] >> 39) | (h[4] << 12),
+        ];
+        let mut output = [0u8; 32];
+        for (i, word) in words.iter().enumerate() {
+            output[i * 8..i * 8 + 8].copy_from_slice(&word.to_le_bytes());
+        }
+        output
+    }
+
+    fn carry(self) -> Fe {
+        let mut h = self.0;
+        for _ in 0..2 {
+            for i in 0..4 {
+                h[i + 1] += h[i] >> 51;
+                h[i] &= MASK51;
+            }
+            h[0] += 19 * (h[4] >> 51);
+            h[4] &= MASK51;
+        }
+        Fe(h)
+    }
+
+    fn add(self, other: Fe) -> Fe {
+        let mut h = [0; 5];
+        for (i, v) in h.iter_mut().enumerate() {
+            *v = self.0[i] + other.0[i];
+        }
+        Fe(h).carry()
+    }
+
+    /// 負にならないように2pを足してから引く
+    fn sub(self, other: Fe) -> Fe {
+        const TWO_P: [u64; 5] = [
+            0xfffffffffffda,
+            0xffffffffffffe,
+            0xffffffffffffe,
+            0xffffffffffffe,
+            0xffffffffffffe,
+        ];
+        let mut h = [0; 5];
+        for (i, v) in h.iter_mut().enumerate() {
+            *v = self.0[i] + TWO_P[i] - other.0[i];
+        }
+        Fe(h).carry()
+    }
+
+    fn mul(self, other: Fe) -> Fe {
+        let a = self.0.map(|v| v as u128);
+        let b = other.0.map(|v| v as u128);
+        // 2^255 = 19なので、あふれた分は19倍して下に戻す
+        let b19 = b.map(|v| v * 19);
+        let t = [
+            a[0] * b[0] + a[1] * b19[4] + a[2] * b19[3] + a[3] * b19[2] + a[4] * b19[1],
+            a[0] * b[1] + a[1] * b[0] + a[2] * b19[4] + a[3] * b19[3] + a[4] * b19[2],
+            a[0] * b[2] + a[1] * b[1] + a[2] * b[0] + a[3] * b19[4] + a[4] * b19[3],
+            a[0] * b[3] + a[1] * b[2] + a[2] * b[1] + a[3] * b[0] + a[4] * b19[4],
+            a[0] * b[4] + a[1] * b[3] + a[2] * b[2] + a[3] * b[1] + a[4] * b[0],
+        ];
+        let mut h = [0u64; 5];
+        let mut carry = 0u128;
+        for i in 0..5 {
+            let v = t[i] + carry;
+            h[i] = (v as u64) & MASK51;
+            carry = v >> 51;
+        }
+        h[0] += (carry * 19) as u64;
+        Fe(h).carry()
+    }
+
+    fn square(self) -> Fe {
+        self.mul(self)
+    }
+
+    fn mul_small(self, n: u64) -> Fe {
+        let mut h = [0u64; 5];
+        let mut carry = 0u128;
+        for (limb, a) in h.iter_mut().zip(self.0) {
+            let v = a as u128 * n as u128 + carry;
+            *limb = (v as u64) & MASK51;
+            carry = v >> 51;
+        }
+        h[0] += (carry * 19) as u64;
+        Fe(h).carry()
+    }
+
+    /// フェルマーの小定理により、x^(p-2)を求める
+    fn invert(self) -> Fe {
+        let mut result = Fe::ONE;
+        // p - 2 = 2^255 - 21
+        for i in (0..255).rev() {
+            result = result.square();
+            let bit = if i >= 5 { 1 } else { (0b01011 >> i) & 1 };
+            if bit == 1 {
+                result = result.mul(self);
+            }
+        }
+        result
+    }
+
+    /// swapが1の場合だけ入れ替える
+    fn cswap(a: &mut Fe, b: &mut Fe, swap: u64) {
+        let mask = 0u64.wrapping_sub(swap);
+        for i in 0..5 {
+            let t = mask & (a.0[i] ^ b.0[i]);
+            a.0[i] ^= t;
+            b.0[i] ^= t;
+        }
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc7748#section-5
+pub fn x25519(scalar: &[u8; 32], u: &[u8; 32]) -> [u8; 32] {
+    let mut k = *scalar;
+    k[0] &= 248;
+    k[31] &= 127;
+    k[31] |= 64;
+
+    let x1 = Fe::from_bytes(u);
+    let mut x2 = Fe::ONE;
+    let mut z2 = Fe::ZERO;
+    let mut x3 = x1;
+    let mut z3 = Fe::ONE;
+    let mut swap = 0;
+
+    for t in (0..255).rev() {
+        let k_t = ((k[t / 8] >> (t % 8)) & 1) as u64;
+        swap ^= k_t;
+        Fe::cswap(&mut x2, &mut x3, swap);
+        Fe::cswap(&mut z2, &mut z3, swap);
+        swap = k_t;
+
+        let a = x2.add(z2);
+        let aa = a.square();
+        let b = x2.sub(z2);
+        let bb = b.square();
+        let e = aa.sub(bb);
+        let c = x3.add(z3);
+        let d = x3.sub(z3);
+        let da = d.mul(a);
+        let cb = c.mul(b);
+        x3 = da.add(cb).square();
+        z3 = x1.mul(da.sub(cb).square());
+        x2 = aa.mul(bb);
+        z2 = e.mul(aa.add(e.mul_small(121665)));
+    }
+    Fe::cswap(&mut x2, &mut x3, swap);
+    Fe::cswap(&mut z2, &mut z3, swap);
+
+    x2.mul(z2.invert()).to_bytes()
+}
+
+/// 秘密鍵に対応する公開鍵を求める
+pub fn public_key(private_key: &[u8; 32]) -> [u8; 32] {
+    let mut base = [0u8; 32];
+    base[0] = 9;
+    x25519(private_key, &base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    fn bytes(hex: &str) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        output.copy_from_slice(&from_hex(hex));
+        output
+    }
+
+    #[test]
+    fn test_x25519() {
+        // https://www.rfc-editor.org/rfc/rfc7748#section-5.2
+        assert_eq!(
+            bytes("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552"),
+            x25519(
+                &bytes("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4"),
+                &bytes("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c")
+            )
+        );
+        assert_eq!(
+            bytes("95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957"),
+            x25519(
+                &bytes("4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d"),
+                &bytes("e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493")
+            )
+        );
+    }
+
+    #[test]
+    fn test_key_agreement() {
+        // https://www.rfc-editor.org/rfc/rfc7748#section-6.1
+        let alice = bytes("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = bytes("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        assert_eq!(
+            bytes("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"),
+            public_key(&alice)
+        );
+        assert_eq!(
+            bytes("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f"),
+            public_key(&bob)
+        );
+        assert_eq!(
+            bytes("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742"),
+            x25519(&alice, &public_key(&bob))
+        );
+    }
+}
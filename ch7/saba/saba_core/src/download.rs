@@ -0,0 +1,846 @@
+use crate::error::Error;
+use crate::http::client::BodyReader;
+use crate::http::content_disposition::ContentDisposition;
+use crate::http::request::HttpRequest;
+use crate::http::HttpResponse;
+use crate::url::percent_encoding::percent_decode_str;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// 1回の書き込みで保存するバイト数
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// ファイル名が決まらない場合に使う名前
+const DEFAULT_FILE_NAME: &str = "download";
+
+/// ダウンロードの状態や進み具合が変わるたびに呼ばれる関数
+pub type ProgressListener = Rc<dyn Fn(&Download)>;
+
+/// ダウンロードしたファイルの保存方法を抽象化する
+pub trait DownloadStorage: Debug {
+    /// 同じ名前のファイルがある場合は空にする
+    fn create(&self, name: &str) -> Result<(), Error>;
+
+    fn append(&self, name: &str, bytes: &[u8]) -> Result<(), Error>;
+
+    /// 保存済みのバイト数。ダウンロードを再開する位置に使う
+    fn size(&self, name: &str) -> Result<u64, Error>;
+}
+
+/// メモリ上にファイルを保存する。ファイルシステムがない環境やテストで使う
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    files: RefCell<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            files: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn read(&self, name: &str) -> Option<Vec<u8>> {
+        self.files.borrow().get(name).cloned()
+    }
+}
+
+impl DownloadStorage for MemoryStorage {
+    fn create(&self, name: &str) -> Result<(), Error> {
+        self.files.borrow_mut().insert(name.to_string(), Vec::new());
+        Ok(())
+    }
+
+    fn append(&self, name: &str, bytes: &[u8]) -> Result<(), Error> {
+        match self.files.borrow_mut().get_mut(name) {
+            Some(file) => {
+                file.extend_from_slice(bytes);
+                Ok(())
+            }
+            None => Err(Error::Other(format!("no such file: {}", name))),
+        }
+    }
+
+    fn size(&self, name: &str) -> Result<u64, Error> {
+        match self.files.borrow().get(name) {
+            Some(file) => Ok(file.len() as u64),
+            None => Err(Error::Other(format!("no such file: {}", name))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadState {
+    InProgress,
+    /// 中断した。保存済みの続きから再開できる
+    Cancelled,
+    Completed,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Download {
+    id: usize,
+    url: Option<Url>,
+    file_name: String,
+    mime_type: String,
+    received: u64,
+    total: Option<u64>,
+    state: DownloadState,
+    /// 再開するときにIf-Rangeで送る、強いETagまたはLast-Modified
+    validator: Option<String>,
+    /// 受信したが、まだ保存していないボディ
+    pending: Vec<u8>,
+    /// ボディの続き。すべて受信し終えたらNoneになる
+    body: Option<Rc<RefCell<Box<dyn BodyReader>>>>,
+}
+
+impl Download {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    pub fn file_name(&self) -> String {
+        self.file_name.clone()
+    }
+
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    /// 保存済みのバイト数
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// ファイル全体のバイト数。わからない場合はNone
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    pub fn state(&self) -> DownloadState {
+        self.state.clone()
+    }
+
+    /// 進み具合をパーセントで返す
+    pub fn progress(&self) -> Option<u64> {
+        match self.total {
+            Some(0) => Some(100),
+            Some(total) => Some(self.received.min(total) * 100 / total),
+            None => None,
+        }
+    }
+}
+
+/// "bytes 100-199/200"のようなContent-Rangeヘッダの値から、最初と最後の位置と全体の長さを返す
+/// https://www.rfc-editor.org/rfc/rfc9110#section-14.4
+fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (range, complete_length) = range.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    let first = first.trim().parse().ok()?;
+    let last = last.trim().parse().ok()?;
+    let complete_length = match complete_length.trim() {
+        "*" => None,
+        length => Some(length.parse().ok()?),
+    };
+    if last < first {
+        return None;
+    }
+    Some((first, last, complete_length))
+}
+
+/// 展開する前のボディの長さなので、Content-Encodingがある場合は使わない
+fn content_length(response: &HttpResponse) -> Option<u64> {
+    if response.header_value("Content-Encoding").is_ok() {
+        return None;
+    }
+    response
+        .header_value("Content-Length")
+        .ok()
+        .and_then(|length| length.trim().parse().ok())
+}
+
+/// If-Rangeに使う値。弱いETagは使えないので、その場合はLast-Modifiedを使う
+/// https://www.rfc-editor.org/rfc/rfc9110#section-13.1.5
+fn validator(response: &HttpResponse) -> Option<String> {
+    if let Ok(etag) = response.header_value("ETag") {
+        if !etag.starts_with("W/") {
+            return Some(etag);
+        }
+    }
+    response.header_value("Last-Modified").ok()
+}
+
+/// パスの区切りやファイル名に使えない文字を"_"に置き換える
+fn sanitize_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or("").trim();
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if name.is_empty() || name == "." || name == ".." {
+        return DEFAULT_FILE_NAME.to_string();
+    }
+    name
+}
+
+/// Content-Dispositionのfilenameか、URLのパスの最後の部分をファイル名にする
+/// https://html.spec.whatwg.org/multipage/links.html#as-a-download
+fn suggested_file_name(response: &HttpResponse) -> String {
+    let disposition = response
+        .header_value("Content-Disposition")
+        .ok()
+        .and_then(|value| ContentDisposition::parse(&value));
+    if let Some(filename) = disposition.as_ref().and_then(|d| d.filename()) {
+        return sanitize_file_name(filename);
+    }
+    match response.url() {
+        Some(url) => sanitize_file_name(&percent_decode_str(&url.path())),
+        None => DEFAULT_FILE_NAME.to_string(),
+    }
+}
+
+/// 表示できないレスポンスのボディを、少しずつストレージに保存する
+/// https://html.spec.whatwg.org/multipage/links.html#downloading-resources
+#[derive(Clone)]
+pub struct DownloadManager {
+    storage: Rc<dyn DownloadStorage>,
+    downloads: Vec<Download>,
+    next_id: usize,
+    listener: Option<ProgressListener>,
+}
+
+impl DownloadManager {
+    pub fn new(storage: Rc<dyn DownloadStorage>) -> Self {
+        Self {
+            storage,
+            downloads: Vec::new(),
+            next_id: 0,
+            listener: None,
+        }
+    }
+
+    /// これから始めるダウンロードを保存するストレージを設定する
+    pub fn set_storage(&mut self, storage: Rc<dyn DownloadStorage>) {
+        self.storage = storage;
+    }
+
+    /// ダウンロードの状態や進み具合が変わるたびに呼ばれる関数を設定する
+    pub fn set_progress_listener(&mut self, listener: ProgressListener) {
+        self.listener = Some(listener);
+    }
+
+    /// 始めた順に返す
+    pub fn downloads(&self) -> Vec<Download> {
+        self.downloads.clone()
+    }
+
+    pub fn download(&self, id: usize) -> Option<Download> {
+        self.downloads.iter().find(|d| d.id == id).cloned()
+    }
+
+    /// 保存中のダウンロードがあるか
+    pub fn is_active(&self) -> bool {
+        self.downloads
+            .iter()
+            .any(|d| d.state == DownloadState::InProgress)
+    }
+
+    fn notify(&self, index: usize) {
+        if let Some(listener) = &self.listener {
+            listener(&self.downloads[index]);
+        }
+    }
+
+    fn index(&self, id: usize) -> Result<usize, Error> {
+        self.downloads
+            .iter()
+            .position(|d| d.id == id)
+            .ok_or(Error::Other(format!("unknown download: {}", id)))
+    }
+
+    /// 同じ名前のファイルがある場合は、"a (1).zip"のように番号を付ける
+    fn unique_file_name(&self, name: String) -> String {
+        let exists = |name: &str| self.downloads.iter().any(|d| d.file_name == name);
+        if !exists(&name) {
+            return name;
+        }
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (name.as_str(), String::new()),
+        };
+        let mut n = 1;
+        loop {
+            let candidate = format!("{} ({}){}", stem, n, extension);
+            if !exists(&candidate) {
+                return candidate;
+            }
+            n += 1;
+        }
+    }
+
+    /// ボディをすべて受信したレスポンスのダウンロードを始めて、そのIDを返す。ボディは
+    /// process()で保存する
+    pub fn start(&mut self, response: HttpResponse) -> usize {
+        let total = response.body().len() as u64;
+        self.begin(response, None, Some(total))
+    }
+
+    /// ヘッダと受信済みのボディの先頭だけがあるレスポンスのダウンロードを始めて、そのIDを
+    /// 返す。ボディの続きはprocess()でbodyから受信した分ずつ保存する
+    pub fn start_streaming(&mut self, response: HttpResponse, body: Box<dyn BodyReader>) -> usize {
+        let total = content_length(&response);
+        self.begin(response, Some(body), total)
+    }
+
+    fn begin(
+        &mut self,
+        response: HttpResponse,
+        body: Option<Box<dyn BodyReader>>,
+        total: Option<u64>,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let file_name = self.unique_file_name(suggested_file_name(&response));
+        let state = match self.storage.create(&file_name) {
+            Ok(()) => DownloadState::InProgress,
+            Err(e) => DownloadState::Failed(format!("{:?}", e)),
+        };
+        self.downloads.push(Download {
+            id,
+            url: response.url(),
+            file_name,
+            mime_type: response.computed_mime_type().essence(),
+            received: 0,
+            total,
+            state,
+            validator: validator(&response),
+            pending: response.body(),
+            body: body.map(|body| Rc::new(RefCell::new(body))),
+        });
+        self.notify(self.downloads.len() - 1);
+        id
+    }
+
+    /// 保存中のダウンロードごとに、ボディの続きを受信して、次の部分をストレージに書き込む。
+    /// まだ保存中のダウンロードが残っている場合はtrueを返す
+    pub fn process(&mut self) -> bool {
+        for index in 0..self.downloads.len() {
+            if self.downloads[index].state != DownloadState::InProgress {
+                continue;
+            }
+
+            let download = &mut self.downloads[index];
+            let mut error = None;
+            if download.pending.len() < CHUNK_SIZE {
+                let received = download.body.as_ref().map(|body| body.borrow_mut().read());
+                match received {
+                    Some(Ok(Some(bytes))) => download.pending.extend_from_slice(&bytes),
+                    Some(Ok(None)) => download.body = None,
+                    // 受信済みの部分は保存して、そこから再開できるようにする
+                    Some(Err(e)) => {
+                        download.body = None;
+                        error = Some(e);
+                    }
+                    None => {}
+                }
+            }
+
+            let length = match error {
+                Some(_) => download.pending.len(),
+                None => download.pending.len().min(CHUNK_SIZE),
+            };
+            let chunk: Vec<u8> = download.pending.drain(..length).collect();
+            match self.storage.append(&download.file_name, &chunk) {
+                Ok(()) => {
+                    download.received += length as u64;
+                    if let Some(e) = error {
+                        download.state = DownloadState::Failed(format!("{:?}", e));
+                    } else if download.pending.is_empty() && download.body.is_none() {
+                        download.state = DownloadState::Completed;
+                    }
+                }
+                Err(e) => {
+                    download.pending = Vec::new();
+                    download.body = None;
+                    download.state = DownloadState::Failed(format!("{:?}", e));
+                }
+            }
+            self.notify(index);
+        }
+        self.is_active()
+    }
+
+    /// 保存中のダウンロードを中断する。保存済みの部分は残す
+    pub fn cancel(&mut self, id: usize) -> bool {
+        let index = match self.index(id) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+        if self.downloads[index].state != DownloadState::InProgress {
+            return false;
+        }
+        self.downloads[index].pending = Vec::new();
+        self.downloads[index].body = None;
+        self.downloads[index].state = DownloadState::Cancelled;
+        self.notify(index);
+        true
+    }
+
+    /// 中断または失敗したダウンロードを、保存済みの続きから再開する。sendには、Rangeヘッダを
+    /// 付けたリクエストを送信し、ヘッダを受信した時点でレスポンスとボディを返す関数を渡す。
+    /// ファイルが変わっていた場合は、最初から保存し直す
+    /// https://www.rfc-editor.org/rfc/rfc9110#section-14.2
+    pub fn resume<F>(&mut self, id: usize, send: F) -> Result<(), Error>
+    where
+        F: FnOnce(&HttpRequest) -> Result<(HttpResponse, Box<dyn BodyReader>), Error>,
+    {
+        let index = self.index(id)?;
+        let download = self.downloads[index].clone();
+        if !matches!(
+            download.state,
+            DownloadState::Cancelled | DownloadState::Failed(_)
+        ) {
+            return Err(Error::Other(format!("download {} is not resumable", id)));
+        }
+        let url = match download.url {
+            Some(url) => url,
+            None => return Err(Error::Other(format!("download {} has no url", id))),
+        };
+
+        let offset = self.storage.size(&download.file_name)?;
+        let mut request = HttpRequest::get(url).with_header("Range", &format!("bytes={}-", offset));
+        if let Some(validator) = &download.validator {
+            request.set_header("If-Range", validator);
+        }
+
+        let result =
+            send(&request).and_then(|(response, body)| self.restart(index, offset, response, body));
+        if let Err(e) = &result {
+            self.downloads[index].state = DownloadState::Failed(format!("{:?}", e));
+        }
+        self.notify(index);
+        result
+    }
+
+    fn restart(
+        &mut self,
+        index: usize,
+        offset: u64,
+        response: HttpResponse,
+        body: Box<dyn BodyReader>,
+    ) -> Result<(), Error> {
+        let download = &mut self.downloads[index];
+        match response.status_code() {
+            // 保存済みの続きを受け取った
+            206 => {
+                let content_range = response.header_value("Content-Range").unwrap_or_default();
+                let (first, _, complete_length) = parse_content_range(&content_range).ok_or(
+                    Error::Network(format!("invalid Content-Range: {:?}", content_range)),
+                )?;
+                if first != offset {
+                    return Err(Error::Network(format!(
+                        "unexpected range start {} (expected {})",
+                        first, offset
+                    )));
+                }
+                download.received = offset;
+                download.total = complete_length.or(content_length(&response).map(|n| offset + n));
+                download.pending = response.body();
+            }
+            // ファイルが変わっていたか、サーバーがRangeに対応していない
+            200 => {
+                self.storage.create(&download.file_name)?;
+                download.received = 0;
+                download.total = content_length(&response);
+                download.validator = validator(&response);
+                download.pending = response.body();
+            }
+            // すでにすべて保存している
+            416 if download.total == Some(offset) => {
+                download.received = offset;
+                download.state = DownloadState::Completed;
+                return Ok(());
+            }
+            status_code => {
+                return Err(Error::Network(format!(
+                    "unexpected status code {} for a range request",
+                    status_code
+                )));
+            }
+        }
+        download.body = Some(Rc::new(RefCell::new(body)));
+        download.state = DownloadState::InProgress;
+        Ok(())
+    }
+
+    /// about:downloadsのHTML
+    pub fn downloads_page(&self) -> String {
+        let mut html = String::from("<html><head></head><body><h1>Downloads</h1>");
+        if self.downloads.is_empty() {
+            html.push_str("<p>No downloads</p>");
+        }
+        for download in &self.downloads {
+            let state = match &download.state {
+                DownloadState::InProgress => match download.progress() {
+                    Some(progress) => format!("in progress ({}%)", progress),
+                    None => "in progress".to_string(),
+                },
+                DownloadState::Cancelled => "cancelled".to_string(),
+                DownloadState::Completed => "completed".to_string(),
+                DownloadState::Failed(_) => "failed".to_string(),
+            };
+            let size = match download.total {
+                Some(total) => format!("{}/{} bytes", download.received, total),
+                None => format!("{} bytes", download.received),
+            };
+            html.push_str(&format!(
+                "<p>{}: {}, {}</p>",
+                download.file_name, state, size
+            ));
+        }
+        html.push_str("</body></html>");
+        html
+    }
+}
+
+impl Debug for DownloadManager {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.debug_struct("DownloadManager")
+            .field("storage", &self.storage)
+            .field("downloads", &self.downloads)
+            .field("next_id", &self.next_id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::CompleteBody;
+    use crate::http::Header;
+    use alloc::vec;
+
+    fn response(
+        status_code: u16,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> HttpResponse {
+        let mut response = HttpResponse::from_parts(
+            status_code,
+            "",
+            headers
+                .iter()
+                .map(|(name, value)| Header::new(name.to_string(), value.to_string()))
+                .collect(),
+            body.to_vec(),
+        );
+        response.set_url(
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        );
+        response
+    }
+
+    /// ヘッダを受信した時点のように、ボディをBodyReaderに移す
+    fn opened(
+        mut response: HttpResponse,
+        chunk_size: usize,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let body = response.take_body();
+        Ok((
+            response,
+            Box::new(CompleteBody::new(body).with_chunk_size(chunk_size)),
+        ))
+    }
+
+    fn manager() -> (DownloadManager, Rc<MemoryStorage>) {
+        let storage = Rc::new(MemoryStorage::new());
+        (DownloadManager::new(storage.clone()), storage)
+    }
+
+    #[test]
+    fn test_file_name() {
+        let (mut manager, _) = manager();
+        let id = manager.start(response(
+            200,
+            "http://example.com/files/a%20b.zip",
+            &[],
+            b"",
+        ));
+        assert_eq!(
+            "a b.zip",
+            manager.download(id).expect("no download").file_name()
+        );
+
+        let id = manager.start(response(
+            200,
+            "http://example.com/files/get?id=1",
+            &[("Content-Disposition", "attachment; filename=\"../a b.zip\"")],
+            b"",
+        ));
+        assert_eq!(
+            "a b (1).zip",
+            manager.download(id).expect("no download").file_name()
+        );
+
+        let id = manager.start(response(200, "http://example.com/", &[], b""));
+        assert_eq!(
+            "download",
+            manager.download(id).expect("no download").file_name()
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let (mut manager, storage) = manager();
+        let progress = Rc::new(RefCell::new(Vec::new()));
+        let recorder = progress.clone();
+        manager.set_progress_listener(Rc::new(move |d: &Download| {
+            recorder.borrow_mut().push((d.received(), d.state()))
+        }));
+
+        let body = vec![0xAB; CHUNK_SIZE * 2 + 10];
+        let id = manager.start(response(200, "http://example.com/a.bin", &[], &body));
+        assert!(manager.is_active());
+        while manager.process() {}
+
+        assert_eq!(
+            vec![
+                (0, DownloadState::InProgress),
+                (CHUNK_SIZE as u64, DownloadState::InProgress),
+                (CHUNK_SIZE as u64 * 2, DownloadState::InProgress),
+                (body.len() as u64, DownloadState::Completed),
+            ],
+            *progress.borrow()
+        );
+        let download = manager.download(id).expect("no download");
+        assert_eq!(Some(100), download.progress());
+        assert_eq!(Some(body), storage.read("a.bin"));
+        assert!(manager
+            .downloads_page()
+            .contains("a.bin: completed, 32778/32778 bytes"));
+    }
+
+    #[test]
+    fn test_streaming_progress() {
+        let (mut manager, storage) = manager();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let recorder = received.clone();
+        manager.set_progress_listener(Rc::new(move |d: &Download| {
+            recorder.borrow_mut().push((d.received(), d.progress()))
+        }));
+
+        // ボディは受信した分ずつ保存するので、受信している間も進み具合が変わる
+        let body = vec![0xCD; 1000];
+        let (response, reader) = opened(
+            response(
+                200,
+                "http://example.com/a.bin",
+                &[("Content-Length", "1000")],
+                &body,
+            ),
+            400,
+        )
+        .expect("failed to open");
+        let id = manager.start_streaming(response, reader);
+        while manager.process() {}
+
+        assert_eq!(
+            vec![
+                (0, Some(0)),
+                (400, Some(40)),
+                (800, Some(80)),
+                (1000, Some(100)),
+                (1000, Some(100)),
+            ],
+            *received.borrow()
+        );
+        assert_eq!(
+            DownloadState::Completed,
+            manager.download(id).expect("no download").state()
+        );
+        assert_eq!(Some(body), storage.read("a.bin"));
+    }
+
+    #[test]
+    fn test_streaming_error() {
+        #[derive(Debug)]
+        struct ResetBody;
+
+        impl BodyReader for ResetBody {
+            fn read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+                Err(Error::ConnectionReset("connection reset".to_string()))
+            }
+        }
+
+        let (mut manager, storage) = manager();
+        let id = manager.start_streaming(
+            response(200, "http://example.com/a.bin", &[], b"head"),
+            Box::new(ResetBody),
+        );
+        assert!(!manager.process());
+        assert!(matches!(
+            manager.download(id).expect("no download").state(),
+            DownloadState::Failed(_)
+        ));
+        // 受信済みの部分は保存してあるので、そこから再開できる
+        assert_eq!(Some(b"head".to_vec()), storage.read("a.bin"));
+        assert_eq!(4, manager.download(id).expect("no download").received());
+    }
+
+    #[test]
+    fn test_cancel_and_resume() {
+        let (mut manager, storage) = manager();
+        let body: Vec<u8> = (0..CHUNK_SIZE * 2).map(|i| i as u8).collect();
+        let id = manager.start(response(
+            200,
+            "http://example.com/a.bin",
+            &[("ETag", "\"v1\"")],
+            &body,
+        ));
+        manager.process();
+        assert!(manager.cancel(id));
+        assert!(!manager.cancel(id));
+        assert!(!manager.process());
+        assert_eq!(
+            DownloadState::Cancelled,
+            manager.download(id).expect("no download").state()
+        );
+        assert_eq!(
+            Some(50),
+            manager.download(id).expect("no download").progress()
+        );
+
+        // 保存済みの続きをRangeで要求する
+        let rest = body[CHUNK_SIZE..].to_vec();
+        manager
+            .resume(id, |request| {
+                assert_eq!(
+                    Some(format!("bytes={}-", CHUNK_SIZE)),
+                    request.header_value("Range")
+                );
+                assert_eq!(Some("\"v1\"".to_string()), request.header_value("If-Range"));
+                let content_range =
+                    format!("bytes {}-{}/{}", CHUNK_SIZE, body.len() - 1, body.len());
+                opened(
+                    response(
+                        206,
+                        "http://example.com/a.bin",
+                        &[("Content-Range", &content_range)],
+                        &rest,
+                    ),
+                    CHUNK_SIZE,
+                )
+            })
+            .expect("failed to resume");
+        while manager.process() {}
+        assert_eq!(
+            DownloadState::Completed,
+            manager.download(id).expect("no download").state()
+        );
+        assert_eq!(Some(body.clone()), storage.read("a.bin"));
+
+        // 完了したダウンロードは再開できない
+        assert!(manager.resume(id, |_| unreachable!()).is_err());
+    }
+
+    #[test]
+    fn test_resume_changed_file() {
+        let (mut manager, storage) = manager();
+        let id = manager.start(response(
+            200,
+            "http://example.com/a.bin",
+            &[
+                ("ETag", "W/\"weak\""),
+                ("Last-Modified", "Thu, 01 Jan 1970 00:00:00 GMT"),
+            ],
+            &[1; CHUNK_SIZE * 2],
+        ));
+        manager.process();
+        manager.cancel(id);
+
+        // If-Rangeの条件に合わない場合は、ファイル全体が返ってくる
+        manager
+            .resume(id, |request| {
+                assert_eq!(
+                    Some("Thu, 01 Jan 1970 00:00:00 GMT".to_string()),
+                    request.header_value("If-Range")
+                );
+                opened(
+                    response(
+                        200,
+                        "http://example.com/a.bin",
+                        &[("Content-Length", "3")],
+                        b"new",
+                    ),
+                    CHUNK_SIZE,
+                )
+            })
+            .expect("failed to resume");
+        while manager.process() {}
+        assert_eq!(Some(b"new".to_vec()), storage.read("a.bin"));
+        assert_eq!(Some(3), manager.download(id).expect("no download").total());
+
+        // 範囲の開始位置が合わない場合や、ネットワークのエラーは失敗として記録する
+        let id = manager.start(response(
+            200,
+            "http://example.com/b.bin",
+            &[],
+            &[1; CHUNK_SIZE * 2],
+        ));
+        manager.process();
+        manager.cancel(id);
+        assert!(manager
+            .resume(id, |_| {
+                opened(
+                    response(
+                        206,
+                        "http://example.com/b.bin",
+                        &[("Content-Range", "bytes 0-9/20")],
+                        &[0; 10],
+                    ),
+                    CHUNK_SIZE,
+                )
+            })
+            .is_err());
+        assert!(matches!(
+            manager.download(id).expect("no download").state(),
+            DownloadState::Failed(_)
+        ));
+        assert!(manager
+            .resume(id, |_| Err(Error::Network("connection reset".to_string())))
+            .is_err());
+        assert_eq!(Some(CHUNK_SIZE as u64), storage.size("b.bin").ok());
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(Some((0, 9, Some(20))), parse_content_range("bytes 0-9/20"));
+        assert_eq!(Some((10, 19, None)), parse_content_range("bytes 10-19/*"));
+        assert_eq!(None, parse_content_range("bytes */20"));
+        assert_eq!(None, parse_content_range("bytes 9-0/20"));
+        assert_eq!(None, parse_content_range("items 0-9/20"));
+    }
+}
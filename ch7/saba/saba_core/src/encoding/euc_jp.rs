@@ -0,0 +1,96 @@
+use crate::encoding::jis0208;
+use crate::encoding::REPLACEMENT_CHARACTER;
+use alloc::string::String;
+
+/// JIS X 0212（補助漢字）の対応表は持っていないので、0x8Fで始まる3バイトの文字はU+FFFDに
+/// 置き換える
+/// https://encoding.spec.whatwg.org/#euc-jp-decoder
+pub fn decode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len());
+    let mut lead: u8 = 0;
+    let mut jis0212 = false;
+
+    for &byte in bytes {
+        if lead == 0x8E && (0xA1..=0xDF).contains(&byte) {
+            lead = 0;
+            // 半角カタカナ
+            if let Some(c) = char::from_u32(0xFF61 + byte as u32 - 0xA1) {
+                output.push(c);
+            }
+            continue;
+        }
+
+        if lead == 0x8F && (0xA1..=0xFE).contains(&byte) {
+            jis0212 = true;
+            lead = byte;
+            continue;
+        }
+
+        if lead != 0 {
+            let lead_byte = lead;
+            lead = 0;
+            let is_jis0212 = jis0212;
+            jis0212 = false;
+
+            if (0xA1..=0xFE).contains(&lead_byte) && (0xA1..=0xFE).contains(&byte) {
+                let pointer = (lead_byte - 0xA1) as usize * 94 + (byte - 0xA1) as usize;
+                if !is_jis0212 {
+                    if let Some(c) = jis0208::code_point(pointer) {
+                        output.push(c);
+                        continue;
+                    }
+                }
+            }
+
+            output.push(REPLACEMENT_CHARACTER);
+            // 2バイト目がASCIIの場合は、その文字を捨てずに出力する
+            if byte.is_ascii() {
+                output.push(byte as char);
+            }
+            continue;
+        }
+
+        match byte {
+            0x00..=0x7F => output.push(byte as char),
+            0x8E | 0x8F | 0xA1..=0xFE => lead = byte,
+            _ => output.push(REPLACEMENT_CHARACTER),
+        }
+    }
+
+    if lead != 0 {
+        output.push(REPLACEMENT_CHARACTER);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_decode() {
+        // "こんにちは、世界"
+        let bytes = b"\xA4\xB3\xA4\xF3\xA4\xCB\xA4\xC1\xA4\xCF\xA1\xA2\xC0\xA4\xB3\xA6";
+        assert_eq!("こんにちは、世界".to_string(), decode(bytes));
+    }
+
+    #[test]
+    fn test_decode_ascii_and_halfwidth_katakana() {
+        assert_eq!(
+            "<p>ｻﾊﾞ</p>".to_string(),
+            decode(b"<p>\x8E\xBB\x8E\xCA\x8E\xDE</p>")
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!("\u{FFFD}a".to_string(), decode(b"\xA4a"));
+        assert_eq!("\u{FFFD}".to_string(), decode(b"\x80"));
+        // JIS X 0212の文字
+        assert_eq!("\u{FFFD}b".to_string(), decode(b"\x8F\xB0\xA1b"));
+        // 途中で終わっている
+        assert_eq!("a\u{FFFD}".to_string(), decode(b"a\xA4"));
+    }
+}
@@ -0,0 +1,238 @@
+use crate::encoding::jis0208;
+use crate::encoding::REPLACEMENT_CHARACTER;
+use alloc::string::String;
+
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ascii,
+    Roman,
+    Katakana,
+    LeadByte,
+    TrailByte,
+    EscapeStart,
+    Escape,
+}
+
+const ESC: u8 = 0x1B;
+
+/// 途中の状態を保ちながら、区切って届いたバイト列を順にデコードする
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    state: State,
+    // エスケープシーケンスの後に戻る状態
+    output_state: State,
+    lead: u8,
+    // エスケープシーケンスが連続した場合はエラーにする
+    output_flag: bool,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Ascii,
+            output_state: State::Ascii,
+            lead: 0,
+            output_flag: false,
+        }
+    }
+
+    /// エスケープシーケンスはもう一度処理することがあるので、bytesはエスケープシーケンスの途中で
+    /// 終わってはいけない
+    pub fn push(&mut self, bytes: &[u8], output: &mut String) {
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i];
+            i += 1;
+
+            match self.state {
+                State::Ascii | State::Roman => match byte {
+                    ESC => self.state = State::EscapeStart,
+                    0x0E | 0x0F => {
+                        self.output_flag = false;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                    0x5C if self.state == State::Roman => {
+                        self.output_flag = false;
+                        output.push('\u{00A5}');
+                    }
+                    0x7E if self.state == State::Roman => {
+                        self.output_flag = false;
+                        output.push('\u{203E}');
+                    }
+                    0x00..=0x7F => {
+                        self.output_flag = false;
+                        output.push(byte as char);
+                    }
+                    _ => {
+                        self.output_flag = false;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                },
+                State::Katakana => match byte {
+                    ESC => self.state = State::EscapeStart,
+                    0x21..=0x5F => {
+                        self.output_flag = false;
+                        if let Some(c) = char::from_u32(0xFF61 - 0x21 + byte as u32) {
+                            output.push(c);
+                        }
+                    }
+                    _ => {
+                        self.output_flag = false;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                },
+                State::LeadByte => match byte {
+                    ESC => self.state = State::EscapeStart,
+                    0x21..=0x7E => {
+                        self.output_flag = false;
+                        self.lead = byte;
+                        self.state = State::TrailByte;
+                    }
+                    _ => {
+                        self.output_flag = false;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                },
+                State::TrailByte => match byte {
+                    ESC => {
+                        self.state = State::EscapeStart;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                    0x21..=0x7E => {
+                        self.state = State::LeadByte;
+                        let pointer = (self.lead - 0x21) as usize * 94 + (byte - 0x21) as usize;
+                        output.push(jis0208::code_point(pointer).unwrap_or(REPLACEMENT_CHARACTER));
+                    }
+                    _ => {
+                        self.state = State::LeadByte;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                },
+                State::EscapeStart => {
+                    if byte == 0x24 || byte == 0x28 {
+                        self.lead = byte;
+                        self.state = State::Escape;
+                    } else {
+                        // 読み込んだバイトを、元の状態でもう一度処理する
+                        i -= 1;
+                        self.output_flag = false;
+                        self.state = self.output_state;
+                        output.push(REPLACEMENT_CHARACTER);
+                    }
+                }
+                State::Escape => {
+                    let next_state = match (self.lead, byte) {
+                        (0x28, 0x42) => Some(State::Ascii),
+                        (0x28, 0x4A) => Some(State::Roman),
+                        (0x28, 0x49) => Some(State::Katakana),
+                        (0x24, 0x40) | (0x24, 0x42) => Some(State::LeadByte),
+                        _ => None,
+                    };
+                    self.lead = 0;
+
+                    match next_state {
+                        Some(next_state) => {
+                            self.state = next_state;
+                            self.output_state = next_state;
+                            if self.output_flag {
+                                output.push(REPLACEMENT_CHARACTER);
+                            }
+                            self.output_flag = true;
+                        }
+                        None => {
+                            // ESCの次のバイトから、元の状態でもう一度処理する
+                            i -= 2;
+                            self.output_flag = false;
+                            self.state = self.output_state;
+                            output.push(REPLACEMENT_CHARACTER);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 入力の終わりで、途中の文字やエスケープシーケンスを出力する
+    pub fn finish(&mut self, output: &mut String) {
+        match self.state {
+            State::TrailByte | State::EscapeStart => output.push(REPLACEMENT_CHARACTER),
+            State::Escape => {
+                output.push(REPLACEMENT_CHARACTER);
+                // ESCの次のバイト（0x24か0x28）を、元の状態でもう一度処理する
+                match self.output_state {
+                    State::Ascii | State::Roman => output.push(self.lead as char),
+                    State::Katakana => {
+                        if let Some(c) = char::from_u32(0xFF61 - 0x21 + self.lead as u32) {
+                            output.push(c);
+                        }
+                    }
+                    _ => output.push(REPLACEMENT_CHARACTER),
+                }
+            }
+            _ => {}
+        }
+        *self = Self::new();
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder
+pub fn decode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len());
+    let mut decoder = Decoder::new();
+    decoder.push(bytes, &mut output);
+    decoder.finish(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            "Hello 世界!".to_string(),
+            decode(b"Hello \x1B$B@$3&\x1B(B!")
+        );
+    }
+
+    #[test]
+    fn test_decode_roman_and_katakana() {
+        assert_eq!("¥‾".to_string(), decode(b"\x1B(J\x5C\x7E\x1B(B"));
+        assert_eq!("ｻﾊﾞ".to_string(), decode(b"\x1B(I;J^\x1B(B"));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // 未知のエスケープシーケンス
+        assert_eq!("\u{FFFD}$Za".to_string(), decode(b"\x1B$Za"));
+        // 連続したエスケープシーケンス
+        assert_eq!("\u{FFFD}a".to_string(), decode(b"\x1B$B\x1B(Ba"));
+        // 2バイト文字の途中で終わっている
+        assert_eq!("\u{FFFD}".to_string(), decode(b"\x1B$B@"));
+        // 途中で終わっているエスケープシーケンス
+        assert_eq!("a\u{FFFD}".to_string(), decode(b"a\x1B"));
+        assert_eq!("a\u{FFFD}(".to_string(), decode(b"a\x1B("));
+        assert_eq!("\u{FFFD}\u{FF64}".to_string(), decode(b"\x1B(I\x1B$"));
+    }
+
+    #[test]
+    fn test_decoder_keeps_state() {
+        let mut decoder = Decoder::new();
+        let mut output = String::new();
+        decoder.push(b"a\x1B$B@", &mut output);
+        assert_eq!("a".to_string(), output);
+        decoder.push(b"$3&\x1B(B!", &mut output);
+        decoder.finish(&mut output);
+        assert_eq!("a世界!".to_string(), output);
+    }
+}
@@ -0,0 +1,208 @@
+pub mod euc_jp;
+pub mod iso_2022_jp;
+pub mod jis0208;
+pub mod shift_jis;
+pub mod sniff;
+pub mod stream;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// https://encoding.spec.whatwg.org/#names-and-labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Be,
+    Utf16Le,
+    ShiftJis,
+    EucJp,
+    Iso2022Jp,
+    Windows1252,
+}
+
+impl Encoding {
+    /// ラベルの前後の空白を取り除き、大文字と小文字を区別せずに比較する。サポートしていない
+    /// エンコーディングのラベルの場合はNoneを返す
+    /// https://encoding.spec.whatwg.org/#concept-encoding-get
+    pub fn for_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_ascii_lowercase();
+
+        match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Encoding::Utf8),
+            "unicodefffe" | "utf-16be" => Some(Encoding::Utf16Be),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Encoding::Utf16Le),
+            "csshiftjis" | "ms932" | "ms_kanji" | "shift-jis" | "shift_jis" | "sjis"
+            | "windows-31j" | "x-sjis" => Some(Encoding::ShiftJis),
+            "cseucpkdfmtjapanese" | "euc-jp" | "x-euc-jp" => Some(Encoding::EucJp),
+            "csiso2022jp" | "iso-2022-jp" => Some(Encoding::Iso2022Jp),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::EucJp => "EUC-JP",
+            Encoding::Iso2022Jp => "ISO-2022-JP",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// BOMは考慮せずに、このエンコーディングでバイト列をデコードする。不正なバイト列は
+    /// U+FFFDに置き換える
+    pub fn decode_without_bom_handling(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Be => decode_utf16(bytes, /*big_endian=*/ true),
+            Encoding::Utf16Le => decode_utf16(bytes, /*big_endian=*/ false),
+            Encoding::ShiftJis => shift_jis::decode(bytes),
+            Encoding::EucJp => euc_jp::decode(bytes),
+            Encoding::Iso2022Jp => iso_2022_jp::decode(bytes),
+            Encoding::Windows1252 => decode_windows_1252(bytes),
+        }
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#bom-sniff
+pub fn bom_sniff(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some((Encoding::Utf8, 3))
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        Some((Encoding::Utf16Be, 2))
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        Some((Encoding::Utf16Le, 2))
+    } else {
+        None
+    }
+}
+
+/// BOMがある場合は、fallbackよりもBOMのエンコーディングを優先する
+/// https://encoding.spec.whatwg.org/#decode
+pub fn decode(bytes: &[u8], fallback: Encoding) -> String {
+    match bom_sniff(bytes) {
+        Some((encoding, bom_length)) => encoding.decode_without_bom_handling(&bytes[bom_length..]),
+        None => fallback.decode_without_bom_handling(bytes),
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#shared-utf-16-decoder
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            if big_endian {
+                u16::from_be_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_le_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+
+    let mut output: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+        .collect();
+    // 奇数個のバイトが残った場合
+    if bytes.len() % 2 == 1 {
+        output.push(REPLACEMENT_CHARACTER);
+    }
+    output
+}
+
+/// 0x80〜0x9Fの範囲のみISO-8859-1と異なる
+/// https://encoding.spec.whatwg.org/index-windows-1252.txt
+const WINDOWS_1252: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+/// https://encoding.spec.whatwg.org/#single-byte-decoder
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| match b {
+            0x80..=0x9F => char::from_u32(WINDOWS_1252[(b - 0x80) as usize] as u32)
+                .unwrap_or(REPLACEMENT_CHARACTER),
+            _ => *b as char,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_for_label() {
+        assert_eq!(Some(Encoding::Utf8), Encoding::for_label(" UTF-8\n"));
+        assert_eq!(Some(Encoding::ShiftJis), Encoding::for_label("Shift_JIS"));
+        assert_eq!(Some(Encoding::ShiftJis), Encoding::for_label("windows-31j"));
+        assert_eq!(Some(Encoding::EucJp), Encoding::for_label("x-euc-jp"));
+        assert_eq!(
+            Some(Encoding::Iso2022Jp),
+            Encoding::for_label("ISO-2022-JP")
+        );
+        assert_eq!(
+            Some(Encoding::Windows1252),
+            Encoding::for_label("iso-8859-1")
+        );
+        assert_eq!(Some(Encoding::Utf16Le), Encoding::for_label("utf-16"));
+        assert_eq!(None, Encoding::for_label("utf-7"));
+    }
+
+    #[test]
+    fn test_decode_with_bom() {
+        assert_eq!(
+            "あ".to_string(),
+            decode(&[0xEF, 0xBB, 0xBF, 0xE3, 0x81, 0x82], Encoding::ShiftJis)
+        );
+        assert_eq!(
+            "あa".to_string(),
+            decode(&[0xFE, 0xFF, 0x30, 0x42, 0x00, 0x61], Encoding::Utf8)
+        );
+        assert_eq!(
+            "あa".to_string(),
+            decode(&[0xFF, 0xFE, 0x42, 0x30, 0x61, 0x00], Encoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn test_decode_utf8_invalid() {
+        assert_eq!(
+            "a\u{FFFD}b".to_string(),
+            Encoding::Utf8.decode_without_bom_handling(b"a\xFFb")
+        );
+    }
+
+    #[test]
+    fn test_decode_utf16_invalid() {
+        // 対になっていないサロゲートと、余った1バイト
+        assert_eq!(
+            "\u{FFFD}a\u{FFFD}".to_string(),
+            Encoding::Utf16Le.decode_without_bom_handling(&[0x00, 0xD8, 0x61, 0x00, 0x62])
+        );
+    }
+
+    #[test]
+    fn test_decode_windows_1252() {
+        assert_eq!(
+            "€ café".to_string(),
+            Encoding::Windows1252.decode_without_bom_handling(b"\x80 caf\xE9")
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::encoding::jis0208;
+use crate::encoding::REPLACEMENT_CHARACTER;
+use alloc::string::String;
+
+/// https://encoding.spec.whatwg.org/#shift_jis-decoder
+pub fn decode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len());
+    let mut lead: u8 = 0;
+
+    for &byte in bytes {
+        if lead != 0 {
+            let lead_byte = lead;
+            lead = 0;
+
+            let offset = if byte < 0x7F { 0x40 } else { 0x41 };
+            let lead_offset = if lead_byte < 0xA0 { 0x81 } else { 0xC1 };
+            if matches!(byte, 0x40..=0x7E | 0x80..=0xFC) {
+                let pointer = (lead_byte - lead_offset) as usize * 188 + (byte - offset) as usize;
+                // 外字（ユーザー定義文字）は私用領域に割り当てる
+                if (8836..=10715).contains(&pointer) {
+                    if let Some(c) = char::from_u32(0xE000 + pointer as u32 - 8836) {
+                        output.push(c);
+                    }
+                    continue;
+                }
+                if let Some(c) = jis0208::code_point(pointer) {
+                    output.push(c);
+                    continue;
+                }
+            }
+
+            output.push(REPLACEMENT_CHARACTER);
+            // 2バイト目がASCIIの場合は、その文字を捨てずに出力する
+            if byte.is_ascii() {
+                output.push(byte as char);
+            }
+            continue;
+        }
+
+        match byte {
+            0x00..=0x80 => output.push(byte as char),
+            // 半角カタカナ
+            0xA1..=0xDF => {
+                if let Some(c) = char::from_u32(0xFF61 + byte as u32 - 0xA1) {
+                    output.push(c);
+                }
+            }
+            0x81..=0x9F | 0xE0..=0xFC => lead = byte,
+            _ => output.push(REPLACEMENT_CHARACTER),
+        }
+    }
+
+    if lead != 0 {
+        output.push(REPLACEMENT_CHARACTER);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_decode() {
+        // "こんにちは、世界"
+        let bytes = b"\x82\xB1\x82\xF1\x82\xC9\x82\xBF\x82\xCD\x81\x41\x90\xA2\x8A\x45";
+        assert_eq!("こんにちは、世界".to_string(), decode(bytes));
+    }
+
+    #[test]
+    fn test_decode_ascii_and_halfwidth_katakana() {
+        assert_eq!("<p>ｻﾊﾞ</p>".to_string(), decode(b"<p>\xBB\xCA\xDE</p>"));
+    }
+
+    #[test]
+    fn test_decode_extensions() {
+        // NEC特殊文字、IBM拡張文字、外字
+        assert_eq!(
+            "①纊\u{E000}".to_string(),
+            decode(b"\x87\x40\xFA\x5C\xF0\x40")
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // 2バイト目がASCIIの場合は、その文字を残す
+        assert_eq!("\u{FFFD}0".to_string(), decode(b"\x820"));
+        assert_eq!("\u{FFFD}\u{FFFD}".to_string(), decode(b"\xA0\x82\xFF"));
+        // 途中で終わっている
+        assert_eq!("a\u{FFFD}".to_string(), decode(b"a\x82"));
+    }
+}
@@ -0,0 +1,400 @@
+use crate::encoding::bom_sniff;
+use crate::encoding::Encoding;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// <meta>を探すのは先頭の1024バイトまで
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+pub const PRESCAN_LENGTH: usize = 1024;
+
+/// BOM、Content-Typeヘッダのcharset、<meta>の順にエンコーディングを決める。どれからも
+/// 決まらない場合はUTF-8とする
+/// https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding
+pub fn sniff(bytes: &[u8], content_type: Option<&str>) -> Encoding {
+    if let Some((encoding, _)) = bom_sniff(bytes) {
+        return encoding;
+    }
+
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return encoding;
+    }
+
+    if let Some(encoding) = prescan(bytes) {
+        return encoding;
+    }
+
+    Encoding::Utf8
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, 0x09 | 0x0A | 0x0C | 0x0D | 0x20)
+}
+
+/// "text/html; charset=Shift_JIS"のようなContent-Typeヘッダの値から、charsetパラメータの
+/// エンコーディングを取り出す
+/// https://mimesniff.spec.whatwg.org/#parsing-a-mime-type
+pub fn charset_from_content_type(content_type: &str) -> Option<Encoding> {
+    for parameter in content_type.split(';').skip(1) {
+        let (name, value) = match parameter.split_once('=') {
+            Some(parameter) => parameter,
+            None => continue,
+        };
+
+        if !name
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .eq_ignore_ascii_case("charset")
+        {
+            continue;
+        }
+
+        let value = value.trim_matches(|c: char| c.is_ascii_whitespace());
+        let value = value
+            .strip_prefix('"')
+            .map(|v| v.split('"').next().unwrap_or(v))
+            .unwrap_or(value);
+        return Encoding::for_label(value);
+    }
+
+    None
+}
+
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn charset_from_meta_content(content: &[u8]) -> Option<Encoding> {
+    let mut position = 0;
+    loop {
+        // "charset"を探す
+        let found = content[position..]
+            .windows(7)
+            .position(|w| w.eq_ignore_ascii_case(b"charset"))?;
+        position += found + 7;
+
+        while position < content.len() && is_whitespace(content[position]) {
+            position += 1;
+        }
+        if content.get(position) != Some(&b'=') {
+            continue;
+        }
+        position += 1;
+        while position < content.len() && is_whitespace(content[position]) {
+            position += 1;
+        }
+
+        let rest = &content[position..];
+        let value = match rest.first() {
+            Some(&quote) if quote == b'"' || quote == b'\'' => {
+                let end = rest[1..].iter().position(|b| *b == quote)?;
+                &rest[1..end + 1]
+            }
+            Some(_) => {
+                let end = rest
+                    .iter()
+                    .position(|b| is_whitespace(*b) || *b == b';')
+                    .unwrap_or(rest.len());
+                &rest[..end]
+            }
+            None => return None,
+        };
+        return Encoding::for_label(&String::from_utf8_lossy(value));
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+struct Prescanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Prescanner<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        let length = bytes.len().min(PRESCAN_LENGTH);
+        Self {
+            bytes: &bytes[..length],
+            position: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn starts_with_ignore_case(&self, prefix: &[u8]) -> bool {
+        match self.bytes.get(self.position..self.position + prefix.len()) {
+            Some(bytes) => bytes.eq_ignore_ascii_case(prefix),
+            None => false,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    /// 指定したバイトの次まで読み進める。見つからない場合は末尾まで読み進める
+    fn skip_past(&mut self, byte: u8) {
+        match self.bytes[self.position..].iter().position(|b| *b == byte) {
+            Some(index) => self.position += index + 1,
+            None => self.position = self.bytes.len(),
+        }
+    }
+
+    /// 属性の名前と値を小文字にして返す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+    fn attribute(&mut self) -> Option<(Vec<u8>, Vec<u8>)> {
+        while self.peek().is_some_and(|b| is_whitespace(b) || b == b'/') {
+            self.position += 1;
+        }
+        if self.peek() == Some(b'>') {
+            return None;
+        }
+
+        let mut name = Vec::new();
+        let mut value = Vec::new();
+
+        // 属性の名前
+        loop {
+            let byte = self.peek()?;
+            if byte == b'=' && !name.is_empty() {
+                self.position += 1;
+                break;
+            }
+            if is_whitespace(byte) {
+                self.skip_whitespace();
+                if self.peek()? != b'=' {
+                    return Some((name, value));
+                }
+                self.position += 1;
+                break;
+            }
+            if byte == b'/' || byte == b'>' {
+                return Some((name, value));
+            }
+            name.push(byte.to_ascii_lowercase());
+            self.position += 1;
+        }
+
+        // 属性の値
+        self.skip_whitespace();
+        let byte = self.peek()?;
+        if byte == b'"' || byte == b'\'' {
+            self.position += 1;
+            loop {
+                let b = self.peek()?;
+                self.position += 1;
+                if b == byte {
+                    return Some((name, value));
+                }
+                value.push(b.to_ascii_lowercase());
+            }
+        }
+        if byte == b'>' {
+            return Some((name, value));
+        }
+        loop {
+            let b = self.peek()?;
+            if is_whitespace(b) || b == b'>' {
+                return Some((name, value));
+            }
+            value.push(b.to_ascii_lowercase());
+            self.position += 1;
+        }
+    }
+
+    /// <meta>の属性からエンコーディングを決める
+    fn meta(&mut self) -> Option<Encoding> {
+        let mut names: Vec<Vec<u8>> = Vec::new();
+        let mut got_pragma = false;
+        let mut need_pragma: Option<bool> = None;
+        let mut charset: Option<Encoding> = None;
+
+        while let Some((name, value)) = self.attribute() {
+            if names.contains(&name) {
+                continue;
+            }
+
+            match name.as_slice() {
+                b"http-equiv" => {
+                    if value == b"content-type" {
+                        got_pragma = true;
+                    }
+                }
+                b"content" => {
+                    if charset.is_none() {
+                        if let Some(encoding) = charset_from_meta_content(&value) {
+                            charset = Some(encoding);
+                            need_pragma = Some(true);
+                        }
+                    }
+                }
+                b"charset" => {
+                    charset = Encoding::for_label(&String::from_utf8_lossy(&value));
+                    need_pragma = Some(false);
+                }
+                _ => {}
+            }
+            names.push(name);
+        }
+
+        match need_pragma {
+            None => None,
+            Some(true) if !got_pragma => None,
+            // UTF-16のバイト列はASCII互換ではないので、<meta>での指定は誤り
+            _ => match charset? {
+                Encoding::Utf16Be | Encoding::Utf16Le => Some(Encoding::Utf8),
+                encoding => Some(encoding),
+            },
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+    fn run(&mut self) -> Option<Encoding> {
+        while self.position < self.bytes.len() {
+            if self.starts_with_ignore_case(b"<!--") {
+                // "<!-->"のように、"<!--"の"--"と">"で閉じることもある
+                self.position += 2;
+                match self.bytes[self.position..]
+                    .windows(3)
+                    .position(|w| w == b"-->")
+                {
+                    Some(index) => self.position += index + 3,
+                    None => return None,
+                }
+                continue;
+            }
+
+            if self.starts_with_ignore_case(b"<meta")
+                && self
+                    .bytes
+                    .get(self.position + 5)
+                    .is_some_and(|b| is_whitespace(*b) || *b == b'/')
+            {
+                self.position += 6;
+                if let Some(encoding) = self.meta() {
+                    return Some(encoding);
+                }
+                continue;
+            }
+
+            let next = self.bytes.get(self.position + 1).copied();
+            let after_slash = self.bytes.get(self.position + 2).copied();
+            let is_start_tag = next.is_some_and(|b| b.is_ascii_alphabetic());
+            let is_end_tag =
+                next == Some(b'/') && after_slash.is_some_and(|b| b.is_ascii_alphabetic());
+            if self.peek() == Some(b'<') && (is_start_tag || is_end_tag) {
+                // タグの名前を読み飛ばし、属性を読み捨てる
+                while self.peek().is_some_and(|b| !is_whitespace(b) && b != b'>') {
+                    self.position += 1;
+                }
+                while self.attribute().is_some() {}
+                continue;
+            }
+
+            if self.peek() == Some(b'<') && matches!(next, Some(b'!') | Some(b'/') | Some(b'?')) {
+                self.skip_past(b'>');
+                continue;
+            }
+
+            self.position += 1;
+        }
+
+        None
+    }
+}
+
+pub fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    Prescanner::new(bytes).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charset_from_content_type() {
+        let cases = [
+            ("text/html; charset=Shift_JIS", Some(Encoding::ShiftJis)),
+            ("text/html;charset=\"euc-jp\"", Some(Encoding::EucJp)),
+            ("text/html; CHARSET = utf-8 ", Some(Encoding::Utf8)),
+            (
+                "text/html; q=1; charset=iso-2022-jp",
+                Some(Encoding::Iso2022Jp),
+            ),
+            ("text/html", None),
+            ("text/html; charset=unknown", None),
+        ];
+        for (content_type, expected) in cases {
+            assert_eq!(
+                expected,
+                charset_from_content_type(content_type),
+                "parsing {}",
+                content_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_prescan() {
+        let cases: [(&[u8], Option<Encoding>); 10] = [
+            (b"<meta charset=\"Shift_JIS\">", Some(Encoding::ShiftJis)),
+            (b"<html><head><META CHARSET=euc-jp>", Some(Encoding::EucJp)),
+            (
+                b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=iso-2022-jp\">",
+                Some(Encoding::Iso2022Jp),
+            ),
+            (
+                b"<meta content='text/html; charset=shift_jis' http-equiv='content-type'>",
+                Some(Encoding::ShiftJis),
+            ),
+            // http-equivがない場合、contentは無視する
+            (b"<meta content=\"text/html; charset=shift_jis\">", None),
+            // コメントの中は無視する
+            (
+                b"<!-- <meta charset=euc-jp> --><meta charset=utf-8>",
+                Some(Encoding::Utf8),
+            ),
+            // 他のタグの属性の中は無視する
+            (
+                b"<div title='<meta charset=euc-jp>'><meta charset=sjis>",
+                Some(Encoding::ShiftJis),
+            ),
+            (b"<meta charset=utf-16le>", Some(Encoding::Utf8)),
+            (b"<meta charset=unknown>", None),
+            (b"<p>no meta</p>", None),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(
+                expected,
+                prescan(bytes),
+                "prescanning {}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn test_prescan_only_first_1024_bytes() {
+        let mut bytes = Vec::new();
+        bytes.resize(PRESCAN_LENGTH, b' ');
+        bytes.extend_from_slice(b"<meta charset=euc-jp>");
+        assert_eq!(None, prescan(&bytes));
+    }
+
+    #[test]
+    fn test_sniff_priority() {
+        let html = b"<meta charset=euc-jp>";
+        assert_eq!(Encoding::EucJp, sniff(html, Some("text/html")));
+        assert_eq!(
+            Encoding::ShiftJis,
+            sniff(html, Some("text/html; charset=shift_jis"))
+        );
+
+        let mut with_bom = [0xEF, 0xBB, 0xBF].to_vec();
+        with_bom.extend_from_slice(html);
+        assert_eq!(
+            Encoding::Utf8,
+            sniff(&with_bom, Some("text/html; charset=shift_jis"))
+        );
+
+        assert_eq!(Encoding::Utf8, sniff(b"<p>hello</p>", None));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::encoding::bom_sniff;
+use crate::encoding::iso_2022_jp;
+use crate::encoding::sniff::charset_from_content_type;
+use crate::encoding::sniff::sniff;
+use crate::encoding::sniff::PRESCAN_LENGTH;
+use crate::encoding::Encoding;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// ネットワークから少しずつ届くバイト列を、届いた順に文字列にデコードする。エンコーディングは
+/// 最初に十分なバイト数が届いた時点で決め、それ以降は変えない
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-input-byte-stream
+#[derive(Debug, Clone)]
+pub struct StreamDecoder {
+    content_type: Option<String>,
+    encoding: Option<Encoding>,
+    /// まだデコードしていないバイト列。エンコーディングが決まるまでのバイト列と、文字の途中で
+    /// 区切られた末尾のバイト列を持つ
+    pending: Vec<u8>,
+    iso_2022_jp: iso_2022_jp::Decoder,
+}
+
+impl StreamDecoder {
+    /// content_typeはContent-Typeヘッダの値
+    pub fn new(content_type: Option<String>) -> Self {
+        Self {
+            content_type,
+            encoding: None,
+            pending: Vec::new(),
+            iso_2022_jp: iso_2022_jp::Decoder::new(),
+        }
+    }
+
+    /// 決まったエンコーディング。まだ決まっていない場合はNone
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    /// 届いたバイト列を追加し、文字の区切りまでをデコードして返す
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        if self.encoding.is_none() && !self.can_determine_encoding() {
+            return String::new();
+        }
+        self.decode_pending(/*finished=*/ false)
+    }
+
+    /// 入力の終わりで、残っているバイト列をすべてデコードする
+    pub fn finish(&mut self) -> String {
+        self.decode_pending(/*finished=*/ true)
+    }
+
+    /// BOMを調べるには3バイト、<meta>を探すにはPRESCAN_LENGTHバイトが必要
+    fn can_determine_encoding(&self) -> bool {
+        if self.pending.len() >= PRESCAN_LENGTH {
+            return true;
+        }
+        if self.pending.len() < 3 {
+            return false;
+        }
+        bom_sniff(&self.pending).is_some()
+            || self
+                .content_type
+                .as_deref()
+                .and_then(charset_from_content_type)
+                .is_some()
+    }
+
+    fn decode_pending(&mut self, finished: bool) -> String {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => {
+                let encoding = sniff(&self.pending, self.content_type.as_deref());
+                if let Some((bom_encoding, bom_length)) = bom_sniff(&self.pending) {
+                    if bom_encoding == encoding {
+                        self.pending.drain(..bom_length);
+                    }
+                }
+                self.encoding = Some(encoding);
+                encoding
+            }
+        };
+
+        let length = if finished {
+            self.pending.len()
+        } else {
+            complete_length(encoding, &self.pending)
+        };
+        let bytes: Vec<u8> = self.pending.drain(..length).collect();
+
+        let mut output = match encoding {
+            Encoding::Iso2022Jp => {
+                let mut output = String::with_capacity(bytes.len());
+                self.iso_2022_jp.push(&bytes, &mut output);
+                output
+            }
+            _ => encoding.decode_without_bom_handling(&bytes),
+        };
+        if finished && encoding == Encoding::Iso2022Jp {
+            self.iso_2022_jp.finish(&mut output);
+        }
+        output
+    }
+}
+
+/// 末尾の途中までしか届いていない文字を除いた、デコードできるバイト数
+fn complete_length(encoding: Encoding, bytes: &[u8]) -> usize {
+    match encoding {
+        Encoding::Utf8 => {
+            // 末尾から、最後の文字の先頭のバイトを探す
+            let start = match bytes.iter().rev().take(4).position(|b| (b & 0xC0) != 0x80) {
+                Some(index) => bytes.len() - 1 - index,
+                None => return bytes.len(),
+            };
+            let expected = match bytes[start] {
+                0xC2..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF4 => 4,
+                _ => 1,
+            };
+            if bytes.len() - start < expected {
+                start
+            } else {
+                bytes.len()
+            }
+        }
+        Encoding::Utf16Be | Encoding::Utf16Le => {
+            let length = bytes.len() - bytes.len() % 2;
+            if length < 2 {
+                return length;
+            }
+            let unit = if encoding == Encoding::Utf16Be {
+                u16::from_be_bytes([bytes[length - 2], bytes[length - 1]])
+            } else {
+                u16::from_le_bytes([bytes[length - 2], bytes[length - 1]])
+            };
+            // 上位サロゲートの後には下位サロゲートが続く
+            if (0xD800..=0xDBFF).contains(&unit) {
+                length - 2
+            } else {
+                length
+            }
+        }
+        Encoding::ShiftJis => {
+            let mut i = 0;
+            while i < bytes.len() {
+                let length = match bytes[i] {
+                    0x81..=0x9F | 0xE0..=0xFC => 2,
+                    _ => 1,
+                };
+                if i + length > bytes.len() {
+                    break;
+                }
+                i += length;
+            }
+            i
+        }
+        Encoding::EucJp => {
+            let mut i = 0;
+            while i < bytes.len() {
+                let length = match bytes[i] {
+                    // JIS X 0212の文字は3バイト
+                    0x8F => match bytes.get(i + 1) {
+                        Some(0xA1..=0xFE) => 3,
+                        _ => 2,
+                    },
+                    0x8E | 0xA1..=0xFE => 2,
+                    _ => 1,
+                };
+                if i + length > bytes.len() {
+                    break;
+                }
+                i += length;
+            }
+            i
+        }
+        Encoding::Iso2022Jp => {
+            // エスケープシーケンスの途中で区切らない
+            let length = bytes.len();
+            if bytes.last() == Some(&0x1B) {
+                length - 1
+            } else if length >= 2
+                && bytes[length - 2] == 0x1B
+                && matches!(bytes[length - 1], 0x24 | 0x28)
+            {
+                length - 2
+            } else {
+                length
+            }
+        }
+        Encoding::Windows1252 => bytes.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    /// 1バイトずつ届いた場合も、まとめてデコードした場合と同じ文字列になる
+    fn decode_bytewise(bytes: &[u8], content_type: Option<&str>) -> String {
+        let mut decoder = StreamDecoder::new(content_type.map(|c| c.to_string()));
+        let mut output = String::new();
+        for byte in bytes {
+            output.push_str(&decoder.push(&[*byte]));
+        }
+        output.push_str(&decoder.finish());
+        output
+    }
+
+    #[test]
+    fn test_utf8() {
+        let bytes = "<p>日本語🦀</p>".as_bytes();
+        assert_eq!(
+            "<p>日本語🦀</p>".to_string(),
+            decode_bytewise(bytes, Some("text/html; charset=utf-8"))
+        );
+        // 途中で終わっている文字
+        assert_eq!(
+            "a\u{FFFD}".to_string(),
+            decode_bytewise(b"a\xE6\x97", Some("text/html; charset=utf-8"))
+        );
+    }
+
+    #[test]
+    fn test_waits_for_prescan() {
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!("".to_string(), decoder.push(b"<meta charset=shift_jis>"));
+        assert_eq!(None, decoder.encoding());
+        assert_eq!("<meta charset=shift_jis>".to_string(), decoder.finish());
+        assert_eq!(Some(Encoding::ShiftJis), decoder.encoding());
+    }
+
+    #[test]
+    fn test_meta_charset() {
+        let mut bytes = b"<meta charset=shift_jis>".to_vec();
+        bytes.resize(PRESCAN_LENGTH, b' ');
+        bytes.extend_from_slice(b"\x93\xFA\x96\x7B");
+        let mut decoder = StreamDecoder::new(None);
+        let output = decoder.push(&bytes[..PRESCAN_LENGTH + 1]);
+        assert_eq!(Some(Encoding::ShiftJis), decoder.encoding());
+        assert!(!output.contains('\u{FFFD}'));
+        let output = output + &decoder.push(&bytes[PRESCAN_LENGTH + 1..]) + &decoder.finish();
+        assert!(output.ends_with("日本"));
+    }
+
+    #[test]
+    fn test_bom() {
+        assert_eq!(
+            "ab".to_string(),
+            decode_bytewise(b"\xFF\xFEa\x00b\x00", None)
+        );
+        assert_eq!(
+            "🦀".to_string(),
+            decode_bytewise(b"\xFE\xFF\xD8\x3E\xDD\x80", None)
+        );
+    }
+
+    #[test]
+    fn test_multibyte_encodings() {
+        assert_eq!(
+            "日本ｻ".to_string(),
+            decode_bytewise(
+                b"\x93\xFA\x96\x7B\xBB",
+                Some("text/html; charset=shift_jis")
+            )
+        );
+        assert_eq!(
+            "日本ｻ\u{FFFD}".to_string(),
+            decode_bytewise(
+                b"\xC6\xFC\xCB\xDC\x8E\xBB\x8F\xA1\xA1",
+                Some("text/html; charset=euc-jp")
+            )
+        );
+        assert_eq!(
+            "Hello 世界!".to_string(),
+            decode_bytewise(
+                b"Hello \x1B$B@$3&\x1B(B!",
+                Some("text/html; charset=iso-2022-jp")
+            )
+        );
+    }
+}
@@ -2,8 +2,38 @@ use alloc::string::String;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
+    /// 接続した後の送受信やレスポンスの形式の失敗など、下の分類に当てはまらないネットワークの失敗
     Network(String),
+    /// 名前解決の失敗
+    Dns(String),
+    /// TCPの接続の失敗
+    Connect(String),
+    /// TLSのハンドシェイクや証明書の検証の失敗
+    Tls(String),
+    /// 送受信が時間内に終わらなかった
+    Timeout(String),
+    /// 相手に接続をリセットされた
+    ConnectionReset(String),
+    /// Cookieが変わらないまま、同じリクエストに戻るリダイレクトを受け取った
+    RedirectLoop(String),
     UnexpectedInput(String),
     InvalidUI(String),
     Other(String),
 }
+
+impl Error {
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Network(message)
+            | Error::Dns(message)
+            | Error::Connect(message)
+            | Error::Tls(message)
+            | Error::Timeout(message)
+            | Error::ConnectionReset(message)
+            | Error::RedirectLoop(message)
+            | Error::UnexpectedInput(message)
+            | Error::InvalidUI(message)
+            | Error::Other(message) => message,
+        }
+    }
+}
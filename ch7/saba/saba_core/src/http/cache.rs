@@ -0,0 +1,1226 @@
+use crate::error::Error;
+use crate::http::client::BodyReader;
+use crate::http::client::CompleteBody;
+use crate::http::date::parse_date;
+use crate::http::request::HttpRequest;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::rc::Weak;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+
+/// キャッシュに保存するレスポンスの合計サイズのデフォルトの上限
+pub const DEFAULT_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// 明示的な鮮度がなくても、Last-Modifiedから鮮度を推定してよいステータスコード
+/// https://www.rfc-editor.org/rfc/rfc9110#section-15.1
+const HEURISTICALLY_CACHEABLE: [u16; 11] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// 304レスポンスで上書きしないヘッダ
+/// https://www.rfc-editor.org/rfc/rfc9111#section-3.2
+const NOT_UPDATED_HEADERS: [&str; 4] = [
+    "content-length",
+    "content-encoding",
+    "transfer-encoding",
+    "content-range",
+];
+
+/// リクエストがキャッシュをどう使うか
+/// https://fetch.spec.whatwg.org/#concept-request-cache-mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// 新鮮なレスポンスはキャッシュから返し、古いものは再検証する
+    Default,
+    /// キャッシュを使わず、レスポンスも保存しない
+    NoStore,
+    /// キャッシュを使わずに取得し、レスポンスは保存する
+    Reload,
+    /// 新鮮なレスポンスがあっても、必ず再検証する
+    NoCache,
+    /// 古くてもキャッシュにあるレスポンスを使う
+    ForceCache,
+}
+
+/// https://www.rfc-editor.org/rfc/rfc9111#section-5.2
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+    must_revalidate: bool,
+}
+
+impl CacheControl {
+    fn parse(headers: &[Header]) -> Self {
+        let values: Vec<String> = headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case("Cache-Control"))
+            .map(|h| h.value.clone())
+            .collect();
+
+        let mut cache_control = Self::default();
+        for (name, value) in parse_directives(&values) {
+            match name.as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "must-revalidate" => cache_control.must_revalidate = true,
+                // 値が不正な場合は古いものとして扱う
+                "max-age" if cache_control.max_age.is_none() => {
+                    cache_control.max_age = Some(
+                        value
+                            .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+                            .map(|v| v.parse().unwrap_or(u64::MAX))
+                            .unwrap_or(0),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        // Cache-ControlがないときはPragma: no-cacheをno-cacheとして扱う
+        // https://www.rfc-editor.org/rfc/rfc9111#section-5.4
+        if values.is_empty()
+            && headers.iter().any(|h| {
+                h.name.eq_ignore_ascii_case("Pragma")
+                    && h.value.trim().eq_ignore_ascii_case("no-cache")
+            })
+        {
+            cache_control.no_cache = true;
+        }
+        cache_control
+    }
+}
+
+/// "max-age=60, no-cache="Set-Cookie""のようなディレクティブを、名前と値の組にする。
+/// 引用符の中のカンマでは区切らない
+/// https://www.rfc-editor.org/rfc/rfc9111#section-5.2
+fn parse_directives(values: &[String]) -> Vec<(String, Option<String>)> {
+    let mut directives = Vec::new();
+    for value in values {
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        let mut quoted = false;
+        let mut escaped = false;
+        for c in value.chars() {
+            if escaped {
+                current.push(c);
+                escaped = false;
+                continue;
+            }
+            match c {
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                ',' if !quoted => parts.push(core::mem::take(&mut current)),
+                _ => current.push(c),
+            }
+        }
+        parts.push(current);
+
+        for part in parts {
+            let (name, value) = match part.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim().to_string())),
+                None => (part.as_str(), None),
+            };
+            let name = name.trim().to_ascii_lowercase();
+            if !name.is_empty() {
+                directives.push((name, value));
+            }
+        }
+    }
+    directives
+}
+
+fn header_value(headers: &[Header], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.clone())
+}
+
+/// Varyで指定されたリクエストヘッダの値を比べるために、前後の空白を取り除く
+/// https://www.rfc-editor.org/rfc/rfc9111#section-4.1
+fn normalize(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim_matches([' ', '\t']).to_string())
+}
+
+/// Varyに含まれるヘッダ名。"*"が含まれている場合はNone
+fn vary_names(response: &HttpResponse) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    for value in response.header_values("Vary") {
+        for name in value.split(',') {
+            let name = name.trim_matches([' ', '\t']).to_ascii_lowercase();
+            if name == "*" {
+                return None;
+            }
+            if !name.is_empty() {
+                names.push(name);
+            }
+        }
+    }
+    Some(names)
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    /// フラグメントを除いたURL
+    key: String,
+    /// Varyで指定されたリクエストヘッダと、保存したときのその値
+    vary: Vec<(String, Option<String>)>,
+    response: HttpResponse,
+    /// レスポンスを受け取った時刻
+    response_time: u64,
+    /// 受け取った時点でのレスポンスの経過時間
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.2.3
+    corrected_initial_age: u64,
+    /// 最後に使われた順番。容量を超えたときに、最も長く使われていないものから捨てる
+    last_used: u64,
+    size: usize,
+}
+
+impl CacheEntry {
+    fn matches(&self, key: &str, request: &HttpRequest) -> bool {
+        self.key == key
+            && self
+                .vary
+                .iter()
+                .all(|(name, value)| normalize(request.header_value(name)) == *value)
+    }
+
+    fn cache_control(&self) -> CacheControl {
+        CacheControl::parse(&self.response.headers)
+    }
+
+    fn date(&self) -> u64 {
+        header_value(&self.response.headers, "Date")
+            .and_then(|date| parse_date(&date))
+            .unwrap_or(self.response_time)
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.2.1
+    fn freshness_lifetime(&self) -> u64 {
+        if let Some(max_age) = self.cache_control().max_age {
+            return max_age;
+        }
+        if let Some(expires) = header_value(&self.response.headers, "Expires") {
+            // 不正なExpiresは、すでに期限切れとして扱う
+            return match parse_date(&expires) {
+                Some(expires) => expires.saturating_sub(self.date()),
+                None => 0,
+            };
+        }
+        // https://www.rfc-editor.org/rfc/rfc9111#section-4.2.2
+        if HEURISTICALLY_CACHEABLE.contains(&self.response.status_code) {
+            if let Some(last_modified) = header_value(&self.response.headers, "Last-Modified")
+                .and_then(|date| parse_date(&date))
+            {
+                return self.date().saturating_sub(last_modified) / 10;
+            }
+        }
+        0
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.2.3
+    fn current_age(&self, now: u64) -> u64 {
+        self.corrected_initial_age
+            .saturating_add(now.saturating_sub(self.response_time))
+    }
+
+    fn is_fresh(&self, now: u64) -> bool {
+        self.current_age(now) < self.freshness_lifetime()
+    }
+
+    /// 再検証のための条件付きリクエストを作る。検証子がない場合はNone
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.3.1
+    fn conditional_request(&self, request: &HttpRequest) -> Option<HttpRequest> {
+        let etag = header_value(&self.response.headers, "ETag");
+        let last_modified = header_value(&self.response.headers, "Last-Modified");
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        let mut request = request.clone();
+        if let Some(etag) = etag {
+            request.set_header("If-None-Match", &etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request.set_header("If-Modified-Since", &last_modified);
+        }
+        Some(request)
+    }
+}
+
+/// Ageヘッダとの大きい方を、受け取った時点での経過時間とする
+/// https://www.rfc-editor.org/rfc/rfc9111#section-4.2.3
+fn corrected_initial_age(response: &HttpResponse, response_time: u64) -> u64 {
+    let age = response
+        .header_value("Age")
+        .ok()
+        .and_then(|age| age.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let apparent_age = match response
+        .header_value("Date")
+        .ok()
+        .and_then(|d| parse_date(&d))
+    {
+        Some(date) => response_time.saturating_sub(date),
+        None => 0,
+    };
+    apparent_age.max(age)
+}
+
+fn response_size(response: &HttpResponse) -> usize {
+    response.body.len()
+        + response
+            .headers
+            .iter()
+            .map(|h| h.name.len() + h.value.len())
+            .sum::<usize>()
+}
+
+/// メモリ上のプライベートなHTTPキャッシュ
+/// https://www.rfc-editor.org/rfc/rfc9111
+#[derive(Clone)]
+pub struct HttpCache {
+    entries: Vec<CacheEntry>,
+    /// 保存するレスポンスの合計サイズの上限
+    capacity: usize,
+    size: usize,
+    mode: CacheMode,
+    use_count: u64,
+    /// 現在のUNIX時間の秒数を返す。ない場合は鮮度を判断できないので、保存したレスポンスは
+    /// 常に古いものとして再検証する
+    clock: Option<Rc<dyn Fn() -> u64>>,
+}
+
+impl fmt::Debug for HttpCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpCache")
+            .field("entries", &self.entries.len())
+            .field("capacity", &self.capacity)
+            .field("size", &self.size)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+impl HttpCache {
+    /// 時刻を取得できない環境のためのキャッシュ。保存したレスポンスは、使う前に毎回再検証する
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity,
+            size: 0,
+            mode: CacheMode::Default,
+            use_count: 0,
+            clock: None,
+        }
+    }
+
+    pub fn with_clock(capacity: usize, clock: Rc<dyn Fn() -> u64>) -> Self {
+        Self {
+            clock: Some(clock),
+            ..Self::with_capacity(capacity)
+        }
+    }
+
+    /// 時計がない場合は0を返す
+    fn now(&self) -> u64 {
+        self.clock.as_ref().map_or(0, |clock| clock())
+    }
+
+    /// 保存しているレスポンスの数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 保存しているレスポンスの合計サイズ
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    pub fn mode(&self) -> CacheMode {
+        self.mode
+    }
+
+    /// 以降のfetchでのキャッシュの使い方を変える。再読み込みのときにキャッシュを
+    /// 迂回するために使う
+    pub fn set_mode(&mut self, mode: CacheMode) {
+        self.mode = mode;
+    }
+
+    /// キャッシュを使ってリクエストに応える。キャッシュから応えられない場合は、
+    /// sendでリクエストを送り、レスポンスを保存できれば保存する
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4
+    pub fn fetch<F>(&mut self, request: &HttpRequest, mut send: F) -> Result<HttpResponse, Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<HttpResponse, Error>,
+    {
+        match self.fetch_inner(request, |request| {
+            send(request).map(|response| (response, ()))
+        })? {
+            Fetched::Stored(response) => Ok(response),
+            Fetched::Network {
+                response, store, ..
+            } => {
+                if store {
+                    self.store(&cache_key(request), request, &response);
+                }
+                Ok(response)
+            }
+        }
+    }
+
+    /// fetch()と同じようにキャッシュを使い、ネットワークから受け取るレスポンスはヘッダを受信した
+    /// 時点で返す。保存できるレスポンスは、ボディを読み終えた時点で保存する
+    pub fn fetch_streaming<F>(
+        cache: &Rc<RefCell<Self>>,
+        request: &HttpRequest,
+        send: F,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<(HttpResponse, Box<dyn BodyReader>), Error>,
+    {
+        let fetched = cache.borrow_mut().fetch_inner(request, send)?;
+        match fetched {
+            Fetched::Stored(mut response) => {
+                let body = response.take_body();
+                Ok((response, Box::new(CompleteBody::new(body))))
+            }
+            Fetched::Network {
+                response,
+                body,
+                store,
+            } => {
+                if !store || !is_storable(&response) {
+                    return Ok((response, body));
+                }
+                let capacity = cache.borrow().capacity;
+                Ok((
+                    response.clone(),
+                    Box::new(StoringBody {
+                        cache: Rc::downgrade(cache),
+                        request: request.clone(),
+                        response: Some(response),
+                        capacity,
+                        body,
+                    }),
+                ))
+            }
+        }
+    }
+
+    /// fetch()とfetch_streaming()に共通する処理。ネットワークから受け取ったレスポンスは、
+    /// ボディを受信し終えてから保存できるように、保存せずに返す
+    fn fetch_inner<B, F>(&mut self, request: &HttpRequest, mut send: F) -> Result<Fetched<B>, Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<(HttpResponse, B), Error>,
+    {
+        let method = request.method();
+        if method != "GET" {
+            let (response, body) = send(request)?;
+            if method != "HEAD" {
+                self.invalidate(request, &response);
+            }
+            return Ok(Fetched::network(response, body, /*store=*/ false));
+        }
+
+        let request_cache_control = CacheControl::parse(&request.headers());
+        if self.mode == CacheMode::NoStore || request_cache_control.no_store {
+            let (response, body) = send(request)?;
+            return Ok(Fetched::network(response, body, /*store=*/ false));
+        }
+
+        let key = cache_key(request);
+        let now = self.now();
+        let index = match self.mode {
+            CacheMode::Reload => None,
+            _ => self.entries.iter().position(|e| e.matches(&key, request)),
+        };
+
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let (response, body) = send(request)?;
+                return Ok(Fetched::network(response, body, /*store=*/ true));
+            }
+        };
+
+        let entry = &self.entries[index];
+        let use_stored = match self.mode {
+            CacheMode::ForceCache => true,
+            CacheMode::Default => {
+                self.clock.is_some()
+                    && entry.is_fresh(now)
+                    && !entry.cache_control().no_cache
+                    && !request_cache_control.no_cache
+                    && request_cache_control
+                        .max_age
+                        .map_or(true, |max_age| entry.current_age(now) <= max_age)
+            }
+            _ => false,
+        };
+        if use_stored {
+            return Ok(Fetched::Stored(self.use_entry(index)));
+        }
+
+        // 古いレスポンスを再検証する
+        // https://www.rfc-editor.org/rfc/rfc9111#section-4.3
+        let conditional = match self.entries[index].conditional_request(request) {
+            Some(conditional) => conditional,
+            None => {
+                let (response, body) = send(request)?;
+                return Ok(Fetched::network(response, body, /*store=*/ true));
+            }
+        };
+        let (response, body) = match send(&conditional) {
+            Ok(fetched) => fetched,
+            // 再検証できない場合は、must-revalidateでなければ古いレスポンスを使ってよい
+            // https://www.rfc-editor.org/rfc/rfc9111#section-4.2.4
+            Err(e) => {
+                let cache_control = self.entries[index].cache_control();
+                if self.mode != CacheMode::Default
+                    || cache_control.must_revalidate
+                    || cache_control.no_cache
+                {
+                    return Err(e);
+                }
+                return Ok(Fetched::Stored(self.use_entry(index)));
+            }
+        };
+        if response.status_code() != 304 {
+            return Ok(Fetched::network(response, body, /*store=*/ true));
+        }
+
+        // 304の場合は、保存しているレスポンスのヘッダを更新して使う
+        // https://www.rfc-editor.org/rfc/rfc9111#section-4.3.4
+        let entry = &mut self.entries[index];
+        for header in &response.headers {
+            let name = header.name.to_ascii_lowercase();
+            if NOT_UPDATED_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            entry
+                .response
+                .headers
+                .retain(|h| !h.name.eq_ignore_ascii_case(&name));
+        }
+        for header in &response.headers {
+            if !NOT_UPDATED_HEADERS.contains(&header.name.to_ascii_lowercase().as_str()) {
+                entry.response.headers.push(header.clone());
+            }
+        }
+        entry.response_time = now;
+        entry.corrected_initial_age = corrected_initial_age(&response, now);
+        let size = response_size(&entry.response);
+        self.size = self.size - entry.size + size;
+        entry.size = size;
+        let response = self.use_entry(index);
+        self.evict();
+        Ok(Fetched::Stored(response))
+    }
+
+    fn use_entry(&mut self, index: usize) -> HttpResponse {
+        self.use_count += 1;
+        self.entries[index].last_used = self.use_count;
+        self.entries[index].response.clone()
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-3
+    fn store(&mut self, key: &str, request: &HttpRequest, response: &HttpResponse) {
+        if !is_storable(response) {
+            return;
+        }
+
+        let vary = match vary_names(response) {
+            Some(names) => names
+                .into_iter()
+                .map(|name| {
+                    let value = normalize(request.header_value(&name));
+                    (name, value)
+                })
+                .collect(),
+            None => return,
+        };
+
+        let size = response_size(response);
+        // 同じリクエストに対する古いレスポンスは、保存できなくても捨てる
+        self.remove(|e| e.matches(key, request));
+        if size > self.capacity {
+            return;
+        }
+
+        let now = self.now();
+        self.use_count += 1;
+        self.entries.push(CacheEntry {
+            key: key.to_string(),
+            vary,
+            response: response.clone(),
+            response_time: now,
+            corrected_initial_age: corrected_initial_age(response, now),
+            last_used: self.use_count,
+            size,
+        });
+        self.size += size;
+        self.evict();
+    }
+
+    fn remove<P: Fn(&CacheEntry) -> bool>(&mut self, predicate: P) {
+        let mut size = self.size;
+        self.entries.retain(|e| {
+            if predicate(e) {
+                size -= e.size;
+                false
+            } else {
+                true
+            }
+        });
+        self.size = size;
+    }
+
+    /// 容量を超えている間、最も長く使われていないものから捨てる
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let oldest = match self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.last_used)
+            {
+                Some((i, _)) => i,
+                None => return,
+            };
+            let entry = self.entries.remove(oldest);
+            self.size -= entry.size;
+        }
+    }
+
+    /// POSTなどの安全でないメソッドが成功した場合は、同じURLと、Location、Content-Locationの
+    /// 同じオリジンのURLのキャッシュを捨てる
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.4
+    fn invalidate(&mut self, request: &HttpRequest, response: &HttpResponse) {
+        if !(200..400).contains(&response.status_code()) {
+            return;
+        }
+        let url = request.url();
+        let mut keys = Vec::new();
+        keys.push(url.serialize(/*exclude_fragment=*/ true));
+        for name in ["Location", "Content-Location"] {
+            if let Ok(value) = response.header_value(name) {
+                if let Ok(target) = url.join(&value) {
+                    if same_origin(&url, &target) {
+                        keys.push(target.serialize(/*exclude_fragment=*/ true));
+                    }
+                }
+            }
+        }
+        self.remove(|e| keys.contains(&e.key));
+    }
+}
+
+/// キャッシュから応えたレスポンスか、ネットワークから受け取ったレスポンス
+enum Fetched<B> {
+    /// キャッシュに保存していたレスポンス。ボディも揃っている
+    Stored(HttpResponse),
+    /// sendで受け取ったレスポンス。storeがtrueの場合は、ボディが揃った後に保存する
+    Network {
+        response: HttpResponse,
+        body: B,
+        store: bool,
+    },
+}
+
+impl<B> Fetched<B> {
+    fn network(response: HttpResponse, body: B, store: bool) -> Self {
+        Fetched::Network {
+            response,
+            body,
+            store,
+        }
+    }
+}
+
+/// 受信したボディを溜めておき、読み終えた時点でレスポンスをキャッシュに保存する
+#[derive(Debug)]
+struct StoringBody {
+    cache: Weak<RefCell<HttpCache>>,
+    request: HttpRequest,
+    /// ボディを溜めているレスポンス。キャッシュの容量を超えた場合は保存しないのでNone
+    response: Option<HttpResponse>,
+    capacity: usize,
+    body: Box<dyn BodyReader>,
+}
+
+impl BodyReader for StoringBody {
+    fn read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        match self.body.read()? {
+            Some(bytes) => {
+                if let Some(response) = self.response.as_mut() {
+                    response.body.extend_from_slice(&bytes);
+                    if response.body.len() > self.capacity {
+                        self.response = None;
+                    }
+                }
+                Ok(Some(bytes))
+            }
+            None => {
+                if let (Some(response), Some(cache)) = (self.response.take(), self.cache.upgrade())
+                {
+                    cache
+                        .borrow_mut()
+                        .store(&cache_key(&self.request), &self.request, &response);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// フラグメントを除いたURL
+fn cache_key(request: &HttpRequest) -> String {
+    request.url().serialize(/*exclude_fragment=*/ true)
+}
+
+/// no-storeでなく、保存してよいステータスコードのレスポンスか。Varyは見ない
+/// https://www.rfc-editor.org/rfc/rfc9111#section-3
+fn is_storable(response: &HttpResponse) -> bool {
+    let cache_control = CacheControl::parse(&response.headers);
+    if cache_control.no_store {
+        return false;
+    }
+
+    let status_code = response.status_code();
+    let explicit_freshness = cache_control.max_age.is_some()
+        || response.header_value("Expires").is_ok()
+        || cache_control.no_cache;
+    HEURISTICALLY_CACHEABLE.contains(&status_code)
+        || (matches!(status_code, 302 | 303 | 307) && explicit_freshness)
+}
+
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme() && a.host() == b.host() && a.port() == b.port()
+}
+
+impl Default for HttpCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::vec;
+    use core::cell::Cell;
+    use core::cell::RefCell;
+
+    /// 送られたリクエストを記録し、用意したレスポンスを順に返す
+    struct Server {
+        requests: RefCell<Vec<HttpRequest>>,
+        responses: RefCell<Vec<HttpResponse>>,
+    }
+
+    impl Server {
+        fn new(responses: Vec<HttpResponse>) -> Self {
+            Self {
+                requests: RefCell::new(Vec::new()),
+                responses: RefCell::new(responses),
+            }
+        }
+
+        fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+            self.requests.borrow_mut().push(request.clone());
+            let mut responses = self.responses.borrow_mut();
+            if responses.is_empty() {
+                return Err(Error::Network("no more responses".to_string()));
+            }
+            Ok(responses.remove(0))
+        }
+
+        fn request_count(&self) -> usize {
+            self.requests.borrow().len()
+        }
+
+        fn last_request(&self) -> HttpRequest {
+            self.requests
+                .borrow()
+                .last()
+                .cloned()
+                .expect("no request was sent")
+        }
+    }
+
+    fn response(status_code: u16, headers: &[(&str, &str)], body: &str) -> HttpResponse {
+        HttpResponse::from_parts(
+            status_code,
+            "",
+            headers
+                .iter()
+                .map(|(name, value)| Header::new(name.to_string(), value.to_string()))
+                .collect(),
+            body.as_bytes().to_vec(),
+        )
+    }
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::get(
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    fn cache_with_clock() -> (HttpCache, Rc<Cell<u64>>) {
+        let now = Rc::new(Cell::new(1_000_000u64));
+        let clock = now.clone();
+        (
+            HttpCache::with_clock(DEFAULT_CAPACITY, Rc::new(move || clock.get())),
+            now,
+        )
+    }
+
+    fn fetch(cache: &mut HttpCache, server: &Server, request: &HttpRequest) -> HttpResponse {
+        cache
+            .fetch(request, |request| server.send(request))
+            .expect("failed to fetch")
+    }
+
+    #[test]
+    fn test_max_age() {
+        let (mut cache, now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(200, &[("Cache-Control", "max-age=60")], "first"),
+            response(200, &[], "second"),
+        ]);
+        let request = get("http://example.com/index.html#top");
+
+        assert_eq!(
+            b"first".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        now.set(1_000_059);
+        assert_eq!(
+            b"first".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert_eq!(1, server.request_count());
+
+        now.set(1_000_060);
+        assert_eq!(
+            b"second".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert_eq!(2, server.request_count());
+    }
+
+    #[test]
+    fn test_revalidate_without_clock() {
+        let mut cache = HttpCache::new();
+        let server = Server::new(vec![
+            response(
+                200,
+                &[("Cache-Control", "max-age=60"), ("ETag", "\"v1\"")],
+                "cached",
+            ),
+            response(304, &[], ""),
+            response(200, &[("Cache-Control", "max-age=60")], "no validator"),
+            response(200, &[], "refetched"),
+        ]);
+
+        // 時計がない場合は鮮度を判断できないので、max-ageがあっても毎回再検証する
+        let request = get("http://example.com/a");
+        fetch(&mut cache, &server, &request);
+        assert_eq!(
+            b"cached".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert_eq!(2, server.request_count());
+        assert_eq!(
+            Some("\"v1\"".to_string()),
+            server.last_request().header_value("If-None-Match")
+        );
+
+        // 検証子がない場合は取得し直す
+        let request = get("http://example.com/b");
+        fetch(&mut cache, &server, &request);
+        assert_eq!(
+            b"refetched".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert_eq!(4, server.request_count());
+    }
+
+    #[test]
+    fn test_expires_and_age() {
+        let (mut cache, now) = cache_with_clock();
+        // Dateから100秒後に期限が切れ、受け取った時点ですでに30秒経っている
+        let server = Server::new(vec![response(
+            200,
+            &[
+                ("Date", "Mon, 12 Jan 1970 13:46:40 GMT"),
+                ("Expires", "Mon, 12 Jan 1970 13:48:20 GMT"),
+                ("Age", "30"),
+            ],
+            "body",
+        )]);
+        let request = get("http://example.com/");
+        fetch(&mut cache, &server, &request);
+
+        now.set(1_000_069);
+        fetch(&mut cache, &server, &request);
+        assert_eq!(1, server.request_count());
+        now.set(1_000_070);
+        assert!(cache.fetch(&request, |r| server.send(r)).is_err());
+        assert_eq!(2, server.request_count());
+    }
+
+    #[test]
+    fn test_revalidate_with_etag() {
+        let (mut cache, now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(
+                200,
+                &[
+                    ("Cache-Control", "no-cache"),
+                    ("ETag", "\"v1\""),
+                    ("X-Version", "1"),
+                ],
+                "cached",
+            ),
+            response(304, &[("X-Version", "2"), ("Content-Length", "99")], ""),
+        ]);
+        let request = get("http://example.com/");
+        fetch(&mut cache, &server, &request);
+
+        now.set(1_000_001);
+        let response = fetch(&mut cache, &server, &request);
+        assert_eq!(2, server.request_count());
+        assert_eq!(
+            Some("\"v1\"".to_string()),
+            server.last_request().header_value("If-None-Match")
+        );
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"cached".to_vec(), response.body());
+        assert_eq!(Ok("2".to_string()), response.header_value("X-Version"));
+        assert!(response.header_value("Content-Length").is_err());
+    }
+
+    #[test]
+    fn test_revalidate_with_last_modified() {
+        let (mut cache, _now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(
+                200,
+                &[
+                    ("Cache-Control", "max-age=0"),
+                    ("Last-Modified", "Thu, 01 Jan 1970 00:00:00 GMT"),
+                ],
+                "old",
+            ),
+            response(200, &[], "new"),
+        ]);
+        let request = get("http://example.com/");
+        fetch(&mut cache, &server, &request);
+
+        let response = fetch(&mut cache, &server, &request);
+        assert_eq!(
+            Some("Thu, 01 Jan 1970 00:00:00 GMT".to_string()),
+            server.last_request().header_value("If-Modified-Since")
+        );
+        assert_eq!(b"new".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_heuristic_freshness() {
+        let (mut cache, now) = cache_with_clock();
+        // Last-Modifiedから1000秒経っているので、100秒間は新鮮とみなす
+        let server = Server::new(vec![response(
+            200,
+            &[
+                ("Date", "Mon, 12 Jan 1970 13:46:40 GMT"),
+                ("Last-Modified", "Mon, 12 Jan 1970 13:30:00 GMT"),
+            ],
+            "body",
+        )]);
+        let request = get("http://example.com/");
+        fetch(&mut cache, &server, &request);
+        now.set(1_000_099);
+        fetch(&mut cache, &server, &request);
+        assert_eq!(1, server.request_count());
+    }
+
+    #[test]
+    fn test_vary() {
+        let (mut cache, _now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(
+                200,
+                &[("Cache-Control", "max-age=60"), ("Vary", "Accept-Language")],
+                "ja",
+            ),
+            response(
+                200,
+                &[("Cache-Control", "max-age=60"), ("Vary", "Accept-Language")],
+                "en",
+            ),
+            response(
+                200,
+                &[("Cache-Control", "max-age=60"), ("Vary", "*")],
+                "any",
+            ),
+            response(200, &[], "any again"),
+        ]);
+        let ja = get("http://example.com/").with_header("Accept-Language", "ja");
+        let en = get("http://example.com/").with_header("Accept-Language", "en");
+        assert_eq!(b"ja".to_vec(), fetch(&mut cache, &server, &ja).body());
+        assert_eq!(b"en".to_vec(), fetch(&mut cache, &server, &en).body());
+        let ja = get("http://example.com/").with_header("Accept-Language", " ja ");
+        assert_eq!(b"ja".to_vec(), fetch(&mut cache, &server, &ja).body());
+        assert_eq!(2, server.request_count());
+
+        let request = get("http://example.com/star");
+        fetch(&mut cache, &server, &request);
+        fetch(&mut cache, &server, &request);
+        assert_eq!(4, server.request_count());
+    }
+
+    #[test]
+    fn test_not_stored() {
+        let (mut cache, _now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(200, &[("Cache-Control", "no-store, max-age=60")], ""),
+            response(302, &[("Location", "/")], ""),
+            response(500, &[("Cache-Control", "max-age=60")], ""),
+        ]);
+        for path in ["/a", "/b", "/c"] {
+            fetch(
+                &mut cache,
+                &server,
+                &get(&format!("http://example.com{}", path)),
+            );
+        }
+        assert!(cache.is_empty());
+
+        // リクエストのno-storeはキャッシュを使わない
+        let server = Server::new(vec![
+            response(200, &[("Cache-Control", "max-age=60")], ""),
+            response(200, &[("Cache-Control", "max-age=60")], ""),
+        ]);
+        let request = get("http://example.com/").with_header("Cache-Control", "no-store");
+        fetch(&mut cache, &server, &request);
+        fetch(&mut cache, &server, &request);
+        assert_eq!(2, server.request_count());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_modes() {
+        let (mut cache, now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(
+                200,
+                &[("Cache-Control", "max-age=60"), ("ETag", "\"a\"")],
+                "first",
+            ),
+            response(304, &[], ""),
+            response(200, &[("Cache-Control", "max-age=60")], "reloaded"),
+        ]);
+        let request = get("http://example.com/");
+        fetch(&mut cache, &server, &request);
+
+        // 再読み込みでは、新鮮でも再検証する
+        cache.set_mode(CacheMode::NoCache);
+        assert_eq!(
+            b"first".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert_eq!(2, server.request_count());
+
+        // 強制的な再読み込みでは、キャッシュを使わない
+        cache.set_mode(CacheMode::Reload);
+        assert_eq!(
+            b"reloaded".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert!(server
+            .last_request()
+            .header_value("If-None-Match")
+            .is_none());
+
+        cache.set_mode(CacheMode::ForceCache);
+        now.set(2_000_000);
+        assert_eq!(
+            b"reloaded".to_vec(),
+            fetch(&mut cache, &server, &request).body()
+        );
+        assert_eq!(3, server.request_count());
+
+        cache.set_mode(CacheMode::NoStore);
+        assert!(cache.fetch(&request, |r| server.send(r)).is_err());
+    }
+
+    #[test]
+    fn test_stale_on_error() {
+        let (mut cache, now) = cache_with_clock();
+        let server = Server::new(vec![response(
+            200,
+            &[("Cache-Control", "max-age=1"), ("ETag", "\"a\"")],
+            "a",
+        )]);
+        let request = get("http://example.com/");
+        fetch(&mut cache, &server, &request);
+        now.set(1_000_010);
+        // 再検証に失敗しても、古いレスポンスを返す
+        assert_eq!(b"a".to_vec(), fetch(&mut cache, &server, &request).body());
+        assert_eq!(2, server.request_count());
+
+        let server = Server::new(vec![response(
+            200,
+            &[
+                ("Cache-Control", "max-age=1, must-revalidate"),
+                ("ETag", "\"b\""),
+            ],
+            "b",
+        )]);
+        let request = get("http://example.com/must");
+        fetch(&mut cache, &server, &request);
+        now.set(1_000_020);
+        assert!(cache.fetch(&request, |r| server.send(r)).is_err());
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let (_, now) = cache_with_clock();
+        let clock = now.clone();
+        let mut cache = HttpCache::with_clock(20, Rc::new(move || clock.get()));
+        let server = Server::new(vec![
+            response(200, &[], "aaaaaaaa"),
+            response(200, &[], "bbbbbbbb"),
+            response(200, &[], "cccccccc"),
+            response(200, &[], "this body is too large to cache"),
+        ]);
+        let (a, b, c) = (
+            get("http://example.com/a"),
+            get("http://example.com/b"),
+            get("http://example.com/c"),
+        );
+        fetch(&mut cache, &server, &a);
+        fetch(&mut cache, &server, &b);
+        assert_eq!(2, cache.len());
+        assert_eq!(16, cache.size());
+
+        // aは新鮮ではないが、最近使ったことにするためにForceCacheで読む
+        cache.set_mode(CacheMode::ForceCache);
+        fetch(&mut cache, &server, &a);
+        cache.set_mode(CacheMode::Default);
+
+        // cを保存すると、最も長く使われていないbが捨てられる
+        fetch(&mut cache, &server, &c);
+        assert_eq!(2, cache.len());
+        cache.set_mode(CacheMode::ForceCache);
+        assert_eq!(b"aaaaaaaa".to_vec(), fetch(&mut cache, &server, &a).body());
+        assert_eq!(3, server.request_count());
+        assert_eq!(
+            b"this body is too large to cache".to_vec(),
+            fetch(&mut cache, &server, &b).body()
+        );
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn test_invalidate_on_unsafe_method() {
+        let (mut cache, _now) = cache_with_clock();
+        let server = Server::new(vec![
+            response(200, &[("Cache-Control", "max-age=60")], "list"),
+            response(200, &[("Cache-Control", "max-age=60")], "item"),
+            response(303, &[("Location", "/items/1")], ""),
+            response(200, &[], "new list"),
+        ]);
+        fetch(&mut cache, &server, &get("http://example.com/items"));
+        fetch(&mut cache, &server, &get("http://example.com/items/1"));
+        assert_eq!(2, cache.len());
+
+        let post = HttpRequest::new(
+            "POST",
+            Url::new("http://example.com/items".to_string())
+                .parse()
+                .expect("failed to parse url"),
+        );
+        fetch(&mut cache, &server, &post);
+        assert!(cache.is_empty());
+        assert_eq!(
+            b"new list".to_vec(),
+            fetch(&mut cache, &server, &get("http://example.com/items")).body()
+        );
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        assert_eq!(
+            vec![
+                ("max-age".to_string(), Some("60".to_string())),
+                ("no-cache".to_string(), Some("Set-Cookie, Foo".to_string())),
+                ("public".to_string(), None),
+            ],
+            parse_directives(&["Max-Age=60, no-cache=\"Set-Cookie, Foo\" , public".to_string()])
+        );
+        let headers = [Header::new("Pragma".to_string(), "no-cache".to_string())];
+        assert!(CacheControl::parse(&headers).no_cache);
+        let headers = [Header::new(
+            "Cache-Control".to_string(),
+            "max-age=abc".to_string(),
+        )];
+        assert_eq!(Some(0), CacheControl::parse(&headers).max_age);
+    }
+
+    #[test]
+    fn test_fetch_streaming_stores_after_body() {
+        let (cache, _now) = cache_with_clock();
+        let cache = Rc::new(RefCell::new(cache));
+        let server = Server::new(vec![response(
+            200,
+            &[("Cache-Control", "max-age=60")],
+            "saba",
+        )]);
+        let request = get("http://example.com/index.html");
+        let open = |request: &HttpRequest| {
+            let mut response = server.send(request)?;
+            let body: Box<dyn BodyReader> =
+                Box::new(CompleteBody::new(response.take_body()).with_chunk_size(2));
+            Ok((response, body))
+        };
+
+        let (response, mut body) =
+            HttpCache::fetch_streaming(&cache, &request, open).expect("failed to fetch");
+        assert_eq!(200, response.status_code());
+        assert_eq!(Some(b"sa".to_vec()), body.read().expect("failed to read"));
+        // ボディを読み終えるまでは保存しない
+        assert!(cache.borrow().is_empty());
+        assert_eq!(Some(b"ba".to_vec()), body.read().expect("failed to read"));
+        assert_eq!(None, body.read().expect("failed to read"));
+        assert_eq!(1, cache.borrow().len());
+
+        let (_, mut body) =
+            HttpCache::fetch_streaming(&cache, &request, open).expect("failed to fetch");
+        assert_eq!(Some(b"saba".to_vec()), body.read().expect("failed to read"));
+        assert_eq!(1, server.request_count());
+    }
+}
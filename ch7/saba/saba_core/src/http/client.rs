@@ -0,0 +1,237 @@
+use crate::error::Error;
+use crate::http::proxy::ProxyConfig;
+use crate::http::request::HttpRequest;
+use crate::http::HttpResponse;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
+
+/// レスポンスのボディを、受信した分から順に読み出す
+pub trait BodyReader: Debug {
+    /// 受信したバイト列を返す。ボディの終わりに達した場合はNoneを返し、それ以降もNoneを返す
+    fn read(&mut self) -> Result<Option<Vec<u8>>, Error>;
+}
+
+/// すでにすべて受信したボディを、先頭から順に返す
+#[derive(Debug, Clone)]
+pub struct CompleteBody {
+    body: Vec<u8>,
+    position: usize,
+    chunk_size: usize,
+}
+
+impl CompleteBody {
+    pub fn new(body: Vec<u8>) -> Self {
+        Self {
+            body,
+            position: 0,
+            chunk_size: usize::MAX,
+        }
+    }
+
+    /// 1回のread()で返すバイト数を制限する。少しずつ受信した場合の動作を確かめるために使う
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+}
+
+impl BodyReader for CompleteBody {
+    fn read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        if self.position >= self.body.len() {
+            return Ok(None);
+        }
+        let end = self
+            .body
+            .len()
+            .min(self.position.saturating_add(self.chunk_size));
+        let chunk = self.body[self.position..end].to_vec();
+        self.position = end;
+        Ok(Some(chunk))
+    }
+}
+
+/// リクエストを送信してレスポンスを受け取る。OSのネットワークに依存する実装は、saba_coreの
+/// 外に置く
+pub trait NetworkClient: Debug {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error>;
+
+    /// ヘッダを受信した時点でボディが空のレスポンスを返し、ボディは返したBodyReaderから
+    /// 受信した分ずつ読み出す。ボディを少しずつ受信できない実装は、send()で受信したボディを
+    /// まとめて返す
+    fn open(&self, request: &HttpRequest) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let mut response = self.send(request)?;
+        let body = response.take_body();
+        Ok((response, Box::new(CompleteBody::new(body))))
+    }
+
+    /// これ以降のリクエストで使うプロキシを設定する。Noneの場合は直接接続する
+    fn set_proxy(&self, _proxy: Option<ProxyConfig>) {}
+}
+
+/// あらかじめ登録したレスポンスを、URLごとに返すメモリ上のクライアント。ネットワークを
+/// 使わずにナビゲーションやリダイレクトをテストするために使う
+#[derive(Debug, Clone)]
+pub struct MockNetworkClient {
+    /// フラグメントを除いたURLをキーにする
+    responses: RefCell<BTreeMap<String, HttpResponse>>,
+    requests: RefCell<Vec<HttpRequest>>,
+    proxy: RefCell<Option<ProxyConfig>>,
+    /// open()で返すボディを、このバイト数ずつ読み出す
+    chunk_size: RefCell<Option<usize>>,
+}
+
+impl MockNetworkClient {
+    pub fn new() -> Self {
+        Self {
+            responses: RefCell::new(BTreeMap::new()),
+            requests: RefCell::new(Vec::new()),
+            proxy: RefCell::new(None),
+            chunk_size: RefCell::new(None),
+        }
+    }
+
+    /// 同じURLのレスポンスがすでに登録されている場合は置き換える
+    pub fn add_response(&self, url: &str, response: HttpResponse) {
+        self.responses
+            .borrow_mut()
+            .insert(url.split('#').next().unwrap_or(url).into(), response);
+    }
+
+    pub fn remove_response(&self, url: &str) {
+        self.responses.borrow_mut().remove(url);
+    }
+
+    /// これまでに送信されたリクエストを、送信した順に返す
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.borrow().clone()
+    }
+
+    pub fn clear_requests(&self) {
+        self.requests.borrow_mut().clear();
+    }
+
+    /// open()で返すボディを指定したバイト数ずつ読み出し、少しずつ受信したように見せる
+    pub fn set_chunk_size(&self, chunk_size: usize) {
+        *self.chunk_size.borrow_mut() = Some(chunk_size);
+    }
+
+    /// 最後に設定されたプロキシ
+    pub fn proxy(&self) -> Option<ProxyConfig> {
+        self.proxy.borrow().clone()
+    }
+}
+
+impl Default for MockNetworkClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkClient for MockNetworkClient {
+    /// 登録されていないURLへのリクエストは、接続に失敗したものとして扱う
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        self.requests.borrow_mut().push(request.clone());
+        let url = request.url().serialize(/*exclude_fragment=*/ true);
+        match self.responses.borrow().get(&url) {
+            Some(response) => Ok(response.clone()),
+            None => Err(Error::Network(format!("no response for {}", url))),
+        }
+    }
+
+    fn open(&self, request: &HttpRequest) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let mut response = self.send(request)?;
+        let mut body = CompleteBody::new(response.take_body());
+        if let Some(chunk_size) = *self.chunk_size.borrow() {
+            body = body.with_chunk_size(chunk_size);
+        }
+        Ok((response, Box::new(body)))
+    }
+
+    fn set_proxy(&self, proxy: Option<ProxyConfig>) {
+        *self.proxy.borrow_mut() = proxy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::url::Url;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::get(
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    #[test]
+    fn test_canned_responses() {
+        let client = MockNetworkClient::new();
+        client.add_response(
+            "http://example.com/index.html",
+            HttpResponse::from_parts(200, "OK", Vec::new(), b"saba".to_vec()),
+        );
+
+        let response = client
+            .send(&get("http://example.com/index.html#top"))
+            .expect("failed to send");
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"saba".to_vec(), response.body());
+
+        assert!(client.send(&get("http://example.com/other.html")).is_err());
+
+        client.remove_response("http://example.com/index.html");
+        assert!(client.send(&get("http://example.com/index.html")).is_err());
+    }
+
+    #[test]
+    fn test_records_requests() {
+        let client = MockNetworkClient::new();
+        let _ = client.send(&get("http://example.com/a"));
+        let _ = client.send(&get("http://example.com/b"));
+
+        let urls: Vec<String> = client
+            .requests()
+            .iter()
+            .map(|request| request.url().href())
+            .collect();
+        assert_eq!(
+            vec![
+                "http://example.com/a".to_string(),
+                "http://example.com/b".to_string()
+            ],
+            urls
+        );
+
+        client.clear_requests();
+        assert!(client.requests().is_empty());
+    }
+
+    #[test]
+    fn test_open_in_chunks() {
+        let client = MockNetworkClient::new();
+        client.add_response(
+            "http://example.com/",
+            HttpResponse::from_parts(200, "OK", Vec::new(), b"saba".to_vec()),
+        );
+        client.set_chunk_size(3);
+
+        let (response, mut body) = client
+            .open(&get("http://example.com/"))
+            .expect("failed to open");
+        assert_eq!(200, response.status_code());
+        assert!(response.body().is_empty());
+        assert_eq!(Some(b"sab".to_vec()), body.read().expect("failed to read"));
+        assert_eq!(Some(b"a".to_vec()), body.read().expect("failed to read"));
+        assert_eq!(None, body.read().expect("failed to read"));
+        assert_eq!(None, body.read().expect("failed to read"));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::compression::deflate::Inflater;
+use crate::compression::gzip::GzipDecoder;
+use crate::compression::zlib::is_zlib_header;
+use crate::compression::zlib::ZlibDecoder;
+use crate::compression::Decoder;
+use crate::error::Error;
+use crate::http::HttpResponse;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// リクエストのAccept-Encodingヘッダに送る値
+/// https://www.rfc-editor.org/rfc/rfc9110#section-12.5.3
+pub const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// "deflate"はzlib形式のはずだが、生のdeflateストリームを送るサーバーもあるので、先頭の
+/// 2バイトを見て決める
+/// https://www.rfc-editor.org/rfc/rfc9110#section-8.4.1.2
+#[derive(Debug)]
+struct DeflateDecoder {
+    pending: Vec<u8>,
+    inner: Option<Box<dyn Decoder>>,
+}
+
+impl DeflateDecoder {
+    fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            inner: None,
+        }
+    }
+
+    fn select(&mut self) -> Box<dyn Decoder> {
+        match self.pending[..] {
+            [cmf, flg, ..] if is_zlib_header(cmf, flg) => Box::new(ZlibDecoder::new()),
+            _ => Box::new(Inflater::new()),
+        }
+    }
+}
+
+impl Decoder for DeflateDecoder {
+    fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        if let Some(inner) = self.inner.as_mut() {
+            return inner.push(input, output);
+        }
+        self.pending.extend_from_slice(input);
+        if self.pending.len() < 2 {
+            return Ok(());
+        }
+        let mut inner = self.select();
+        let pending = core::mem::take(&mut self.pending);
+        let result = inner.push(&pending, output);
+        self.inner = Some(inner);
+        result
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> Result<(), Error> {
+        if self.inner.is_none() {
+            let mut inner = self.select();
+            let pending = core::mem::take(&mut self.pending);
+            inner.push(&pending, output)?;
+            self.inner = Some(inner);
+        }
+        match self.inner.as_mut() {
+            Some(inner) => inner.finish(output),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Content-Encodingに従ってボディを展開する。変換が複数ある場合は、後に書かれたものから
+/// 順に戻す
+/// https://www.rfc-editor.org/rfc/rfc9110#section-8.4
+#[derive(Debug)]
+pub struct ContentDecoder {
+    decoders: Vec<Box<dyn Decoder>>,
+}
+
+impl ContentDecoder {
+    /// 変換がない場合と、知らない変換が含まれている場合はNoneを返し、ボディはそのまま扱う
+    pub fn for_response(response: &HttpResponse) -> Option<Self> {
+        let codings: Vec<String> = response
+            .header_values("Content-Encoding")
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|coding| coding.trim_matches([' ', '\t']).to_ascii_lowercase())
+            .filter(|coding| !coding.is_empty() && coding != "identity")
+            .collect();
+
+        let mut decoders: Vec<Box<dyn Decoder>> = Vec::new();
+        for coding in codings.iter().rev() {
+            match coding.as_str() {
+                "gzip" | "x-gzip" => decoders.push(Box::new(GzipDecoder::new())),
+                "deflate" => decoders.push(Box::new(DeflateDecoder::new())),
+                _ => return None,
+            }
+        }
+        if decoders.is_empty() {
+            None
+        } else {
+            Some(Self { decoders })
+        }
+    }
+
+    pub fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        let mut data = input.to_vec();
+        let last = self.decoders.len() - 1;
+        for (i, decoder) in self.decoders.iter_mut().enumerate() {
+            if i == last {
+                return decoder.push(&data, output);
+            }
+            let mut decoded = Vec::new();
+            decoder.push(&data, &mut decoded)?;
+            data = decoded;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> Result<(), Error> {
+        // 前の変換を終えたときに出てきたバイト列を、次の変換に渡してから終える
+        let mut data = Vec::new();
+        let last = self.decoders.len() - 1;
+        for (i, decoder) in self.decoders.iter_mut().enumerate() {
+            if i == last {
+                decoder.push(&data, output)?;
+                return decoder.finish(output);
+            }
+            let mut decoded = Vec::new();
+            decoder.push(&data, &mut decoded)?;
+            decoder.finish(&mut decoded)?;
+            data = decoded;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+    use crate::http::Header;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    const HELLO_GZIP: &str = "1f8b08000000000002032bcf2fca4901004311773a05000000";
+
+    fn decoder(content_encoding: &str) -> Option<ContentDecoder> {
+        let response = HttpResponse::from_parts(
+            200,
+            "OK",
+            vec![Header::new(
+                "Content-Encoding".to_string(),
+                content_encoding.to_string(),
+            )],
+            Vec::new(),
+        );
+        ContentDecoder::for_response(&response)
+    }
+
+    fn decode(content_encoding: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut decoder = decoder(content_encoding).expect("no decoder");
+        let mut output = Vec::new();
+        for b in input {
+            decoder.push(&[*b], &mut output)?;
+        }
+        decoder.finish(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_gzip() {
+        assert_eq!(
+            b"world".to_vec(),
+            decode("gzip", &from_hex(HELLO_GZIP)).expect("failed to decode")
+        );
+        assert_eq!(
+            b"world".to_vec(),
+            decode("X-Gzip", &from_hex(HELLO_GZIP)).expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_deflate() {
+        // zlib形式
+        assert_eq!(
+            b"world".to_vec(),
+            decode("deflate", &from_hex("789c2bcf2fca49010006a60229")).expect("failed to decode")
+        );
+        // 生のdeflateストリーム
+        assert_eq!(
+            b"world".to_vec(),
+            decode("deflate", &from_hex("2bcf2fca490100")).expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_multiple_codings() {
+        // gzipで圧縮したものを、さらにdeflateで圧縮している
+        let input = from_hex("789c93efe660000126666d3f1f2f1686499a4b6b58807c0029b903ab");
+        assert_eq!(
+            b"saba".to_vec(),
+            decode("gzip, identity, deflate", &input).expect("failed to decode")
+        );
+    }
+
+    #[test]
+    fn test_no_decoder() {
+        assert!(decoder("").is_none());
+        assert!(decoder("identity").is_none());
+        assert!(decoder("br").is_none());
+        assert!(decoder("gzip, br").is_none());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let input = from_hex(HELLO_GZIP);
+        assert!(decode("gzip", &input[..input.len() - 1]).is_err());
+    }
+}
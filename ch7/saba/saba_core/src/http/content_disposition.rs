@@ -0,0 +1,155 @@
+use crate::url::percent_encoding::percent_decode;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Content-Dispositionヘッダ。ダウンロードとして扱うかどうかと、保存するときのファイル名を表す
+/// https://www.rfc-editor.org/rfc/rfc6266
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDisposition {
+    disposition_type: String,
+    filename: Option<String>,
+}
+
+/// ";"で区切る。ただし、引用符の中の";"では区切らない
+fn split_parameters(value: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if quoted => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' => {
+                current.push(c);
+                quoted = !quoted;
+            }
+            ';' if !quoted => parts.push(core::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+/// 引用符で囲まれた値は、引用符とエスケープを取り除く
+/// https://www.rfc-editor.org/rfc/rfc9110#section-5.6.4
+fn unquote(value: &str) -> String {
+    let inner = match value.strip_prefix('"') {
+        Some(inner) => inner.strip_suffix('"').unwrap_or(inner),
+        None => return String::from(value),
+    };
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                unquoted.push(escaped);
+            }
+            continue;
+        }
+        unquoted.push(c);
+    }
+    unquoted
+}
+
+/// "UTF-8''%E2%82%AC.txt"のような、文字コードと言語を指定した値をデコードする
+/// https://www.rfc-editor.org/rfc/rfc8187#section-3.2
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let bytes = percent_decode(parts.next()?.as_bytes());
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        Some(bytes.iter().map(|b| *b as char).collect())
+    } else {
+        None
+    }
+}
+
+impl ContentDisposition {
+    /// https://www.rfc-editor.org/rfc/rfc6266#section-4.1
+    pub fn parse(value: &str) -> Option<Self> {
+        let parts = split_parameters(value);
+        let disposition_type = parts[0].trim().to_ascii_lowercase();
+        if disposition_type.is_empty() || disposition_type.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let mut filename = None;
+        let mut ext_filename = None;
+        for part in &parts[1..] {
+            let (name, value) = match part.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            if name.eq_ignore_ascii_case("filename") && filename.is_none() {
+                filename = Some(unquote(value));
+            } else if name.eq_ignore_ascii_case("filename*") && ext_filename.is_none() {
+                ext_filename = decode_ext_value(value);
+            }
+        }
+
+        Some(Self {
+            disposition_type,
+            // filename*がある場合は、そちらを優先する
+            filename: ext_filename.or(filename),
+        })
+    }
+
+    pub fn disposition_type(&self) -> &str {
+        &self.disposition_type
+    }
+
+    /// inline以外の種類は、attachmentとして扱う
+    /// https://www.rfc-editor.org/rfc/rfc6266#section-4.2
+    pub fn is_attachment(&self) -> bool {
+        self.disposition_type != "inline"
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let disposition = ContentDisposition::parse("inline").expect("failed to parse");
+        assert!(!disposition.is_attachment());
+        assert_eq!(None, disposition.filename());
+
+        let disposition = ContentDisposition::parse("Attachment; filename=\"a;b \\\"c\\\".zip\"")
+            .expect("failed to parse");
+        assert_eq!("attachment", disposition.disposition_type());
+        assert!(disposition.is_attachment());
+        assert_eq!(Some("a;b \"c\".zip"), disposition.filename());
+
+        let disposition = ContentDisposition::parse(
+            "attachment; filename=\"EURO rates\"; filename*=utf-8''%e2%82%ac%20rates",
+        )
+        .expect("failed to parse");
+        assert_eq!(Some("\u{20AC} rates"), disposition.filename());
+
+        // 未知の種類はattachmentとして扱う
+        let disposition =
+            ContentDisposition::parse("foo; filename=a.pdf").expect("failed to parse");
+        assert!(disposition.is_attachment());
+        assert_eq!(Some("a.pdf"), disposition.filename());
+
+        assert_eq!(None, ContentDisposition::parse(""));
+        assert_eq!(None, ContentDisposition::parse("; filename=a"));
+    }
+}
@@ -0,0 +1,900 @@
+use crate::http::date::parse_date;
+use crate::http::request::HttpRequest;
+use crate::http::HttpResponse;
+use crate::url::host::Host;
+use crate::url::Url;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// 名前と値を合わせた長さの上限
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6
+const MAX_NAME_VALUE_LENGTH: usize = 4096;
+/// 属性の値の長さの上限
+const MAX_ATTRIBUTE_VALUE_LENGTH: usize = 1024;
+/// Max-Ageの上限は400日
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.5
+const MAX_AGE_LIMIT: u64 = 400 * 24 * 60 * 60;
+/// 時計がない場合に、これより前のExpiresは過去の日時とみなす（2024-01-01T00:00:00Z）
+const CLOCKLESS_EPOCH: u64 = 1_704_067_200;
+
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+    /// 属性がないか、知らない値の場合。Laxとして扱う
+    Default,
+}
+
+/// Cookieを読み書きするAPIの種類。HttpOnlyのCookieはHTTP以外から扱えない
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieSource {
+    Http,
+    /// document.cookieのような、スクリプトから使うAPI
+    NonHttp,
+}
+
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.7
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    path: String,
+    /// UNIX時間の秒数。Noneの場合はセッションが終わるまで残す
+    expiry: Option<u64>,
+    secure: bool,
+    http_only: bool,
+    host_only: bool,
+    same_site: SameSite,
+    /// 作られた順番。同じ長さのパスのCookieを並べるのに使う
+    creation_index: u64,
+}
+
+impl Cookie {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    pub fn domain(&self) -> String {
+        self.domain.clone()
+    }
+
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn expiry(&self) -> Option<u64> {
+        self.expiry
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn host_only(&self) -> bool {
+        self.host_only
+    }
+
+    pub fn same_site(&self) -> SameSite {
+        self.same_site
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        match self.expiry {
+            Some(expiry) => expiry <= now,
+            None => false,
+        }
+    }
+
+    /// 名前が空の場合は値だけを出力する
+    /// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.8.3
+    fn serialize(&self) -> String {
+        if self.name.is_empty() {
+            self.value.clone()
+        } else {
+            let mut output = self.name.clone();
+            output.push('=');
+            output.push_str(&self.value);
+            output
+        }
+    }
+}
+
+/// Set-Cookieヘッダを解析した結果。ストアに保存するときに、URLに従ってドメインとパスを決める
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SetCookie {
+    name: String,
+    value: String,
+    /// Expiresの日時
+    expires: Option<u64>,
+    /// Max-Ageの秒数。0以下の場合はすぐに期限切れにする
+    max_age: Option<i64>,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: SameSite,
+}
+
+fn trim_whitespace(s: &str) -> &str {
+    s.trim_matches([' ', '\t'])
+}
+
+fn has_control(s: &str) -> bool {
+    s.chars()
+        .any(|c| matches!(c, '\u{0}'..='\u{8}' | '\u{A}'..='\u{1F}' | '\u{7F}'))
+}
+
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6
+fn parse_set_cookie(input: &str) -> Option<SetCookie> {
+    let (name_value, attributes) = match input.split_once(';') {
+        Some((name_value, attributes)) => (name_value, Some(attributes)),
+        None => (input, None),
+    };
+    if has_control(name_value) {
+        return None;
+    }
+    // "="がない場合は、名前が空で値だけのCookieとして扱う
+    let (name, value) = match name_value.split_once('=') {
+        Some((name, value)) => (trim_whitespace(name), trim_whitespace(value)),
+        None => ("", trim_whitespace(name_value)),
+    };
+    if (name.is_empty() && value.is_empty()) || name.len() + value.len() > MAX_NAME_VALUE_LENGTH {
+        return None;
+    }
+
+    let mut cookie = SetCookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        expires: None,
+        max_age: None,
+        domain: None,
+        path: None,
+        secure: false,
+        http_only: false,
+        same_site: SameSite::Default,
+    };
+
+    for attribute in attributes.unwrap_or("").split(';') {
+        let (name, value) = match attribute.split_once('=') {
+            Some((name, value)) => (trim_whitespace(name), trim_whitespace(value)),
+            None => (trim_whitespace(attribute), ""),
+        };
+        if value.len() > MAX_ATTRIBUTE_VALUE_LENGTH {
+            continue;
+        }
+        match name.to_ascii_lowercase().as_str() {
+            // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.1
+            "expires" => {
+                if let Some(time) = parse_date(value) {
+                    cookie.expires = Some(time);
+                }
+            }
+            // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.2
+            "max-age" => {
+                let digits = value.strip_prefix('-').unwrap_or(value);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    continue;
+                }
+                // 大きすぎる値は上限に丸める
+                let seconds = digits.parse::<i64>().unwrap_or(i64::MAX);
+                cookie.max_age = Some(if value.starts_with('-') {
+                    -seconds
+                } else {
+                    seconds
+                });
+            }
+            // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.3
+            "domain" => {
+                let domain = value.strip_prefix('.').unwrap_or(value);
+                if !domain.is_empty() {
+                    cookie.domain = Some(domain.to_ascii_lowercase());
+                }
+            }
+            // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.4
+            "path" => {
+                cookie.path = if value.starts_with('/') {
+                    Some(value.to_string())
+                } else {
+                    None
+                };
+            }
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.7
+            "samesite" => {
+                cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                    "strict" => SameSite::Strict,
+                    "lax" => SameSite::Lax,
+                    "none" => SameSite::None,
+                    _ => SameSite::Default,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Some(cookie)
+}
+
+fn is_ip_address(url: &Url) -> bool {
+    matches!(url.host_kind(), Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)))
+}
+
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.1.3
+fn domain_string_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+/// IPアドレスの場合は、完全に一致する場合だけ
+fn domain_match(url: &Url, domain: &str) -> bool {
+    let host = url.host();
+    if is_ip_address(url) {
+        return host == domain;
+    }
+    domain_string_match(&host, domain)
+}
+
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.1.4
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.1.4
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    if request_path == cookie_path {
+        return true;
+    }
+    request_path.starts_with(cookie_path)
+        && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/'))
+}
+
+fn is_secure_url(url: &Url) -> bool {
+    url.scheme() == "https"
+}
+
+/// パブリックサフィックスリストを持っていないので、ホストの最後の2つのラベルが同じ場合を
+/// 同じサイトとみなす
+/// https://html.spec.whatwg.org/multipage/browsers.html#same-site
+pub fn is_same_site(a: &Url, b: &Url) -> bool {
+    fn site(url: &Url) -> String {
+        let host = url.host();
+        if is_ip_address(url) {
+            return host;
+        }
+        let labels: Vec<&str> = host.rsplitn(3, '.').collect();
+        match labels.as_slice() {
+            [last, second, ..] => {
+                let mut site = second.to_string();
+                site.push('.');
+                site.push_str(last);
+                site
+            }
+            _ => host,
+        }
+    }
+    a.scheme() == b.scheme() && site(a) == site(b)
+}
+
+/// Cookieを保存しておき、リクエストのURLに合うものを取り出す
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.7
+#[derive(Clone)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+    next_creation_index: u64,
+    /// Cookieが追加、削除されるか、値が変わるたびに増える
+    generation: u64,
+    /// 現在のUNIX時間の秒数を返す。ない場合は期限を判断できないので、期限付きのCookieは
+    /// 削除の指定でなければセッションCookieとして保存する
+    clock: Option<Rc<dyn Fn() -> u64>>,
+}
+
+impl fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieJar")
+            .field("cookies", &self.cookies)
+            .finish()
+    }
+}
+
+impl CookieJar {
+    /// 時刻を取得できない環境のためのCookieの保存先。0以下のMax-Ageと、レスポンスのDate
+    /// （ない場合はCLOCKLESS_EPOCH）以前のExpiresは削除として扱う
+    pub fn new() -> Self {
+        Self {
+            cookies: Vec::new(),
+            next_creation_index: 0,
+            generation: 0,
+            clock: None,
+        }
+    }
+
+    pub fn with_clock(clock: Rc<dyn Fn() -> u64>) -> Self {
+        Self {
+            clock: Some(clock),
+            ..Self::new()
+        }
+    }
+
+    /// Cookieが変わったかどうかを比べるための値。Cookieが追加、削除されるか、値が変わるたびに
+    /// 増える
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// 時計がない場合は0を返す。期限が0のCookie、つまり削除されたCookieだけが期限切れになる
+    fn now(&self) -> u64 {
+        self.clock.as_ref().map_or(0, |clock| clock())
+    }
+
+    /// 期限切れのものを除いた、保存されているCookie
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = self.now();
+        self.cookies
+            .iter()
+            .filter(|c| !c.is_expired(now))
+            .cloned()
+            .collect()
+    }
+
+    /// レスポンスのSet-Cookieヘッダをすべて保存する
+    pub fn store_response_cookies(&mut self, url: &Url, response: &HttpResponse) {
+        let date = response
+            .header_value("Date")
+            .ok()
+            .and_then(|date| parse_date(&date));
+        for value in response.header_values("Set-Cookie") {
+            self.store_cookie(url, &value, CookieSource::Http, date);
+        }
+    }
+
+    /// Set-Cookieヘッダの値、またはdocument.cookieに代入された文字列を保存する。
+    /// 保存しなかった場合はfalseを返す
+    /// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.7
+    pub fn set_cookie(&mut self, url: &Url, input: &str, source: CookieSource) -> bool {
+        self.store_cookie(url, input, source, None)
+    }
+
+    /// dateはレスポンスのDateヘッダの時刻で、時計がない場合にExpiresが過去かどうかを
+    /// 判断するために使う
+    fn store_cookie(
+        &mut self,
+        url: &Url,
+        input: &str,
+        source: CookieSource,
+        date: Option<u64>,
+    ) -> bool {
+        let parsed = match parse_set_cookie(input) {
+            Some(parsed) => parsed,
+            None => return false,
+        };
+        let now = self.now();
+
+        if !matches!(url.scheme().as_str(), "http" | "https") || url.host().is_empty() {
+            return false;
+        }
+        if parsed.secure && !is_secure_url(url) {
+            return false;
+        }
+        if parsed.http_only && source == CookieSource::NonHttp {
+            return false;
+        }
+
+        // パブリックサフィックスリストを持っていないので、"com"のような1つのラベルだけの
+        // ドメインには、そのホスト自身でない限り設定させない
+        let (domain, host_only) = match parsed.domain {
+            Some(domain) => {
+                if !domain_match(url, &domain) {
+                    return false;
+                }
+                if !domain.contains('.') && domain != url.host() {
+                    return false;
+                }
+                (domain, false)
+            }
+            None => (url.host(), true),
+        };
+        let path = parsed.path.unwrap_or_else(|| default_path(url));
+
+        // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-4.1.3
+        let lower_name = parsed.name.to_ascii_lowercase();
+        if lower_name.starts_with("__secure-") && !parsed.secure {
+            return false;
+        }
+        if lower_name.starts_with("__host-") && (!parsed.secure || !host_only || path != "/") {
+            return false;
+        }
+        // SameSite=NoneはSecureと一緒でなければならない
+        if parsed.same_site == SameSite::None && !parsed.secure {
+            return false;
+        }
+
+        // 安全でない接続から、Secureなクッキーを上書きさせない
+        // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.7
+        if !parsed.secure && !is_secure_url(url) {
+            let shadows_secure = self.cookies.iter().any(|c| {
+                c.secure
+                    && c.name == parsed.name
+                    && (domain_string_match(&c.domain, &domain)
+                        || domain_string_match(&domain, &c.domain))
+                    && path_match(&path, &c.path)
+            });
+            if shadows_secure {
+                return false;
+            }
+        }
+
+        // Max-AgeはExpiresより優先する
+        let expiry = match (parsed.max_age, parsed.expires) {
+            (Some(max_age), _) if max_age <= 0 => Some(0),
+            (None, None) => None,
+            // 時計がない場合は、過去の日時のExpiresだけを削除として扱い、それ以外は期限を
+            // 判断できないのでセッションCookieにする
+            _ if self.clock.is_none() => match parsed.expires {
+                Some(expires)
+                    if parsed.max_age.is_none()
+                        && expires <= date.unwrap_or(0).max(CLOCKLESS_EPOCH) =>
+                {
+                    Some(0)
+                }
+                _ => None,
+            },
+            (Some(max_age), _) => Some(now.saturating_add((max_age as u64).min(MAX_AGE_LIMIT))),
+            (None, Some(expires)) => Some(expires.min(now.saturating_add(MAX_AGE_LIMIT))),
+        };
+
+        let mut creation_index = self.next_creation_index;
+        let mut previous_value = None;
+        if let Some(i) = self
+            .cookies
+            .iter()
+            .position(|c| c.name == parsed.name && c.domain == domain && c.path == path)
+        {
+            // スクリプトからHttpOnlyのCookieは上書きできない
+            if self.cookies[i].http_only && source == CookieSource::NonHttp {
+                return false;
+            }
+            creation_index = self.cookies[i].creation_index;
+            previous_value = Some(self.cookies.remove(i).value);
+        } else {
+            self.next_creation_index += 1;
+        }
+
+        let cookie = Cookie {
+            name: parsed.name,
+            value: parsed.value,
+            domain,
+            path,
+            expiry,
+            secure: parsed.secure,
+            http_only: parsed.http_only,
+            host_only,
+            same_site: parsed.same_site,
+            creation_index,
+        };
+        if cookie.is_expired(now) {
+            // 期限切れのCookieを設定すると、同じCookieを削除することになる
+            if previous_value.is_some() {
+                self.generation += 1;
+            }
+            return true;
+        }
+        if previous_value.as_ref() != Some(&cookie.value) {
+            self.generation += 1;
+        }
+        self.cookies.push(cookie);
+        true
+    }
+
+    /// URLに送るCookieを、"name=value; name2=value2"の形にして返す。initiatorは
+    /// リクエストを始めたドキュメントのURLで、アドレスバーからの移動の場合はNone
+    /// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.8.3
+    pub fn cookie_string(
+        &mut self,
+        url: &Url,
+        source: CookieSource,
+        initiator: Option<&Url>,
+        method: &str,
+    ) -> String {
+        let now = self.now();
+        self.cookies.retain(|c| !c.is_expired(now));
+
+        let same_site = match initiator {
+            Some(initiator) => is_same_site(url, initiator),
+            None => true,
+        };
+        let path = url.path();
+        let mut matched: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| {
+                if c.host_only {
+                    url.host() == c.domain
+                } else {
+                    domain_match(url, &c.domain)
+                }
+            })
+            .filter(|c| path_match(if path.is_empty() { "/" } else { &path }, &c.path))
+            .filter(|c| !c.secure || is_secure_url(url))
+            .filter(|c| !c.http_only || source == CookieSource::Http)
+            // 別のサイトから始まったリクエストには、Laxの場合は安全なメソッドのときだけ送る
+            // https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.8.3
+            .filter(|c| match c.same_site {
+                SameSite::None => true,
+                _ if same_site => true,
+                SameSite::Strict => false,
+                SameSite::Lax | SameSite::Default => {
+                    matches!(method, "GET" | "HEAD" | "OPTIONS" | "TRACE")
+                }
+            })
+            .collect();
+
+        // パスが長いものを先に、同じ長さの場合は先に作られたものを先にする
+        matched.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then(a.creation_index.cmp(&b.creation_index))
+        });
+
+        let values: Vec<String> = matched.iter().map(|c| c.serialize()).collect();
+        values.join("; ")
+    }
+
+    /// リクエストのURLに合うCookieがあれば、Cookieヘッダを付ける
+    pub fn add_cookie_header(&mut self, request: &mut HttpRequest, initiator: Option<&Url>) {
+        let cookies = self.cookie_string(
+            &request.url(),
+            CookieSource::Http,
+            initiator,
+            &request.method(),
+        );
+        if cookies.is_empty() {
+            request.remove_header("Cookie");
+        } else {
+            request.set_header("Cookie", &cookies);
+        }
+    }
+
+    /// document.cookieの値。httpやhttpsでないドキュメントでは空になる
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-cookie
+    pub fn document_cookie(&mut self, url: &Url) -> String {
+        self.cookie_string(url, CookieSource::NonHttp, None, "GET")
+    }
+
+    /// document.cookieへの代入
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-cookie
+    pub fn set_document_cookie(&mut self, url: &Url, value: &str) {
+        self.set_cookie(url, value, CookieSource::NonHttp);
+    }
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Header;
+    use alloc::vec;
+    use core::cell::Cell;
+
+    fn url(url: &str) -> Url {
+        Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url")
+    }
+
+    fn cookies(jar: &mut CookieJar, input: &str) -> String {
+        jar.cookie_string(&url(input), CookieSource::Http, None, "GET")
+    }
+
+    #[test]
+    fn test_parse_set_cookie() {
+        let cookie = parse_set_cookie(
+            " sid = 31d4d96e407aad42 ; Path=/app; Domain=.Example.com; Secure; HttpOnly; SameSite=Strict; Max-Age=60; Unknown",
+        )
+        .expect("failed to parse");
+        assert_eq!("sid".to_string(), cookie.name);
+        assert_eq!("31d4d96e407aad42".to_string(), cookie.value);
+        assert_eq!(Some("/app".to_string()), cookie.path);
+        assert_eq!(Some("example.com".to_string()), cookie.domain);
+        assert!(cookie.secure);
+        assert!(cookie.http_only);
+        assert_eq!(SameSite::Strict, cookie.same_site);
+        assert_eq!(Some(60), cookie.max_age);
+
+        let cookie = parse_set_cookie("token").expect("failed to parse");
+        assert_eq!("".to_string(), cookie.name);
+        assert_eq!("token".to_string(), cookie.value);
+
+        let cookie = parse_set_cookie("a=b; Max-Age=abc; Path=relative; SameSite=Foo")
+            .expect("failed to parse");
+        assert_eq!(None, cookie.max_age);
+        assert_eq!(None, cookie.path);
+        assert_eq!(SameSite::Default, cookie.same_site);
+
+        assert_eq!(None, parse_set_cookie(""));
+        assert_eq!(None, parse_set_cookie("="));
+        assert_eq!(None, parse_set_cookie("a=b\u{1}c"));
+    }
+
+    #[test]
+    fn test_domain_and_path() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://www.example.com/app/login");
+        assert!(jar.set_cookie(&origin, "host=1", CookieSource::Http));
+        assert!(jar.set_cookie(&origin, "domain=2; Domain=example.com", CookieSource::Http));
+        assert!(jar.set_cookie(&origin, "root=3; Path=/", CookieSource::Http));
+        // 別のドメインや、パブリックサフィックスのようなドメインには設定できない
+        assert!(!jar.set_cookie(&origin, "other=4; Domain=example.org", CookieSource::Http));
+        assert!(!jar.set_cookie(&origin, "tld=5; Domain=com", CookieSource::Http));
+
+        // パスが長いものが先に来る
+        assert_eq!(
+            "host=1; domain=2; root=3".to_string(),
+            cookies(&mut jar, "http://www.example.com/app/page")
+        );
+        assert_eq!(
+            "root=3".to_string(),
+            cookies(&mut jar, "http://www.example.com/application")
+        );
+        assert_eq!(
+            "domain=2".to_string(),
+            cookies(&mut jar, "http://sub.example.com/app/")
+        );
+        assert_eq!("".to_string(), cookies(&mut jar, "http://example.org/"));
+    }
+
+    #[test]
+    fn test_replace_and_delete() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/");
+        jar.set_cookie(&origin, "a=1", CookieSource::Http);
+        jar.set_cookie(&origin, "b=2", CookieSource::Http);
+        jar.set_cookie(&origin, "a=3", CookieSource::Http);
+        // 上書きしても、作られた順番は変わらない
+        assert_eq!(
+            "a=3; b=2".to_string(),
+            cookies(&mut jar, "http://example.com/")
+        );
+
+        jar.set_cookie(&origin, "a=; Max-Age=0", CookieSource::Http);
+        jar.set_cookie(
+            &origin,
+            "b=; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            CookieSource::Http,
+        );
+        assert_eq!("".to_string(), cookies(&mut jar, "http://example.com/"));
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/");
+        jar.set_cookie(&origin, "a=1", CookieSource::Http);
+        assert_eq!(1, jar.generation());
+        // 同じ値で上書きしても変わらない
+        jar.set_cookie(&origin, "a=1; Path=/", CookieSource::Http);
+        assert_eq!(1, jar.generation());
+        jar.set_cookie(&origin, "a=2", CookieSource::Http);
+        assert_eq!(2, jar.generation());
+        jar.set_cookie(&origin, "a=; Max-Age=0", CookieSource::Http);
+        assert_eq!(3, jar.generation());
+        // ないCookieを削除しても変わらない
+        jar.set_cookie(&origin, "a=; Max-Age=0", CookieSource::Http);
+        assert_eq!(3, jar.generation());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let now = Rc::new(Cell::new(1_000_000u64));
+        let clock = now.clone();
+        let mut jar = CookieJar::with_clock(Rc::new(move || clock.get()));
+        let origin = url("http://example.com/");
+        jar.set_cookie(&origin, "short=1; Max-Age=60", CookieSource::Http);
+        jar.set_cookie(
+            &origin,
+            "long=2; Max-Age=60; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            CookieSource::Http,
+        );
+        jar.set_cookie(&origin, "session=3", CookieSource::Http);
+        assert_eq!(
+            Some(1_000_060),
+            jar.cookies()
+                .iter()
+                .find(|c| c.name() == "long")
+                .and_then(|c| c.expiry())
+        );
+
+        now.set(1_000_059);
+        assert_eq!(
+            "short=1; long=2; session=3".to_string(),
+            cookies(&mut jar, "http://example.com/")
+        );
+        now.set(1_000_060);
+        assert_eq!(
+            "session=3".to_string(),
+            cookies(&mut jar, "http://example.com/")
+        );
+    }
+
+    #[test]
+    fn test_expiry_without_clock() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/");
+        jar.set_cookie(&origin, "sid=abc", CookieSource::Http);
+        jar.set_cookie(&origin, "lang=ja", CookieSource::Http);
+        jar.set_cookie(&origin, "theme=dark", CookieSource::Http);
+        // 期限を判断できないCookieは、セッションCookieとして保存する
+        jar.set_cookie(&origin, "long=1; Max-Age=60", CookieSource::Http);
+        jar.set_cookie(
+            &origin,
+            "future=2; Expires=Fri, 01 Jan 2100 00:00:00 GMT",
+            CookieSource::Http,
+        );
+        assert_eq!(None, jar.cookies()[3].expiry());
+        assert_eq!(None, jar.cookies()[4].expiry());
+
+        // 0以下のMax-Ageと、過去の日時のExpiresは削除として扱う
+        jar.set_cookie(&origin, "sid=; Max-Age=-1", CookieSource::Http);
+        jar.set_cookie(
+            &origin,
+            "lang=; Expires=Thu, 01 Jan 2015 00:00:00 GMT",
+            CookieSource::Http,
+        );
+        // レスポンスのDate以前のExpiresも削除として扱う
+        let response = HttpResponse::from_parts(
+            200,
+            "OK",
+            vec![
+                Header::new(
+                    "Date".to_string(),
+                    "Sun, 01 Jun 2025 00:00:00 GMT".to_string(),
+                ),
+                Header::new(
+                    "Set-Cookie".to_string(),
+                    "theme=; Expires=Sat, 01 Mar 2025 00:00:00 GMT".to_string(),
+                ),
+            ],
+            Vec::new(),
+        );
+        jar.store_response_cookies(&origin, &response);
+        assert_eq!(
+            "long=1; future=2".to_string(),
+            cookies(&mut jar, "http://example.com/")
+        );
+    }
+
+    #[test]
+    fn test_secure() {
+        let mut jar = CookieJar::new();
+        // 安全でない接続からはSecureなCookieを設定できない
+        assert!(!jar.set_cookie(
+            &url("http://example.com/"),
+            "a=1; Secure",
+            CookieSource::Http
+        ));
+        assert!(jar.set_cookie(
+            &url("https://example.com/"),
+            "a=1; Secure",
+            CookieSource::Http
+        ));
+        // 安全でない接続から、SecureなCookieを上書きできない
+        assert!(!jar.set_cookie(&url("http://example.com/"), "a=2", CookieSource::Http));
+
+        assert_eq!("a=1".to_string(), cookies(&mut jar, "https://example.com/"));
+        assert_eq!("".to_string(), cookies(&mut jar, "http://example.com/"));
+
+        let https = url("https://example.com/");
+        assert!(!jar.set_cookie(&https, "__Secure-a=1", CookieSource::Http));
+        assert!(jar.set_cookie(&https, "__Secure-a=1; Secure", CookieSource::Http));
+        assert!(!jar.set_cookie(
+            &https,
+            "__Host-a=1; Secure; Domain=example.com",
+            CookieSource::Http
+        ));
+        assert!(jar.set_cookie(&https, "__Host-a=1; Secure; Path=/", CookieSource::Http));
+        assert!(!jar.set_cookie(&https, "n=1; SameSite=None", CookieSource::Http));
+    }
+
+    #[test]
+    fn test_http_only() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/");
+        jar.set_cookie(&origin, "sid=1; HttpOnly", CookieSource::Http);
+        jar.set_document_cookie(&origin, "theme=dark");
+        // スクリプトからはHttpOnlyのCookieを読めず、作ることも上書きすることもできない
+        jar.set_document_cookie(&origin, "sid=2");
+        jar.set_document_cookie(&origin, "token=3; HttpOnly");
+
+        assert_eq!("theme=dark".to_string(), jar.document_cookie(&origin));
+        assert_eq!(
+            "sid=1; theme=dark".to_string(),
+            cookies(&mut jar, "http://example.com/")
+        );
+        assert_eq!(
+            "".to_string(),
+            jar.document_cookie(&url("file:///index.html"))
+        );
+    }
+
+    #[test]
+    fn test_same_site() {
+        let mut jar = CookieJar::new();
+        let origin = url("https://www.example.com/");
+        jar.set_cookie(&origin, "strict=1; SameSite=Strict", CookieSource::Http);
+        jar.set_cookie(&origin, "lax=2; SameSite=Lax", CookieSource::Http);
+        jar.set_cookie(&origin, "none=3; SameSite=None; Secure", CookieSource::Http);
+
+        let same = url("https://login.example.com/");
+        let cross = url("https://other.example/");
+        let cookies = |jar: &mut CookieJar, initiator: &Url, method: &str| {
+            jar.cookie_string(&origin, CookieSource::Http, Some(initiator), method)
+        };
+        assert_eq!(
+            "strict=1; lax=2; none=3".to_string(),
+            cookies(&mut jar, &same, "POST")
+        );
+        assert_eq!(
+            "lax=2; none=3".to_string(),
+            cookies(&mut jar, &cross, "GET")
+        );
+        assert_eq!("none=3".to_string(), cookies(&mut jar, &cross, "POST"));
+    }
+
+    #[test]
+    fn test_response_and_request() {
+        let mut jar = CookieJar::new();
+        let origin = url("http://example.com/login");
+        let response = HttpResponse::from_parts(
+            302,
+            "Found",
+            vec![
+                Header::new("Set-Cookie".to_string(), "sid=abc; Path=/".to_string()),
+                Header::new("Set-Cookie".to_string(), "lang=ja; Path=/".to_string()),
+            ],
+            Vec::new(),
+        );
+        jar.store_response_cookies(&origin, &response);
+
+        let mut request = HttpRequest::get(url("http://example.com/home"));
+        jar.add_cookie_header(&mut request, None);
+        assert_eq!(
+            Some("sid=abc; lang=ja".to_string()),
+            request.header_value("Cookie")
+        );
+
+        let mut request = HttpRequest::get(url("http://example.org/"));
+        jar.add_cookie_header(&mut request, None);
+        assert_eq!(None, request.header_value("Cookie"));
+    }
+}
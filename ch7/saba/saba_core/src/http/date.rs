@@ -0,0 +1,139 @@
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.1.1
+fn is_date_delimiter(c: char) -> bool {
+    matches!(c, '\t' | ' '..='/' | ';'..='@' | '['..='`' | '{'..='~')
+}
+
+/// 先頭の1〜max桁の数字を読み、続く文字が数字でないことを確かめる
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<(u32, &str)> {
+    let length = token.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if length < min || length > max {
+        return None;
+    }
+    let value = token[..length].parse().ok()?;
+    Some((value, &token[length..]))
+}
+
+/// "hh:mm:ss"の形の時刻
+fn parse_time(token: &str) -> Option<(u32, u32, u32)> {
+    let (hour, rest) = leading_digits(token, 1, 2)?;
+    let (minute, rest) = leading_digits(rest.strip_prefix(':')?, 1, 2)?;
+    let (second, _) = leading_digits(rest.strip_prefix(':')?, 1, 2)?;
+    Some((hour, minute, second))
+}
+
+fn parse_month(token: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let prefix = token.get(..3)?.to_ascii_lowercase();
+    MONTHS
+        .iter()
+        .position(|m| *m == prefix)
+        .map(|i| i as u32 + 1)
+}
+
+/// 1970年1月1日からの日数
+/// https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// 日時をUNIX時間の秒数にする。1970年より前の日時は0にする。Cookieの日時の解析方法を
+/// 使うので、HTTP-dateの3つの形式のどれも読める
+/// https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.1.1
+/// https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7
+pub fn parse_date(input: &str) -> Option<u64> {
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    for token in input.split(is_date_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            if let Some(t) = parse_time(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some((d, _)) = leading_digits(token, 1, 2) {
+                day = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() {
+            if let Some(m) = parse_month(token) {
+                month = Some(m);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some((y, _)) = leading_digits(token, 2, 4) {
+                year = Some(y);
+                continue;
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, year) = (day?, month?, year?);
+    let year = match year {
+        70..=99 => year + 1900,
+        0..=69 => year + 2000,
+        _ => year,
+    };
+    if year < 1601
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let seconds = days_from_civil(year as i64, month, day) * 86400
+        + (hour * 3600 + minute * 60 + second) as i64;
+    Some(seconds.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        let cases = [
+            ("Wed, 21 Oct 2015 07:28:00 GMT", Some(1445412480)),
+            ("Sunday, 06-Nov-94 08:49:37 GMT", Some(784111777)),
+            ("Sun Nov  6 08:49:37 1994", Some(784111777)),
+            ("Thu, 01 Jan 1970 00:00:00 GMT", Some(0)),
+            ("Fri, 31 Dec 1965 23:59:59 GMT", Some(0)),
+            ("Thu, 29 Feb 2024 12:00:00 GMT", Some(1709208000)),
+            ("Thu, 30 Feb 2024 12:00:00 GMT", None),
+            ("Thu, 01 Jan 2015 24:00:00 GMT", None),
+            ("Thu, 01 Foo 2015 00:00:00 GMT", None),
+            ("01 Jan 1600 00:00:00", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(expected, parse_date(input), "{}", input);
+        }
+    }
+}
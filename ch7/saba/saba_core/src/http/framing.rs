@@ -0,0 +1,653 @@
+use crate::error::Error;
+use crate::http::content_coding::ContentDecoder;
+use crate::http::isomorphic_decode;
+use crate::http::next_line;
+use crate::http::parse_field_line;
+use crate::http::HttpResponse;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// レスポンスのボディの長さの決め方
+/// https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyLength {
+    /// ボディを持たない
+    Empty,
+    ContentLength(usize),
+    Chunked,
+    /// 接続が閉じられるまでをボディとする
+    UntilClose,
+}
+
+/// https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+pub fn body_length(request_method: &str, response: &HttpResponse) -> Result<BodyLength, Error> {
+    let status_code = response.status_code();
+    if request_method == "HEAD"
+        || (100..200).contains(&status_code)
+        || status_code == 204
+        || status_code == 304
+    {
+        return Ok(BodyLength::Empty);
+    }
+
+    // Transfer-EncodingはContent-Lengthより優先する
+    let codings: Vec<String> = response
+        .header_values("Transfer-Encoding")
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(|coding| coding.trim_matches([' ', '\t']).to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty())
+        .collect();
+    if !codings.is_empty() {
+        // 最後の変換がchunkedでない場合は、接続が閉じられるまでがボディになる
+        return match codings.last().map(|c| c.as_str()) {
+            Some("chunked") => Ok(BodyLength::Chunked),
+            _ => Ok(BodyLength::UntilClose),
+        };
+    }
+
+    match content_length(response)? {
+        Some(length) => Ok(BodyLength::ContentLength(length)),
+        None => Ok(BodyLength::UntilClose),
+    }
+}
+
+/// "42, 42"のように同じ値が繰り返されている場合は受け付けるが、異なる値がある場合は不正とする
+/// https://www.rfc-editor.org/rfc/rfc9110#section-8.6
+fn content_length(response: &HttpResponse) -> Result<Option<usize>, Error> {
+    let mut length = None;
+    for value in response.header_values("Content-Length") {
+        for v in value.split(',') {
+            let v = v.trim_matches([' ', '\t']);
+            let invalid = || Error::Network(format!("invalid Content-Length: {:?}", value));
+            if v.is_empty() || !v.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let n = v.parse::<usize>().map_err(|_| invalid())?;
+            match length {
+                Some(l) if l != n => return Err(invalid()),
+                _ => length = Some(n),
+            }
+        }
+    }
+    Ok(length)
+}
+
+/// ヘッダの終わりの空行の次の位置を返す。まだ揃っていなければNone
+fn header_section_end(buffer: &[u8]) -> Option<usize> {
+    // ステータス行の前にある空行は無視する
+    let mut position = buffer.iter().position(|b| *b != b'\r' && *b != b'\n')?;
+    loop {
+        let (line, rest) = next_line(&buffer[position..])?;
+        position = buffer.len() - rest.len();
+        if line.is_empty() {
+            return Some(position);
+        }
+    }
+}
+
+/// チャンク拡張は無視する
+/// https://www.rfc-editor.org/rfc/rfc9112#section-7.1.1
+fn parse_chunk_size(line: &[u8]) -> Result<usize, Error> {
+    let line = isomorphic_decode(line);
+    let size = match line.split_once(';') {
+        Some((size, _extension)) => size,
+        None => &line,
+    }
+    .trim_end_matches([' ', '\t']);
+
+    let invalid = || Error::Network(format!("invalid chunk size: {:?}", line));
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    usize::from_str_radix(size, 16).map_err(|_| invalid())
+}
+
+/// チャンク形式のボディのどこまで読んだか
+/// https://www.rfc-editor.org/rfc/rfc9112#section-7.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    Size,
+    /// チャンクデータの残りのバイト数
+    Data(usize),
+    /// チャンクデータの後の改行
+    DataEnd,
+    Trailers,
+}
+
+/// ヘッダを読み終えて、ボディを受信しているレスポンス
+#[derive(Debug)]
+struct PartialResponse {
+    response: HttpResponse,
+    length: BodyLength,
+    /// Content-Lengthで指定されたボディの残りのバイト数
+    remaining: usize,
+    chunk: ChunkState,
+    /// Content-Encodingで圧縮されている場合は、受信しながら展開する
+    decoder: Option<ContentDecoder>,
+}
+
+impl PartialResponse {
+    fn append_body(&mut self, data: &[u8]) -> Result<(), Error> {
+        match self.decoder.as_mut() {
+            Some(decoder) => decoder.push(data, &mut self.response.body),
+            None => {
+                self.response.body.extend_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(mut self) -> Result<HttpResponse, Error> {
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.finish(&mut self.response.body)?;
+        }
+        Ok(self.response)
+    }
+
+    /// 受信したバイト列をボディとして読み進め、ボディが終わったかを返す。読んだバイト列は
+    /// バッファから取り除く
+    fn read_body(&mut self, buffer: &mut Vec<u8>, closed: bool) -> Result<bool, Error> {
+        match self.length {
+            BodyLength::Empty => Ok(true),
+            BodyLength::ContentLength(_) => {
+                let n = self.remaining.min(buffer.len());
+                let data: Vec<u8> = buffer.drain(..n).collect();
+                self.append_body(&data)?;
+                self.remaining -= n;
+                Ok(self.remaining == 0)
+            }
+            BodyLength::Chunked => self.read_chunked(buffer),
+            BodyLength::UntilClose => {
+                let data = core::mem::take(buffer);
+                self.append_body(&data)?;
+                Ok(closed)
+            }
+        }
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc9112#section-7.1.3
+    fn read_chunked(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Error> {
+        loop {
+            match self.chunk {
+                ChunkState::Size => {
+                    let (line, rest) = match next_line(buffer) {
+                        Some(line) => line,
+                        None => return Ok(false),
+                    };
+                    let size = parse_chunk_size(line)?;
+                    let consumed = buffer.len() - rest.len();
+                    buffer.drain(..consumed);
+                    self.chunk = if size == 0 {
+                        ChunkState::Trailers
+                    } else {
+                        ChunkState::Data(size)
+                    };
+                }
+                ChunkState::Data(size) => {
+                    let n = size.min(buffer.len());
+                    if n == 0 {
+                        return Ok(false);
+                    }
+                    let data: Vec<u8> = buffer.drain(..n).collect();
+                    self.append_body(&data)?;
+                    self.chunk = if n == size {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(size - n)
+                    };
+                }
+                // チャンクデータの後には改行が必要
+                ChunkState::DataEnd => {
+                    if buffer.starts_with(b"\r\n") {
+                        buffer.drain(..2);
+                    } else if buffer.starts_with(b"\n") {
+                        buffer.drain(..1);
+                    } else if buffer.is_empty() || buffer[..] == b"\r"[..] {
+                        return Ok(false);
+                    } else {
+                        return Err(Error::Network(
+                            "invalid chunked body: missing line break after chunk data".to_string(),
+                        ));
+                    }
+                    self.chunk = ChunkState::Size;
+                }
+                // https://www.rfc-editor.org/rfc/rfc9112#section-7.1.2
+                ChunkState::Trailers => {
+                    let (line, rest) = match next_line(buffer) {
+                        Some(line) => line,
+                        None => return Ok(false),
+                    };
+                    let trailer = if line.is_empty() {
+                        None
+                    } else {
+                        Some(parse_field_line(&isomorphic_decode(line))?)
+                    };
+                    let consumed = buffer.len() - rest.len();
+                    buffer.drain(..consumed);
+                    match trailer {
+                        Some(trailer) => self.response.trailers.push(trailer),
+                        None => return Ok(true),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// ソケットから受信したバイト列を溜めておき、1つのレスポンスが揃ったら取り出す。ボディは
+/// 受信した分から読み進めるので、圧縮されたボディ全体を溜めておくことはない
+/// https://www.rfc-editor.org/rfc/rfc9112#section-6
+#[derive(Debug)]
+pub struct MessageReader {
+    request_method: String,
+    buffer: Vec<u8>,
+    partial: Option<PartialResponse>,
+}
+
+impl MessageReader {
+    /// HEADリクエストのレスポンスはボディを持たないので、リクエストのメソッドが必要になる
+    pub fn new(request_method: &str) -> Self {
+        Self {
+            request_method: request_method.to_string(),
+            buffer: Vec::new(),
+            partial: None,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// 読みかけのレスポンスも、まだ取り出していないバイト列もないか
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty() && self.partial.is_none()
+    }
+
+    /// 1つのレスポンスが揃っていれば、それを取り出す。続くバイト列は次のレスポンスのために残す
+    pub fn take_response(&mut self) -> Result<Option<HttpResponse>, Error> {
+        self.read(/*closed=*/ false)
+    }
+
+    /// 接続が閉じられたときに呼び、残りのバイト列からレスポンスを取り出す
+    pub fn finish(&mut self) -> Result<HttpResponse, Error> {
+        match self.read(/*closed=*/ true)? {
+            Some(response) => Ok(response),
+            None => Err(Error::Network(
+                "connection closed before receiving a response".to_string(),
+            )),
+        }
+    }
+
+    /// ヘッダが揃っていれば、ボディを受信する前のレスポンスを返す。ボディはtake_body()で
+    /// 受信した分ずつ取り出す
+    pub fn take_head(&mut self, closed: bool) -> Result<Option<HttpResponse>, Error> {
+        if self.partial.is_none() {
+            self.partial = self.read_head(closed)?;
+        }
+        Ok(self
+            .partial
+            .as_ref()
+            .map(|partial| partial.response.clone()))
+    }
+
+    /// take_head()の後に呼び、これまでに受信したボディを取り出す。ボディが終わった場合は
+    /// trueを合わせて返す。続くバイト列は次のレスポンスのために残す
+    pub fn take_body(&mut self, closed: bool) -> Result<(Vec<u8>, bool), Error> {
+        let partial = match self.partial.as_mut() {
+            Some(partial) => partial,
+            None => return Err(Error::Network("no response is being received".to_string())),
+        };
+
+        if !partial.read_body(&mut self.buffer, closed)? {
+            if closed {
+                return Err(Error::Network(
+                    "connection closed before the body is complete".to_string(),
+                ));
+            }
+            return Ok((core::mem::take(&mut partial.response.body), false));
+        }
+        match self.partial.take() {
+            Some(partial) => partial.finish().map(|response| (response.body, true)),
+            None => Ok((Vec::new(), true)),
+        }
+    }
+
+    fn read(&mut self, closed: bool) -> Result<Option<HttpResponse>, Error> {
+        let mut partial = match self.partial.take() {
+            Some(partial) => partial,
+            None => match self.read_head(closed)? {
+                Some(partial) => partial,
+                None => return Ok(None),
+            },
+        };
+
+        if !partial.read_body(&mut self.buffer, closed)? {
+            if closed {
+                return Err(Error::Network(
+                    "connection closed before the body is complete".to_string(),
+                ));
+            }
+            self.partial = Some(partial);
+            return Ok(None);
+        }
+        partial.finish().map(Some)
+    }
+
+    /// ステータス行とヘッダを読む
+    fn read_head(&mut self, closed: bool) -> Result<Option<PartialResponse>, Error> {
+        loop {
+            let header_end = match header_section_end(&self.buffer) {
+                Some(end) => end,
+                None if closed && self.buffer.iter().all(|b| *b == b'\r' || *b == b'\n') => {
+                    return Ok(None)
+                }
+                None if closed => {
+                    return Err(Error::Network(
+                        "connection closed before the header section is complete".to_string(),
+                    ))
+                }
+                None => return Ok(None),
+            };
+
+            let response = HttpResponse::new(self.buffer[..header_end].to_vec())?;
+            self.buffer.drain(..header_end);
+
+            // 1xxの中間レスポンスは読み捨てて、続く最終レスポンスを待つ
+            // https://www.rfc-editor.org/rfc/rfc9110#section-15.2
+            let status_code = response.status_code();
+            if (100..200).contains(&status_code) && status_code != 101 {
+                continue;
+            }
+
+            let length = body_length(&self.request_method, &response)?;
+            let decoder = match length {
+                BodyLength::Empty => None,
+                _ => ContentDecoder::for_response(&response),
+            };
+            let remaining = match length {
+                BodyLength::ContentLength(length) => length,
+                _ => 0,
+            };
+            return Ok(Some(PartialResponse {
+                response,
+                length,
+                remaining,
+                chunk: ChunkState::Size,
+                decoder,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1バイトずつ受信した場合も、まとめて受信した場合も同じ結果になることを確かめる
+    fn read(method: &str, raw: &[u8], closed: bool) -> Result<Option<HttpResponse>, Error> {
+        let mut whole = MessageReader::new(method);
+        whole.push(raw);
+        let expected = if closed {
+            whole.finish().map(Some)
+        } else {
+            whole.take_response()
+        };
+
+        let mut reader = MessageReader::new(method);
+        let mut actual = Ok(None);
+        for byte in raw {
+            reader.push(&[*byte]);
+            actual = reader.take_response();
+            if !matches!(actual, Ok(None)) {
+                break;
+            }
+        }
+        if closed && matches!(actual, Ok(None)) {
+            actual = reader.finish().map(Some);
+        }
+
+        assert_eq!(
+            format!("{:?}", expected),
+            format!("{:?}", actual),
+            "reading {:?}",
+            String::from_utf8_lossy(raw)
+        );
+        expected
+    }
+
+    fn body(method: &str, raw: &[u8], closed: bool) -> Option<Vec<u8>> {
+        read(method, raw, closed)
+            .expect("failed to read a response")
+            .map(|response| response.body())
+    }
+
+    #[test]
+    fn test_content_length() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello, extra";
+        assert_eq!(Some(b"hello".to_vec()), body("GET", raw, false));
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 5, 5\r\n\r\nhello";
+        assert_eq!(Some(b"hello".to_vec()), body("GET", raw, false));
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(Some(Vec::new()), body("GET", raw, false));
+    }
+
+    #[test]
+    fn test_content_length_incomplete() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        assert_eq!(None, body("GET", raw, false));
+        assert!(read("GET", raw, true).is_err());
+    }
+
+    #[test]
+    fn test_chunked() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n\
+            7;name=value\r\n, world\r\n\
+            A \r\n<p>saba</p\r\n\
+            0\r\n\r\n";
+        assert_eq!(
+            Some(b"hello, world<p>saba</p".to_vec()),
+            body("GET", raw, false)
+        );
+    }
+
+    #[test]
+    fn test_chunked_with_trailers() {
+        let raw =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, Chunked\r\nContent-Length: 100\r\n\r\n\
+            3\r\nabc\r\n0\r\nExpires: never\r\nX-Checksum: 42\r\n\r\n";
+        let response = read("GET", raw, false)
+            .expect("failed to read a response")
+            .expect("response is not complete");
+        assert_eq!(b"abc".to_vec(), response.body());
+        let trailers = response.trailers();
+        assert_eq!(2, trailers.len());
+        assert_eq!("Expires".to_string(), trailers[0].name());
+        assert_eq!("42".to_string(), trailers[1].value());
+    }
+
+    #[test]
+    fn test_chunked_incomplete() {
+        let cases: [&[u8]; 4] = [
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nExpires: never\r\n",
+        ];
+        for raw in cases {
+            assert_eq!(None, body("GET", raw, false));
+            assert!(read("GET", raw, true).is_err());
+        }
+    }
+
+    #[test]
+    fn test_chunked_malformed() {
+        let cases: [&[u8]; 5] = [
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nxyz\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n-1\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nFFFFFFFFFFFFFFFFFFFF\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabcdef\r\n",
+        ];
+        for raw in cases {
+            assert!(read("GET", raw, false).is_err());
+        }
+    }
+
+    #[test]
+    fn test_content_encoding() {
+        let gzip =
+            crate::compression::from_hex("1f8b08000000000002032bcf2fca4901004311773a05000000");
+
+        let mut raw = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            gzip.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(&gzip);
+        assert_eq!(Some(b"world".to_vec()), body("GET", &raw, false));
+
+        let mut raw =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Encoding: gzip\r\n\r\n"
+                .to_vec();
+        for chunk in gzip.chunks(7) {
+            raw.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            raw.extend_from_slice(chunk);
+            raw.extend_from_slice(b"\r\n");
+        }
+        raw.extend_from_slice(b"0\r\n\r\n");
+        assert_eq!(Some(b"world".to_vec()), body("GET", &raw, false));
+
+        // 知らない変換の場合は、ボディをそのまま返す
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Encoding: br\r\nContent-Length: 3\r\n\r\nabc";
+        assert_eq!(Some(b"abc".to_vec()), body("GET", raw, false));
+
+        // HEADのレスポンスは展開しない
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 25\r\n\r\n";
+        assert_eq!(Some(Vec::new()), body("HEAD", raw, false));
+    }
+
+    #[test]
+    fn test_content_encoding_corrupted() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 5\r\n\r\nhello";
+        assert!(read("GET", raw, false).is_err());
+    }
+
+    #[test]
+    fn test_until_close() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<p>saba</p>";
+        assert_eq!(None, body("GET", raw, false));
+        assert_eq!(Some(b"<p>saba</p>".to_vec()), body("GET", raw, true));
+
+        // 最後の変換がchunkedでない場合
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip\r\n\r\n5\r\nhello";
+        assert_eq!(Some(b"5\r\nhello".to_vec()), body("GET", raw, true));
+    }
+
+    #[test]
+    fn test_no_body() {
+        let raw = b"HTTP/1.1 204 No Content\r\nContent-Length: 5\r\n\r\n";
+        assert_eq!(Some(Vec::new()), body("GET", raw, false));
+
+        let raw = b"HTTP/1.1 304 Not Modified\r\n\r\n";
+        assert_eq!(Some(Vec::new()), body("GET", raw, false));
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n";
+        assert_eq!(Some(Vec::new()), body("HEAD", raw, false));
+    }
+
+    #[test]
+    fn test_skip_interim_response() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let response = read("GET", raw, false)
+            .expect("failed to read a response")
+            .expect("response is not complete");
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"ok".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_pipelined_responses() {
+        let mut reader = MessageReader::new("GET");
+        reader.push(b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na");
+        reader.push(b"HTTP/1.1 404 Not Found\r\nContent-Length: 1\r\n\r\nb");
+
+        let first = reader
+            .take_response()
+            .expect("failed to read a response")
+            .expect("response is not complete");
+        assert_eq!(b"a".to_vec(), first.body());
+
+        let second = reader
+            .take_response()
+            .expect("failed to read a response")
+            .expect("response is not complete");
+        assert_eq!(404, second.status_code());
+        assert_eq!(b"b".to_vec(), second.body());
+
+        assert!(matches!(reader.take_response(), Ok(None)));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let cases: [&[u8]; 4] = [
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1, 2\r\n\r\nab",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\nhello!",
+            b"HTTP/1.1 200 OK\r\nContent-Length: -1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 99999999999999999999999\r\n\r\n",
+        ];
+        for raw in cases {
+            assert!(read("GET", raw, false).is_err());
+        }
+    }
+
+    #[test]
+    fn test_closed_without_response() {
+        let mut reader = MessageReader::new("GET");
+        assert!(reader.finish().is_err());
+
+        let mut reader = MessageReader::new("GET");
+        reader.push(b"HTTP/1.1 200 OK\r\nContent-");
+        assert!(reader.finish().is_err());
+    }
+
+    #[test]
+    fn test_take_body_in_pieces() {
+        let mut reader = MessageReader::new("GET");
+        reader.push(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel");
+        let head = reader
+            .take_head(/*closed=*/ false)
+            .expect("failed to read the head")
+            .expect("head should be complete");
+        assert_eq!(200, head.status_code());
+
+        assert_eq!(
+            (b"hel".to_vec(), false),
+            reader.take_body(false).expect("failed to read the body")
+        );
+        reader.push(b"lo\r\n0\r\n\r\nHTTP/1.1");
+        assert_eq!(
+            (b"lo".to_vec(), true),
+            reader.take_body(false).expect("failed to read the body")
+        );
+        // 次のレスポンスのバイト列は残す
+        assert!(!reader.is_empty());
+    }
+
+    #[test]
+    fn test_take_body_closed_early() {
+        let mut reader = MessageReader::new("GET");
+        reader.push(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello");
+        assert!(matches!(reader.take_head(false), Ok(Some(_))));
+        assert_eq!(
+            (b"hello".to_vec(), false),
+            reader.take_body(false).expect("failed to read the body")
+        );
+        assert!(reader.take_body(/*closed=*/ true).is_err());
+    }
+}
@@ -0,0 +1,1423 @@
+use crate::error::Error;
+use crate::http::content_coding::ContentDecoder;
+use crate::http::h2::frame::ConnectionError;
+use crate::http::h2::frame::ErrorCode;
+use crate::http::h2::frame::Frame;
+use crate::http::h2::frame::DEFAULT_MAX_FRAME_SIZE;
+use crate::http::h2::frame::DEFAULT_WINDOW_SIZE;
+use crate::http::h2::frame::MAX_MAX_FRAME_SIZE;
+use crate::http::h2::frame::MAX_WINDOW_SIZE;
+use crate::http::h2::frame::SETTINGS_ENABLE_PUSH;
+use crate::http::h2::frame::SETTINGS_HEADER_TABLE_SIZE;
+use crate::http::h2::frame::SETTINGS_INITIAL_WINDOW_SIZE;
+use crate::http::h2::frame::SETTINGS_MAX_CONCURRENT_STREAMS;
+use crate::http::h2::frame::SETTINGS_MAX_FRAME_SIZE;
+use crate::http::h2::frame::SETTINGS_MAX_HEADER_LIST_SIZE;
+use crate::http::h2::h2_error;
+use crate::http::h2::hpack::Decoder;
+use crate::http::h2::hpack::Encoder;
+use crate::http::h2::hpack::HeaderField;
+use crate::http::h2::hpack::MAX_HEADER_LIST_SIZE;
+use crate::http::is_tchar;
+use crate::http::isomorphic_decode;
+use crate::http::pool::Connection;
+use crate::http::request::HttpRequest;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// クライアントが接続の最初に送るバイト列
+/// https://www.rfc-editor.org/rfc/rfc9113#section-3.4
+pub const CONNECTION_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// ストリームと接続の受信のウィンドウ。初期値の64KiBのままだと、大きなレスポンスを受信する
+/// ときにWINDOW_UPDATEの往復を何度も待つことになる
+const RECEIVE_WINDOW: u32 = 1 << 20;
+const READ_BUFFER_LEN: usize = 16 * 1024;
+
+/// HTTP/2では使えない、接続ごとのヘッダ
+/// https://www.rfc-editor.org/rfc/rfc9113#section-8.2.2
+const CONNECTION_SPECIFIC_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// ストリームの失敗。同じリクエストを送り直してよいかどうかで分ける
+/// https://www.rfc-editor.org/rfc/rfc9113#section-8.7
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamError {
+    /// GOAWAYやREFUSED_STREAMで、サーバーが処理していないとわかっている。どのメソッドでも
+    /// 新しい接続で送り直せる
+    Refused(Error),
+    /// レスポンスを何も受け取らないうちに接続が閉じられた。冪等なリクエストだけを送り直せる
+    Unanswered(Error),
+    Failed(Error),
+}
+
+impl From<StreamError> for Error {
+    fn from(e: StreamError) -> Self {
+        match e {
+            StreamError::Refused(e) | StreamError::Unanswered(e) | StreamError::Failed(e) => e,
+        }
+    }
+}
+
+fn error_message(e: Error) -> String {
+    e.message().to_string()
+}
+
+/// リクエストを、疑似ヘッダから始まるHTTP/2のヘッダの一覧にする
+/// https://www.rfc-editor.org/rfc/rfc9113#section-8.3.1
+fn request_headers(request: &HttpRequest) -> Result<Vec<HeaderField>, Error> {
+    let method = request.method();
+    if method.is_empty() || !method.bytes().all(is_tchar) {
+        return Err(Error::UnexpectedInput(format!(
+            "invalid http method: {:?}",
+            method
+        )));
+    }
+    // Hostヘッダがある場合は:authorityに移す
+    let authority = request
+        .header_value("Host")
+        .unwrap_or_else(|| request.host());
+    if authority.is_empty() {
+        return Err(Error::UnexpectedInput(format!(
+            "url has no host: {}",
+            request.url().href()
+        )));
+    }
+
+    let mut headers = vec![
+        (b":method".to_vec(), method.into_bytes()),
+        (b":scheme".to_vec(), request.url().scheme().into_bytes()),
+        (b":authority".to_vec(), authority.into_bytes()),
+        (b":path".to_vec(), request.target().into_bytes()),
+    ];
+    for header in request.headers() {
+        let name = header.name.to_ascii_lowercase();
+        if name.is_empty() || !name.bytes().all(is_tchar) {
+            return Err(Error::UnexpectedInput(format!(
+                "invalid http header name: {:?}",
+                header.name
+            )));
+        }
+        if header
+            .value
+            .bytes()
+            .any(|b| b == b'\r' || b == b'\n' || b == 0)
+        {
+            return Err(Error::UnexpectedInput(format!(
+                "invalid http header value: {:?}",
+                header.value
+            )));
+        }
+        // TEはtrailersだけを送れる
+        if name == "host"
+            || CONNECTION_SPECIFIC_HEADERS.contains(&name.as_str())
+            || (name == "te" && !header.value.trim().eq_ignore_ascii_case("trailers"))
+        {
+            continue;
+        }
+        headers.push((name.into_bytes(), header.value.into_bytes()));
+    }
+    if request.needs_content_length() && request.header_value("Content-Length").is_none() {
+        headers.push((
+            b"content-length".to_vec(),
+            request.body().len().to_string().into_bytes(),
+        ));
+    }
+    Ok(headers)
+}
+
+/// 受け取ったヘッダを、疑似ヘッダと通常のヘッダに分ける。正しくない場合はストリームのエラーに
+/// する
+/// https://www.rfc-editor.org/rfc/rfc9113#section-8.2
+fn split_headers(fields: Vec<HeaderField>) -> Result<(Vec<Header>, Vec<Header>), String> {
+    let mut pseudo_headers = Vec::new();
+    let mut headers = Vec::new();
+    for (name, value) in fields {
+        let name = isomorphic_decode(&name);
+        let value = isomorphic_decode(&value);
+        if value.contains(['\r', '\n', '\0']) {
+            return Err(format!("invalid value of the header {:?}", name));
+        }
+        if let Some(pseudo) = name.strip_prefix(':') {
+            // 疑似ヘッダは通常のヘッダより前に置く
+            if !headers.is_empty() {
+                return Err(format!("pseudo-header {:?} after regular headers", name));
+            }
+            pseudo_headers.push(Header::new(pseudo.to_string(), value));
+            continue;
+        }
+        if name.is_empty()
+            || !name.bytes().all(is_tchar)
+            || name.bytes().any(|b| b.is_ascii_uppercase())
+        {
+            return Err(format!("invalid header name {:?}", name));
+        }
+        if CONNECTION_SPECIFIC_HEADERS.contains(&name.as_str()) {
+            return Err(format!("connection-specific header {:?}", name));
+        }
+        headers.push(Header::new(name, value));
+    }
+    Ok((pseudo_headers, headers))
+}
+
+#[derive(Debug)]
+struct Stream {
+    method: String,
+    /// まだ送っていないリクエストのボディ
+    body: Vec<u8>,
+    /// END_STREAMを送ったか
+    request_sent: bool,
+    send_window: i64,
+    recv_window: i64,
+    response: Option<HttpResponse>,
+    /// Content-Encodingで圧縮されている場合は、受信しながら展開する
+    decoder: Option<ContentDecoder>,
+    content_length: Option<usize>,
+    /// パディングを除いたDATAの長さの合計
+    received: usize,
+    /// サーバーから何かを受け取ったか
+    responded: bool,
+    complete: bool,
+    error: Option<StreamError>,
+}
+
+impl Stream {
+    fn new(method: String, body: Vec<u8>, send_window: i64) -> Self {
+        Self {
+            method,
+            request_sent: body.is_empty(),
+            body,
+            send_window,
+            recv_window: RECEIVE_WINDOW as i64,
+            response: None,
+            decoder: None,
+            content_length: None,
+            received: 0,
+            responded: false,
+            complete: false,
+            error: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.complete || self.error.is_some()
+    }
+
+    /// 1xxのレスポンスは読み飛ばし、最終的なレスポンスを待つ
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-8.1
+    fn receive_response_headers(
+        &mut self,
+        fields: Vec<HeaderField>,
+        end_stream: bool,
+    ) -> Result<(), String> {
+        let (pseudo_headers, headers) = split_headers(fields)?;
+        let status = match &pseudo_headers[..] {
+            [header] if header.name == "status" => &header.value,
+            _ => {
+                return Err(format!(
+                    "invalid pseudo-headers in a response: {:?}",
+                    pseudo_headers
+                ))
+            }
+        };
+        let status_code = match status.parse::<u16>() {
+            Ok(code) if status.len() == 3 && (100..1000).contains(&code) => code,
+            _ => return Err(format!("invalid status code: {:?}", status)),
+        };
+        if (100..200).contains(&status_code) {
+            if status_code == 101 || end_stream {
+                return Err(format!("invalid informational response: {}", status_code));
+            }
+            return Ok(());
+        }
+
+        let mut content_length = None;
+        for header in headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case("Content-Length"))
+        {
+            let length = match header.value.trim().parse::<usize>() {
+                Ok(length) if content_length.unwrap_or(length) == length => length,
+                _ => return Err(format!("invalid Content-Length: {:?}", header.value)),
+            };
+            content_length = Some(length);
+        }
+
+        let response = HttpResponse {
+            version: "HTTP/2".to_string(),
+            status_code,
+            reason: String::new(),
+            headers,
+            body: Vec::new(),
+            trailers: Vec::new(),
+            url: None,
+        };
+        self.decoder = ContentDecoder::for_response(&response);
+        self.content_length = content_length;
+        self.response = Some(response);
+        Ok(())
+    }
+
+    /// ボディの後のヘッダブロックはトレーラーで、ストリームの終わりでなければならない
+    fn receive_trailers(
+        &mut self,
+        fields: Vec<HeaderField>,
+        end_stream: bool,
+    ) -> Result<(), String> {
+        let (pseudo_headers, trailers) = split_headers(fields)?;
+        if !end_stream || !pseudo_headers.is_empty() {
+            return Err("invalid trailers".to_string());
+        }
+        if let Some(response) = self.response.as_mut() {
+            response.trailers = trailers;
+        }
+        Ok(())
+    }
+
+    fn append_body(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.received += data.len();
+        let response = match self.response.as_mut() {
+            Some(response) => response,
+            None => return Err(h2_error("DATA frame before the response headers")),
+        };
+        match self.decoder.as_mut() {
+            Some(decoder) => decoder.push(data, &mut response.body),
+            None => {
+                response.body.extend_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+
+    /// END_STREAMを受け取ったときに呼ぶ
+    fn finish(&mut self) -> Result<(), Error> {
+        let response = match self.response.as_mut() {
+            Some(response) => response,
+            None => return Err(h2_error("stream ended without a response")),
+        };
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.finish(&mut response.body)?;
+        }
+        // HEADや204、304のレスポンスのContent-Lengthは、ボディの長さを表さない
+        // https://www.rfc-editor.org/rfc/rfc9113#section-8.1.1
+        if let Some(length) = self.content_length {
+            if self.method != "HEAD"
+                && !matches!(response.status_code, 204 | 304)
+                && length != self.received
+            {
+                return Err(h2_error(&format!(
+                    "received {} bytes although Content-Length is {}",
+                    self.received, length
+                )));
+            }
+        }
+        self.complete = true;
+        Ok(())
+    }
+}
+
+/// HTTP/2の接続。1つの接続で複数のリクエストを同時に送り、レスポンスはストリームごとに
+/// 受け取る。サーバープッシュは使わない
+/// https://www.rfc-editor.org/rfc/rfc9113
+#[derive(Debug)]
+pub struct H2Connection<C: Connection> {
+    connection: C,
+    encoder: Encoder,
+    decoder: Decoder,
+    streams: BTreeMap<u32, Stream>,
+    /// クライアントが始めるストリームのIDは奇数
+    next_stream_id: u32,
+    peer_max_frame_size: u32,
+    peer_initial_window: i64,
+    peer_max_concurrent_streams: u32,
+    /// 接続全体の送信と受信のウィンドウ
+    send_window: i64,
+    recv_window: i64,
+    settings_received: bool,
+    /// CONTINUATIONで続きを受け取っているヘッダブロックの、ストリームID、ブロック、END_STREAM
+    continuation: Option<(u32, Vec<u8>, bool)>,
+    /// GOAWAYで知らされた、サーバーが処理する最後のストリームID
+    goaway: Option<u32>,
+    closed: bool,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl<C: Connection> H2Connection<C> {
+    /// プリフェイスとSETTINGSを送る。サーバーのSETTINGSを待たずにリクエストを送れる
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-3.4
+    pub fn connect(connection: C) -> Result<Self, Error> {
+        let mut h2 = Self {
+            connection,
+            encoder: Encoder::new(),
+            decoder: Decoder::new(),
+            streams: BTreeMap::new(),
+            next_stream_id: 1,
+            peer_max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            peer_initial_window: DEFAULT_WINDOW_SIZE as i64,
+            peer_max_concurrent_streams: u32::MAX,
+            send_window: DEFAULT_WINDOW_SIZE as i64,
+            recv_window: RECEIVE_WINDOW as i64,
+            settings_received: false,
+            continuation: None,
+            goaway: None,
+            closed: false,
+            input: Vec::new(),
+            output: CONNECTION_PREFACE.to_vec(),
+        };
+        Frame::Settings {
+            ack: false,
+            settings: vec![
+                (SETTINGS_ENABLE_PUSH, 0),
+                (SETTINGS_INITIAL_WINDOW_SIZE, RECEIVE_WINDOW),
+                (SETTINGS_MAX_HEADER_LIST_SIZE, MAX_HEADER_LIST_SIZE as u32),
+            ],
+        }
+        .encode(&mut h2.output);
+        Frame::WindowUpdate {
+            stream_id: 0,
+            increment: RECEIVE_WINDOW - DEFAULT_WINDOW_SIZE,
+        }
+        .encode(&mut h2.output);
+        h2.flush()?;
+        Ok(h2)
+    }
+
+    /// 新しいリクエストを送れるか。GOAWAYを受け取った後や、閉じられた後は送れない
+    pub fn is_usable(&self) -> bool {
+        !self.closed && self.goaway.is_none() && self.next_stream_id <= MAX_WINDOW_SIZE
+    }
+
+    /// レスポンスを待っているストリームの数
+    pub fn active_streams(&self) -> usize {
+        self.streams.values().filter(|s| !s.is_done()).count()
+    }
+
+    pub fn send(&mut self, request: &HttpRequest) -> Result<HttpResponse, StreamError> {
+        let stream_id = self.send_request(request)?;
+        self.response(stream_id)
+    }
+
+    /// リクエストを送り、ストリームIDを返す。複数のリクエストを送ってから、それぞれの
+    /// レスポンスをresponse()で受け取れる
+    pub fn send_request(&mut self, request: &HttpRequest) -> Result<u32, StreamError> {
+        let headers = request_headers(request).map_err(StreamError::Failed)?;
+        // サーバーが同時に処理できるストリームの数を超えないように、空くまで待つ。最初の
+        // リクエストの他は、その数を知らせるサーバーのSETTINGSを受け取ってから送る
+        while self.is_usable()
+            && (self.active_streams() >= self.peer_max_concurrent_streams as usize
+                || (!self.settings_received && self.active_streams() > 0))
+        {
+            self.receive().map_err(StreamError::Refused)?;
+        }
+        if !self.is_usable() {
+            return Err(StreamError::Refused(h2_error(
+                "connection can no longer be used for new requests",
+            )));
+        }
+
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 2;
+        let body = request.body();
+        let block = self.encoder.encode(&headers);
+        self.write_header_block(stream_id, &block, body.is_empty());
+        self.streams.insert(
+            stream_id,
+            Stream::new(request.method(), body, self.peer_initial_window),
+        );
+        self.send_data();
+        self.flush().map_err(StreamError::Unanswered)?;
+        Ok(stream_id)
+    }
+
+    /// ストリームのレスポンスを、ボディの終わりまで受信する
+    pub fn response(&mut self, stream_id: u32) -> Result<HttpResponse, StreamError> {
+        loop {
+            match self.streams.get(&stream_id) {
+                Some(stream) if stream.is_done() => break,
+                Some(_) => {}
+                None => {
+                    return Err(StreamError::Failed(h2_error(&format!(
+                        "unknown stream {}",
+                        stream_id
+                    ))))
+                }
+            }
+            // 接続が閉じられた場合は、close()ですべてのストリームが失敗になる
+            let _ = self.receive();
+        }
+
+        let stream = match self.streams.remove(&stream_id) {
+            Some(stream) => stream,
+            None => return Err(StreamError::Failed(h2_error("stream is lost"))),
+        };
+        // レスポンスが先に終わった場合は、残りのボディを送らない
+        if !stream.request_sent && !self.closed {
+            Frame::RstStream {
+                stream_id,
+                error_code: ErrorCode::CANCEL,
+            }
+            .encode(&mut self.output);
+            let _ = self.flush();
+        }
+        match (stream.error, stream.response) {
+            (Some(e), _) => Err(e),
+            (None, Some(response)) => Ok(response),
+            (None, None) => Err(StreamError::Failed(h2_error(
+                "stream ended without a response",
+            ))),
+        }
+    }
+
+    /// ヘッダブロックがフレームに収まらない場合は、CONTINUATIONに分ける
+    fn write_header_block(&mut self, stream_id: u32, block: &[u8], end_stream: bool) {
+        let mut chunks: Vec<&[u8]> = block.chunks(self.peer_max_frame_size as usize).collect();
+        if chunks.is_empty() {
+            chunks.push(&[]);
+        }
+        let last = chunks.len() - 1;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let frame = if i == 0 {
+                Frame::Headers {
+                    stream_id,
+                    header_block: chunk.to_vec(),
+                    end_stream,
+                    end_headers: i == last,
+                    padding: 0,
+                }
+            } else {
+                Frame::Continuation {
+                    stream_id,
+                    header_block: chunk.to_vec(),
+                    end_headers: i == last,
+                }
+            };
+            frame.encode(&mut self.output);
+        }
+    }
+
+    /// フロー制御のウィンドウが許す分だけ、リクエストのボディをDATAで送る
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-5.2
+    fn send_data(&mut self) {
+        for (stream_id, stream) in self.streams.iter_mut() {
+            while !stream.request_sent && !stream.is_done() {
+                let length = stream
+                    .body
+                    .len()
+                    .min(self.peer_max_frame_size as usize)
+                    .min(stream.send_window.max(0) as usize)
+                    .min(self.send_window.max(0) as usize);
+                if length == 0 {
+                    break;
+                }
+                let data: Vec<u8> = stream.body.drain(..length).collect();
+                stream.send_window -= length as i64;
+                self.send_window -= length as i64;
+                stream.request_sent = stream.body.is_empty();
+                Frame::Data {
+                    stream_id: *stream_id,
+                    data,
+                    end_stream: stream.request_sent,
+                    padding: 0,
+                }
+                .encode(&mut self.output);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let output = core::mem::take(&mut self.output);
+        let mut written = 0;
+        while written < output.len() {
+            let result = match self.connection.write(&output[written..]) {
+                Ok(0) => Err(h2_error("connection closed while sending")),
+                result => result,
+            };
+            match result {
+                Ok(bytes) => written += bytes,
+                Err(e) => {
+                    self.close(&e);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 接続を使えなくし、終わっていないストリームを失敗にする
+    fn close(&mut self, error: &Error) {
+        self.closed = true;
+        for stream in self.streams.values_mut() {
+            if stream.is_done() {
+                continue;
+            }
+            stream.error = Some(if stream.responded {
+                StreamError::Failed(error.clone())
+            } else {
+                StreamError::Unanswered(error.clone())
+            });
+        }
+    }
+
+    /// 接続のエラー。GOAWAYを送って接続を閉じる
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-5.4.1
+    fn fail(&mut self, e: ConnectionError) -> Error {
+        Frame::GoAway {
+            last_stream_id: 0,
+            error_code: e.code,
+            debug_data: e.message.as_bytes().to_vec(),
+        }
+        .encode(&mut self.output);
+        let _ = self.flush();
+        let error = Error::from(e);
+        self.close(&error);
+        error
+    }
+
+    /// ストリームのエラー。RST_STREAMを送り、そのストリームだけを失敗にする
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-5.4.2
+    fn reset_stream(&mut self, stream_id: u32, error_code: ErrorCode, message: &str) {
+        Frame::RstStream {
+            stream_id,
+            error_code,
+        }
+        .encode(&mut self.output);
+        if let Some(stream) = self.streams.get_mut(&stream_id) {
+            stream.request_sent = true;
+            stream.body.clear();
+            if !stream.is_done() {
+                stream.error = Some(StreamError::Failed(h2_error(&format!(
+                    "{} ({})",
+                    message,
+                    error_code.name()
+                ))));
+            }
+        }
+    }
+
+    /// フレームを1つ受信して処理する
+    fn receive(&mut self) -> Result<(), Error> {
+        if self.closed {
+            return Err(h2_error("connection is closed"));
+        }
+        loop {
+            match Frame::decode(&self.input, DEFAULT_MAX_FRAME_SIZE) {
+                Ok(Some((frame, length))) => {
+                    self.input.drain(..length);
+                    if let Err(e) = self.process_frame(frame) {
+                        return Err(self.fail(e));
+                    }
+                    self.send_data();
+                    return self.flush();
+                }
+                Ok(None) => {}
+                Err(e) => return Err(self.fail(e)),
+            }
+
+            let mut buf = [0u8; READ_BUFFER_LEN];
+            let result = match self.connection.read(&mut buf) {
+                Ok(0) => Err(h2_error("connection closed by the server")),
+                result => result,
+            };
+            match result {
+                Ok(bytes) => self.input.extend_from_slice(&buf[..bytes]),
+                Err(e) => {
+                    self.close(&e);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// まだ使っていないストリームIDか。プッシュを無効にしているので、偶数のIDは使われない
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-5.1
+    fn is_idle(&self, stream_id: u32) -> bool {
+        stream_id % 2 == 0 || stream_id >= self.next_stream_id
+    }
+
+    fn check_not_idle(&self, stream_id: u32, name: &str) -> Result<(), ConnectionError> {
+        if self.is_idle(stream_id) {
+            return Err(ConnectionError::new(
+                ErrorCode::PROTOCOL_ERROR,
+                &format!("{} frame on idle stream {}", name, stream_id),
+            ));
+        }
+        Ok(())
+    }
+
+    fn process_frame(&mut self, frame: Frame) -> Result<(), ConnectionError> {
+        // ヘッダブロックの途中には、同じストリームのCONTINUATIONしか来ない
+        // https://www.rfc-editor.org/rfc/rfc9113#section-6.10
+        if let Some((stream_id, _, _)) = &self.continuation {
+            if !matches!(&frame, Frame::Continuation { stream_id: id, .. } if id == stream_id) {
+                return Err(ConnectionError::new(
+                    ErrorCode::PROTOCOL_ERROR,
+                    "expected a CONTINUATION frame",
+                ));
+            }
+        }
+        // サーバーのプリフェイスはSETTINGSで始まる
+        if !self.settings_received && !matches!(frame, Frame::Settings { ack: false, .. }) {
+            return Err(ConnectionError::new(
+                ErrorCode::PROTOCOL_ERROR,
+                "server preface does not start with SETTINGS",
+            ));
+        }
+
+        match frame {
+            Frame::Data {
+                stream_id,
+                data,
+                end_stream,
+                padding,
+            } => self.receive_data(stream_id, &data, end_stream, padding),
+            Frame::Headers {
+                stream_id,
+                header_block,
+                end_stream,
+                end_headers,
+                ..
+            } => {
+                self.check_not_idle(stream_id, "HEADERS")?;
+                if end_headers {
+                    self.receive_headers(stream_id, &header_block, end_stream)
+                } else {
+                    self.continuation = Some((stream_id, header_block, end_stream));
+                    Ok(())
+                }
+            }
+            Frame::Continuation {
+                header_block,
+                end_headers,
+                ..
+            } => {
+                let (stream_id, mut block, end_stream) = match self.continuation.take() {
+                    Some(continuation) => continuation,
+                    None => {
+                        return Err(ConnectionError::new(
+                            ErrorCode::PROTOCOL_ERROR,
+                            "unexpected CONTINUATION frame",
+                        ))
+                    }
+                };
+                block.extend_from_slice(&header_block);
+                if block.len() > MAX_HEADER_LIST_SIZE {
+                    return Err(ConnectionError::new(
+                        ErrorCode::ENHANCE_YOUR_CALM,
+                        "header block is too large",
+                    ));
+                }
+                if end_headers {
+                    self.receive_headers(stream_id, &block, end_stream)
+                } else {
+                    self.continuation = Some((stream_id, block, end_stream));
+                    Ok(())
+                }
+            }
+            Frame::Priority { .. } | Frame::Unknown { .. } => Ok(()),
+            Frame::RstStream {
+                stream_id,
+                error_code,
+            } => {
+                self.check_not_idle(stream_id, "RST_STREAM")?;
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.request_sent = true;
+                    stream.body.clear();
+                    // レスポンスを送り終えた後のNO_ERRORは、残りのリクエストが不要という意味
+                    if !stream.is_done() {
+                        let error = h2_error(&format!(
+                            "stream reset by the server ({})",
+                            error_code.name()
+                        ));
+                        stream.error = Some(if error_code == ErrorCode::REFUSED_STREAM {
+                            StreamError::Refused(error)
+                        } else {
+                            StreamError::Failed(error)
+                        });
+                    }
+                }
+                Ok(())
+            }
+            Frame::Settings { ack: true, .. } => Ok(()),
+            Frame::Settings {
+                ack: false,
+                settings,
+            } => self.apply_settings(&settings),
+            Frame::PushPromise { .. } => Err(ConnectionError::new(
+                ErrorCode::PROTOCOL_ERROR,
+                "PUSH_PROMISE although server push is disabled",
+            )),
+            Frame::Ping { ack: false, data } => {
+                Frame::Ping { ack: true, data }.encode(&mut self.output);
+                Ok(())
+            }
+            Frame::Ping { ack: true, .. } => Ok(()),
+            Frame::GoAway {
+                last_stream_id,
+                error_code,
+                debug_data,
+            } => {
+                // 2回目のGOAWAYで、最後のストリームIDが大きくなることはない
+                let last_stream_id = last_stream_id.min(self.goaway.unwrap_or(u32::MAX));
+                self.goaway = Some(last_stream_id);
+                let mut message = format!("server sent GOAWAY ({})", error_code.name());
+                if !debug_data.is_empty() {
+                    message.push_str(&format!(": {}", isomorphic_decode(&debug_data)));
+                }
+                for (stream_id, stream) in self.streams.iter_mut() {
+                    if *stream_id > last_stream_id && !stream.is_done() {
+                        stream.error = Some(StreamError::Refused(h2_error(&message)));
+                    }
+                }
+                Ok(())
+            }
+            Frame::WindowUpdate {
+                stream_id,
+                increment,
+            } => self.receive_window_update(stream_id, increment),
+        }
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-6.5.2
+    fn apply_settings(&mut self, settings: &[(u16, u32)]) -> Result<(), ConnectionError> {
+        for (id, value) in settings {
+            match *id {
+                SETTINGS_HEADER_TABLE_SIZE => self.encoder.set_max_table_size(*value as usize),
+                SETTINGS_ENABLE_PUSH if *value != 0 => {
+                    return Err(ConnectionError::new(
+                        ErrorCode::PROTOCOL_ERROR,
+                        "server enabled SETTINGS_ENABLE_PUSH",
+                    ))
+                }
+                SETTINGS_MAX_CONCURRENT_STREAMS => self.peer_max_concurrent_streams = *value,
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    if *value > MAX_WINDOW_SIZE {
+                        return Err(ConnectionError::new(
+                            ErrorCode::FLOW_CONTROL_ERROR,
+                            "too large SETTINGS_INITIAL_WINDOW_SIZE",
+                        ));
+                    }
+                    // 送信中のストリームのウィンドウも、差分だけ変える。負になることもある
+                    // https://www.rfc-editor.org/rfc/rfc9113#section-6.9.2
+                    let delta = *value as i64 - self.peer_initial_window;
+                    for stream in self.streams.values_mut() {
+                        stream.send_window += delta;
+                        if stream.send_window > MAX_WINDOW_SIZE as i64 {
+                            return Err(ConnectionError::new(
+                                ErrorCode::FLOW_CONTROL_ERROR,
+                                "stream window exceeds the maximum",
+                            ));
+                        }
+                    }
+                    self.peer_initial_window = *value as i64;
+                }
+                SETTINGS_MAX_FRAME_SIZE => {
+                    if !(DEFAULT_MAX_FRAME_SIZE..=MAX_MAX_FRAME_SIZE).contains(value) {
+                        return Err(ConnectionError::new(
+                            ErrorCode::PROTOCOL_ERROR,
+                            "invalid SETTINGS_MAX_FRAME_SIZE",
+                        ));
+                    }
+                    self.peer_max_frame_size = *value;
+                }
+                // ヘッダの一覧の大きさの上限と、知らないパラメータは使わない
+                _ => {}
+            }
+        }
+        self.settings_received = true;
+        Frame::Settings {
+            ack: true,
+            settings: Vec::new(),
+        }
+        .encode(&mut self.output);
+        Ok(())
+    }
+
+    fn receive_data(
+        &mut self,
+        stream_id: u32,
+        data: &[u8],
+        end_stream: bool,
+        padding: usize,
+    ) -> Result<(), ConnectionError> {
+        self.check_not_idle(stream_id, "DATA")?;
+        // フロー制御では、パディングも含めて数える
+        let length = (data.len() + padding) as i64;
+        self.recv_window -= length;
+        if self.recv_window < 0 {
+            return Err(ConnectionError::new(
+                ErrorCode::FLOW_CONTROL_ERROR,
+                "received more data than the connection window",
+            ));
+        }
+        // ウィンドウが半分を下回ったら、受信した分を増やす
+        if self.recv_window < RECEIVE_WINDOW as i64 / 2 {
+            Frame::WindowUpdate {
+                stream_id: 0,
+                increment: (RECEIVE_WINDOW as i64 - self.recv_window) as u32,
+            }
+            .encode(&mut self.output);
+            self.recv_window = RECEIVE_WINDOW as i64;
+        }
+
+        // 閉じたストリームで、送信中だったフレームは無視する
+        let stream = match self.streams.get_mut(&stream_id) {
+            Some(stream) if !stream.is_done() => stream,
+            _ => return Ok(()),
+        };
+        stream.responded = true;
+        stream.recv_window -= length;
+        if stream.recv_window < 0 {
+            self.reset_stream(
+                stream_id,
+                ErrorCode::FLOW_CONTROL_ERROR,
+                "received more data than the stream window",
+            );
+            return Ok(());
+        }
+        let mut result = stream.append_body(data);
+        if result.is_ok() && end_stream {
+            result = stream.finish();
+        }
+        if !end_stream && stream.recv_window < RECEIVE_WINDOW as i64 / 2 {
+            Frame::WindowUpdate {
+                stream_id,
+                increment: (RECEIVE_WINDOW as i64 - stream.recv_window) as u32,
+            }
+            .encode(&mut self.output);
+            stream.recv_window = RECEIVE_WINDOW as i64;
+        }
+        if let Err(e) = result {
+            self.reset_stream(stream_id, ErrorCode::PROTOCOL_ERROR, &error_message(e));
+        }
+        Ok(())
+    }
+
+    fn receive_headers(
+        &mut self,
+        stream_id: u32,
+        block: &[u8],
+        end_stream: bool,
+    ) -> Result<(), ConnectionError> {
+        // HPACKの動的テーブルを相手と揃えるために、使わないブロックもデコードする
+        let fields = self
+            .decoder
+            .decode(block)
+            .map_err(|e| ConnectionError::new(ErrorCode::COMPRESSION_ERROR, &error_message(e)))?;
+        let stream = match self.streams.get_mut(&stream_id) {
+            Some(stream) if !stream.is_done() => stream,
+            _ => return Ok(()),
+        };
+        stream.responded = true;
+        let mut result = if stream.response.is_none() {
+            stream.receive_response_headers(fields, end_stream)
+        } else {
+            stream.receive_trailers(fields, end_stream)
+        };
+        if result.is_ok() && end_stream {
+            result = stream.finish().map_err(error_message);
+        }
+        if let Err(message) = result {
+            self.reset_stream(stream_id, ErrorCode::PROTOCOL_ERROR, &message);
+        }
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-6.9.1
+    fn receive_window_update(
+        &mut self,
+        stream_id: u32,
+        increment: u32,
+    ) -> Result<(), ConnectionError> {
+        if stream_id == 0 {
+            if increment == 0 {
+                return Err(ConnectionError::new(
+                    ErrorCode::PROTOCOL_ERROR,
+                    "WINDOW_UPDATE with zero increment",
+                ));
+            }
+            self.send_window += increment as i64;
+            if self.send_window > MAX_WINDOW_SIZE as i64 {
+                return Err(ConnectionError::new(
+                    ErrorCode::FLOW_CONTROL_ERROR,
+                    "connection window exceeds the maximum",
+                ));
+            }
+            return Ok(());
+        }
+
+        self.check_not_idle(stream_id, "WINDOW_UPDATE")?;
+        let stream = match self.streams.get_mut(&stream_id) {
+            Some(stream) if !stream.is_done() => stream,
+            _ => return Ok(()),
+        };
+        if increment == 0 {
+            self.reset_stream(
+                stream_id,
+                ErrorCode::PROTOCOL_ERROR,
+                "WINDOW_UPDATE with zero increment",
+            );
+            return Ok(());
+        }
+        stream.send_window += increment as i64;
+        if stream.send_window > MAX_WINDOW_SIZE as i64 {
+            self.reset_stream(
+                stream_id,
+                ErrorCode::FLOW_CONTROL_ERROR,
+                "stream window exceeds the maximum",
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<C: Connection> Drop for H2Connection<C> {
+    /// 閉じることをGOAWAYでサーバーに伝える
+    fn drop(&mut self) {
+        if !self.closed {
+            Frame::GoAway {
+                last_stream_id: 0,
+                error_code: ErrorCode::NO_ERROR,
+                debug_data: Vec::new(),
+            }
+            .encode(&mut self.output);
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::h2::frame::DEFAULT_MAX_FRAME_SIZE;
+    use crate::url::Url;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// あらかじめ用意したサーバーのフレームを返し、クライアントが送ったバイト列を記録する接続
+    #[derive(Debug)]
+    struct ScriptedConnection {
+        input: Vec<u8>,
+        position: usize,
+        written: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Connection for ScriptedConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.written.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let length = buf.len().min(self.input.len() - self.position);
+            buf[..length].copy_from_slice(&self.input[self.position..self.position + length]);
+            self.position += length;
+            Ok(length)
+        }
+    }
+
+    type Written = Rc<RefCell<Vec<u8>>>;
+
+    fn connect(frames: &[Frame]) -> (H2Connection<ScriptedConnection>, Written) {
+        let mut input = Vec::new();
+        for frame in frames {
+            frame.encode(&mut input);
+        }
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let connection = ScriptedConnection {
+            input,
+            position: 0,
+            written: written.clone(),
+        };
+        (
+            H2Connection::connect(connection).expect("failed to connect"),
+            written,
+        )
+    }
+
+    /// クライアントが送ったフレームを、プリフェイスの後から読む
+    fn sent_frames(written: &Written) -> Vec<Frame> {
+        let written = written.borrow();
+        assert!(written.starts_with(CONNECTION_PREFACE));
+        let mut input = &written[CONNECTION_PREFACE.len()..];
+        let mut frames = Vec::new();
+        while let Some((frame, length)) =
+            Frame::decode(input, DEFAULT_MAX_FRAME_SIZE).expect("invalid frame")
+        {
+            frames.push(frame);
+            input = &input[length..];
+        }
+        assert!(input.is_empty());
+        frames
+    }
+
+    fn settings(settings: &[(u16, u32)]) -> Frame {
+        Frame::Settings {
+            ack: false,
+            settings: settings.to_vec(),
+        }
+    }
+
+    fn headers(stream_id: u32, fields: &[(&str, &str)], end_stream: bool) -> Frame {
+        let fields: Vec<HeaderField> = fields
+            .iter()
+            .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect();
+        // 動的テーブルのインデックスを使わないように、ブロックごとに新しいエンコーダーを使う
+        Frame::Headers {
+            stream_id,
+            header_block: Encoder::new().encode(&fields),
+            end_stream,
+            end_headers: true,
+            padding: 0,
+        }
+    }
+
+    fn data(stream_id: u32, data: &[u8], end_stream: bool) -> Frame {
+        Frame::Data {
+            stream_id,
+            data: data.to_vec(),
+            end_stream,
+            padding: 0,
+        }
+    }
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest::get(
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    #[test]
+    fn test_get() {
+        let (mut connection, written) = connect(&[
+            settings(&[(SETTINGS_MAX_CONCURRENT_STREAMS, 100)]),
+            Frame::Settings {
+                ack: true,
+                settings: Vec::new(),
+            },
+            headers(1, &[(":status", "200"), ("content-length", "4")], false),
+            data(1, b"sa", false),
+            Frame::Data {
+                stream_id: 1,
+                data: b"ba".to_vec(),
+                end_stream: true,
+                padding: 10,
+            },
+        ]);
+        let request = get("https://example.com:8443/index.html?q=saba")
+            .with_header("Accept", "text/html")
+            .with_header("Connection", "keep-alive");
+        let response = connection.send(&request).expect("failed to send");
+        assert_eq!("HTTP/2", response.version());
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"saba".to_vec(), response.body());
+        assert_eq!(Ok("4".to_string()), response.header_value("Content-Length"));
+        assert_eq!(0, connection.active_streams());
+        assert!(connection.is_usable());
+
+        let frames = sent_frames(&written);
+        assert!(matches!(
+            &frames[0],
+            Frame::Settings { ack: false, settings } if settings.contains(&(SETTINGS_ENABLE_PUSH, 0))
+        ));
+        let block = frames
+            .iter()
+            .find_map(|frame| match frame {
+                Frame::Headers {
+                    stream_id: 1,
+                    header_block,
+                    end_stream: true,
+                    end_headers: true,
+                    ..
+                } => Some(header_block.clone()),
+                _ => None,
+            })
+            .expect("no HEADERS frame");
+        let expected: Vec<HeaderField> = [
+            (":method", "GET"),
+            (":scheme", "https"),
+            (":authority", "example.com:8443"),
+            (":path", "/index.html?q=saba"),
+            ("accept", "text/html"),
+        ]
+        .iter()
+        .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+        .collect();
+        assert_eq!(Ok(expected), Decoder::new().decode(&block));
+        // サーバーのSETTINGSに応答する
+        assert!(frames.contains(&Frame::Settings {
+            ack: true,
+            settings: Vec::new()
+        }));
+    }
+
+    #[test]
+    fn test_multiplexing() {
+        // 後から送ったリクエストのレスポンスが先に届き、DATAが交互に届く
+        let (mut connection, _) = connect(&[
+            settings(&[]),
+            headers(3, &[(":status", "404")], false),
+            headers(1, &[(":status", "103"), ("link", "</style.css>")], false),
+            headers(1, &[(":status", "200")], false),
+            data(3, b"not ", false),
+            data(1, b"first", false),
+            data(3, b"found", true),
+            headers(1, &[("grpc-status", "0")], true),
+        ]);
+        let first = connection
+            .send_request(&get("http://example.com/first"))
+            .expect("failed to send");
+        let second = connection
+            .send_request(&get("http://example.com/second"))
+            .expect("failed to send");
+        assert_eq!((1, 3), (first, second));
+        assert_eq!(2, connection.active_streams());
+
+        let response = connection.response(first).expect("failed to receive");
+        assert_eq!(200, response.status_code());
+        assert_eq!(b"first".to_vec(), response.body());
+        assert_eq!("grpc-status", response.trailers()[0].name());
+        let response = connection.response(second).expect("failed to receive");
+        assert_eq!(404, response.status_code());
+        assert_eq!(b"not found".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_ping_and_window_update() {
+        // 受信のウィンドウの半分を超えるボディを受け取ると、WINDOW_UPDATEを送る
+        let mut frames = vec![
+            settings(&[]),
+            Frame::Ping {
+                ack: false,
+                data: *b"sabasaba",
+            },
+            headers(1, &[(":status", "200")], false),
+        ];
+        for _ in 0..40 {
+            frames.push(data(1, &[b'a'; DEFAULT_MAX_FRAME_SIZE as usize], false));
+        }
+        frames.push(data(1, b"", true));
+        let (mut connection, written) = connect(&frames);
+        let response = connection
+            .send(&get("http://example.com/"))
+            .expect("failed to send");
+        assert_eq!(40 * DEFAULT_MAX_FRAME_SIZE as usize, response.body().len());
+
+        let frames = sent_frames(&written);
+        assert!(frames.contains(&Frame::Ping {
+            ack: true,
+            data: *b"sabasaba"
+        }));
+        let updates: Vec<u32> = frames
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::WindowUpdate {
+                    stream_id: 1,
+                    increment,
+                } => Some(*increment),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![33 * DEFAULT_MAX_FRAME_SIZE], updates);
+    }
+
+    #[test]
+    fn test_send_flow_control() {
+        let (mut connection, written) = connect(&[
+            settings(&[(SETTINGS_INITIAL_WINDOW_SIZE, 10)]),
+            headers(1, &[(":status", "204")], true),
+            Frame::WindowUpdate {
+                stream_id: 3,
+                increment: 10,
+            },
+            Frame::WindowUpdate {
+                stream_id: 3,
+                increment: 100,
+            },
+            headers(3, &[(":status", "201")], true),
+        ]);
+        connection
+            .send(&get("http://example.com/"))
+            .expect("failed to send");
+        let request = HttpRequest::new(
+            "POST",
+            Url::new("http://example.com/form".to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+        .with_body(b"abcdefghijklmnopqrstuvwxy".to_vec());
+        let response = connection.send(&request).expect("failed to send");
+        assert_eq!(201, response.status_code());
+
+        let sent: Vec<(Vec<u8>, bool)> = sent_frames(&written)
+            .into_iter()
+            .filter_map(|frame| match frame {
+                Frame::Data {
+                    stream_id: 3,
+                    data,
+                    end_stream,
+                    ..
+                } => Some((data, end_stream)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (b"abcdefghij".to_vec(), false),
+                (b"klmnopqrst".to_vec(), false),
+                (b"uvwxy".to_vec(), true),
+            ],
+            sent
+        );
+    }
+
+    #[test]
+    fn test_goaway_and_reset() {
+        let (mut connection, _) = connect(&[
+            settings(&[]),
+            Frame::RstStream {
+                stream_id: 3,
+                error_code: ErrorCode::REFUSED_STREAM,
+            },
+            Frame::RstStream {
+                stream_id: 5,
+                error_code: ErrorCode::INTERNAL_ERROR,
+            },
+            Frame::GoAway {
+                last_stream_id: 5,
+                error_code: ErrorCode::NO_ERROR,
+                debug_data: b"shutdown".to_vec(),
+            },
+            headers(1, &[(":status", "200")], true),
+        ]);
+        let ids: Vec<u32> = (0..4)
+            .map(|_| {
+                connection
+                    .send_request(&get("http://example.com/"))
+                    .expect("failed to send")
+            })
+            .collect();
+        assert_eq!(vec![1, 3, 5, 7], ids);
+
+        assert!(matches!(
+            connection.response(7),
+            Err(StreamError::Refused(_))
+        ));
+        assert!(!connection.is_usable());
+        assert!(matches!(
+            connection.send_request(&get("http://example.com/")),
+            Err(StreamError::Refused(_))
+        ));
+        assert!(matches!(
+            connection.response(3),
+            Err(StreamError::Refused(_))
+        ));
+        assert_eq!(
+            Some(StreamError::Failed(Error::Network(
+                "http2: stream reset by the server (INTERNAL_ERROR)".to_string()
+            ))),
+            connection.response(5).err()
+        );
+        assert_eq!(
+            200,
+            connection
+                .response(1)
+                .expect("failed to receive")
+                .status_code()
+        );
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        // サーバーのプリフェイスがSETTINGSで始まらない
+        let (mut connection, written) = connect(&[headers(1, &[(":status", "200")], true)]);
+        assert!(matches!(
+            connection.send(&get("http://example.com/")),
+            Err(StreamError::Unanswered(_))
+        ));
+        assert!(!connection.is_usable());
+        assert!(sent_frames(&written).iter().any(|frame| matches!(
+            frame,
+            Frame::GoAway {
+                error_code: ErrorCode::PROTOCOL_ERROR,
+                ..
+            }
+        )));
+
+        // レスポンスの途中で接続が閉じられる
+        let (mut connection, _) = connect(&[
+            settings(&[]),
+            headers(1, &[(":status", "200")], false),
+            data(1, b"sa", false),
+        ]);
+        assert!(matches!(
+            connection.send(&get("http://example.com/")),
+            Err(StreamError::Failed(_))
+        ));
+
+        // ストリームのエラーは、そのストリームだけを失敗にする
+        let (mut connection, written) = connect(&[
+            settings(&[]),
+            headers(1, &[(":status", "200"), ("content-length", "5")], false),
+            data(1, b"saba", true),
+            headers(3, &[(":status", "200"), ("Upper", "case")], true),
+            headers(5, &[(":status", "200"), ("connection", "close")], true),
+            headers(7, &[(":status", "200")], true),
+        ]);
+        for _ in 0..4 {
+            connection
+                .send_request(&get("http://example.com/"))
+                .expect("failed to send");
+        }
+        for stream_id in [1, 3, 5] {
+            assert!(matches!(
+                connection.response(stream_id),
+                Err(StreamError::Failed(_))
+            ));
+        }
+        assert!(connection.response(7).is_ok());
+        let resets = sent_frames(&written)
+            .iter()
+            .filter(|frame| {
+                matches!(
+                    frame,
+                    Frame::RstStream {
+                        error_code: ErrorCode::PROTOCOL_ERROR,
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(3, resets);
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let (mut connection, _) = connect(&[settings(&[])]);
+        let request = get("http://example.com/").with_header("X-Saba", "a\r\nb");
+        assert!(matches!(
+            connection.send_request(&request),
+            Err(StreamError::Failed(Error::UnexpectedInput(_)))
+        ));
+        let request = get("http://example.com/").with_header("Bad Name", "value");
+        assert!(matches!(
+            connection.send_request(&request),
+            Err(StreamError::Failed(Error::UnexpectedInput(_)))
+        ));
+        assert!(connection.is_usable());
+    }
+}
@@ -0,0 +1,725 @@
+use crate::error::Error;
+use crate::http::h2::h2_error;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// フレームのヘッダの長さ
+pub const FRAME_HEADER_LEN: usize = 9;
+/// SETTINGS_MAX_FRAME_SIZEの初期値と上限
+/// https://www.rfc-editor.org/rfc/rfc9113#section-6.5.2
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 1 << 14;
+pub const MAX_MAX_FRAME_SIZE: u32 = (1 << 24) - 1;
+/// フロー制御のウィンドウの初期値と上限
+/// https://www.rfc-editor.org/rfc/rfc9113#section-6.9.2
+pub const DEFAULT_WINDOW_SIZE: u32 = 65535;
+pub const MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+
+const TYPE_DATA: u8 = 0x0;
+const TYPE_HEADERS: u8 = 0x1;
+const TYPE_PRIORITY: u8 = 0x2;
+const TYPE_RST_STREAM: u8 = 0x3;
+const TYPE_SETTINGS: u8 = 0x4;
+const TYPE_PUSH_PROMISE: u8 = 0x5;
+const TYPE_PING: u8 = 0x6;
+const TYPE_GOAWAY: u8 = 0x7;
+const TYPE_WINDOW_UPDATE: u8 = 0x8;
+const TYPE_CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_ACK: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+/// SETTINGSフレームのパラメータ
+/// https://www.rfc-editor.org/rfc/rfc9113#section-6.5.2
+pub const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+pub const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+pub const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+pub const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+pub const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+pub const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+/// RST_STREAMとGOAWAYで送るエラーコード
+/// https://www.rfc-editor.org/rfc/rfc9113#section-7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode(pub u32);
+
+impl ErrorCode {
+    pub const NO_ERROR: Self = Self(0x0);
+    pub const PROTOCOL_ERROR: Self = Self(0x1);
+    pub const INTERNAL_ERROR: Self = Self(0x2);
+    pub const FLOW_CONTROL_ERROR: Self = Self(0x3);
+    pub const SETTINGS_TIMEOUT: Self = Self(0x4);
+    pub const STREAM_CLOSED: Self = Self(0x5);
+    pub const FRAME_SIZE_ERROR: Self = Self(0x6);
+    pub const REFUSED_STREAM: Self = Self(0x7);
+    pub const CANCEL: Self = Self(0x8);
+    pub const COMPRESSION_ERROR: Self = Self(0x9);
+    pub const CONNECT_ERROR: Self = Self(0xa);
+    pub const ENHANCE_YOUR_CALM: Self = Self(0xb);
+    pub const INADEQUATE_SECURITY: Self = Self(0xc);
+    pub const HTTP_1_1_REQUIRED: Self = Self(0xd);
+
+    /// 知らないエラーコードは16進数で表す
+    pub fn name(&self) -> String {
+        let name = match *self {
+            Self::NO_ERROR => "NO_ERROR",
+            Self::PROTOCOL_ERROR => "PROTOCOL_ERROR",
+            Self::INTERNAL_ERROR => "INTERNAL_ERROR",
+            Self::FLOW_CONTROL_ERROR => "FLOW_CONTROL_ERROR",
+            Self::SETTINGS_TIMEOUT => "SETTINGS_TIMEOUT",
+            Self::STREAM_CLOSED => "STREAM_CLOSED",
+            Self::FRAME_SIZE_ERROR => "FRAME_SIZE_ERROR",
+            Self::REFUSED_STREAM => "REFUSED_STREAM",
+            Self::CANCEL => "CANCEL",
+            Self::COMPRESSION_ERROR => "COMPRESSION_ERROR",
+            Self::CONNECT_ERROR => "CONNECT_ERROR",
+            Self::ENHANCE_YOUR_CALM => "ENHANCE_YOUR_CALM",
+            Self::INADEQUATE_SECURITY => "INADEQUATE_SECURITY",
+            Self::HTTP_1_1_REQUIRED => "HTTP_1_1_REQUIRED",
+            ErrorCode(code) => return format!("0x{:x}", code),
+        };
+        name.to_string()
+    }
+}
+
+/// 接続全体を閉じる必要がある誤り。GOAWAYでエラーコードを相手に伝える
+/// https://www.rfc-editor.org/rfc/rfc9113#section-5.4.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ConnectionError {
+    pub fn new(code: ErrorCode, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<ConnectionError> for Error {
+    fn from(e: ConnectionError) -> Self {
+        h2_error(&format!("{} ({})", e.message, e.code.name()))
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc9113#section-6
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Data {
+        stream_id: u32,
+        data: Vec<u8>,
+        end_stream: bool,
+        /// パディングの長さを表す1バイトを含む、パディングのバイト数。フロー制御では
+        /// パディングも数える
+        padding: usize,
+    },
+    /// PRIORITYフラグの優先度の情報は使わないので読み飛ばす
+    Headers {
+        stream_id: u32,
+        header_block: Vec<u8>,
+        end_stream: bool,
+        end_headers: bool,
+        padding: usize,
+    },
+    /// 優先度はRFC 9113で非推奨になったので、内容は読まない
+    Priority {
+        stream_id: u32,
+    },
+    RstStream {
+        stream_id: u32,
+        error_code: ErrorCode,
+    },
+    Settings {
+        ack: bool,
+        settings: Vec<(u16, u32)>,
+    },
+    PushPromise {
+        stream_id: u32,
+        promised_stream_id: u32,
+        header_block: Vec<u8>,
+        end_headers: bool,
+    },
+    Ping {
+        ack: bool,
+        data: [u8; 8],
+    },
+    GoAway {
+        last_stream_id: u32,
+        error_code: ErrorCode,
+        debug_data: Vec<u8>,
+    },
+    WindowUpdate {
+        stream_id: u32,
+        increment: u32,
+    },
+    Continuation {
+        stream_id: u32,
+        header_block: Vec<u8>,
+        end_headers: bool,
+    },
+    /// 知らない種類のフレームは無視する
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-4.1
+    Unknown {
+        frame_type: u8,
+        stream_id: u32,
+    },
+}
+
+fn write_header(output: &mut Vec<u8>, length: usize, frame_type: u8, flags: u8, stream_id: u32) {
+    output.extend_from_slice(&(length as u32).to_be_bytes()[1..]);
+    output.push(frame_type);
+    output.push(flags);
+    output.extend_from_slice(&(stream_id & MAX_WINDOW_SIZE).to_be_bytes());
+}
+
+/// パディングを付ける場合は、パディングの長さを表す1バイトを先頭に置く
+fn write_padded(
+    output: &mut Vec<u8>,
+    frame_type: u8,
+    mut flags: u8,
+    stream_id: u32,
+    payload: &[u8],
+    padding: usize,
+) {
+    if padding == 0 {
+        write_header(output, payload.len(), frame_type, flags, stream_id);
+        output.extend_from_slice(payload);
+        return;
+    }
+    flags |= FLAG_PADDED;
+    write_header(
+        output,
+        payload.len() + padding,
+        frame_type,
+        flags,
+        stream_id,
+    );
+    output.push((padding - 1) as u8);
+    output.extend_from_slice(payload);
+    output.resize(output.len() + padding - 1, 0);
+}
+
+/// PADDEDフラグがある場合は、パディングを取り除いたペイロードと、パディングのバイト数を返す
+fn strip_padding(payload: &[u8], flags: u8) -> Result<(&[u8], usize), ConnectionError> {
+    if flags & FLAG_PADDED == 0 {
+        return Ok((payload, 0));
+    }
+    let pad_length = match payload.first() {
+        Some(pad_length) => *pad_length as usize,
+        None => {
+            return Err(ConnectionError::new(
+                ErrorCode::FRAME_SIZE_ERROR,
+                "padded frame without a pad length",
+            ))
+        }
+    };
+    if pad_length >= payload.len() {
+        return Err(ConnectionError::new(
+            ErrorCode::PROTOCOL_ERROR,
+            "padding is longer than the frame",
+        ));
+    }
+    Ok((&payload[1..payload.len() - pad_length], pad_length + 1))
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl Frame {
+    /// ストリームに属さないフレームでは0を返す
+    pub fn stream_id(&self) -> u32 {
+        match self {
+            Frame::Data { stream_id, .. }
+            | Frame::Headers { stream_id, .. }
+            | Frame::Priority { stream_id }
+            | Frame::RstStream { stream_id, .. }
+            | Frame::PushPromise { stream_id, .. }
+            | Frame::WindowUpdate { stream_id, .. }
+            | Frame::Continuation { stream_id, .. }
+            | Frame::Unknown { stream_id, .. } => *stream_id,
+            Frame::Settings { .. } | Frame::Ping { .. } | Frame::GoAway { .. } => 0,
+        }
+    }
+
+    /// フレームをバイト列にして末尾に追加する。長さがSETTINGS_MAX_FRAME_SIZEを超えないように
+    /// 分けるのは呼び出し側の役割
+    pub fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Frame::Data {
+                stream_id,
+                data,
+                end_stream,
+                padding,
+            } => {
+                let flags = if *end_stream { FLAG_END_STREAM } else { 0 };
+                write_padded(output, TYPE_DATA, flags, *stream_id, data, *padding);
+            }
+            Frame::Headers {
+                stream_id,
+                header_block,
+                end_stream,
+                end_headers,
+                padding,
+            } => {
+                let mut flags = 0;
+                if *end_stream {
+                    flags |= FLAG_END_STREAM;
+                }
+                if *end_headers {
+                    flags |= FLAG_END_HEADERS;
+                }
+                write_padded(
+                    output,
+                    TYPE_HEADERS,
+                    flags,
+                    *stream_id,
+                    header_block,
+                    *padding,
+                );
+            }
+            Frame::Priority { stream_id } => {
+                // 依存先なし、重みは既定の16
+                write_header(output, 5, TYPE_PRIORITY, 0, *stream_id);
+                output.extend_from_slice(&[0, 0, 0, 0, 15]);
+            }
+            Frame::RstStream {
+                stream_id,
+                error_code,
+            } => {
+                write_header(output, 4, TYPE_RST_STREAM, 0, *stream_id);
+                output.extend_from_slice(&error_code.0.to_be_bytes());
+            }
+            Frame::Settings { ack, settings } => {
+                let flags = if *ack { FLAG_ACK } else { 0 };
+                write_header(output, settings.len() * 6, TYPE_SETTINGS, flags, 0);
+                for (id, value) in settings {
+                    output.extend_from_slice(&id.to_be_bytes());
+                    output.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            Frame::PushPromise {
+                stream_id,
+                promised_stream_id,
+                header_block,
+                end_headers,
+            } => {
+                let flags = if *end_headers { FLAG_END_HEADERS } else { 0 };
+                write_header(
+                    output,
+                    4 + header_block.len(),
+                    TYPE_PUSH_PROMISE,
+                    flags,
+                    *stream_id,
+                );
+                output.extend_from_slice(&(promised_stream_id & MAX_WINDOW_SIZE).to_be_bytes());
+                output.extend_from_slice(header_block);
+            }
+            Frame::Ping { ack, data } => {
+                let flags = if *ack { FLAG_ACK } else { 0 };
+                write_header(output, 8, TYPE_PING, flags, 0);
+                output.extend_from_slice(data);
+            }
+            Frame::GoAway {
+                last_stream_id,
+                error_code,
+                debug_data,
+            } => {
+                write_header(output, 8 + debug_data.len(), TYPE_GOAWAY, 0, 0);
+                output.extend_from_slice(&(last_stream_id & MAX_WINDOW_SIZE).to_be_bytes());
+                output.extend_from_slice(&error_code.0.to_be_bytes());
+                output.extend_from_slice(debug_data);
+            }
+            Frame::WindowUpdate {
+                stream_id,
+                increment,
+            } => {
+                write_header(output, 4, TYPE_WINDOW_UPDATE, 0, *stream_id);
+                output.extend_from_slice(&(increment & MAX_WINDOW_SIZE).to_be_bytes());
+            }
+            Frame::Continuation {
+                stream_id,
+                header_block,
+                end_headers,
+            } => {
+                let flags = if *end_headers { FLAG_END_HEADERS } else { 0 };
+                write_header(
+                    output,
+                    header_block.len(),
+                    TYPE_CONTINUATION,
+                    flags,
+                    *stream_id,
+                );
+                output.extend_from_slice(header_block);
+            }
+            Frame::Unknown {
+                frame_type,
+                stream_id,
+            } => write_header(output, 0, *frame_type, 0, *stream_id),
+        }
+    }
+
+    /// バッファの先頭から1つのフレームを読み、読んだバイト数と合わせて返す。フレームが
+    /// 揃っていない場合はNoneを返す
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-4.1
+    pub fn decode(
+        input: &[u8],
+        max_frame_size: u32,
+    ) -> Result<Option<(Frame, usize)>, ConnectionError> {
+        if input.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        let length = read_u32(&[0, input[0], input[1], input[2]]) as usize;
+        let frame_type = input[3];
+        let flags = input[4];
+        // 先頭の1ビットは予約されているので無視する
+        let stream_id = read_u32(&input[5..9]) & MAX_WINDOW_SIZE;
+        if length > max_frame_size as usize {
+            return Err(ConnectionError::new(
+                ErrorCode::FRAME_SIZE_ERROR,
+                &format!("frame of {} bytes exceeds the maximum frame size", length),
+            ));
+        }
+        if input.len() < FRAME_HEADER_LEN + length {
+            return Ok(None);
+        }
+        let payload = &input[FRAME_HEADER_LEN..FRAME_HEADER_LEN + length];
+
+        let require_stream = |name: &str| {
+            if stream_id == 0 {
+                Err(ConnectionError::new(
+                    ErrorCode::PROTOCOL_ERROR,
+                    &format!("{} frame on stream 0", name),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        let require_connection = |name: &str| {
+            if stream_id != 0 {
+                Err(ConnectionError::new(
+                    ErrorCode::PROTOCOL_ERROR,
+                    &format!("{} frame on stream {}", name, stream_id),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        let require_length = |name: &str, expected: usize| {
+            if length != expected {
+                Err(ConnectionError::new(
+                    ErrorCode::FRAME_SIZE_ERROR,
+                    &format!("{} frame of {} bytes", name, length),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+
+        let frame = match frame_type {
+            TYPE_DATA => {
+                require_stream("DATA")?;
+                let (data, padding) = strip_padding(payload, flags)?;
+                Frame::Data {
+                    stream_id,
+                    data: data.to_vec(),
+                    end_stream: flags & FLAG_END_STREAM != 0,
+                    padding,
+                }
+            }
+            TYPE_HEADERS => {
+                require_stream("HEADERS")?;
+                let (mut header_block, padding) = strip_padding(payload, flags)?;
+                if flags & FLAG_PRIORITY != 0 {
+                    if header_block.len() < 5 {
+                        return Err(ConnectionError::new(
+                            ErrorCode::FRAME_SIZE_ERROR,
+                            "HEADERS frame is too short for the priority",
+                        ));
+                    }
+                    header_block = &header_block[5..];
+                }
+                Frame::Headers {
+                    stream_id,
+                    header_block: header_block.to_vec(),
+                    end_stream: flags & FLAG_END_STREAM != 0,
+                    end_headers: flags & FLAG_END_HEADERS != 0,
+                    padding,
+                }
+            }
+            TYPE_PRIORITY => {
+                require_stream("PRIORITY")?;
+                require_length("PRIORITY", 5)?;
+                Frame::Priority { stream_id }
+            }
+            TYPE_RST_STREAM => {
+                require_stream("RST_STREAM")?;
+                require_length("RST_STREAM", 4)?;
+                Frame::RstStream {
+                    stream_id,
+                    error_code: ErrorCode(read_u32(payload)),
+                }
+            }
+            TYPE_SETTINGS => {
+                require_connection("SETTINGS")?;
+                let ack = flags & FLAG_ACK != 0;
+                if (ack && length != 0) || length % 6 != 0 {
+                    return Err(ConnectionError::new(
+                        ErrorCode::FRAME_SIZE_ERROR,
+                        &format!("SETTINGS frame of {} bytes", length),
+                    ));
+                }
+                let settings = payload
+                    .chunks(6)
+                    .map(|s| (u16::from_be_bytes([s[0], s[1]]), read_u32(&s[2..])))
+                    .collect();
+                Frame::Settings { ack, settings }
+            }
+            TYPE_PUSH_PROMISE => {
+                require_stream("PUSH_PROMISE")?;
+                let (payload, _) = strip_padding(payload, flags)?;
+                if payload.len() < 4 {
+                    return Err(ConnectionError::new(
+                        ErrorCode::FRAME_SIZE_ERROR,
+                        "PUSH_PROMISE frame is too short",
+                    ));
+                }
+                Frame::PushPromise {
+                    stream_id,
+                    promised_stream_id: read_u32(payload) & MAX_WINDOW_SIZE,
+                    header_block: payload[4..].to_vec(),
+                    end_headers: flags & FLAG_END_HEADERS != 0,
+                }
+            }
+            TYPE_PING => {
+                require_connection("PING")?;
+                require_length("PING", 8)?;
+                let mut data = [0u8; 8];
+                data.copy_from_slice(payload);
+                Frame::Ping {
+                    ack: flags & FLAG_ACK != 0,
+                    data,
+                }
+            }
+            TYPE_GOAWAY => {
+                require_connection("GOAWAY")?;
+                if length < 8 {
+                    return Err(ConnectionError::new(
+                        ErrorCode::FRAME_SIZE_ERROR,
+                        "GOAWAY frame is too short",
+                    ));
+                }
+                Frame::GoAway {
+                    last_stream_id: read_u32(payload) & MAX_WINDOW_SIZE,
+                    error_code: ErrorCode(read_u32(&payload[4..])),
+                    debug_data: payload[8..].to_vec(),
+                }
+            }
+            TYPE_WINDOW_UPDATE => {
+                require_length("WINDOW_UPDATE", 4)?;
+                Frame::WindowUpdate {
+                    stream_id,
+                    increment: read_u32(payload) & MAX_WINDOW_SIZE,
+                }
+            }
+            TYPE_CONTINUATION => {
+                require_stream("CONTINUATION")?;
+                Frame::Continuation {
+                    stream_id,
+                    header_block: payload.to_vec(),
+                    end_headers: flags & FLAG_END_HEADERS != 0,
+                }
+            }
+            _ => Frame::Unknown {
+                frame_type,
+                stream_id,
+            },
+        };
+        Ok(Some((frame, FRAME_HEADER_LEN + length)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+    use alloc::vec;
+
+    fn encode(frame: &Frame) -> Vec<u8> {
+        let mut output = Vec::new();
+        frame.encode(&mut output);
+        output
+    }
+
+    fn decode(input: &[u8]) -> Result<Option<(Frame, usize)>, ConnectionError> {
+        Frame::decode(input, DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for frame in [
+            Frame::Data {
+                stream_id: 1,
+                data: b"saba".to_vec(),
+                end_stream: true,
+                padding: 3,
+            },
+            Frame::Headers {
+                stream_id: 3,
+                header_block: vec![0x82, 0x86],
+                end_stream: false,
+                end_headers: true,
+                padding: 0,
+            },
+            Frame::Priority { stream_id: 5 },
+            Frame::RstStream {
+                stream_id: 1,
+                error_code: ErrorCode::CANCEL,
+            },
+            Frame::Settings {
+                ack: false,
+                settings: vec![
+                    (SETTINGS_ENABLE_PUSH, 0),
+                    (SETTINGS_INITIAL_WINDOW_SIZE, 1 << 20),
+                ],
+            },
+            Frame::Settings {
+                ack: true,
+                settings: Vec::new(),
+            },
+            Frame::PushPromise {
+                stream_id: 1,
+                promised_stream_id: 2,
+                header_block: vec![0x82],
+                end_headers: true,
+            },
+            Frame::Ping {
+                ack: true,
+                data: *b"sabasaba",
+            },
+            Frame::GoAway {
+                last_stream_id: 7,
+                error_code: ErrorCode::ENHANCE_YOUR_CALM,
+                debug_data: b"slow down".to_vec(),
+            },
+            Frame::WindowUpdate {
+                stream_id: 0,
+                increment: MAX_WINDOW_SIZE,
+            },
+            Frame::Continuation {
+                stream_id: 3,
+                header_block: vec![0x84],
+                end_headers: true,
+            },
+            Frame::Unknown {
+                frame_type: 0xfa,
+                stream_id: 9,
+            },
+        ] {
+            let bytes = encode(&frame);
+            assert_eq!(Ok(Some((frame, bytes.len()))), decode(&bytes));
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            from_hex("0000080601000000007361626173616261"),
+            encode(&Frame::Ping {
+                ack: true,
+                data: *b"sabasaba",
+            })
+        );
+        // パディングの長さの1バイトと、0で埋めた2バイト
+        assert_eq!(
+            from_hex("00000700090000000102736162610000"),
+            encode(&Frame::Data {
+                stream_id: 1,
+                data: b"saba".to_vec(),
+                end_stream: true,
+                padding: 3,
+            })
+        );
+        // 予約ビットは0にする
+        assert_eq!(
+            from_hex("0000040800000000007fffffff"),
+            encode(&Frame::WindowUpdate {
+                stream_id: 0,
+                increment: u32::MAX,
+            })
+        );
+    }
+
+    #[test]
+    fn test_incomplete() {
+        let bytes = encode(&Frame::Ping {
+            ack: false,
+            data: [0; 8],
+        });
+        for length in 0..bytes.len() {
+            assert_eq!(Ok(None), decode(&bytes[..length]));
+        }
+    }
+
+    #[test]
+    fn test_decode_priority_and_padding() {
+        // PADDEDとPRIORITYのフラグが付いたHEADERS
+        let bytes = from_hex("00000a012d0000000302000000010f82860000");
+        assert_eq!(
+            Ok(Some((
+                Frame::Headers {
+                    stream_id: 3,
+                    header_block: vec![0x82, 0x86],
+                    end_stream: true,
+                    end_headers: true,
+                    padding: 3,
+                },
+                19
+            ))),
+            decode(&bytes)
+        );
+    }
+
+    #[test]
+    fn test_invalid_frames() {
+        for (hex, code) in [
+            // 最大のフレームサイズを超える
+            ("004001000000000001", ErrorCode::FRAME_SIZE_ERROR),
+            // ストリーム0のDATA
+            ("000000000000000000", ErrorCode::PROTOCOL_ERROR),
+            // ストリーム1のSETTINGS
+            ("000000040000000001", ErrorCode::PROTOCOL_ERROR),
+            // ACKに値がある
+            (
+                "000006040100000000000200000000",
+                ErrorCode::FRAME_SIZE_ERROR,
+            ),
+            // 長さが6の倍数ではない
+            ("0000050400000000000002000000", ErrorCode::FRAME_SIZE_ERROR),
+            // 7バイトのPING
+            (
+                "00000706000000000000000000000000",
+                ErrorCode::FRAME_SIZE_ERROR,
+            ),
+            // パディングがペイロードより長い
+            ("0000020008000000010500", ErrorCode::PROTOCOL_ERROR),
+            // 短いGOAWAY
+            ("00000407000000000000000000", ErrorCode::FRAME_SIZE_ERROR),
+            // ストリーム0のCONTINUATION
+            ("000000090400000000", ErrorCode::PROTOCOL_ERROR),
+        ] {
+            let bytes = from_hex(hex);
+            match Frame::decode(&bytes, DEFAULT_MAX_FRAME_SIZE) {
+                Err(e) => assert_eq!(code, e.code, "{}", hex),
+                result => panic!("{}: unexpected result: {:?}", hex, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,624 @@
+use crate::error::Error;
+use crate::http::h2::h2_error;
+use crate::http::h2::huffman;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// SETTINGS_HEADER_TABLE_SIZEの初期値
+pub const DEFAULT_HEADER_TABLE_SIZE: usize = 4096;
+/// 受け取るヘッダの一覧の大きさの上限。SETTINGS_MAX_HEADER_LIST_SIZEで相手に伝える。1バイトの
+/// インデックスで動的テーブルの大きなエントリを何度も参照できるので、展開した後の大きさで制限する
+pub const MAX_HEADER_LIST_SIZE: usize = 64 * 1024;
+/// 動的テーブルのエントリの大きさは、名前と値の長さに32を足したもの
+/// https://www.rfc-editor.org/rfc/rfc7541#section-4.1
+const ENTRY_OVERHEAD: usize = 32;
+
+/// ヘッダの名前と値。HTTP/2のヘッダの名前は小文字にする
+pub type HeaderField = (Vec<u8>, Vec<u8>);
+
+/// インデックスの1から61に対応する
+/// https://www.rfc-editor.org/rfc/rfc7541#appendix-A
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// 値を動的テーブルに入れない、資格情報を含むヘッダ
+/// https://www.rfc-editor.org/rfc/rfc7541#section-7.1.3
+const NEVER_INDEXED: [&str; 2] = ["authorization", "proxy-authorization"];
+
+/// 新しいエントリほど前に置き、インデックスは静的テーブルの後に続く
+/// https://www.rfc-editor.org/rfc/rfc7541#section-2.3.2
+#[derive(Debug, Clone)]
+struct DynamicTable {
+    entries: VecDeque<HeaderField>,
+    size: usize,
+    max_size: usize,
+}
+
+impl DynamicTable {
+    fn new(max_size: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            size: 0,
+            max_size,
+        }
+    }
+
+    /// 静的テーブルと合わせたインデックスでエントリを引く
+    fn get(&self, index: usize) -> Option<(&[u8], &[u8])> {
+        if index == 0 {
+            return None;
+        }
+        if index <= STATIC_TABLE.len() {
+            let (name, value) = STATIC_TABLE[index - 1];
+            return Some((name.as_bytes(), value.as_bytes()));
+        }
+        self.entries
+            .get(index - STATIC_TABLE.len() - 1)
+            .map(|(name, value)| (&name[..], &value[..]))
+    }
+
+    /// 名前と値が一致するエントリと、名前だけが一致するエントリのインデックスを探す
+    fn find(&self, name: &[u8], value: &[u8]) -> (Option<usize>, Option<usize>) {
+        let mut name_match = None;
+        let entries = STATIC_TABLE
+            .iter()
+            .map(|(n, v)| (n.as_bytes(), v.as_bytes()))
+            .chain(self.entries.iter().map(|(n, v)| (&n[..], &v[..])));
+        for (i, (n, v)) in entries.enumerate() {
+            if n == name {
+                if v == value {
+                    return (Some(i + 1), name_match);
+                }
+                name_match = name_match.or(Some(i + 1));
+            }
+        }
+        (None, name_match)
+    }
+
+    /// 入りきらない場合は古いエントリから追い出す。テーブルより大きいエントリを入れると
+    /// テーブルは空になる
+    /// https://www.rfc-editor.org/rfc/rfc7541#section-4.4
+    fn insert(&mut self, name: Vec<u8>, value: Vec<u8>) {
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        self.evict(self.max_size.saturating_sub(size));
+        if size <= self.max_size {
+            self.size += size;
+            self.entries.push_front((name, value));
+        }
+    }
+
+    fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        self.evict(max_size);
+    }
+
+    fn evict(&mut self, size: usize) {
+        while self.size > size {
+            match self.entries.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+/// 整数を、先頭のバイトの下位prefix_bitsビットから続けて書く。先頭のバイトの上位ビットには
+/// flagsを書く
+/// https://www.rfc-editor.org/rfc/rfc7541#section-5.1
+fn encode_integer(value: usize, prefix_bits: u32, flags: u8, output: &mut Vec<u8>) {
+    let max_prefix = (1usize << prefix_bits) - 1;
+    if value < max_prefix {
+        output.push(flags | value as u8);
+        return;
+    }
+    output.push(flags | max_prefix as u8);
+    let mut rest = value - max_prefix;
+    while rest >= 0x80 {
+        output.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    output.push(rest as u8);
+}
+
+fn decode_integer(input: &[u8], position: &mut usize, prefix_bits: u32) -> Result<usize, Error> {
+    let truncated = || h2_error("truncated integer in a header block");
+    let max_prefix = (1usize << prefix_bits) - 1;
+    let first = *input.get(*position).ok_or_else(truncated)? as usize & max_prefix;
+    *position += 1;
+    if first < max_prefix {
+        return Ok(first);
+    }
+
+    let mut value = max_prefix;
+    let mut shift = 0;
+    loop {
+        let byte = *input.get(*position).ok_or_else(truncated)?;
+        *position += 1;
+        // 巨大な値で桁あふれしないように、32ビットに収まらない値はエラーにする
+        if shift > 28 {
+            return Err(h2_error("too large integer in a header block"));
+        }
+        value = value
+            .checked_add(((byte & 0x7f) as usize) << shift)
+            .filter(|value| *value <= u32::MAX as usize)
+            .ok_or_else(|| h2_error("too large integer in a header block"))?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+/// ハフマン符号の方が短い場合はハフマン符号にする
+/// https://www.rfc-editor.org/rfc/rfc7541#section-5.2
+fn encode_string(value: &[u8], output: &mut Vec<u8>) {
+    let huffman_len = huffman::encoded_len(value);
+    if huffman_len < value.len() {
+        encode_integer(huffman_len, 7, 0x80, output);
+        huffman::encode(value, output);
+    } else {
+        encode_integer(value.len(), 7, 0, output);
+        output.extend_from_slice(value);
+    }
+}
+
+fn decode_string(input: &[u8], position: &mut usize) -> Result<Vec<u8>, Error> {
+    let huffman_encoded = input.get(*position).is_some_and(|b| b & 0x80 != 0);
+    let length = decode_integer(input, position, 7)?;
+    if input.len() - *position < length {
+        return Err(h2_error("truncated string in a header block"));
+    }
+    let bytes = &input[*position..*position + length];
+    *position += length;
+    if huffman_encoded {
+        huffman::decode(bytes)
+    } else {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// 受け取ったヘッダブロックをヘッダの一覧に戻す。動的テーブルは接続全体で共有するので、
+/// 接続ごとに1つ作り、受け取った順にすべてのブロックを渡す
+/// https://www.rfc-editor.org/rfc/rfc7541#section-3
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    table: DynamicTable,
+    /// SETTINGS_HEADER_TABLE_SIZEで相手に伝えた、動的テーブルの大きさの上限
+    max_table_size: usize,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::with_max_table_size(DEFAULT_HEADER_TABLE_SIZE)
+    }
+
+    pub fn with_max_table_size(max_table_size: usize) -> Self {
+        Self {
+            table: DynamicTable::new(max_table_size),
+            max_table_size,
+        }
+    }
+
+    /// ヘッダの一覧がMAX_HEADER_LIST_SIZEを超える場合はエラーにする
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<HeaderField>, Error> {
+        let mut headers = Vec::new();
+        let mut list_size = 0;
+        let mut position = 0;
+        while position < block.len() {
+            let (name, value) = match self.decode_field(block, &mut position, headers.is_empty())? {
+                Some(field) => field,
+                None => continue,
+            };
+            list_size += name.len() + value.len() + ENTRY_OVERHEAD;
+            if list_size > MAX_HEADER_LIST_SIZE {
+                return Err(h2_error("header list is too large"));
+            }
+            headers.push((name, value));
+        }
+        Ok(headers)
+    }
+
+    /// 1つの表現を読む。動的テーブルの大きさの変更の場合はNoneを返す
+    fn decode_field(
+        &mut self,
+        block: &[u8],
+        position: &mut usize,
+        first_field: bool,
+    ) -> Result<Option<HeaderField>, Error> {
+        let first = block[*position];
+        if first & 0x80 != 0 {
+            // https://www.rfc-editor.org/rfc/rfc7541#section-6.1
+            let index = decode_integer(block, position, 7)?;
+            let (name, value) = self
+                .table
+                .get(index)
+                .ok_or_else(|| h2_error("invalid index in a header block"))?;
+            return Ok(Some((name.to_vec(), value.to_vec())));
+        }
+
+        if first & 0xe0 == 0x20 {
+            // 動的テーブルの大きさの変更は、ブロックの先頭にしか置けない
+            // https://www.rfc-editor.org/rfc/rfc7541#section-6.3
+            if !first_field {
+                return Err(h2_error("dynamic table size update after a header field"));
+            }
+            let size = decode_integer(block, position, 5)?;
+            if size > self.max_table_size {
+                return Err(h2_error("dynamic table size update exceeds the limit"));
+            }
+            self.table.set_max_size(size);
+            return Ok(None);
+        }
+
+        // https://www.rfc-editor.org/rfc/rfc7541#section-6.2
+        let (prefix_bits, indexing) = if first & 0xc0 == 0x40 {
+            (6, true)
+        } else {
+            (4, false)
+        };
+        let name_index = decode_integer(block, position, prefix_bits)?;
+        let name = if name_index == 0 {
+            decode_string(block, position)?
+        } else {
+            self.table
+                .get(name_index)
+                .map(|(name, _)| name.to_vec())
+                .ok_or_else(|| h2_error("invalid index in a header block"))?
+        };
+        let value = decode_string(block, position)?;
+        if indexing {
+            self.table.insert(name.clone(), value.clone());
+        }
+        Ok(Some((name, value)))
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ヘッダの一覧をヘッダブロックにする。静的テーブルと動的テーブルにある名前と値は
+/// インデックスで送る
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    table: DynamicTable,
+    /// 次のブロックの先頭で知らせる、動的テーブルの大きさの変更。途中で小さくしてから
+    /// 大きくした場合は、最小の値と最後の値の両方を知らせる
+    /// https://www.rfc-editor.org/rfc/rfc7541#section-4.2
+    pending_size_update: Option<(usize, usize)>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self {
+            table: DynamicTable::new(DEFAULT_HEADER_TABLE_SIZE),
+            pending_size_update: None,
+        }
+    }
+
+    /// 相手のSETTINGS_HEADER_TABLE_SIZEを受け取ったときに呼ぶ。初期値より大きなテーブルは
+    /// 使わない
+    pub fn set_max_table_size(&mut self, max_size: usize) {
+        let size = max_size.min(DEFAULT_HEADER_TABLE_SIZE);
+        if size == self.table.max_size && self.pending_size_update.is_none() {
+            return;
+        }
+        let minimum = match self.pending_size_update {
+            Some((minimum, _)) => minimum.min(size),
+            None => size.min(self.table.max_size),
+        };
+        self.pending_size_update = Some((minimum, size));
+        self.table.set_max_size(size);
+    }
+
+    pub fn encode(&mut self, headers: &[HeaderField]) -> Vec<u8> {
+        let mut output = Vec::new();
+        if let Some((minimum, size)) = self.pending_size_update.take() {
+            if minimum < size {
+                encode_integer(minimum, 5, 0x20, &mut output);
+            }
+            encode_integer(size, 5, 0x20, &mut output);
+        }
+
+        for (name, value) in headers {
+            let (index, name_index) = self.table.find(name, value);
+            if let Some(index) = index {
+                encode_integer(index, 7, 0x80, &mut output);
+                continue;
+            }
+
+            let never_indexed = NEVER_INDEXED.iter().any(|n| n.as_bytes() == &name[..]);
+            // テーブルの半分を超えるヘッダを入れると、他のエントリをほとんど追い出してしまう
+            let indexing = !never_indexed
+                && name.len() + value.len() + ENTRY_OVERHEAD <= self.table.max_size / 2;
+            let (prefix_bits, flags) = if indexing {
+                (6, 0x40)
+            } else if never_indexed {
+                (4, 0x10)
+            } else {
+                (4, 0x00)
+            };
+            match name_index {
+                Some(name_index) => encode_integer(name_index, prefix_bits, flags, &mut output),
+                None => {
+                    output.push(flags);
+                    encode_string(name, &mut output);
+                }
+            }
+            encode_string(value, &mut output);
+            if indexing {
+                self.table.insert(name.clone(), value.clone());
+            }
+        }
+        output
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+    use alloc::vec;
+
+    fn fields(headers: &[(&str, &str)]) -> Vec<HeaderField> {
+        headers
+            .iter()
+            .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_integer() {
+        // https://www.rfc-editor.org/rfc/rfc7541#appendix-C.1
+        for (value, prefix_bits, expected) in [
+            (10, 5, vec![0x0a]),
+            (1337, 5, vec![0x1f, 0x9a, 0x0a]),
+            (42, 8, vec![0x2a]),
+        ] {
+            let mut output = Vec::new();
+            encode_integer(value, prefix_bits, 0, &mut output);
+            assert_eq!(expected, output);
+            let mut position = 0;
+            assert_eq!(
+                Ok(value),
+                decode_integer(&output, &mut position, prefix_bits)
+            );
+            assert_eq!(output.len(), position);
+        }
+
+        assert!(decode_integer(&[0x1f, 0x9a], &mut 0, 5).is_err());
+        assert!(decode_integer(&[0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01], &mut 0, 5).is_err());
+    }
+
+    #[test]
+    fn test_decode_requests() {
+        let expected = [
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ]),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ]),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ]),
+        ];
+
+        // https://www.rfc-editor.org/rfc/rfc7541#appendix-C.3
+        let mut decoder = Decoder::new();
+        for (hex, expected) in [
+            "828684410f7777772e6578616d706c652e636f6d",
+            "828684be58086e6f2d6361636865",
+            "828785bf400a637573746f6d2d6b65790c637573746f6d2d76616c7565",
+        ]
+        .iter()
+        .zip(&expected)
+        {
+            assert_eq!(Ok(expected.clone()), decoder.decode(&from_hex(hex)));
+        }
+        assert_eq!(164, decoder.table.size);
+
+        // ハフマン符号を使う場合
+        // https://www.rfc-editor.org/rfc/rfc7541#appendix-C.4
+        let mut decoder = Decoder::new();
+        for (hex, expected) in [
+            "828684418cf1e3c2e5f23a6ba0ab90f4ff",
+            "828684be5886a8eb10649cbf",
+            "828785bf408825a849e95ba97d7f8925a849e95bb8e8b4bf",
+        ]
+        .iter()
+        .zip(&expected)
+        {
+            assert_eq!(Ok(expected.clone()), decoder.decode(&from_hex(hex)));
+        }
+
+        // 同じヘッダをエンコードすると、RFCの例と同じバイト列になる
+        let mut encoder = Encoder::new();
+        assert_eq!(
+            from_hex("828684418cf1e3c2e5f23a6ba0ab90f4ff"),
+            encoder.encode(&expected[0])
+        );
+        assert_eq!(
+            from_hex("828684be5886a8eb10649cbf"),
+            encoder.encode(&expected[1])
+        );
+    }
+
+    #[test]
+    fn test_decode_responses_with_eviction() {
+        // https://www.rfc-editor.org/rfc/rfc7541#appendix-C.5
+        let mut decoder = Decoder::with_max_table_size(256);
+        let first = decoder
+            .decode(&from_hex(
+                "4803333032580770726976617465611d4d6f6e2c203231204f637420323031332032303a31333a32\
+                 3120474d546e1768747470733a2f2f7777772e6578616d706c652e636f6d",
+            ))
+            .expect("failed to decode");
+        assert_eq!(
+            fields(&[
+                (":status", "302"),
+                ("cache-control", "private"),
+                ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                ("location", "https://www.example.com"),
+            ]),
+            first
+        );
+        assert_eq!(222, decoder.table.size);
+
+        // ":status: 307"を入れるために、最も古い":status: 302"を追い出す
+        let second = decoder
+            .decode(&from_hex("4803333037c1c0bf"))
+            .expect("failed to decode");
+        assert_eq!(
+            fields(&[
+                (":status", "307"),
+                ("cache-control", "private"),
+                ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                ("location", "https://www.example.com"),
+            ]),
+            second
+        );
+        assert_eq!(222, decoder.table.size);
+        assert_eq!(4, decoder.table.entries.len());
+    }
+
+    #[test]
+    fn test_encoder_roundtrip() {
+        let mut encoder = Encoder::new();
+        let mut decoder = Decoder::new();
+        let long_value = "x".repeat(3000);
+        let blocks = [
+            fields(&[
+                (":method", "GET"),
+                (":path", "/style.css"),
+                ("user-agent", "saba"),
+                ("authorization", "Basic c2FiYTpib29r"),
+            ]),
+            fields(&[
+                (":method", "GET"),
+                (":path", "/script.js"),
+                ("user-agent", "saba"),
+                ("authorization", "Basic c2FiYTpib29r"),
+                ("x-long", &long_value),
+            ]),
+        ];
+        for block in &blocks {
+            let encoded = encoder.encode(block);
+            assert_eq!(Ok(block.clone()), decoder.decode(&encoded));
+        }
+        // 2つ目のブロックでは、user-agentは動的テーブルのインデックスで送る
+        let encoded = encoder.encode(&fields(&[("user-agent", "saba")]));
+        assert_eq!(1, encoded.len());
+        // 資格情報と大きすぎるヘッダは動的テーブルに入れない
+        assert!(decoder
+            .table
+            .entries
+            .iter()
+            .all(|(name, _)| name != b"authorization" && name != b"x-long"));
+
+        // 動的テーブルを小さくした場合は、次のブロックの先頭で知らせる
+        encoder.set_max_table_size(0);
+        encoder.set_max_table_size(256);
+        let encoded = encoder.encode(&fields(&[("user-agent", "saba")]));
+        assert_eq!(vec![0x20, 0x3f, 0xe1, 0x01], encoded[..4].to_vec());
+        assert_eq!(
+            Ok(fields(&[("user-agent", "saba")])),
+            decoder.decode(&encoded)
+        );
+    }
+
+    #[test]
+    fn test_invalid_blocks() {
+        let mut decoder = Decoder::with_max_table_size(256);
+        for hex in [
+            // インデックス0
+            "80",     // 存在しないインデックス
+            "be",     // ヘッダの後の動的テーブルの大きさの変更
+            "8220",   // 上限を超える動的テーブルの大きさ
+            "3fe201", // 途中で切れた文字列
+            "400a6375",
+        ] {
+            assert!(decoder.decode(&from_hex(hex)).is_err(), "{}", hex);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod connection;
+pub mod frame;
+pub mod hpack;
+pub mod huffman;
+pub mod pool;
+
+use crate::error::Error;
+use alloc::format;
+
+/// ALPNでHTTP/2を選ぶためのプロトコルID。TLSを使わないh2cは、事前にサーバーが
+/// HTTP/2に対応していると知っている場合にだけ使う
+/// https://www.rfc-editor.org/rfc/rfc9113#section-3.2
+pub const ALPN_PROTOCOL: &str = "h2";
+
+/// HTTP/2の失敗はすべてネットワークのエラーとして扱う
+pub(crate) fn h2_error(message: &str) -> Error {
+    Error::Network(format!("http2: {}", message))
+}
@@ -0,0 +1,257 @@
+use crate::error::Error;
+use crate::http::h2::connection::H2Connection;
+use crate::http::h2::connection::StreamError;
+use crate::http::pool::is_idempotent;
+use crate::http::pool::Connection;
+use crate::http::request::HttpRequest;
+use crate::http::HttpResponse;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+struct Session<C: Connection> {
+    host: String,
+    port: u16,
+    connection: H2Connection<C>,
+}
+
+/// HTTP/2の接続を(ホスト, ポート)ごとに1つ保持する。HTTP/2では1つの接続で複数のリクエストを
+/// 送れるので、同じホストへの接続を増やさない
+/// https://www.rfc-editor.org/rfc/rfc9113#section-9.1
+pub struct SessionPool<C: Connection> {
+    sessions: Vec<Session<C>>,
+}
+
+impl<C: Connection> SessionPool<C> {
+    pub fn new() -> Self {
+        Self {
+            sessions: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// 保持している接続をすべて閉じる
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+
+    /// 新しいリクエストに使える接続があるか
+    pub fn contains(&self, host: &str, port: u16) -> bool {
+        self.sessions
+            .iter()
+            .any(|s| s.host == host && s.port == port && s.connection.is_usable())
+    }
+
+    /// 同じホストの接続がある場合は置き換える
+    pub fn insert(&mut self, host: &str, port: u16, connection: H2Connection<C>) {
+        self.sessions.retain(|s| s.host != host || s.port != port);
+        if connection.is_usable() {
+            self.sessions.push(Session {
+                host: host.to_string(),
+                port,
+                connection,
+            });
+        }
+    }
+
+    fn take(&mut self, host: &str, port: u16) -> Option<H2Connection<C>> {
+        let index = self
+            .sessions
+            .iter()
+            .position(|s| s.host == host && s.port == port)?;
+        let session = self.sessions.remove(index);
+        if session.connection.is_usable() {
+            Some(session.connection)
+        } else {
+            None
+        }
+    }
+
+    /// 保持している接続があればそれを使ってリクエストを送信する。サーバーが処理しなかった
+    /// リクエストは、新しい接続で1度だけ送り直す
+    /// https://www.rfc-editor.org/rfc/rfc9113#section-8.7
+    pub fn send<F>(
+        &mut self,
+        host: &str,
+        port: u16,
+        request: &HttpRequest,
+        mut connect: F,
+    ) -> Result<HttpResponse, Error>
+    where
+        F: FnMut() -> Result<H2Connection<C>, Error>,
+    {
+        let (mut connection, reused) = match self.take(host, port) {
+            Some(connection) => (connection, true),
+            None => (connect()?, false),
+        };
+
+        let result = match connection.send(request) {
+            Err(StreamError::Refused(_)) => {
+                connection = connect()?;
+                connection.send(request)
+            }
+            // アイドル中にサーバーが接続を閉じていた場合
+            Err(StreamError::Unanswered(_)) if reused && is_idempotent(&request.method()) => {
+                connection = connect()?;
+                connection.send(request)
+            }
+            result => result,
+        };
+
+        self.insert(host, port, connection);
+        result.map_err(Error::from)
+    }
+}
+
+impl<C: Connection> Default for SessionPool<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Connection> Debug for SessionPool<C> {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let sessions: Vec<(&String, u16)> =
+            self.sessions.iter().map(|s| (&s.host, s.port)).collect();
+        f.debug_struct("SessionPool")
+            .field("sessions", &sessions)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::h2::frame::ErrorCode;
+    use crate::http::h2::frame::Frame;
+    use crate::http::h2::hpack::Encoder;
+    use crate::url::Url;
+    use alloc::vec;
+
+    /// あらかじめ用意したフレームを返す接続。用意したフレームがなくなった場合は、サーバーが
+    /// 接続を閉じたとみなす
+    #[derive(Debug)]
+    struct MockConnection {
+        input: Vec<u8>,
+    }
+
+    impl Connection for MockConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            Ok(buf.len())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let length = buf.len().min(self.input.len());
+            buf[..length].copy_from_slice(&self.input[..length]);
+            self.input.drain(..length);
+            Ok(length)
+        }
+    }
+
+    fn connect(frames: &[Frame]) -> Result<H2Connection<MockConnection>, Error> {
+        let mut input = Vec::new();
+        for frame in frames {
+            frame.encode(&mut input);
+        }
+        H2Connection::connect(MockConnection { input })
+    }
+
+    fn settings() -> Frame {
+        Frame::Settings {
+            ack: false,
+            settings: Vec::new(),
+        }
+    }
+
+    fn ok(stream_id: u32) -> Frame {
+        let fields = vec![(b":status".to_vec(), b"200".to_vec())];
+        Frame::Headers {
+            stream_id,
+            header_block: Encoder::new().encode(&fields),
+            end_stream: true,
+            end_headers: true,
+            padding: 0,
+        }
+    }
+
+    fn request(method: &str) -> HttpRequest {
+        HttpRequest::new(
+            method,
+            Url::new("http://example.com/".to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    #[test]
+    fn test_reuse_session() {
+        let mut pool = SessionPool::new();
+        let mut connected = 0;
+        for _ in 0..2 {
+            let response = pool
+                .send("example.com", 80, &request("GET"), || {
+                    connected += 1;
+                    connect(&[settings(), ok(1), ok(3)])
+                })
+                .expect("failed to send");
+            assert_eq!(200, response.status_code());
+        }
+        assert_eq!(1, connected);
+        assert!(pool.contains("example.com", 80));
+        assert!(!pool.contains("example.com", 443));
+        pool.clear();
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_retry() {
+        // GOAWAYで処理されなかったリクエストは、POSTでも送り直す
+        let mut pool = SessionPool::new();
+        let mut connected = 0;
+        let goaway = Frame::GoAway {
+            last_stream_id: 0,
+            error_code: ErrorCode::NO_ERROR,
+            debug_data: Vec::new(),
+        };
+        pool.send("example.com", 80, &request("POST"), || {
+            connected += 1;
+            if connected == 1 {
+                connect(&[settings(), goaway.clone()])
+            } else {
+                connect(&[settings(), ok(1)])
+            }
+        })
+        .expect("failed to send");
+        assert_eq!(2, connected);
+
+        // 保持していた接続が閉じられていた場合は、冪等なリクエストだけを送り直す
+        assert!(pool.contains("example.com", 80));
+        let result = pool.send("example.com", 80, &request("POST"), || {
+            connected += 1;
+            connect(&[settings(), ok(1)])
+        });
+        assert!(result.is_err());
+        assert_eq!(2, connected);
+
+        pool.insert(
+            "example.com",
+            80,
+            connect(&[settings()]).expect("failed to connect"),
+        );
+        pool.send("example.com", 80, &request("GET"), || {
+            connected += 1;
+            connect(&[settings(), ok(1)])
+        })
+        .expect("failed to send");
+        assert_eq!(3, connected);
+    }
+}
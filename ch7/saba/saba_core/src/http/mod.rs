@@ -0,0 +1,592 @@
+pub mod cache;
+pub mod client;
+pub mod content_coding;
+pub mod content_disposition;
+pub mod cookie;
+pub mod date;
+pub mod framing;
+pub mod h2;
+pub mod pool;
+pub mod proxy;
+pub mod redirect;
+pub mod request;
+pub mod settings;
+
+use crate::alloc::string::ToString;
+use crate::encoding::decode;
+use crate::encoding::sniff::sniff;
+use crate::encoding::Encoding;
+use crate::error::Error;
+use crate::mime::MimeType;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone)]
+pub struct Header {
+    name: String,
+    value: String,
+}
+
+impl Header {
+    pub fn new(name: String, value: String) -> Self {
+        Self { name, value }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    version: String,
+    status_code: u16,
+    reason: String,
+    headers: Vec<Header>,
+    body: Vec<u8>,
+    trailers: Vec<Header>,
+    url: Option<Url>,
+}
+
+impl HttpResponse {
+    /// 空行より後ろはすべてボディとして扱う。Content-Lengthやチャンク形式に従ってメッセージを
+    /// 区切るには、framing::MessageReaderを使う
+    /// https://www.rfc-editor.org/rfc/rfc9112#section-2.1
+    pub fn new(raw_response: Vec<u8>) -> Result<Self, Error> {
+        // ステータス行の前にある空行は無視する
+        // https://www.rfc-editor.org/rfc/rfc9112#section-2.2
+        let start = raw_response
+            .iter()
+            .position(|b| *b != b'\r' && *b != b'\n')
+            .unwrap_or(raw_response.len());
+        let input = &raw_response[start..];
+
+        let (status_line, mut remaining) = match next_line(input) {
+            Some(line) => line,
+            None => {
+                return Err(Error::Network(format!(
+                    "invalid http response: missing status line: {:?}",
+                    String::from_utf8_lossy(input)
+                )))
+            }
+        };
+        let (version, status_code, reason) = parse_status_line(&isomorphic_decode(status_line))?;
+
+        let mut headers: Vec<Header> = Vec::new();
+        loop {
+            let (line, rest) = match next_line(remaining) {
+                Some(line) => line,
+                None => {
+                    return Err(Error::Network(format!(
+                        "invalid http response: header section is not terminated: {:?}",
+                        String::from_utf8_lossy(remaining)
+                    )))
+                }
+            };
+            remaining = rest;
+            let line = isomorphic_decode(line);
+
+            // 空行でヘッダの終わり
+            if line.is_empty() {
+                break;
+            }
+
+            // 空白で始まる行は、直前のヘッダの値の続き（obs-fold）なので、空白1つに置き換えて連結する
+            // https://www.rfc-editor.org/rfc/rfc9112#section-5.2
+            if line.starts_with([' ', '\t']) {
+                let last = match headers.last_mut() {
+                    Some(last) => last,
+                    None => {
+                        return Err(Error::Network(format!(
+                            "invalid http response: folded line without a header: {:?}",
+                            line
+                        )))
+                    }
+                };
+                let continuation = parse_field_value(&line)?;
+                if !continuation.is_empty() {
+                    if !last.value.is_empty() {
+                        last.value.push(' ');
+                    }
+                    last.value.push_str(continuation);
+                }
+                continue;
+            }
+
+            headers.push(parse_field_line(&line)?);
+        }
+
+        Ok(Self {
+            version,
+            status_code,
+            reason,
+            headers,
+            body: remaining.to_vec(),
+            trailers: Vec::new(),
+            url: None,
+        })
+    }
+
+    /// ネットワークを介さずにレスポンスを作る。data:やabout:などのスキームで使う
+    pub fn from_parts(status_code: u16, reason: &str, headers: Vec<Header>, body: Vec<u8>) -> Self {
+        Self {
+            version: "HTTP/1.1".to_string(),
+            status_code,
+            reason: reason.to_string(),
+            headers,
+            body,
+            trailers: Vec::new(),
+            url: None,
+        }
+    }
+
+    pub fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    pub fn reason(&self) -> String {
+        self.reason.clone()
+    }
+
+    pub fn headers(&self) -> Vec<Header> {
+        self.headers.clone()
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    /// ボディをコピーせずに、その長さを返す
+    pub fn body_len(&self) -> usize {
+        self.body.len()
+    }
+
+    /// 続きを受信したボディを追加する
+    pub fn extend_body(&mut self, bytes: &[u8]) {
+        self.body.extend_from_slice(bytes);
+    }
+
+    /// ボディを取り出し、レスポンスのボディを空にする
+    pub fn take_body(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.body)
+    }
+
+    /// チャンク形式のボディの後に送られてきたヘッダ
+    /// https://www.rfc-editor.org/rfc/rfc9112#section-7.1.2
+    pub fn trailers(&self) -> Vec<Header> {
+        self.trailers.clone()
+    }
+
+    /// BOM、Content-Typeヘッダのcharset、<meta>の順に調べたボディのエンコーディング
+    pub fn encoding(&self) -> Encoding {
+        sniff(
+            &self.body,
+            self.header_value("Content-Type").ok().as_deref(),
+        )
+    }
+
+    /// ボディを文字列にデコードする
+    pub fn text(&self) -> String {
+        decode(&self.body, self.encoding())
+    }
+
+    /// Content-Typeヘッダの値。ない場合や正しくない場合はNone
+    /// https://fetch.spec.whatwg.org/#concept-header-extract-mime-type
+    pub fn mime_type(&self) -> Option<MimeType> {
+        self.header_value("Content-Type")
+            .ok()
+            .and_then(|value| MimeType::parse(&value))
+    }
+
+    /// Content-Typeヘッダとボディの先頭から決めたMIMEタイプ
+    /// https://mimesniff.spec.whatwg.org/#computed-mime-type
+    pub fn computed_mime_type(&self) -> MimeType {
+        crate::mime::sniff::sniff(
+            &self.body,
+            self.header_value("Content-Type").ok().as_deref(),
+            self.no_sniff(),
+        )
+    }
+
+    /// X-Content-Type-Options: nosniffが指定されているか
+    /// https://fetch.spec.whatwg.org/#determine-nosniff
+    fn no_sniff(&self) -> bool {
+        match self.header_value("X-Content-Type-Options") {
+            Ok(value) => value
+                .split(',')
+                .next()
+                .map(|v| v.trim().eq_ignore_ascii_case("nosniff"))
+                .unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// レスポンスを取得したURL。リダイレクトされた場合は最終的なURL
+    /// https://fetch.spec.whatwg.org/#concept-response-url
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    pub fn set_url(&mut self, url: Url) {
+        self.url = Some(url);
+    }
+
+    /// ヘッダの名前は大文字と小文字を区別しない
+    /// https://www.rfc-editor.org/rfc/rfc9110#section-5.1
+    pub fn header_value(&self, name: &str) -> Result<String, String> {
+        for h in &self.headers {
+            if h.name.eq_ignore_ascii_case(name) {
+                return Ok(h.value.clone());
+            }
+        }
+
+        Err(format!("failed to find {} in headers", name))
+    }
+
+    /// 同じ名前のヘッダが複数ある場合に、すべての値を返す
+    pub fn header_values(&self, name: &str) -> Vec<String> {
+        self.headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+            .collect()
+    }
+}
+
+/// 行末のCRLFを取り除いた1行と、残りのバイト列を返す。LFのみの行末も受け付ける
+/// https://www.rfc-editor.org/rfc/rfc9112#section-2.2
+fn next_line(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = input.iter().position(|b| *b == b'\n')?;
+    let line = &input[..index];
+    Some((
+        line.strip_suffix(b"\r").unwrap_or(line),
+        &input[index + 1..],
+    ))
+}
+
+/// ヘッダの値のASCII以外のバイト（obs-text）は、そのままU+0080〜U+00FFとして扱う
+/// https://infra.spec.whatwg.org/#isomorphic-decode
+fn isomorphic_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// https://www.rfc-editor.org/rfc/rfc9112#section-2.3
+fn is_http_version(version: &str) -> bool {
+    match version.strip_prefix("HTTP/") {
+        Some(number) => {
+            let bytes = number.as_bytes();
+            bytes.len() == 3
+                && bytes[0].is_ascii_digit()
+                && bytes[1] == b'.'
+                && bytes[2].is_ascii_digit()
+        }
+        None => false,
+    }
+}
+
+/// https://www.rfc-editor.org/rfc/rfc9110#section-5.6.2
+pub(crate) fn is_tchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+        || matches!(
+            byte,
+            b'!' | b'#'
+                | b'$'
+                | b'%'
+                | b'&'
+                | b'\''
+                | b'*'
+                | b'+'
+                | b'-'
+                | b'.'
+                | b'^'
+                | b'_'
+                | b'`'
+                | b'|'
+                | b'~'
+        )
+}
+
+/// 水平タブ以外の制御文字を含んでいないか
+fn has_invalid_control(input: &str) -> bool {
+    input.chars().any(|c| c.is_ascii_control() && c != '\t')
+}
+
+/// 理由句は省略されることがあるので、"HTTP/1.1 200"のようなステータス行も受け付ける
+/// https://www.rfc-editor.org/rfc/rfc9112#section-4
+fn parse_status_line(line: &str) -> Result<(String, u16, String), Error> {
+    let invalid = || Error::Network(format!("invalid http response status line: {:?}", line));
+
+    let (version, rest) = line.split_once(' ').ok_or_else(invalid)?;
+    if !is_http_version(version) {
+        return Err(invalid());
+    }
+
+    let (status_code, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+    if status_code.len() != 3 || !status_code.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let status_code = status_code.parse::<u16>().map_err(|_| invalid())?;
+
+    if has_invalid_control(reason) {
+        return Err(invalid());
+    }
+
+    Ok((version.to_string(), status_code, reason.to_string()))
+}
+
+/// 前後の空白を取り除いたヘッダの値を返す
+/// https://www.rfc-editor.org/rfc/rfc9112#section-5.1
+fn parse_field_value(value: &str) -> Result<&str, Error> {
+    if has_invalid_control(value) {
+        return Err(Error::Network(format!(
+            "invalid http response header value: {:?}",
+            value
+        )));
+    }
+    Ok(value.trim_matches([' ', '\t']))
+}
+
+/// ヘッダの名前とコロンの間に空白があるものは不正とする
+/// https://www.rfc-editor.org/rfc/rfc9112#section-5
+fn parse_field_line(line: &str) -> Result<Header, Error> {
+    let (name, value) = match line.split_once(':') {
+        Some(field) => field,
+        None => {
+            return Err(Error::Network(format!(
+                "invalid http response header: missing colon: {:?}",
+                line
+            )))
+        }
+    };
+
+    if name.is_empty() || !name.bytes().all(is_tchar) {
+        return Err(Error::Network(format!(
+            "invalid http response header name: {:?}",
+            name
+        )));
+    }
+
+    Ok(Header::new(
+        name.to_string(),
+        parse_field_value(value)?.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_invalid() {
+        let raw = "HTTP/1.1 200 OK".to_string();
+        assert!(HttpResponse::new(raw.into_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_status_line_only() {
+        let raw = "HTTP/1.1 200 OK\n\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+    }
+
+    #[test]
+    fn test_one_header() {
+        let raw = "HTTP/1.1 200 OK\nDate:xx xx xx\n\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+
+        assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
+    }
+
+    #[test]
+    fn test_two_headers_with_white_space() {
+        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\nContent-Length: 42\n\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+
+        assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
+        assert_eq!(res.header_value("Content-Length"), Ok("42".to_string()));
+    }
+
+    #[test]
+    fn test_body() {
+        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\n\nbody message".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+
+        assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
+
+        assert_eq!(res.body(), b"body message".to_vec());
+    }
+
+    #[test]
+    fn test_reason_phrase_with_spaces() {
+        let raw = "HTTP/1.1 404 Not Found\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(404, res.status_code());
+        assert_eq!("Not Found".to_string(), res.reason());
+    }
+
+    #[test]
+    fn test_empty_reason_phrase() {
+        for raw in ["HTTP/1.1 200\r\n\r\n", "HTTP/1.1 200 \r\n\r\n"] {
+            let res =
+                HttpResponse::new(raw.as_bytes().to_vec()).expect("failed to parse http response");
+            assert_eq!(200, res.status_code());
+            assert_eq!("".to_string(), res.reason());
+        }
+    }
+
+    #[test]
+    fn test_leading_empty_lines() {
+        let raw = "\r\n\r\nHTTP/1.1 200 OK\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(200, res.status_code());
+    }
+
+    #[test]
+    fn test_header_value_trimming() {
+        let raw = "HTTP/1.1 200 OK\r\nX-Test:\t a  b \t\r\nX-Empty:\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(res.header_value("X-Test"), Ok("a  b".to_string()));
+        assert_eq!(res.header_value("X-Empty"), Ok("".to_string()));
+    }
+
+    #[test]
+    fn test_header_name_is_case_insensitive() {
+        let raw = "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(
+            res.header_value("Content-Type"),
+            Ok("text/html".to_string())
+        );
+    }
+
+    #[test]
+    fn test_obs_fold() {
+        let raw = "HTTP/1.1 200 OK\r\nX-Folded: first\r\n  second\r\n\tthird\r\nDate: xx\r\n\r\n"
+            .to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(
+            res.header_value("X-Folded"),
+            Ok("first second third".to_string())
+        );
+        assert_eq!(res.header_value("Date"), Ok("xx".to_string()));
+        assert_eq!(2, res.headers().len());
+    }
+
+    #[test]
+    fn test_body_is_not_modified() {
+        let raw = "HTTP/1.1 200 OK\r\n\r\nline1\r\nline2\r\n\r\n".to_string();
+        let res = HttpResponse::new(raw.into_bytes()).expect("failed to parse http response");
+        assert_eq!(b"line1\r\nline2\r\n\r\n".to_vec(), res.body());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let cases = [
+            "",
+            "\r\n",
+            "HTTP/1.1 200 OK\r\n",
+            "HTTP/1.1 200 OK\r\nDate: xx\r\n",
+            "HTTP/1.1\r\n\r\n",
+            "HTTP/1.1 20 OK\r\n\r\n",
+            "HTTP/1.1 2000 OK\r\n\r\n",
+            "HTTP/1.1 abc OK\r\n\r\n",
+            "HTTP/1.1 -20 OK\r\n\r\n",
+            "HTTP/1.1  200 OK\r\n\r\n",
+            "HTTX/1.1 200 OK\r\n\r\n",
+            "HTTP/11 200 OK\r\n\r\n",
+            "HTTP/1.1 200 O\u{0}K\r\n\r\n",
+            "<html>\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nDate\r\n\r\n",
+            "HTTP/1.1 200 OK\r\n: xx\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nDate : xx\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nX Test: xx\r\n\r\n",
+            "HTTP/1.1 200 OK\r\n folded\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nX-Test: a\rb\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nX-Test: a\r\n b\u{7f}\r\n\r\n",
+        ];
+        for raw in cases {
+            match HttpResponse::new(raw.as_bytes().to_vec()) {
+                Err(Error::Network(_)) => {}
+                other => panic!(
+                    "expected a network error for {:?}, but got {:?}",
+                    raw, other
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_body() {
+        let mut raw = b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[0x89, 0x50, 0x4E, 0x47, 0x00, 0xFF]);
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(vec![0x89, 0x50, 0x4E, 0x47, 0x00, 0xFF], res.body());
+    }
+
+    #[test]
+    fn test_non_ascii_header_value() {
+        let raw = b"HTTP/1.1 200 OK\r\nX-Test: caf\xE9\r\n\r\n".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.header_value("X-Test"), Ok("caf\u{E9}".to_string()));
+    }
+
+    #[test]
+    fn test_text_with_charset_header() {
+        // "日本語"をShift_JISで符号化したもの
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=Shift_JIS\r\n\r\n\x93\xFA\x96\x7B\x8C\xEA".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(Encoding::ShiftJis, res.encoding());
+        assert_eq!("日本語".to_string(), res.text());
+    }
+
+    #[test]
+    fn test_text_with_meta_charset() {
+        // "日本語"をEUC-JPで符号化したもの
+        let raw = b"HTTP/1.1 200 OK\r\n\r\n<meta charset=euc-jp>\xC6\xFC\xCB\xDC\xB8\xEC".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(Encoding::EucJp, res.encoding());
+        assert_eq!("<meta charset=euc-jp>日本語".to_string(), res.text());
+    }
+
+    #[test]
+    fn test_computed_mime_type() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n\x00\x01".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(Some(MimeType::new("text", "plain")), res.mime_type());
+        assert_eq!(
+            "application/octet-stream",
+            res.computed_mime_type().essence()
+        );
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nX-Content-Type-Options: NoSniff, foo\r\n\r\n\x00\x01".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!("text/plain", res.computed_mime_type().essence());
+
+        let raw = b"HTTP/1.1 200 OK\r\n\r\n<html>".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(None, res.mime_type());
+        assert_eq!("text/html", res.computed_mime_type().essence());
+    }
+}
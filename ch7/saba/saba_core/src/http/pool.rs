@@ -0,0 +1,725 @@
+use crate::error::Error;
+use crate::http::client::BodyReader;
+use crate::http::framing::body_length;
+use crate::http::framing::BodyLength;
+use crate::http::framing::MessageReader;
+use crate::http::HttpResponse;
+use alloc::rc::Rc;
+use alloc::rc::Weak;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// ブラウザが同じホストに同時に張る接続の数の慣習に合わせる
+const MAX_IDLE_CONNECTIONS_PER_HOST: usize = 6;
+const MAX_IDLE_CONNECTIONS: usize = 32;
+
+/// TCPなど、バイト列を送受信できるOSの接続を抽象化する
+pub trait Connection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error>;
+    /// 接続が閉じられている場合は0を返す
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// https://www.rfc-editor.org/rfc/rfc9110#section-9.2.2
+pub fn is_idempotent(method: &str) -> bool {
+    matches!(
+        method,
+        "GET" | "HEAD" | "OPTIONS" | "TRACE" | "PUT" | "DELETE"
+    )
+}
+
+/// レスポンスを受け取った後も接続を使い続けられるか
+/// https://www.rfc-editor.org/rfc/rfc9112#section-9.3
+pub fn is_persistent(response: &HttpResponse) -> bool {
+    let options: Vec<String> = response
+        .header_values("Connection")
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(|option| option.trim_matches([' ', '\t']).to_ascii_lowercase())
+        .collect();
+
+    if options.iter().any(|option| option == "close") {
+        return false;
+    }
+    if response.version() == "HTTP/1.0" {
+        return options.iter().any(|option| option == "keep-alive");
+    }
+    true
+}
+
+/// リクエストとレスポンスのやりとりの失敗
+enum ExchangeError {
+    /// レスポンスを1バイトも受信しないうちに、接続が閉じられたかリセットされた。アイドル中に
+    /// サーバーが接続を閉じていた場合に起きる
+    Closed(Error),
+    /// タイムアウトなど、送り直しても解決しない失敗
+    Other(Error),
+}
+
+impl ExchangeError {
+    /// 接続が閉じられたことを表すエラーだけをClosedにする
+    fn from_io(error: Error) -> Self {
+        match error {
+            Error::ConnectionReset(_) => ExchangeError::Closed(error),
+            _ => ExchangeError::Other(error),
+        }
+    }
+}
+
+/// リクエストを送信し、レスポンスのヘッダまでを受信する。ボディの続きは返したreaderに
+/// 受信したバイト列を渡して読む
+fn exchange_head<C: Connection>(
+    connection: &mut C,
+    method: &str,
+    request: &[u8],
+) -> Result<(HttpResponse, MessageReader), ExchangeError> {
+    let mut written = 0;
+    while written < request.len() {
+        match connection.write(&request[written..]) {
+            Ok(0) => {
+                return Err(ExchangeError::Closed(Error::Network(
+                    "connection closed while sending a request".to_string(),
+                )))
+            }
+            Ok(bytes) => written += bytes,
+            Err(e) => return Err(ExchangeError::from_io(e)),
+        }
+    }
+
+    let mut reader = MessageReader::new(method);
+    let mut received_any = false;
+    loop {
+        let mut buf = [0u8; 4096];
+        let bytes_read = match connection.read(&mut buf) {
+            Ok(bytes) => bytes,
+            Err(e) if received_any => return Err(ExchangeError::Other(e)),
+            Err(e) => return Err(ExchangeError::from_io(e)),
+        };
+
+        if bytes_read == 0 && !received_any {
+            return Err(ExchangeError::Closed(Error::Network(
+                "connection closed before receiving a response".to_string(),
+            )));
+        }
+        received_any = true;
+
+        reader.push(&buf[..bytes_read]);
+        match reader.take_head(/*closed=*/ bytes_read == 0) {
+            Ok(Some(head)) => return Ok((head, reader)),
+            Ok(None) if bytes_read == 0 => {
+                return Err(ExchangeError::Other(Error::Network(
+                    "connection closed before receiving a response".to_string(),
+                )))
+            }
+            Ok(None) => {}
+            Err(e) => return Err(ExchangeError::Other(e)),
+        }
+    }
+}
+
+/// プールの接続があればそれを使ってリクエストを送信し、レスポンスのヘッダを受信する。
+/// プールの接続がサーバーに閉じられていた場合、冪等なリクエストは新しい接続で1度だけ
+/// 送り直す。タイムアウトは送り直さずに返す
+/// https://www.rfc-editor.org/rfc/rfc9112#section-9.3.1
+fn start_exchange<C, F>(
+    pooled: Option<C>,
+    method: &str,
+    request: &[u8],
+    mut connect: F,
+) -> Result<(HttpResponse, ResponseBody<C>), Error>
+where
+    C: Connection,
+    F: FnMut() -> Result<C, Error>,
+{
+    let reused = pooled.is_some();
+    let mut connection = match pooled {
+        Some(connection) => connection,
+        None => connect()?,
+    };
+
+    let result = match exchange_head(&mut connection, method, request) {
+        Err(ExchangeError::Closed(_)) if reused && is_idempotent(method) => {
+            connection = connect()?;
+            exchange_head(&mut connection, method, request)
+        }
+        result => result,
+    };
+
+    match result {
+        Ok((head, reader)) => {
+            // ボディの終わりが接続の終わりで決まる場合は使い続けない
+            let persistent = is_persistent(&head)
+                && body_length(method, &head).ok() != Some(BodyLength::UntilClose);
+            Ok((
+                head,
+                ResponseBody {
+                    connection,
+                    reader,
+                    persistent,
+                    closed: false,
+                    complete: false,
+                },
+            ))
+        }
+        Err(ExchangeError::Closed(e)) | Err(ExchangeError::Other(e)) => Err(e),
+    }
+}
+
+/// ヘッダを受信した後、ボディを受信している接続
+struct ResponseBody<C> {
+    connection: C,
+    reader: MessageReader,
+    /// ボディを読み終えた後も接続を使い続けられるか
+    persistent: bool,
+    closed: bool,
+    complete: bool,
+}
+
+impl<C: Connection> ResponseBody<C> {
+    /// 受信したボディのバイト列を返す。ボディが終わった後はNoneを返す
+    fn read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            if self.complete {
+                return Ok(None);
+            }
+            let (body, complete) = self.reader.take_body(self.closed)?;
+            self.complete = complete;
+            if !body.is_empty() {
+                return Ok(Some(body));
+            }
+            if complete {
+                return Ok(None);
+            }
+
+            let mut buf = [0u8; 4096];
+            let bytes_read = self.connection.read(&mut buf)?;
+            if bytes_read == 0 {
+                self.closed = true;
+            }
+            self.reader.push(&buf[..bytes_read]);
+        }
+    }
+
+    /// ボディを読み終えて、接続を使い続けられる場合は接続を返す。余分なバイト列を受信している
+    /// 場合は使い続けない
+    fn into_reusable(self) -> Option<C> {
+        if self.complete && self.persistent && !self.closed && self.reader.is_empty() {
+            Some(self.connection)
+        } else {
+            None
+        }
+    }
+}
+
+/// プールから取り出した接続で受信しているボディ。ボディを読み終えた接続はプールに戻す
+pub struct PooledBody<C: Connection> {
+    pool: Weak<RefCell<ConnectionPool<C>>>,
+    host: String,
+    port: u16,
+    body: Option<ResponseBody<C>>,
+}
+
+impl<C: Connection> PooledBody<C> {
+    fn release(&mut self) {
+        let connection = match self.body.take().and_then(ResponseBody::into_reusable) {
+            Some(connection) => connection,
+            None => return,
+        };
+        if let Some(pool) = self.pool.upgrade() {
+            pool.borrow_mut().put(&self.host, self.port, connection);
+        }
+    }
+}
+
+impl<C: Connection> BodyReader for PooledBody<C> {
+    fn read(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let body = match self.body.as_mut() {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        match body.read() {
+            Ok(bytes) => {
+                if body.complete {
+                    self.release();
+                }
+                Ok(bytes)
+            }
+            Err(e) => {
+                self.body = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<C: Connection> Debug for PooledBody<C> {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.debug_struct("PooledBody")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("complete", &self.body.is_none())
+            .finish()
+    }
+}
+
+struct IdleConnection<C> {
+    host: String,
+    port: u16,
+    connection: C,
+}
+
+/// 使い終わった接続を(ホスト, ポート)ごとに保持し、次のリクエストで再利用する
+pub struct ConnectionPool<C: Connection> {
+    /// 古いものから順に並べる
+    idle: Vec<IdleConnection<C>>,
+    max_idle_per_host: usize,
+    max_idle: usize,
+}
+
+impl<C: Connection> ConnectionPool<C> {
+    pub fn new() -> Self {
+        Self::with_limits(MAX_IDLE_CONNECTIONS_PER_HOST, MAX_IDLE_CONNECTIONS)
+    }
+
+    pub fn with_limits(max_idle_per_host: usize, max_idle: usize) -> Self {
+        Self {
+            idle: Vec::new(),
+            max_idle_per_host,
+            max_idle,
+        }
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.idle.len()
+    }
+
+    /// 保持している接続をすべて閉じる
+    pub fn clear(&mut self) {
+        self.idle.clear();
+    }
+
+    pub fn contains(&self, host: &str, port: u16) -> bool {
+        self.idle
+            .iter()
+            .any(|idle| idle.host == host && idle.port == port)
+    }
+
+    /// 最も最近使われた接続を取り出す
+    pub fn take(&mut self, host: &str, port: u16) -> Option<C> {
+        let index = self
+            .idle
+            .iter()
+            .rposition(|idle| idle.host == host && idle.port == port)?;
+        Some(self.idle.remove(index).connection)
+    }
+
+    /// 上限を超えた場合は、最も古い接続を閉じる
+    pub fn put(&mut self, host: &str, port: u16, connection: C) {
+        if self.max_idle_per_host == 0 || self.max_idle == 0 {
+            return;
+        }
+
+        let same_host = self
+            .idle
+            .iter()
+            .filter(|idle| idle.host == host && idle.port == port)
+            .count();
+        if same_host >= self.max_idle_per_host {
+            if let Some(index) = self
+                .idle
+                .iter()
+                .position(|idle| idle.host == host && idle.port == port)
+            {
+                self.idle.remove(index);
+            }
+        }
+        if self.idle.len() >= self.max_idle {
+            self.idle.remove(0);
+        }
+
+        self.idle.push(IdleConnection {
+            host: host.to_string(),
+            port,
+            connection,
+        });
+    }
+
+    /// プールの接続があればそれを使ってリクエストを送信し、ボディまで受信する
+    pub fn send<F>(
+        &mut self,
+        host: &str,
+        port: u16,
+        method: &str,
+        request: &[u8],
+        connect: F,
+    ) -> Result<HttpResponse, Error>
+    where
+        F: FnMut() -> Result<C, Error>,
+    {
+        let pooled = self.take(host, port);
+        let (mut response, mut body) = start_exchange(pooled, method, request, connect)?;
+        while let Some(bytes) = body.read()? {
+            response.body.extend_from_slice(&bytes);
+        }
+        if let Some(connection) = body.into_reusable() {
+            self.put(host, port, connection);
+        }
+        Ok(response)
+    }
+
+    /// send()と同じようにリクエストを送信し、ヘッダを受信した時点でレスポンスを返す。ボディは
+    /// 返したPooledBodyから受信した分ずつ読み、読み終えた接続はプールに戻す
+    pub fn open<F>(
+        pool: &Rc<RefCell<Self>>,
+        host: &str,
+        port: u16,
+        method: &str,
+        request: &[u8],
+        connect: F,
+    ) -> Result<(HttpResponse, PooledBody<C>), Error>
+    where
+        F: FnMut() -> Result<C, Error>,
+    {
+        let pooled = pool.borrow_mut().take(host, port);
+        let (response, body) = start_exchange(pooled, method, request, connect)?;
+        Ok((
+            response,
+            PooledBody {
+                pool: Rc::downgrade(pool),
+                host: host.to_string(),
+                port,
+                body: Some(body),
+            },
+        ))
+    }
+}
+
+impl<C: Connection> Default for ConnectionPool<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Connection> Debug for ConnectionPool<C> {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let idle: Vec<(&String, u16)> = self
+            .idle
+            .iter()
+            .map(|idle| (&idle.host, idle.port))
+            .collect();
+        f.debug_struct("ConnectionPool")
+            .field("idle", &idle)
+            .field("max_idle_per_host", &self.max_idle_per_host)
+            .field("max_idle", &self.max_idle)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+
+    /// 接続のIDと、その接続で送信されたバイト列
+    type SentRequests = Rc<RefCell<Vec<(usize, Vec<u8>)>>>;
+
+    /// 送信されたリクエストを記録し、あらかじめ用意したレスポンスを返す接続
+    #[derive(Debug, Default)]
+    struct MockConnection {
+        id: usize,
+        responses: VecDeque<Vec<u8>>,
+        sent: SentRequests,
+        /// 用意したレスポンスがなくなった後の読み込みで返すエラー
+        read_error: Option<Error>,
+    }
+
+    impl Connection for MockConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.sent.borrow_mut().push((self.id, buf.to_vec()));
+            Ok(buf.len())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            // 用意したレスポンスがなくなった場合は、サーバーが接続を閉じたとみなす
+            let response = match self.responses.front_mut() {
+                Some(response) => response,
+                None => {
+                    return match &self.read_error {
+                        Some(e) => Err(e.clone()),
+                        None => Ok(0),
+                    }
+                }
+            };
+            let length = response.len().min(buf.len());
+            buf[..length].copy_from_slice(&response[..length]);
+            response.drain(..length);
+            if response.is_empty() {
+                self.responses.pop_front();
+            }
+            Ok(length)
+        }
+    }
+
+    const OK: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+    struct Server {
+        sent: SentRequests,
+        connected: usize,
+    }
+
+    impl Server {
+        fn new() -> Self {
+            Self {
+                sent: Rc::new(RefCell::new(Vec::new())),
+                connected: 0,
+            }
+        }
+
+        fn connect(&mut self, responses: &[&[u8]]) -> Result<MockConnection, Error> {
+            self.connected += 1;
+            Ok(MockConnection {
+                id: self.connected,
+                responses: responses.iter().map(|r| r.to_vec()).collect(),
+                sent: self.sent.clone(),
+                read_error: None,
+            })
+        }
+
+        /// リクエストを送信した接続のIDの一覧
+        fn used_connections(&self) -> Vec<usize> {
+            self.sent.borrow().iter().map(|(id, _)| *id).collect()
+        }
+    }
+
+    #[test]
+    fn test_reuse_connection() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::new();
+
+        for _ in 0..3 {
+            let response = pool
+                .send("example.com", 80, "GET", b"GET / HTTP/1.1\r\n\r\n", || {
+                    server.connect(&[OK, OK, OK])
+                })
+                .expect("failed to send a request");
+            assert_eq!(b"ok".to_vec(), response.body());
+        }
+
+        assert_eq!(vec![1, 1, 1], server.used_connections());
+        assert_eq!(1, pool.idle_count());
+        assert!(pool.contains("example.com", 80));
+        assert!(!pool.contains("example.com", 8080));
+    }
+
+    #[test]
+    fn test_open_reads_body_as_received() {
+        let mut server = Server::new();
+        let pool = Rc::new(RefCell::new(ConnectionPool::new()));
+        let head: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello";
+        let rest: &[u8] = b", saba";
+
+        let (response, mut body) = ConnectionPool::open(
+            &pool,
+            "example.com",
+            80,
+            "GET",
+            b"GET / HTTP/1.1\r\n\r\n",
+            || server.connect(&[head, rest, OK]),
+        )
+        .expect("failed to open");
+        assert_eq!(200, response.status_code());
+        assert!(response.body().is_empty());
+
+        assert_eq!(
+            Some(b"hello".to_vec()),
+            body.read().expect("failed to read")
+        );
+        // ボディを読み終えるまでは、接続をプールに戻さない
+        assert_eq!(0, pool.borrow().idle_count());
+        assert_eq!(
+            Some(b", saba".to_vec()),
+            body.read().expect("failed to read")
+        );
+        assert_eq!(1, pool.borrow().idle_count());
+        assert_eq!(None, body.read().expect("failed to read"));
+
+        let response = pool
+            .borrow_mut()
+            .send("example.com", 80, "GET", b"GET / HTTP/1.1\r\n\r\n", || {
+                server.connect(&[OK])
+            })
+            .expect("failed to send a request");
+        assert_eq!(b"ok".to_vec(), response.body());
+        assert_eq!(vec![1, 1], server.used_connections());
+    }
+
+    #[test]
+    fn test_connection_close() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::new();
+        let close: &[u8] = b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok";
+        let until_close: &[u8] = b"HTTP/1.1 200 OK\r\n\r\nok";
+        let http10: &[u8] = b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+        for response in [close, until_close, http10] {
+            pool.send("example.com", 80, "GET", b"GET / HTTP/1.1\r\n\r\n", || {
+                server.connect(&[response])
+            })
+            .expect("failed to send a request");
+            assert_eq!(0, pool.idle_count());
+        }
+        assert_eq!(vec![1, 2, 3], server.used_connections());
+    }
+
+    #[test]
+    fn test_retry_idempotent_request_on_closed_connection() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::new();
+
+        // 1つ目の接続は、レスポンスを1つ返した後にサーバーに閉じられる
+        pool.send("example.com", 80, "GET", b"GET /a HTTP/1.1\r\n\r\n", || {
+            server.connect(&[OK])
+        })
+        .expect("failed to send a request");
+        assert_eq!(1, pool.idle_count());
+
+        let response = pool
+            .send("example.com", 80, "GET", b"GET /b HTTP/1.1\r\n\r\n", || {
+                server.connect(&[OK])
+            })
+            .expect("failed to retry a request");
+        assert_eq!(200, response.status_code());
+        assert_eq!(vec![1, 1, 2], server.used_connections());
+    }
+
+    #[test]
+    fn test_retry_on_reset_but_not_on_timeout() {
+        for (error, retried) in [
+            (Error::ConnectionReset("reset by peer".to_string()), true),
+            (Error::Timeout("timed out".to_string()), false),
+        ] {
+            let mut server = Server::new();
+            let mut pool = ConnectionPool::new();
+            let mut first = server.connect(&[OK]).expect("connect");
+            first.read_error = Some(error.clone());
+            let mut connection = Some(first);
+            pool.send("example.com", 80, "GET", b"GET /a HTTP/1.1\r\n\r\n", || {
+                Ok(connection.take().expect("connected twice"))
+            })
+            .expect("failed to send a request");
+
+            let result = pool.send("example.com", 80, "GET", b"GET /b HTTP/1.1\r\n\r\n", || {
+                server.connect(&[OK])
+            });
+            if retried {
+                assert_eq!(200, result.expect("failed to retry").status_code());
+                assert_eq!(vec![1, 1, 2], server.used_connections());
+            } else {
+                // タイムアウトは送り直さずに、そのまま返す
+                assert_eq!(Err(error), result.map(|r| r.status_code()));
+                assert_eq!(vec![1, 1], server.used_connections());
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_retry_for_non_idempotent_request() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::new();
+
+        pool.send("example.com", 80, "GET", b"GET / HTTP/1.1\r\n\r\n", || {
+            server.connect(&[OK])
+        })
+        .expect("failed to send a request");
+
+        let result = pool.send(
+            "example.com",
+            80,
+            "POST",
+            b"POST / HTTP/1.1\r\n\r\n",
+            || server.connect(&[OK]),
+        );
+        assert!(result.is_err());
+        assert_eq!(vec![1, 1], server.used_connections());
+    }
+
+    #[test]
+    fn test_no_retry_for_new_connection() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::new();
+
+        let result = pool.send("example.com", 80, "GET", b"GET / HTTP/1.1\r\n\r\n", || {
+            server.connect(&[])
+        });
+        assert!(result.is_err());
+        assert_eq!(vec![1], server.used_connections());
+    }
+
+    #[test]
+    fn test_pool_is_keyed_by_host_and_port() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::new();
+
+        pool.put("example.com", 80, server.connect(&[]).expect("connect"));
+        pool.put("example.com", 8080, server.connect(&[]).expect("connect"));
+
+        assert!(pool.take("example.org", 80).is_none());
+        assert_eq!(
+            Some(2),
+            pool.take("example.com", 8080)
+                .map(|connection| connection.id)
+        );
+        assert_eq!(
+            Some(1),
+            pool.take("example.com", 80).map(|connection| connection.id)
+        );
+        assert_eq!(0, pool.idle_count());
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let mut server = Server::new();
+        let mut pool = ConnectionPool::with_limits(2, 3);
+
+        for _ in 0..3 {
+            pool.put("a.test", 80, server.connect(&[]).expect("connect"));
+        }
+        // ホストごとの上限を超えたので、最も古い接続を閉じる
+        assert_eq!(2, pool.idle_count());
+
+        pool.put("b.test", 80, server.connect(&[]).expect("connect"));
+        pool.put("c.test", 80, server.connect(&[]).expect("connect"));
+        // 全体の上限を超えたので、最も古い接続を閉じる
+        assert_eq!(3, pool.idle_count());
+        assert_eq!(Some(3), pool.take("a.test", 80).map(|c| c.id));
+        assert!(pool.take("a.test", 80).is_none());
+    }
+
+    #[test]
+    fn test_is_persistent() {
+        let cases: [(&[u8], bool); 5] = [
+            (b"HTTP/1.1 200 OK\r\n\r\n", true),
+            (b"HTTP/1.1 200 OK\r\nConnection: Close\r\n\r\n", false),
+            (
+                b"HTTP/1.1 200 OK\r\nConnection: upgrade, close\r\n\r\n",
+                false,
+            ),
+            (b"HTTP/1.0 200 OK\r\n\r\n", false),
+            (b"HTTP/1.0 200 OK\r\nConnection: keep-alive\r\n\r\n", true),
+        ];
+        for (raw, expected) in cases {
+            let response = HttpResponse::new(raw.to_vec()).expect("failed to parse");
+            assert_eq!(
+                expected,
+                is_persistent(&response),
+                "{}",
+                String::from_utf8_lossy(raw)
+            );
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use crate::error::Error;
+use crate::http::cookie::CookieJar;
+use crate::http::request::HttpRequest;
+use crate::http::HttpResponse;
+use crate::url::Url;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// https://fetch.spec.whatwg.org/#http-redirect-fetch
+const DEFAULT_MAX_REDIRECTS: usize = 20;
+
+/// メソッドがGETに変わるときに取り除く、リクエストのボディについてのヘッダ
+/// https://fetch.spec.whatwg.org/#request-body-header-name
+const REQUEST_BODY_HEADER_NAMES: [&str; 5] = [
+    "Content-Encoding",
+    "Content-Language",
+    "Content-Location",
+    "Content-Type",
+    "Content-Length",
+];
+
+/// https://fetch.spec.whatwg.org/#redirect-status
+pub fn is_redirect_status(status_code: u16) -> bool {
+    matches!(status_code, 301 | 302 | 303 | 307 | 308)
+}
+
+fn is_same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme() && a.host() == b.host() && a.port() == b.port()
+}
+
+/// リダイレクトをたどりながらリクエストを送信する
+#[derive(Debug, Clone)]
+pub struct Redirector {
+    max_redirects: usize,
+    /// リダイレクトのループを見つけるために、送信の間にCookieが変わったかを調べる
+    cookie_jar: Option<Rc<RefCell<CookieJar>>>,
+}
+
+impl Redirector {
+    pub fn new() -> Self {
+        Self::with_limit(DEFAULT_MAX_REDIRECTS)
+    }
+
+    pub fn with_limit(max_redirects: usize) -> Self {
+        Self {
+            max_redirects,
+            cookie_jar: None,
+        }
+    }
+
+    /// sendがCookieを付けて送信し、Set-Cookieを保存する場合に、そのCookieの保存先を渡す。
+    /// 同じメソッドとURLのリクエストに、Cookieが変わらないまま戻った場合はループとして
+    /// 失敗にする。渡さない場合は、リダイレクトの回数の上限だけで止める
+    pub fn with_cookie_jar(mut self, cookie_jar: Rc<RefCell<CookieJar>>) -> Self {
+        self.cookie_jar = Some(cookie_jar);
+        self
+    }
+
+    /// Locationヘッダが相対URLの場合は、リクエストしたURLに対して解決する
+    /// https://fetch.spec.whatwg.org/#concept-response-location-url
+    fn location_url(request_url: &Url, response: &HttpResponse) -> Result<Option<Url>, Error> {
+        let location = match response.header_value("Location") {
+            Ok(location) => location,
+            Err(_) => return Ok(None),
+        };
+
+        let mut location_url = match request_url.join(&location) {
+            Ok(url) => url,
+            Err(e) => {
+                return Err(Error::Network(format!(
+                    "invalid Location header {:?}: {}",
+                    location, e
+                )))
+            }
+        };
+
+        // Locationにフラグメントがない場合は、元のURLのフラグメントを引き継ぐ
+        if location_url.fragment().is_none() {
+            if let Some(fragment) = request_url.fragment() {
+                if let Ok(url) = location_url.join(&format!("#{}", fragment)) {
+                    location_url = url;
+                }
+            }
+        }
+
+        Ok(Some(location_url))
+    }
+
+    /// 転送先へのリクエストを作る。301と302のPOST、および303のGETとHEAD以外のリクエストは、
+    /// ボディを持たないGETに書き換える
+    /// https://fetch.spec.whatwg.org/#http-redirect-fetch
+    fn redirect_request(request: &HttpRequest, status_code: u16, location_url: Url) -> HttpRequest {
+        let method = request.method();
+        let change_to_get = ((status_code == 301 || status_code == 302) && method == "POST")
+            || (status_code == 303 && method != "GET" && method != "HEAD");
+
+        let mut redirected = if change_to_get {
+            HttpRequest::new("GET", location_url.clone())
+        } else {
+            HttpRequest::new(&method, location_url.clone()).with_body(request.body())
+        };
+        for header in request.headers() {
+            redirected = redirected.with_header(&header.name(), &header.value());
+        }
+
+        if change_to_get {
+            for name in REQUEST_BODY_HEADER_NAMES {
+                redirected.remove_header(name);
+            }
+        }
+        // Hostヘッダは転送先のURLから作り直す
+        redirected.remove_header("Host");
+        // 別のオリジンに認証情報を送らない
+        if !is_same_origin(&request.url(), &location_url) {
+            redirected.remove_header("Authorization");
+        }
+
+        redirected
+    }
+
+    /// 最終的なレスポンスを返す。レスポンスのURLには、転送された後のURLを設定する
+    pub fn fetch<F>(&self, request: HttpRequest, mut send: F) -> Result<HttpResponse, Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<HttpResponse, Error>,
+    {
+        self.follow(request, |request| {
+            send(request).map(|response| (response, ()))
+        })
+        .map(|(response, ())| response)
+    }
+
+    /// fetch()と同じようにリダイレクトをたどる。sendはレスポンスと一緒に、まだ読んでいない
+    /// ボディなどを返してよく、最終的なレスポンスのものだけを返す
+    pub fn follow<T, F>(
+        &self,
+        mut request: HttpRequest,
+        mut send: F,
+    ) -> Result<(HttpResponse, T), Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<(HttpResponse, T), Error>,
+    {
+        // 同じURLを再び訪れることは、Cookieを設定してから元のURLに戻る場合のように正当なことも
+        // あるので、送信したときのCookieの状態も合わせて記録する
+        let first_url = request.url().serialize(true);
+        let mut redirects = 0;
+        let mut visited: Vec<(String, String, u64)> = Vec::new();
+
+        loop {
+            if let Some(cookie_jar) = &self.cookie_jar {
+                let key = (
+                    request.method(),
+                    request.url().serialize(true),
+                    cookie_jar.borrow().generation(),
+                );
+                if visited.contains(&key) {
+                    return Err(Error::RedirectLoop(format!(
+                        "redirect loop detected at {}",
+                        key.1
+                    )));
+                }
+                visited.push(key);
+            }
+
+            let (mut response, body) = send(&request)?;
+            let request_url = request.url();
+            response.set_url(request_url.clone());
+
+            if !is_redirect_status(response.status_code()) {
+                return Ok((response, body));
+            }
+
+            let location_url = match Self::location_url(&request_url, &response)? {
+                Some(url) => url,
+                // Locationヘッダがない場合は、リダイレクトのレスポンスそのものを返す
+                None => return Ok((response, body)),
+            };
+
+            if location_url.scheme() != "http" && location_url.scheme() != "https" {
+                return Err(Error::Network(format!(
+                    "redirect to an unsupported scheme: {}",
+                    location_url.href()
+                )));
+            }
+
+            redirects += 1;
+            if redirects > self.max_redirects {
+                return Err(Error::Network(format!(
+                    "too many redirects: more than {} redirects from {}",
+                    self.max_redirects, first_url
+                )));
+            }
+
+            request = Self::redirect_request(&request, response.status_code(), location_url);
+        }
+    }
+}
+
+impl Default for Redirector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::cookie::CookieSource;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn url(url: &str) -> Url {
+        Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url")
+    }
+
+    fn response(raw: &str) -> HttpResponse {
+        HttpResponse::new(raw.as_bytes().to_vec()).expect("failed to parse http response")
+    }
+
+    fn redirect(status_code: u16, location: &str) -> HttpResponse {
+        response(&format!(
+            "HTTP/1.1 {} Redirect\r\nLocation: {}\r\n\r\n",
+            status_code, location
+        ))
+    }
+
+    /// URLごとに用意したレスポンスを返し、送信されたリクエストを記録する
+    fn fetch(
+        redirector: &Redirector,
+        request: HttpRequest,
+        routes: &[(&str, HttpResponse)],
+    ) -> (Result<HttpResponse, Error>, Vec<HttpRequest>) {
+        let mut sent = Vec::new();
+        let result = redirector.fetch(request, |request| {
+            sent.push(request.clone());
+            let href = request.url().serialize(true);
+            routes
+                .iter()
+                .find(|(u, _)| *u == href)
+                .map(|(_, r)| r.clone())
+                .ok_or_else(|| Error::Network(format!("no route for {}", href)))
+        });
+        (result, sent)
+    }
+
+    #[test]
+    fn test_no_redirect() {
+        let (result, sent) = fetch(
+            &Redirector::new(),
+            HttpRequest::get(url("http://example.com/")),
+            &[("http://example.com/", response("HTTP/1.1 200 OK\r\n\r\nok"))],
+        );
+        let response = result.expect("failed to fetch");
+        assert_eq!(b"ok".to_vec(), response.body());
+        assert_eq!(
+            Some("http://example.com/".to_string()),
+            response.url().map(|u| u.href())
+        );
+        assert_eq!(1, sent.len());
+    }
+
+    #[test]
+    fn test_redirect_chain_with_relative_location() {
+        let (result, sent) = fetch(
+            &Redirector::new(),
+            HttpRequest::get(url("http://example.com/a#section")),
+            &[
+                ("http://example.com/a", redirect(301, "/b")),
+                ("http://example.com/b", redirect(302, "c?x=1")),
+                (
+                    "http://example.com/c?x=1",
+                    redirect(307, "http://example.org/d"),
+                ),
+                ("http://example.org/d", redirect(308, "//example.net/e#top")),
+                ("http://example.net/e", response("HTTP/1.1 200 OK\r\n\r\n")),
+            ],
+        );
+        let response = result.expect("failed to fetch");
+        assert_eq!(200, response.status_code());
+        // 最終的なURLのフラグメントはLocationのものを優先する
+        assert_eq!(
+            Some("http://example.net/e#top".to_string()),
+            response.url().map(|u| u.href())
+        );
+        assert_eq!(5, sent.len());
+        // フラグメントはLocationにない場合は引き継ぐ
+        assert_eq!(
+            "http://example.com/b#section".to_string(),
+            sent[1].url().href()
+        );
+    }
+
+    #[test]
+    fn test_method_rewriting() {
+        let cases = [
+            (301, "POST", "GET", false),
+            (302, "POST", "GET", false),
+            (303, "POST", "GET", false),
+            (303, "PUT", "GET", false),
+            (303, "HEAD", "HEAD", true),
+            (301, "PUT", "PUT", true),
+            (307, "POST", "POST", true),
+            (308, "POST", "POST", true),
+        ];
+        for (status_code, method, expected_method, keeps_body) in cases {
+            let request = HttpRequest::new(method, url("http://example.com/form"))
+                .with_header("Content-Type", "text/plain")
+                .with_header("X-Custom", "1")
+                .with_body(b"data".to_vec());
+            let (result, sent) = fetch(
+                &Redirector::new(),
+                request,
+                &[
+                    ("http://example.com/form", redirect(status_code, "/done")),
+                    (
+                        "http://example.com/done",
+                        response("HTTP/1.1 200 OK\r\n\r\n"),
+                    ),
+                ],
+            );
+            result.expect("failed to fetch");
+
+            let redirected = &sent[1];
+            let case = format!("{} {}", status_code, method);
+            assert_eq!(expected_method.to_string(), redirected.method(), "{}", case);
+            assert_eq!(keeps_body, !redirected.body().is_empty(), "{}", case);
+            assert_eq!(
+                keeps_body,
+                redirected.header_value("Content-Type").is_some(),
+                "{}",
+                case
+            );
+            assert_eq!(
+                Some("1".to_string()),
+                redirected.header_value("X-Custom"),
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn test_authorization_is_removed_across_origins() {
+        let request = HttpRequest::get(url("http://example.com/"))
+            .with_header("Authorization", "Basic c2FiYQ==");
+        let (result, sent) = fetch(
+            &Redirector::new(),
+            request,
+            &[
+                ("http://example.com/", redirect(302, "/same")),
+                (
+                    "http://example.com/same",
+                    redirect(302, "http://example.org/"),
+                ),
+                ("http://example.org/", response("HTTP/1.1 200 OK\r\n\r\n")),
+            ],
+        );
+        result.expect("failed to fetch");
+        assert!(sent[1].header_value("Authorization").is_some());
+        assert!(sent[2].header_value("Authorization").is_none());
+    }
+
+    #[test]
+    fn test_redirect_without_location() {
+        let (result, _) = fetch(
+            &Redirector::new(),
+            HttpRequest::get(url("http://example.com/")),
+            &[(
+                "http://example.com/",
+                response("HTTP/1.1 302 Found\r\n\r\nmoved"),
+            )],
+        );
+        assert_eq!(302, result.expect("failed to fetch").status_code());
+    }
+
+    #[test]
+    fn test_too_many_redirects() {
+        let routes: Vec<(String, HttpResponse)> = (0..5)
+            .map(|i| {
+                (
+                    format!("http://example.com/{}", i),
+                    redirect(302, &format!("/{}", i + 1)),
+                )
+            })
+            .collect();
+        let routes: Vec<(&str, HttpResponse)> = routes
+            .iter()
+            .map(|(u, r)| (u.as_str(), r.clone()))
+            .collect();
+
+        let (result, sent) = fetch(
+            &Redirector::with_limit(3),
+            HttpRequest::get(url("http://example.com/0")),
+            &routes,
+        );
+        assert!(result.is_err());
+        assert_eq!(4, sent.len());
+    }
+
+    #[test]
+    fn test_redirect_loop() {
+        let routes = [
+            ("http://example.com/a", redirect(301, "/b")),
+            ("http://example.com/b", redirect(301, "/a")),
+        ];
+        // Cookieが変わらないまま同じリクエストに戻ったら、ループとして失敗する
+        let (result, sent) = fetch(
+            &Redirector::new().with_cookie_jar(Rc::new(RefCell::new(CookieJar::new()))),
+            HttpRequest::get(url("http://example.com/a")),
+            &routes,
+        );
+        assert_eq!(
+            Err(Error::RedirectLoop(
+                "redirect loop detected at http://example.com/a".to_string()
+            )),
+            result.map(|response| response.status_code())
+        );
+        assert_eq!(2, sent.len());
+
+        // Cookieの保存先がない場合は、上限の20回までリダイレクトをたどってから失敗する
+        let (result, sent) = fetch(
+            &Redirector::new(),
+            HttpRequest::get(url("http://example.com/a")),
+            &routes,
+        );
+        assert!(matches!(result, Err(Error::Network(_))));
+        assert_eq!(21, sent.len());
+    }
+
+    #[test]
+    fn test_revisit_url_after_cookie_change() {
+        let cookie_jar = Rc::new(RefCell::new(CookieJar::new()));
+        let mut sent = Vec::new();
+        let result = Redirector::new().with_cookie_jar(cookie_jar.clone()).fetch(
+            HttpRequest::get(url("http://example.com/login")),
+            |request| {
+                let path = request.url().path();
+                sent.push(path.clone());
+                let has_cookie = !cookie_jar.borrow().cookies().is_empty();
+                match path.as_str() {
+                    "/login" if has_cookie => Ok(response("HTTP/1.1 200 OK\r\n\r\nwelcome")),
+                    "/login" => Ok(redirect(302, "/set-cookie")),
+                    _ => {
+                        cookie_jar.borrow_mut().set_cookie(
+                            &request.url(),
+                            "sid=1",
+                            CookieSource::Http,
+                        );
+                        Ok(redirect(302, "/login"))
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            200,
+            result.expect("failed to follow redirects").status_code()
+        );
+        assert_eq!(vec!["/login", "/set-cookie", "/login"], sent);
+    }
+
+    #[test]
+    fn test_revisit_url_after_redirect() {
+        // /loginはCookieがなければ/set-cookieに転送し、/set-cookieはCookieを設定して/loginに戻す
+        let mut has_cookie = false;
+        let mut sent = Vec::new();
+        let result = Redirector::new().fetch(
+            HttpRequest::get(url("http://example.com/login")),
+            |request| {
+                let path = request.url().path();
+                sent.push(path.clone());
+                match path.as_str() {
+                    "/login" if has_cookie => Ok(response("HTTP/1.1 200 OK\r\n\r\nwelcome")),
+                    "/login" => Ok(redirect(302, "/set-cookie")),
+                    _ => {
+                        has_cookie = true;
+                        Ok(redirect(302, "/login"))
+                    }
+                }
+            },
+        );
+
+        let response = result.expect("failed to follow redirects");
+        assert_eq!(200, response.status_code());
+        assert_eq!("welcome", response.text());
+        assert_eq!(vec!["/login", "/set-cookie", "/login"], sent);
+    }
+
+    #[test]
+    fn test_invalid_redirects() {
+        let cases = [
+            redirect(302, "http://[invalid/"),
+            redirect(302, "data:text/html,hello"),
+        ];
+        for response in cases {
+            let (result, _) = fetch(
+                &Redirector::new(),
+                HttpRequest::get(url("http://example.com/")),
+                &[("http://example.com/", response)],
+            );
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_is_redirect_status() {
+        for status_code in [301, 302, 303, 307, 308] {
+            assert!(is_redirect_status(status_code));
+        }
+        for status_code in [200, 300, 304, 305, 306, 309] {
+            assert!(!is_redirect_status(status_code));
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::http::request::HttpRequest;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// リクエストのUser-Agentヘッダに送る値
+/// https://www.rfc-editor.org/rfc/rfc9110#section-10.1.5
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (compatible; SaBA/0.1)";
+
+/// リクエストで取得したリソースの使い道
+/// https://fetch.spec.whatwg.org/#concept-request-destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    /// ページとして表示するHTML文書
+    Document,
+    Style,
+    Script,
+    Image,
+}
+
+/// ブラウザがすべてのHTTPリクエストに付けるヘッダの設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestSettings {
+    user_agent: String,
+    accept_document: String,
+    accept_style: String,
+    accept_script: String,
+    accept_image: String,
+    /// 優先する順に並べた言語タグ
+    accept_languages: Vec<String>,
+}
+
+impl RequestSettings {
+    pub fn new() -> Self {
+        // Acceptの値はFetchの仕様に合わせる
+        // https://fetch.spec.whatwg.org/#fetching
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            accept_document: "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+                .to_string(),
+            accept_style: "text/css,*/*;q=0.1".to_string(),
+            accept_script: "*/*".to_string(),
+            accept_image: "image/png,image/*;q=0.8,*/*;q=0.5".to_string(),
+            accept_languages: ["ja", "en"].iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    /// 空文字列の場合はUser-Agentを送らない
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn with_accept(mut self, destination: Destination, accept: &str) -> Self {
+        let value = match destination {
+            Destination::Document => &mut self.accept_document,
+            Destination::Style => &mut self.accept_style,
+            Destination::Script => &mut self.accept_script,
+            Destination::Image => &mut self.accept_image,
+        };
+        *value = accept.to_string();
+        self
+    }
+
+    /// 優先する順に言語タグを並べる。空の場合はAccept-Languageを送らない
+    pub fn with_accept_languages(mut self, languages: &[&str]) -> Self {
+        self.accept_languages = languages
+            .iter()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        self
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn accept(&self, destination: Destination) -> &str {
+        match destination {
+            Destination::Document => &self.accept_document,
+            Destination::Style => &self.accept_style,
+            Destination::Script => &self.accept_script,
+            Destination::Image => &self.accept_image,
+        }
+    }
+
+    pub fn accept_languages(&self) -> &[String] {
+        &self.accept_languages
+    }
+
+    /// Accept-Languageヘッダの値。2番目以降の言語には、順に小さくなるqの値を付ける
+    /// https://www.rfc-editor.org/rfc/rfc9110#section-12.5.4
+    pub fn accept_language(&self) -> Option<String> {
+        if self.accept_languages.is_empty() {
+            return None;
+        }
+        let mut values = Vec::new();
+        for (i, language) in self.accept_languages.iter().enumerate() {
+            if i == 0 {
+                values.push(language.clone());
+                continue;
+            }
+            // 0.9、0.8と下げていき、0.1より小さくはしない
+            let q = 10usize.saturating_sub(i).max(1);
+            values.push(format!("{};q=0.{}", language, q));
+        }
+        Some(values.join(","))
+    }
+
+    /// リクエストに、まだ付いていないヘッダを付ける
+    pub fn apply(&self, request: &mut HttpRequest, destination: Destination) {
+        if !self.user_agent.is_empty() && request.header_value("User-Agent").is_none() {
+            request.set_header("User-Agent", &self.user_agent);
+        }
+        if request.header_value("Accept").is_none() {
+            request.set_header("Accept", self.accept(destination));
+        }
+        if let Some(accept_language) = self.accept_language() {
+            if request.header_value("Accept-Language").is_none() {
+                request.set_header("Accept-Language", &accept_language);
+            }
+        }
+    }
+}
+
+impl Default for RequestSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::url::Url;
+
+    fn request() -> HttpRequest {
+        HttpRequest::get(
+            Url::new("http://example.com/".to_string())
+                .parse()
+                .expect("failed to parse url"),
+        )
+    }
+
+    #[test]
+    fn test_default_headers() {
+        let settings = RequestSettings::new();
+        let mut document = request();
+        settings.apply(&mut document, Destination::Document);
+        assert_eq!(
+            Some(DEFAULT_USER_AGENT.to_string()),
+            document.header_value("User-Agent")
+        );
+        assert_eq!(
+            Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8".to_string()),
+            document.header_value("Accept")
+        );
+        assert_eq!(
+            Some("ja,en;q=0.9".to_string()),
+            document.header_value("Accept-Language")
+        );
+
+        let mut style = request();
+        settings.apply(&mut style, Destination::Style);
+        assert_eq!(
+            Some("text/css,*/*;q=0.1".to_string()),
+            style.header_value("Accept")
+        );
+        let mut script = request();
+        settings.apply(&mut script, Destination::Script);
+        assert_eq!(Some("*/*".to_string()), script.header_value("Accept"));
+    }
+
+    #[test]
+    fn test_custom_settings() {
+        let settings = RequestSettings::new()
+            .with_user_agent("")
+            .with_accept(Destination::Image, "image/png")
+            .with_accept_languages(&["en-US", " en ", "", "fr", "de"]);
+        assert_eq!(
+            Some("en-US,en;q=0.9,fr;q=0.8,de;q=0.7".to_string()),
+            settings.accept_language()
+        );
+
+        let mut image = request();
+        settings.apply(&mut image, Destination::Image);
+        assert_eq!(None, image.header_value("User-Agent"));
+        assert_eq!(Some("image/png".to_string()), image.header_value("Accept"));
+
+        let settings = settings.with_accept_languages(&[]);
+        let mut document = request();
+        settings.apply(&mut document, Destination::Document);
+        assert_eq!(None, document.header_value("Accept-Language"));
+    }
+
+    #[test]
+    fn test_keep_request_headers() {
+        let mut request = request()
+            .with_header("Accept", "application/json")
+            .with_header("Accept-Language", "fr");
+        RequestSettings::new().apply(&mut request, Destination::Document);
+        assert_eq!(
+            Some("application/json".to_string()),
+            request.header_value("Accept")
+        );
+        assert_eq!(
+            Some("fr".to_string()),
+            request.header_value("Accept-Language")
+        );
+    }
+}
@@ -2,11 +2,20 @@
 
 extern crate alloc;
 
+pub mod base64;
 pub mod browser;
+pub mod compression;
 pub mod constants;
+pub mod crypto;
 pub mod display_item;
+pub mod download;
+pub mod encoding;
 pub mod error;
 pub mod http;
+pub mod mime;
 pub mod renderer;
+pub mod resolver;
+pub mod scheme;
+pub mod tls;
 pub mod url;
 pub mod utils;
@@ -0,0 +1,292 @@
+pub mod sniff;
+
+use crate::http::is_tchar;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// https://fetch.spec.whatwg.org/#http-whitespace
+fn is_http_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | ' ')
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_tchar)
+}
+
+/// https://mimesniff.spec.whatwg.org/#http-quoted-string-token-code-point
+fn is_quoted_string_token(c: char) -> bool {
+    matches!(c, '\t' | ' '..='~' | '\u{80}'..='\u{FF}')
+}
+
+/// https://fetch.spec.whatwg.org/#collect-an-http-quoted-string
+fn collect_quoted_string(input: &[char], pos: &mut usize) -> String {
+    let mut value = String::new();
+    // 最初の"を読み飛ばす
+    *pos += 1;
+    while *pos < input.len() {
+        let c = input[*pos];
+        *pos += 1;
+        match c {
+            '"' => break,
+            '\\' => match input.get(*pos) {
+                Some(escaped) => {
+                    value.push(*escaped);
+                    *pos += 1;
+                }
+                None => value.push('\\'),
+            },
+            _ => value.push(c),
+        }
+    }
+    value
+}
+
+/// "text/html; charset=utf-8"のようなMIMEタイプ。タイプ、サブタイプ、パラメータの名前は
+/// 小文字にする
+/// https://mimesniff.spec.whatwg.org/#mime-type-representation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeType {
+    type_name: String,
+    subtype: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl MimeType {
+    pub fn new(type_name: &str, subtype: &str) -> Self {
+        Self {
+            type_name: type_name.to_ascii_lowercase(),
+            subtype: subtype.to_ascii_lowercase(),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// 正しくないMIMEタイプの場合はNoneを返す。同じ名前のパラメータは最初のものを使う
+    /// https://mimesniff.spec.whatwg.org/#parse-a-mime-type
+    pub fn parse(input: &str) -> Option<Self> {
+        let input: Vec<char> = input.trim_matches(is_http_whitespace).chars().collect();
+        let mut pos = 0;
+
+        let type_name: String = input.iter().take_while(|c| **c != '/').collect();
+        pos += type_name.chars().count();
+        if !is_token(&type_name) || pos >= input.len() {
+            return None;
+        }
+        // "/"を読み飛ばす
+        pos += 1;
+
+        let subtype: String = input[pos..].iter().take_while(|c| **c != ';').collect();
+        pos += subtype.chars().count();
+        let subtype = subtype.trim_end_matches(is_http_whitespace);
+        if !is_token(subtype) {
+            return None;
+        }
+
+        let mut mime_type = Self::new(&type_name, subtype);
+        while pos < input.len() {
+            // ";"と、その後の空白を読み飛ばす
+            pos += 1;
+            while pos < input.len() && is_http_whitespace(input[pos]) {
+                pos += 1;
+            }
+
+            let name: String = input[pos..]
+                .iter()
+                .take_while(|c| **c != ';' && **c != '=')
+                .collect();
+            pos += name.chars().count();
+            let name = name.to_ascii_lowercase();
+            if pos < input.len() {
+                if input[pos] == ';' {
+                    continue;
+                }
+                // "="を読み飛ばす
+                pos += 1;
+            }
+            if pos >= input.len() {
+                break;
+            }
+
+            let value = if input[pos] == '"' {
+                let value = collect_quoted_string(&input, &mut pos);
+                // 閉じる"の後ろは、次の";"まで無視する
+                while pos < input.len() && input[pos] != ';' {
+                    pos += 1;
+                }
+                value
+            } else {
+                let value: String = input[pos..].iter().take_while(|c| **c != ';').collect();
+                pos += value.chars().count();
+                let value = value.trim_end_matches(is_http_whitespace).to_string();
+                if value.is_empty() {
+                    continue;
+                }
+                value
+            };
+
+            if is_token(&name)
+                && value.chars().all(is_quoted_string_token)
+                && mime_type.parameter(&name).is_none()
+            {
+                mime_type.parameters.push((name, value));
+            }
+        }
+
+        Some(mime_type)
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    /// パラメータを除いた"text/html"のような部分
+    /// https://mimesniff.spec.whatwg.org/#mime-type-essence
+    pub fn essence(&self) -> String {
+        let mut essence = self.type_name.clone();
+        essence.push('/');
+        essence.push_str(&self.subtype);
+        essence
+    }
+
+    /// パラメータの名前は大文字と小文字を区別しない
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// https://mimesniff.spec.whatwg.org/#serialize-a-mime-type
+    pub fn serialize(&self) -> String {
+        let mut serialized = self.essence();
+        for (name, value) in &self.parameters {
+            serialized.push(';');
+            serialized.push_str(name);
+            serialized.push('=');
+            if is_token(value) {
+                serialized.push_str(value);
+                continue;
+            }
+            serialized.push('"');
+            for c in value.chars() {
+                if c == '"' || c == '\\' {
+                    serialized.push('\\');
+                }
+                serialized.push(c);
+            }
+            serialized.push('"');
+        }
+        serialized
+    }
+
+    /// https://mimesniff.spec.whatwg.org/#html-mime-type
+    pub fn is_html(&self) -> bool {
+        self.essence() == "text/html"
+    }
+
+    /// https://mimesniff.spec.whatwg.org/#xml-mime-type
+    pub fn is_xml(&self) -> bool {
+        self.subtype.ends_with("+xml")
+            || matches!(self.essence().as_str(), "text/xml" | "application/xml")
+    }
+
+    /// https://mimesniff.spec.whatwg.org/#image-mime-type
+    pub fn is_image(&self) -> bool {
+        self.type_name == "image"
+    }
+
+    /// https://mimesniff.spec.whatwg.org/#javascript-mime-type
+    pub fn is_javascript(&self) -> bool {
+        matches!(
+            self.essence().as_str(),
+            "application/ecmascript"
+                | "application/javascript"
+                | "application/x-ecmascript"
+                | "application/x-javascript"
+                | "text/ecmascript"
+                | "text/javascript"
+                | "text/javascript1.0"
+                | "text/javascript1.1"
+                | "text/javascript1.2"
+                | "text/javascript1.3"
+                | "text/javascript1.4"
+                | "text/javascript1.5"
+                | "text/jscript"
+                | "text/livescript"
+                | "text/x-ecmascript"
+                | "text/x-javascript"
+        )
+    }
+
+    /// https://mimesniff.spec.whatwg.org/#json-mime-type
+    pub fn is_json(&self) -> bool {
+        self.subtype.ends_with("+json")
+            || matches!(self.essence().as_str(), "application/json" | "text/json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_parse() {
+        let mime_type = MimeType::parse(" Text/HTML ; Charset=\"Shift_JIS\" ; foo=bar")
+            .expect("failed to parse");
+        assert_eq!("text", mime_type.type_name());
+        assert_eq!("html", mime_type.subtype());
+        assert_eq!("text/html", mime_type.essence());
+        assert_eq!(Some("Shift_JIS"), mime_type.parameter("charset"));
+        assert_eq!(Some("bar"), mime_type.parameter("FOO"));
+        assert_eq!("text/html;charset=Shift_JIS;foo=bar", mime_type.serialize());
+        assert!(mime_type.is_html());
+
+        // 同じ名前のパラメータは最初のものを使い、正しくないパラメータは無視する
+        let mime_type = MimeType::parse("text/plain;a=1;a=2;;b;c=;d=\"x\\\"y\" z;e=\u{3042}")
+            .expect("failed to parse");
+        assert_eq!(
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("d".to_string(), "x\"y".to_string())
+            ],
+            mime_type.parameters
+        );
+        assert_eq!("text/plain;a=1;d=\"x\\\"y\"", mime_type.serialize());
+
+        let mime_type = MimeType::parse("application/json;charset=\"").expect("failed to parse");
+        assert_eq!(Some(""), mime_type.parameter("charset"));
+        assert_eq!("application/json;charset=\"\"", mime_type.serialize());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in [
+            "",
+            "text",
+            "text/",
+            "/html",
+            "te xt/html",
+            "text/ht ml",
+            "text/html/",
+        ] {
+            assert_eq!(None, MimeType::parse(input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_groups() {
+        let parse = |s: &str| MimeType::parse(s).expect("failed to parse");
+        assert!(parse("image/svg+xml").is_xml());
+        assert!(parse("image/svg+xml").is_image());
+        assert!(parse("application/xml").is_xml());
+        assert!(!parse("text/html").is_xml());
+        assert!(parse("text/javascript; charset=utf-8").is_javascript());
+        assert!(parse("application/ld+json").is_json());
+        assert!(!parse("text/plain").is_json());
+    }
+}
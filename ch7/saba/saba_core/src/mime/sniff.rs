@@ -0,0 +1,348 @@
+use crate::mime::MimeType;
+
+/// 先頭の1445バイトまでを調べる
+/// https://mimesniff.spec.whatwg.org/#reading-the-resource-header
+pub const RESOURCE_HEADER_LENGTH: usize = 1445;
+
+/// https://mimesniff.spec.whatwg.org/#whitespace-byte
+const WHITESPACE_BYTES: &[u8] = b"\t\n\x0C\r ";
+
+/// パターン、マスク、読み飛ばす先頭のバイト、一致したときのMIMEタイプ
+type Pattern = (
+    &'static [u8],
+    &'static [u8],
+    &'static [u8],
+    (&'static str, &'static str),
+);
+
+/// https://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+const IMAGE_PATTERNS: &[Pattern] = &[
+    (
+        b"\x00\x00\x01\x00",
+        b"\xFF\xFF\xFF\xFF",
+        b"",
+        ("image", "x-icon"),
+    ),
+    (
+        b"\x00\x00\x02\x00",
+        b"\xFF\xFF\xFF\xFF",
+        b"",
+        ("image", "x-icon"),
+    ),
+    (b"BM", b"\xFF\xFF", b"", ("image", "bmp")),
+    (
+        b"GIF87a",
+        b"\xFF\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("image", "gif"),
+    ),
+    (
+        b"GIF89a",
+        b"\xFF\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("image", "gif"),
+    ),
+    (
+        b"RIFF\x00\x00\x00\x00WEBPVP",
+        b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("image", "webp"),
+    ),
+    (
+        b"\x89PNG\r\n\x1A\n",
+        b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("image", "png"),
+    ),
+    (b"\xFF\xD8\xFF", b"\xFF\xFF\xFF", b"", ("image", "jpeg")),
+];
+
+/// https://mimesniff.spec.whatwg.org/#matching-an-archive-type-pattern
+const ARCHIVE_PATTERNS: &[Pattern] = &[
+    (
+        b"\x1F\x8B\x08",
+        b"\xFF\xFF\xFF",
+        b"",
+        ("application", "x-gzip"),
+    ),
+    (
+        b"PK\x03\x04",
+        b"\xFF\xFF\xFF\xFF",
+        b"",
+        ("application", "zip"),
+    ),
+    (
+        b"Rar!\x1A\x07\x00",
+        b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("application", "x-rar-compressed"),
+    ),
+];
+
+/// HTMLのタグで始まるかを調べるパターン。大文字と小文字を区別せずに比較し、続くバイトが空白か
+/// ">"の場合だけ一致とする
+/// https://mimesniff.spec.whatwg.org/#identifying-a-resource-with-an-unknown-mime-type
+const HTML_PATTERNS: &[&[u8]] = &[
+    b"<!DOCTYPE HTML",
+    b"<HTML",
+    b"<HEAD",
+    b"<SCRIPT",
+    b"<IFRAME",
+    b"<H1",
+    b"<DIV",
+    b"<FONT",
+    b"<TABLE",
+    b"<A",
+    b"<STYLE",
+    b"<TITLE",
+    b"<B",
+    b"<BODY",
+    b"<BR",
+    b"<P",
+    b"<!--",
+];
+
+/// 先頭の空白を読み飛ばしてから比較するパターン
+const OTHER_PATTERNS: &[Pattern] = &[
+    (
+        b"<?xml",
+        b"\xFF\xFF\xFF\xFF\xFF",
+        WHITESPACE_BYTES,
+        ("text", "xml"),
+    ),
+    (
+        b"%PDF-",
+        b"\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("application", "pdf"),
+    ),
+    (
+        b"%!PS-Adobe-",
+        b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        b"",
+        ("application", "postscript"),
+    ),
+    (
+        b"\xFE\xFF\x00\x00",
+        b"\xFF\xFF\x00\x00",
+        b"",
+        ("text", "plain"),
+    ),
+    (
+        b"\xFF\xFE\x00\x00",
+        b"\xFF\xFF\x00\x00",
+        b"",
+        ("text", "plain"),
+    ),
+    (
+        b"\xEF\xBB\xBF\x00",
+        b"\xFF\xFF\xFF\x00",
+        b"",
+        ("text", "plain"),
+    ),
+];
+
+/// https://mimesniff.spec.whatwg.org/#pattern-matching-algorithm
+fn pattern_matches(input: &[u8], pattern: &[u8], mask: &[u8], ignored: &[u8]) -> bool {
+    let start = input
+        .iter()
+        .position(|b| !ignored.contains(b))
+        .unwrap_or(input.len());
+    let input = &input[start..];
+    if input.len() < pattern.len() {
+        return false;
+    }
+    pattern
+        .iter()
+        .zip(mask)
+        .zip(input)
+        .all(|((p, m), b)| b & m == *p)
+}
+
+fn match_patterns(input: &[u8], patterns: &[Pattern]) -> Option<MimeType> {
+    patterns
+        .iter()
+        .find(|(pattern, mask, ignored, _)| pattern_matches(input, pattern, mask, ignored))
+        .map(|(_, _, _, (type_name, subtype))| MimeType::new(type_name, subtype))
+}
+
+/// https://mimesniff.spec.whatwg.org/#binary-data-byte
+fn is_binary_data_byte(byte: u8) -> bool {
+    matches!(byte, 0x00..=0x08 | 0x0B | 0x0E..=0x1A | 0x1C..=0x1F)
+}
+
+fn matches_html(input: &[u8]) -> bool {
+    let start = input
+        .iter()
+        .position(|b| !WHITESPACE_BYTES.contains(b))
+        .unwrap_or(input.len());
+    let input = &input[start..];
+    HTML_PATTERNS.iter().any(|pattern| {
+        input.len() > pattern.len()
+            && input[..pattern.len()].eq_ignore_ascii_case(pattern)
+            && matches!(input[pattern.len()], b' ' | b'>')
+    })
+}
+
+/// Content-Typeがない場合などに、内容からMIMEタイプを決める。sniff_scriptableがfalseの場合は
+/// HTMLやXMLとは判定しない
+/// https://mimesniff.spec.whatwg.org/#rules-for-identifying-an-unknown-mime-type
+fn identify_unknown(header: &[u8], sniff_scriptable: bool) -> MimeType {
+    if sniff_scriptable {
+        if matches_html(header) {
+            return MimeType::new("text", "html");
+        }
+        if let Some(mime_type) = match_patterns(header, &OTHER_PATTERNS[..1]) {
+            return mime_type;
+        }
+    }
+    if let Some(mime_type) = match_patterns(header, &OTHER_PATTERNS[1..]) {
+        return mime_type;
+    }
+    if let Some(mime_type) = match_patterns(header, IMAGE_PATTERNS) {
+        return mime_type;
+    }
+    if let Some(mime_type) = match_patterns(header, ARCHIVE_PATTERNS) {
+        return mime_type;
+    }
+    if !header.iter().any(|b| is_binary_data_byte(*b)) {
+        return MimeType::new("text", "plain");
+    }
+    MimeType::new("application", "octet-stream")
+}
+
+/// https://mimesniff.spec.whatwg.org/#rules-for-text-or-binary
+fn distinguish_text_or_binary(header: &[u8]) -> MimeType {
+    if header.starts_with(b"\xFE\xFF")
+        || header.starts_with(b"\xFF\xFE")
+        || header.starts_with(b"\xEF\xBB\xBF")
+        || !header.iter().any(|b| is_binary_data_byte(*b))
+    {
+        return MimeType::new("text", "plain");
+    }
+    MimeType::new("application", "octet-stream")
+}
+
+/// Content-Typeヘッダの値とボディの先頭から、リソースのMIMEタイプを決める。no_sniffは
+/// X-Content-Type-Options: nosniffが指定されている場合にtrueにする
+/// https://mimesniff.spec.whatwg.org/#mime-type-sniffing-algorithm
+pub fn sniff(bytes: &[u8], content_type: Option<&str>, no_sniff: bool) -> MimeType {
+    let header = &bytes[..bytes.len().min(RESOURCE_HEADER_LENGTH)];
+    let supplied = content_type.and_then(MimeType::parse);
+
+    let supplied = match supplied {
+        Some(mime_type)
+            if !matches!(
+                mime_type.essence().as_str(),
+                "unknown/unknown" | "application/unknown" | "*/*"
+            ) =>
+        {
+            mime_type
+        }
+        _ => return identify_unknown(header, !no_sniff),
+    };
+    if no_sniff {
+        return supplied;
+    }
+
+    // 古いApacheは、どのファイルにもこれらのContent-Typeを付けていた
+    // https://mimesniff.spec.whatwg.org/#supplied-mime-type-detection-algorithm
+    if matches!(
+        content_type,
+        Some("text/plain")
+            | Some("text/plain; charset=ISO-8859-1")
+            | Some("text/plain; charset=iso-8859-1")
+            | Some("text/plain; charset=UTF-8")
+    ) {
+        return distinguish_text_or_binary(header);
+    }
+
+    if supplied.is_xml() || supplied.is_html() {
+        return supplied;
+    }
+    if supplied.is_image() {
+        if let Some(mime_type) = match_patterns(header, IMAGE_PATTERNS) {
+            return mime_type;
+        }
+    }
+    supplied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn essence(bytes: &[u8], content_type: Option<&str>, no_sniff: bool) -> String {
+        sniff(bytes, content_type, no_sniff).essence()
+    }
+
+    #[test]
+    fn test_unknown() {
+        for (bytes, expected) in [
+            (&b"  <!doctype html><p>saba"[..], "text/html"),
+            (b"\n<HTML>", "text/html"),
+            (b"<!-- comment -->", "text/html"),
+            (b"<?xml version=\"1.0\"?>", "text/xml"),
+            (b"%PDF-1.7", "application/pdf"),
+            (b"\xEF\xBB\xBFtext", "text/plain"),
+            (b"\x89PNG\r\n\x1A\n\x00\x00", "image/png"),
+            (b"GIF89a\x01\x00", "image/gif"),
+            (b"RIFF\x10\x00\x00\x00WEBPVP8 ", "image/webp"),
+            (b"PK\x03\x04\x14\x00", "application/zip"),
+            (b"\x1F\x8B\x08\x00", "application/x-gzip"),
+            (b"plain text\r\n", "text/plain"),
+            (b"<pre>", "text/plain"),
+            (b"\x00\x01\x02", "application/octet-stream"),
+            (b"", "text/plain"),
+        ] {
+            assert_eq!(expected, essence(bytes, None, false), "{:?}", bytes);
+        }
+        assert_eq!(
+            "text/html",
+            essence(b"<html>", Some("application/unknown"), false)
+        );
+        assert_eq!("text/html", essence(b"<p>saba", Some("invalid"), false));
+        // nosniffの場合は、HTMLやXMLとは判定しない
+        assert_eq!("text/plain", essence(b"<html>", None, true));
+    }
+
+    #[test]
+    fn test_supplied() {
+        assert_eq!(
+            "text/html",
+            essence(b"\x00\x01", Some("text/html; charset=utf-8"), false)
+        );
+        assert_eq!(
+            "application/json",
+            essence(b"{}", Some("application/json"), false)
+        );
+        // 画像はパターンで実際の形式を調べる
+        assert_eq!(
+            "image/gif",
+            essence(b"GIF87a\x01\x00", Some("image/png"), false)
+        );
+        assert_eq!("image/png", essence(b"GIF87a", Some("image/png"), true));
+        assert_eq!(
+            "image/svg+xml",
+            essence(b"<svg>", Some("image/svg+xml"), false)
+        );
+    }
+
+    #[test]
+    fn test_apache_bug() {
+        assert_eq!(
+            "application/octet-stream",
+            essence(b"PK\x03\x04\x14\x00", Some("text/plain"), false)
+        );
+        assert_eq!(
+            "text/plain",
+            essence(b"plain text", Some("text/plain; charset=UTF-8"), false)
+        );
+        assert_eq!(
+            "text/plain",
+            essence(b"\x00\x01", Some("text/plain; charset=utf-8"), false)
+        );
+        assert_eq!("text/plain", essence(b"\x00\x01", Some("text/plain"), true));
+    }
+}
@@ -54,6 +54,13 @@ pub fn get_target_element_node(
     }
 }
 
+/// https://html.spec.whatwg.org/multipage/semantics.html#frozen-base-url
+pub fn get_base_href(root: Rc<RefCell<Node>>) -> Option<String> {
+    let base_node = get_target_element_node(Some(root), ElementKind::Base)?;
+    let element = base_node.borrow().get_element()?;
+    element.get_attribute("href")
+}
+
 pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
     let style_node = match get_target_element_node(Some(root), ElementKind::Style) {
         Some(node) => node,
@@ -184,7 +184,11 @@ impl Element {
 
     pub fn is_block_element(&self) -> bool {
         match self.kind {
-            ElementKind::Body | ElementKind::H1 | ElementKind::H2 | ElementKind::P => true,
+            ElementKind::Body
+            | ElementKind::H1
+            | ElementKind::H2
+            | ElementKind::P
+            | ElementKind::Pre => true,
             _ => false,
         }
     }
@@ -197,6 +201,8 @@ pub enum ElementKind {
     Html,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-head-element
     Head,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-base-element
+    Base,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-style-element
     Style,
     /// https://html.spec.whatwg.org/multipage/scripting.html#the-script-element
@@ -208,8 +214,12 @@ pub enum ElementKind {
     /// https://html.spec.whatwg.org/multipage/sections.html#the-h1,-h2,-h3,-h4,-h5,-and-h6-elements
     H1,
     H2,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-pre-element
+    Pre,
     /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-a-element
     A,
+    /// https://html.spec.whatwg.org/multipage/embedded-content.html#the-img-element
+    Img,
 }
 
 impl Display for ElementKind {
@@ -217,13 +227,16 @@ impl Display for ElementKind {
         let s = match self {
             ElementKind::Html => "html",
             ElementKind::Head => "head",
+            ElementKind::Base => "base",
             ElementKind::Style => "style",
             ElementKind::Script => "script",
             ElementKind::Body => "body",
             ElementKind::H1 => "h1",
             ElementKind::H2 => "h2",
             ElementKind::P => "p",
+            ElementKind::Pre => "pre",
             ElementKind::A => "a",
+            ElementKind::Img => "img",
         };
         write!(f, "{}", s)
     }
@@ -236,13 +249,16 @@ impl FromStr for ElementKind {
         match s {
             "html" => Ok(ElementKind::Html),
             "head" => Ok(ElementKind::Head),
+            "base" => Ok(ElementKind::Base),
             "style" => Ok(ElementKind::Style),
             "script" => Ok(ElementKind::Script),
             "body" => Ok(ElementKind::Body),
             "p" => Ok(ElementKind::P),
             "h1" => Ok(ElementKind::H1),
             "h2" => Ok(ElementKind::H2),
+            "pre" => Ok(ElementKind::Pre),
             "a" => Ok(ElementKind::A),
+            "img" => Ok(ElementKind::Img),
             _ => Err(format!("unimplemented element name {:?}", s)),
         }
     }
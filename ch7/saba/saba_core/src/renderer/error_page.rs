@@ -0,0 +1,270 @@
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+
+/// ナビゲーションが失敗した理由の分類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// URLが正しくないか、対応していないスキーム
+    Url,
+    /// 名前解決の失敗
+    Dns,
+    /// TCPの接続の失敗
+    Connect,
+    /// TLSのハンドシェイクや証明書の検証の失敗
+    Tls,
+    /// 接続した後の、送受信やレスポンスの形式の失敗
+    Protocol,
+    /// 4xxまたは5xxのステータスコードで、ボディが空のレスポンス
+    Http(u16),
+    Other,
+}
+
+impl ErrorCategory {
+    pub fn for_error(error: &Error) -> Self {
+        match error {
+            Error::Dns(_) => ErrorCategory::Dns,
+            Error::Connect(_) => ErrorCategory::Connect,
+            Error::Tls(_) => ErrorCategory::Tls,
+            Error::Network(_)
+            | Error::Timeout(_)
+            | Error::ConnectionReset(_)
+            | Error::RedirectLoop(_) => ErrorCategory::Protocol,
+            Error::UnexpectedInput(_) => ErrorCategory::Url,
+            Error::InvalidUI(_) | Error::Other(_) => ErrorCategory::Other,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ErrorCategory::Url => "URL".to_string(),
+            ErrorCategory::Dns => "DNS".to_string(),
+            ErrorCategory::Connect => "Connect".to_string(),
+            ErrorCategory::Tls => "TLS".to_string(),
+            ErrorCategory::Protocol => "Protocol".to_string(),
+            ErrorCategory::Http(status_code) => format!("HTTP {}", status_code),
+            ErrorCategory::Other => "Internal".to_string(),
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ErrorCategory::Url => "Invalid address",
+            ErrorCategory::Dns => "Server not found",
+            ErrorCategory::Connect => "Unable to connect",
+            ErrorCategory::Tls => "Secure connection failed",
+            ErrorCategory::Protocol => "Connection error",
+            ErrorCategory::Http(400..=499) => "Client error",
+            ErrorCategory::Http(_) => "Server error",
+            ErrorCategory::Other => "Page cannot be displayed",
+        }
+    }
+}
+
+/// タグや属性の区切りになる文字を、文字参照にする
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// ナビゲーションが失敗したときに、ブラウザが代わりに表示するページ
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorPage {
+    /// 読み込もうとしたURL。正しいURLでない場合は入力された文字列
+    url: String,
+    /// 再読み込みに使うURL
+    retry_url: Option<Url>,
+    category: ErrorCategory,
+    detail: String,
+}
+
+impl ErrorPage {
+    pub fn for_error(url: &str, error: &Error) -> Self {
+        let detail = error.message().to_string();
+        let retry_url = Url::new(url.to_string()).parse().ok();
+        Self {
+            url: retry_url
+                .as_ref()
+                .map(|url| url.href())
+                .unwrap_or(url.to_string()),
+            retry_url,
+            category: ErrorCategory::for_error(error),
+            detail,
+        }
+    }
+
+    /// サーバーがエラーのステータスコードと空のボディを返した場合は、代わりに表示するページを
+    /// 返す。ボディがある場合は、サーバーが用意したページをそのまま表示する
+    pub fn for_response(response: &HttpResponse) -> Option<Self> {
+        let status_code = response.status_code();
+        if status_code < 400 || !response.body().is_empty() {
+            return None;
+        }
+        let retry_url = response.url();
+        Some(Self {
+            url: retry_url.as_ref().map(|url| url.href()).unwrap_or_default(),
+            retry_url,
+            category: ErrorCategory::Http(status_code),
+            detail: format!("{} {}", status_code, response.reason())
+                .trim_end()
+                .to_string(),
+        })
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        self.category
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = format!(
+            "<html><head></head><body><h1>{}</h1><p>{}</p><p>Error: {}</p><p>{}</p>",
+            self.category.title(),
+            escape(&self.url),
+            self.category.name(),
+            escape(&self.detail)
+        );
+        if let Some(url) = &self.retry_url {
+            html.push_str(&format!(
+                "<p><a href=\"{}\">Retry</a></p>",
+                escape(&url.href())
+            ));
+        }
+        html.push_str("</body></html>");
+        html
+    }
+
+    /// ページとして読み込むためのレスポンス。URLは読み込もうとしたURLにするので、再読み込みで
+    /// もう一度読み込める
+    pub fn response(&self) -> HttpResponse {
+        let mut response = HttpResponse::from_parts(
+            200,
+            "OK",
+            vec![Header::new(
+                "Content-Type".to_string(),
+                "text/html;charset=utf-8".to_string(),
+            )],
+            self.html().into_bytes(),
+        );
+        if let Some(url) = &self.retry_url {
+            response.set_url(url.clone());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::ResolveError;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_category() {
+        for (error, expected) in [
+            (
+                Error::from(ResolveError::NotFound("a.test".to_string())),
+                ErrorCategory::Dns,
+            ),
+            (
+                Error::from(ResolveError::Timeout("a.test".to_string())),
+                ErrorCategory::Dns,
+            ),
+            (
+                Error::Connect("Failed to connect to TCP stream: refused".to_string()),
+                ErrorCategory::Connect,
+            ),
+            (
+                Error::Tls("tls: certificate has expired".to_string()),
+                ErrorCategory::Tls,
+            ),
+            (
+                Error::Timeout("Failed to receive a response: timed out".to_string()),
+                ErrorCategory::Protocol,
+            ),
+            (
+                Error::Network("invalid chunk size: \"x\"".to_string()),
+                ErrorCategory::Protocol,
+            ),
+            (
+                Error::UnexpectedInput("invalid url: invalid port".to_string()),
+                ErrorCategory::Url,
+            ),
+        ] {
+            assert_eq!(expected, ErrorCategory::for_error(&error), "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_for_error() {
+        let page = ErrorPage::for_error(
+            "http://a.test/",
+            &Error::from(ResolveError::NotFound("a.test".to_string())),
+        );
+        let html = page.html();
+        assert!(html.contains("<h1>Server not found</h1>"), "{}", html);
+        assert!(html.contains("<p>http://a.test/</p>"), "{}", html);
+        assert!(html.contains("<p>Error: DNS</p>"), "{}", html);
+        assert!(
+            html.contains("<a href=\"http://a.test/\">Retry</a>"),
+            "{}",
+            html
+        );
+        assert_eq!(
+            Some("http://a.test/".to_string()),
+            page.response().url().map(|url| url.href())
+        );
+
+        // 正しくないURLは、再読み込みのリンクを表示しない
+        let page = ErrorPage::for_error(
+            "http://a.test:port/<p>",
+            &Error::UnexpectedInput("invalid url: invalid port".to_string()),
+        );
+        let html = page.html();
+        assert!(
+            html.contains("<p>http://a.test:port/&lt;p&gt;</p>"),
+            "{}",
+            html
+        );
+        assert!(!html.contains("Retry"), "{}", html);
+        assert_eq!(None, page.response().url());
+    }
+
+    #[test]
+    fn test_for_response() {
+        let mut response = HttpResponse::from_parts(404, "Not Found", Vec::new(), Vec::new());
+        response.set_url(
+            Url::new("http://a.test/missing".to_string())
+                .parse()
+                .expect("failed to parse url"),
+        );
+        let page = ErrorPage::for_response(&response).expect("no error page");
+        assert_eq!(ErrorCategory::Http(404), page.category());
+        let html = page.html();
+        assert!(html.contains("<h1>Client error</h1>"), "{}", html);
+        assert!(
+            html.contains("<p>Error: HTTP 404</p><p>404 Not Found</p>"),
+            "{}",
+            html
+        );
+
+        let response = HttpResponse::from_parts(500, "", Vec::new(), b"oops".to_vec());
+        assert_eq!(None, ErrorPage::for_response(&response));
+        let response = HttpResponse::from_parts(200, "OK", Vec::new(), Vec::new());
+        assert_eq!(None, ErrorPage::for_response(&response));
+    }
+}
@@ -34,6 +34,11 @@ pub struct HtmlParser {
     original_insertion_mode: InsertionMode,
     /// https://html.spec.whatwg.org/multipage/parsing.html#the-stack-of-open-elements
     stack_of_open_elements: Vec<Rc<RefCell<Node>>>,
+    /// <pre>の開始タグの直後の改行を無視するかどうか。入力が開始タグの直後で区切られた場合に、
+    /// 次の入力の先頭の改行を無視するために使う
+    skip_line_feed: bool,
+    /// 前回take_metadata_changedを呼んだ後に、<base>か<style>の内容が追加されたかどうか
+    metadata_changed: bool,
     t: HtmlTokenizer,
 }
 
@@ -44,10 +49,30 @@ impl HtmlParser {
             mode: InsertionMode::Initial,
             original_insertion_mode: InsertionMode::Initial,
             stack_of_open_elements: Vec::new(),
+            skip_line_feed: false,
+            metadata_changed: false,
             t,
         }
     }
 
+    /// 続きの入力を追加する。追加した後にconstruct_treeを呼ぶと、それまでに作ったDOMツリーに
+    /// 続きのノードを追加する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#overview-of-the-parsing-model
+    pub fn push_str(&mut self, chunk: &str) {
+        self.t.push_str(chunk);
+    }
+
+    /// これ以上入力がないことを示す。この後にconstruct_treeを呼ぶと、残りの入力を解析する
+    pub fn finish(&mut self) {
+        self.t.finish();
+    }
+
+    /// 前回呼んだ後に、ベースURLやスタイルシートを変える<base>か<style>が追加されたかを返す。
+    /// 途中までのDOMツリーを描画するときに、スタイルを計算し直すかを決めるために使う
+    pub fn take_metadata_changed(&mut self) -> bool {
+        core::mem::take(&mut self.metadata_changed)
+    }
+
     fn contain_in_stack(&mut self, element_kind: ElementKind) -> bool {
         for i in 0..self.stack_of_open_elements.len() {
             if self.stack_of_open_elements[i].borrow().element_kind() == Some(element_kind) {
@@ -109,28 +134,32 @@ impl HtmlParser {
             return;
         }
 
-        // 改行文字や空白文字のときはテキストノードを追加しない。
-        if c == '\n' || c == ' ' {
+        // 改行文字や空白文字のときはテキストノードを追加しない。ただし<pre>の中では空白も表示する
+        let preformatted = current.borrow().element_kind() == Some(ElementKind::Pre);
+        if !preformatted && (c == '\n' || c == ' ') {
             return;
         }
 
         let node = Rc::new(RefCell::new(self.create_char(c)));
+        Self::append_child(&current, &node);
+        self.stack_of_open_elements.push(node);
+    }
 
-        if current.borrow().first_child().is_some() {
-            current
-                .borrow()
-                .first_child()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
+    /// 子ノードの末尾に追加する。文書の末尾にだけノードが追加されるので、途中までのDOMツリーを
+    /// 描画するときは、追加された部分だけをレイアウトすればよい
+    fn append_child(parent: &Rc<RefCell<Node>>, node: &Rc<RefCell<Node>>) {
+        let last_child = parent.borrow().last_child().upgrade();
+        match last_child {
+            Some(last_child) => {
+                last_child.borrow_mut().set_next_sibling(Some(node.clone()));
+                node.borrow_mut()
+                    .set_previous_sibling(Rc::downgrade(&last_child));
+            }
+            None => parent.borrow_mut().set_first_child(Some(node.clone())),
         }
 
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
-
-        self.stack_of_open_elements.push(node);
+        parent.borrow_mut().set_last_child(Rc::downgrade(node));
+        node.borrow_mut().set_parent(Rc::downgrade(parent));
     }
 
     fn create_element(&self, tag: &str, attributes: Vec<Attribute>) -> Node {
@@ -145,42 +174,21 @@ impl HtmlParser {
         };
 
         let node = Rc::new(RefCell::new(self.create_element(tag, attributes)));
-
-        if current.borrow().first_child().is_some() {
-            let mut last_sibling = current.borrow().first_child();
-            loop {
-                last_sibling = match last_sibling {
-                    Some(ref node) => {
-                        if node.borrow().next_sibling().is_some() {
-                            node.borrow().next_sibling()
-                        } else {
-                            break;
-                        }
-                    }
-                    None => unimplemented!("last_sibling should be Some"),
-                };
-            }
-
-            last_sibling
-                .as_ref()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-            node.borrow_mut().set_previous_sibling(Rc::downgrade(
-                &last_sibling.expect("last_sibling should be Some"),
-            ))
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
-        }
-
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
+        Self::append_child(&current, &node);
 
         self.stack_of_open_elements.push(node);
     }
 
+    /// 今ある入力をすべて解析する。入力の途中で呼ばれた場合は、続きの入力が追加された後に
+    /// もう一度呼ぶと、止まったところから解析を続ける
     pub fn construct_tree(&mut self) -> Rc<RefCell<Window>> {
         let mut token = self.t.next();
+        if self.skip_line_feed && token.is_some() {
+            self.skip_line_feed = false;
+            if let Some(HtmlToken::Char('\n')) = token {
+                token = self.t.next();
+            }
+        }
 
         while token.is_some() {
             match self.mode {
@@ -273,6 +281,14 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
+                            // <base>は空要素なので、挿入した直後にスタックから取り除く
+                            if tag == "base" {
+                                self.insert_element(tag, attributes.to_vec());
+                                self.stack_of_open_elements.pop();
+                                self.metadata_changed = true;
+                                token = self.t.next();
+                                continue;
+                            }
                             if tag == "style" || tag == "script" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.original_insertion_mode = self.mode;
@@ -362,6 +378,24 @@ impl HtmlParser {
                                 token = self.t.next();
                                 continue;
                             }
+                            "pre" => {
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                // 開始タグの直後の改行は無視する
+                                match token {
+                                    Some(HtmlToken::Char('\n')) => token = self.t.next(),
+                                    None => self.skip_line_feed = true,
+                                    _ => {}
+                                }
+                                continue;
+                            }
+                            "img" => {
+                                // 空要素なので、すぐにスタックから取り除く
+                                self.insert_element(tag, attributes.to_vec());
+                                self.pop_current_node(ElementKind::Img);
+                                token = self.t.next();
+                                continue;
+                            }
                             _ => {
                                 token = self.t.next();
                             }
@@ -401,7 +435,7 @@ impl HtmlParser {
                                     self.pop_until(element_kind);
                                     continue;
                                 }
-                                "a" => {
+                                "a" | "pre" => {
                                     let element_kind = ElementKind::from_str(tag)
                                         .expect("failed to convert string to ElementKind");
                                     token = self.t.next();
@@ -431,6 +465,7 @@ impl HtmlParser {
                         Some(HtmlToken::EndTag { ref tag }) => {
                             if tag == "style" {
                                 self.pop_until(ElementKind::Style);
+                                self.metadata_changed = true;
                                 self.mode = self.original_insertion_mode;
                                 token = self.t.next();
                                 continue;
@@ -499,6 +534,7 @@ impl HtmlParser {
 mod tests {
     use super::*;
     use crate::alloc::string::ToString;
+    use crate::renderer::dom::api::get_target_element_node;
     use alloc::vec;
 
     #[test]
@@ -672,4 +708,187 @@ mod tests {
             text
         );
     }
+
+    #[test]
+    fn test_base_in_head() {
+        let html = "<html><head><base href=/docs/></head><body>text</body></html>".to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let document = window.borrow().document();
+
+        let head = document
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document")
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of html");
+        let base = head
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of head");
+        assert_eq!(
+            Some("/docs/".to_string()),
+            base.borrow()
+                .get_element()
+                .expect("base should be an element")
+                .get_attribute("href")
+        );
+
+        // <base>の後の<body>がheadの子にならないことを確認する
+        let body = head
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of head");
+        assert_eq!(
+            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+                "body",
+                Vec::new()
+            ))))),
+            body
+        );
+    }
+
+    #[test]
+    fn test_plaintext() {
+        let t = HtmlTokenizer::new_plaintext("\n<p>  a\n".to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        let document = window.borrow().document();
+
+        let body = document
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document")
+            .borrow()
+            .last_child()
+            .upgrade()
+            .expect("failed to get a last child of html");
+        let pre = body
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of body");
+        assert_eq!(
+            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+                "pre",
+                Vec::new()
+            ))))),
+            pre
+        );
+
+        // タグや空白、先頭の改行もそのまま残る
+        let text = pre
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of pre");
+        assert_eq!(
+            Rc::new(RefCell::new(Node::new(NodeKind::Text(
+                "\n<p>  a\n".to_string()
+            )))),
+            text
+        );
+    }
+
+    #[test]
+    fn test_img() {
+        let html = "<html><head></head><body><img src=a.png><p>text</p></body></html>".to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let document = window.borrow().document();
+
+        let body = document
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document")
+            .borrow()
+            .last_child()
+            .upgrade()
+            .expect("failed to get a last child of html");
+        let img = body
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of body");
+        assert_eq!(Some(ElementKind::Img), img.borrow().element_kind());
+        assert!(img.borrow().first_child().is_none());
+
+        // <img>の後の<p>が<img>の子にならないことを確認する
+        let p = img
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of img");
+        assert_eq!(Some(ElementKind::P), p.borrow().element_kind());
+    }
+
+    /// ノードの種類と子の並びを文字列にする
+    fn dump(node: &Rc<RefCell<Node>>) -> String {
+        let mut s = alloc::format!("{:?}(", node.borrow().kind());
+        let mut child = node.borrow().first_child();
+        while let Some(c) = child {
+            s.push_str(&dump(&c));
+            child = c.borrow().next_sibling();
+        }
+        s.push(')');
+        s
+    }
+
+    #[test]
+    fn test_push_str() {
+        let html = "<html><head><style>p{color:red;}</style></head><body><p class=a>ab</p><pre>\n x</pre><a href=b>c</a></body></html>";
+        let expected = dump(
+            &HtmlParser::new(HtmlTokenizer::new(html.to_string()))
+                .construct_tree()
+                .borrow()
+                .document(),
+        );
+
+        // どの位置で入力が区切られても、一度にすべて解析した場合と同じツリーになる
+        for i in 0..=html.len() {
+            let mut parser = HtmlParser::new(HtmlTokenizer::new(String::new()));
+            parser.push_str(&html[..i]);
+            parser.construct_tree();
+            parser.push_str(&html[i..]);
+            let window = parser.construct_tree();
+            assert_eq!(expected, dump(&window.borrow().document()), "{}", i);
+        }
+    }
+
+    #[test]
+    fn test_text_after_children() {
+        let html = "<html><head></head><body><p><a>1</a><a>2</a>3</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let p = get_target_element_node(Some(window.borrow().document()), ElementKind::P)
+            .expect("failed to get p");
+
+        let mut children = Vec::new();
+        let mut child = p.borrow().first_child();
+        while let Some(c) = child {
+            children.push(c.borrow().kind());
+            child = c.borrow().next_sibling();
+        }
+        assert_eq!(
+            vec![
+                NodeKind::Element(Element::new("a", Vec::new())),
+                NodeKind::Element(Element::new("a", Vec::new())),
+                NodeKind::Text("3".to_string()),
+            ],
+            children
+        );
+    }
+
+    #[test]
+    fn test_metadata_changed() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(String::new()));
+        parser.push_str("<html><head><style>p{color:red;}");
+        parser.construct_tree();
+        // <style>の内容は終了タグまで揃わない
+        assert!(!parser.take_metadata_changed());
+
+        parser.push_str("</style><base href=\"/a/\">");
+        parser.construct_tree();
+        assert!(parser.take_metadata_changed());
+        assert!(!parser.take_metadata_changed());
+
+        parser.push_str("</head><body><p>text</p></body></html>");
+        parser.construct_tree();
+        assert!(!parser.take_metadata_changed());
+    }
 }
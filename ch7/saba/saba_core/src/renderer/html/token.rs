@@ -1,4 +1,5 @@
 use crate::renderer::html::attribute::Attribute;
+use alloc::collections::VecDeque;
 use alloc::string::String;
 use alloc::vec::Vec;
 
@@ -58,6 +59,29 @@ pub enum State {
     ScriptDataEndTagName,
     /// https://html.spec.whatwg.org/multipage/parsing.html#temporary-buffer
     TemporaryBuffer,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#plaintext-state
+    Plaintext,
+}
+
+/// サポートする名前付き文字参照。名前は";"まで含める
+/// https://html.spec.whatwg.org/multipage/named-characters.html
+const NAMED_CHARACTER_REFERENCES: [(&str, char); 6] = [
+    ("amp;", '&'),
+    ("lt;", '<'),
+    ("gt;", '>'),
+    ("quot;", '"'),
+    ("apos;", '\''),
+    ("nbsp;", '\u{A0}'),
+];
+
+/// 文字参照を読んだ結果
+enum CharacterReference {
+    /// 文字参照だった
+    Char(char),
+    /// 文字参照ではないので、"&"をそのまま文字として扱う
+    NotReference,
+    /// 入力の途中で区切られているので、続きの入力が必要
+    Incomplete,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -68,6 +92,9 @@ pub struct HtmlTokenizer {
     latest_token: Option<HtmlToken>,
     input: Vec<char>,
     buf: String,
+    pending_tokens: VecDeque<HtmlToken>,
+    /// 入力がすべて揃っているかどうか。push_strで入力を追加している間はfalseになる
+    finished: bool,
 }
 
 impl HtmlTokenizer {
@@ -79,6 +106,94 @@ impl HtmlTokenizer {
             latest_token: None,
             input: html.chars().collect(),
             buf: String::new(),
+            pending_tokens: VecDeque::new(),
+            finished: true,
+        }
+    }
+
+    /// text/plainの文書を読み込むためのトークナイザ。<pre>の開始タグの後に、入力のすべての文字を
+    /// そのまま文字トークンとして返す
+    /// https://html.spec.whatwg.org/multipage/document-lifecycle.html#read-text
+    pub fn new_plaintext(text: String) -> Self {
+        let mut tokenizer = Self::new(text);
+        tokenizer.state = State::Plaintext;
+        tokenizer.pending_tokens.push_back(HtmlToken::StartTag {
+            tag: String::from("pre"),
+            self_closing: false,
+            attributes: Vec::new(),
+        });
+        // <pre>の直後の改行は無視されるので、本文の先頭の改行が消えないように改行を入れる
+        tokenizer.pending_tokens.push_back(HtmlToken::Char('\n'));
+        tokenizer
+    }
+
+    /// ネットワークから受け取った続きの入力を追加する。入力の途中で止まっているトークンは、
+    /// 追加した入力から続けて作る
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-input-byte-stream
+    pub fn push_str(&mut self, chunk: &str) {
+        self.input.extend(chunk.chars());
+        self.finished = false;
+    }
+
+    /// これ以上入力がないことを示す。入力の終わりで区切られていた文字参照を解決する
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// "&"の後の文字参照を読む。文字参照だった場合は、その分だけ入力を進める
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+    fn consume_character_reference(&mut self) -> CharacterReference {
+        let rest = &self.input[self.pos..];
+
+        if rest.first() == Some(&'#') {
+            return self.consume_numeric_character_reference();
+        }
+
+        for (name, c) in NAMED_CHARACTER_REFERENCES {
+            let name: Vec<char> = name.chars().collect();
+            if rest.starts_with(&name) {
+                self.pos += name.len();
+                return CharacterReference::Char(c);
+            }
+            if !self.finished && name.starts_with(rest) {
+                return CharacterReference::Incomplete;
+            }
+        }
+        CharacterReference::NotReference
+    }
+
+    /// "&#38;"や"&#x26;"のような数値文字参照を読む
+    /// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+    fn consume_numeric_character_reference(&mut self) -> CharacterReference {
+        let rest = &self.input[self.pos + 1..];
+        let (radix, start) = match rest.first() {
+            Some('x') | Some('X') => (16, 1),
+            _ => (10, 0),
+        };
+
+        let mut value: u32 = 0;
+        let mut digits = 0;
+        for c in &rest[start..] {
+            if *c == ';' {
+                if digits == 0 {
+                    break;
+                }
+                self.pos += 1 + start + digits + 1;
+                // 範囲外の値やサロゲートは、U+FFFDに置き換える
+                return CharacterReference::Char(char::from_u32(value).unwrap_or('\u{FFFD}'));
+            }
+            match c.to_digit(radix) {
+                Some(digit) => {
+                    value = value.saturating_mul(radix).saturating_add(digit);
+                    digits += 1;
+                }
+                None => return CharacterReference::NotReference,
+            }
+        }
+        if self.finished || digits < rest.len().saturating_sub(start) {
+            CharacterReference::NotReference
+        } else {
+            CharacterReference::Incomplete
         }
     }
 
@@ -172,6 +287,25 @@ impl HtmlTokenizer {
         }
     }
 
+    /// 属性の値に文字を追加する。"&"の場合は文字参照として解決する。続きの入力が必要な場合は
+    /// "&"から読み直すようにしてfalseを返す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#attribute-value-(double-quoted)-state
+    fn append_attribute_value(&mut self, c: char) -> bool {
+        if c != '&' {
+            self.append_attribute(c, /*is_name*/ false);
+            return true;
+        }
+        match self.consume_character_reference() {
+            CharacterReference::Char(c) => self.append_attribute(c, /*is_name*/ false),
+            CharacterReference::NotReference => self.append_attribute('&', /*is_name*/ false),
+            CharacterReference::Incomplete => {
+                self.reconsume = true;
+                return false;
+            }
+        }
+        true
+    }
+
     fn set_self_closing_flag(&mut self) {
         assert!(self.latest_token.is_some());
 
@@ -192,11 +326,17 @@ impl Iterator for HtmlTokenizer {
     type Item = HtmlToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
-            return None;
+        if let Some(token) = self.pending_tokens.pop_front() {
+            return Some(token);
         }
 
         loop {
+            // 入力を使い切った場合は、続きの入力が追加されるまで待つ。作りかけのトークンや状態は
+            // そのまま残しておく
+            if !self.reconsume && self.pos >= self.input.len() {
+                return None;
+            }
+
             let c = match self.reconsume {
                 true => self.reconsume_input(),
                 false => self.consume_next_input(),
@@ -209,6 +349,18 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '&' {
+                        match self.consume_character_reference() {
+                            CharacterReference::Char(c) => return Some(HtmlToken::Char(c)),
+                            CharacterReference::NotReference => return Some(HtmlToken::Char('&')),
+                            CharacterReference::Incomplete => {
+                                // 続きの入力が追加されたら、"&"から読み直す
+                                self.reconsume = true;
+                                return None;
+                            }
+                        }
+                    }
+
                     if self.is_eof() {
                         return Some(HtmlToken::Eof);
                     }
@@ -363,7 +515,9 @@ impl Iterator for HtmlTokenizer {
                         return Some(HtmlToken::Eof);
                     }
 
-                    self.append_attribute(c, /*is_name*/ false);
+                    if !self.append_attribute_value(c) {
+                        return None;
+                    }
                 }
                 State::AttributeValueSingleQuoted => {
                     if c == '\'' {
@@ -375,7 +529,9 @@ impl Iterator for HtmlTokenizer {
                         return Some(HtmlToken::Eof);
                     }
 
-                    self.append_attribute(c, /*is_name*/ false);
+                    if !self.append_attribute_value(c) {
+                        return None;
+                    }
                 }
                 State::AttributeValueUnquoted => {
                     if c == ' ' {
@@ -392,7 +548,9 @@ impl Iterator for HtmlTokenizer {
                         return Some(HtmlToken::Eof);
                     }
 
-                    self.append_attribute(c, /*is_name*/ false);
+                    if !self.append_attribute_value(c) {
+                        return None;
+                    }
                 }
                 State::AfterAttributeValueQuoted => {
                     if c == ' ' {
@@ -502,6 +660,9 @@ impl Iterator for HtmlTokenizer {
                     self.buf.remove(0);
                     return Some(HtmlToken::Char(c));
                 }
+                State::Plaintext => {
+                    return Some(HtmlToken::Char(c));
+                }
             }
         }
     }
@@ -619,4 +780,92 @@ mod tests {
             assert_eq!(Some(e), tokenizer.next());
         }
     }
+
+    #[test]
+    fn test_plaintext() {
+        let text = "<p>a</p>".to_string();
+        let mut tokenizer = HtmlTokenizer::new_plaintext(text);
+        let mut expected = vec![
+            HtmlToken::StartTag {
+                tag: "pre".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            },
+            HtmlToken::Char('\n'),
+        ];
+        expected.extend("<p>a</p>".chars().map(HtmlToken::Char));
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_push_str() {
+        // タグや属性の途中で入力が区切られても、続きの入力からトークンを作る
+        let mut tokenizer = HtmlTokenizer::new("<p cla".to_string());
+        assert!(tokenizer.next().is_none());
+        tokenizer.push_str("ss=\"A\">x</");
+        let mut attr = Attribute::new();
+        attr.add_char('c', true);
+        attr.add_char('l', true);
+        attr.add_char('a', true);
+        attr.add_char('s', true);
+        attr.add_char('s', true);
+        attr.add_char('A', false);
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: vec![attr],
+            }),
+            tokenizer.next()
+        );
+        assert_eq!(Some(HtmlToken::Char('x')), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+        tokenizer.push_str("p>");
+        assert_eq!(
+            Some(HtmlToken::EndTag {
+                tag: "p".to_string(),
+            }),
+            tokenizer.next()
+        );
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_character_reference() {
+        let html = "a&lt;b&amp;&#60;&#x3e;&foo;&#;&".to_string();
+        let tokenizer = HtmlTokenizer::new(html);
+        let expected: Vec<HtmlToken> = "a<b&<>&foo;&#;&".chars().map(HtmlToken::Char).collect();
+        assert_eq!(expected, tokenizer.collect::<Vec<HtmlToken>>());
+
+        let html = "<a href=\"/?a=1&amp;b=&quot;2&quot;&c=3\">".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let attributes = match tokenizer.next() {
+            Some(HtmlToken::StartTag { attributes, .. }) => attributes,
+            token => panic!("unexpected token: {:?}", token),
+        };
+        assert_eq!("/?a=1&b=\"2\"&c=3", attributes[0].value());
+    }
+
+    #[test]
+    fn test_incomplete_character_reference() {
+        let mut tokenizer = HtmlTokenizer::new(String::new());
+        tokenizer.push_str("x&l");
+        assert_eq!(Some(HtmlToken::Char('x')), tokenizer.next());
+        // 文字参照の途中で区切られている場合は、続きの入力を待つ
+        assert!(tokenizer.next().is_none());
+        tokenizer.push_str("t;&#6");
+        assert_eq!(Some(HtmlToken::Char('<')), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+        tokenizer.push_str("0;&am");
+        assert_eq!(Some(HtmlToken::Char('<')), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+
+        // 入力が終わった場合は、文字参照ではないものとして扱う
+        tokenizer.finish();
+        let expected: Vec<HtmlToken> = "&am".chars().map(HtmlToken::Char).collect();
+        assert_eq!(expected, tokenizer.collect::<Vec<HtmlToken>>());
+    }
 }
@@ -1,8 +1,10 @@
+use crate::http::cookie::CookieJar;
 use crate::renderer::dom::api::get_element_by_id;
 use crate::renderer::dom::node::Node as DomNode;
 use crate::renderer::dom::node::NodeKind as DomNodeKind;
 use crate::renderer::js::ast::Node;
 use crate::renderer::js::ast::Program;
+use crate::url::Url;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
@@ -133,6 +135,8 @@ pub struct JsRuntime {
     dom_root: Rc<RefCell<DomNode>>,
     env: Rc<RefCell<Environment>>,
     functions: Vec<Function>,
+    /// document.cookieで読み書きする、ドキュメントのURLとCookieの保存先
+    cookie_jar: Option<(Url, Rc<RefCell<CookieJar>>)>,
 }
 
 impl JsRuntime {
@@ -141,9 +145,38 @@ impl JsRuntime {
             dom_root,
             functions: Vec::new(),
             env: Rc::new(RefCell::new(Environment::new(None))),
+            cookie_jar: None,
         }
     }
 
+    pub fn set_cookie_jar(&mut self, url: Url, cookie_jar: Rc<RefCell<CookieJar>>) {
+        self.cookie_jar = Some((url, cookie_jar));
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-cookie
+    fn document_cookie(&self) -> String {
+        match &self.cookie_jar {
+            Some((url, cookie_jar)) => cookie_jar.borrow_mut().document_cookie(url),
+            None => String::new(),
+        }
+    }
+
+    fn set_document_cookie(&self, value: &str) {
+        if let Some((url, cookie_jar)) = &self.cookie_jar {
+            cookie_jar.borrow_mut().set_document_cookie(url, value);
+        }
+    }
+
+    /// `document.cookie`のメンバー式かどうか
+    fn is_document_cookie(node: &Option<Rc<Node>>) -> bool {
+        let (object, property) = match node.as_deref() {
+            Some(Node::MemberExpression { object, property }) => (object, property),
+            _ => return false,
+        };
+        matches!(object.as_deref(), Some(Node::Identifier(name)) if name == "document")
+            && matches!(property.as_deref(), Some(Node::Identifier(name)) if name == "cookie")
+    }
+
     /// (bool, Option<RuntimeValue>) のタプルを返す
     ///   bool: ブラウザAPIが呼ばれたかどうか。trueなら何かしらのAPIが呼ばれたことを示す
     ///   Option<RuntimeValue>: ブラウザAPIの呼び出しによって得られた結果
@@ -216,6 +249,13 @@ impl JsRuntime {
                 if operator != &'=' {
                     return None;
                 }
+                // document.cookie = "name=value"; はCookieを1つ保存する
+                if Self::is_document_cookie(left) {
+                    if let Some(value) = self.eval(right, env.clone()) {
+                        self.set_document_cookie(&value.to_string());
+                    }
+                    return None;
+                }
                 // 変数の再割り当て
                 if let Some(node) = left {
                     if let Node::Identifier(id) = node.borrow() {
@@ -248,6 +288,9 @@ impl JsRuntime {
                 None
             }
             Node::MemberExpression { object, property } => {
+                if Self::is_document_cookie(&Some(node.clone())) {
+                    return Some(RuntimeValue::StringLiteral(self.document_cookie()));
+                }
                 let object_value = match self.eval(object, env.clone()) {
                     Some(value) => value,
                     None => return None,
@@ -380,6 +423,7 @@ impl JsRuntime {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::cookie::CookieSource;
     use crate::renderer::js::ast::JsParser;
     use crate::renderer::js::token::JsLexer;
 
@@ -544,4 +588,43 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_document_cookie() {
+        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let url = Url::new("http://example.com/".to_string())
+            .parse()
+            .expect("failed to parse url");
+        let cookie_jar = Rc::new(RefCell::new(CookieJar::new()));
+        cookie_jar
+            .borrow_mut()
+            .set_cookie(&url, "sid=1; HttpOnly", CookieSource::Http);
+
+        let input = "document.cookie = \"theme=dark; Path=/\"; document.cookie = \"sid=2\"; document.cookie"
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom);
+        runtime.set_cookie_jar(url.clone(), cookie_jar.clone());
+        // HttpOnlyのCookieはスクリプトから読めず、上書きもできない
+        let expected = [
+            None,
+            None,
+            Some(RuntimeValue::StringLiteral("theme=dark".to_string())),
+        ];
+        let mut i = 0;
+
+        for node in ast.body() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+            i += 1;
+        }
+        assert_eq!(
+            "sid=1; theme=dark".to_string(),
+            cookie_jar
+                .borrow_mut()
+                .cookie_string(&url, CookieSource::Http, None, "GET")
+        );
+    }
 }
@@ -15,6 +15,7 @@ pub struct ComputedStyle {
     display: Option<DisplayType>,
     font_size: Option<FontSize>,
     text_decoration: Option<TextDecoration>,
+    white_space: Option<WhiteSpace>,
     height: Option<f64>,
     width: Option<f64>,
 }
@@ -27,6 +28,7 @@ impl ComputedStyle {
             display: None,
             font_size: None,
             text_decoration: None,
+            white_space: None,
             height: None,
             width: None,
         }
@@ -50,6 +52,9 @@ impl ComputedStyle {
             {
                 self.text_decoration = Some(parent_style.text_decoration());
             }
+            if self.white_space.is_none() && parent_style.white_space() != WhiteSpace::Normal {
+                self.white_space = Some(parent_style.white_space());
+            }
         }
 
         // 各プロパティに対して、初期値を設定する
@@ -68,6 +73,9 @@ impl ComputedStyle {
         if self.text_decoration.is_none() {
             self.text_decoration = Some(TextDecoration::default(node));
         }
+        if self.white_space.is_none() {
+            self.white_space = Some(WhiteSpace::default(node));
+        }
         if self.height.is_none() {
             self.height = Some(0.0);
         }
@@ -115,6 +123,11 @@ impl ComputedStyle {
             .expect("failed to access CSS property: text_decoration")
     }
 
+    pub fn white_space(&self) -> WhiteSpace {
+        self.white_space
+            .expect("failed to access CSS property: white_space")
+    }
+
     pub fn set_height(&mut self, height: f64) {
         self.height = Some(height);
     }
@@ -310,3 +323,24 @@ impl TextDecoration {
         }
     }
 }
+
+/// https://www.w3.org/TR/css-text-3/#white-space-property
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WhiteSpace {
+    /// 空白をまとめ、行を折り返す
+    Normal,
+    /// 空白と改行をそのまま表示する
+    Pre,
+}
+
+impl WhiteSpace {
+    fn default(node: &Rc<RefCell<Node>>) -> Self {
+        match &node.borrow().kind() {
+            NodeKind::Element(element) => match element.kind() {
+                ElementKind::Pre => WhiteSpace::Pre,
+                _ => WhiteSpace::Normal,
+            },
+            _ => WhiteSpace::Normal,
+        }
+    }
+}
@@ -9,12 +9,14 @@ use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::cssom::Declaration;
 use crate::renderer::css::cssom::Selector;
 use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::dom::node::ElementKind;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
 use crate::renderer::layout::computed_style::Color;
 use crate::renderer::layout::computed_style::ComputedStyle;
 use crate::renderer::layout::computed_style::DisplayType;
 use crate::renderer::layout::computed_style::FontSize;
+use crate::renderer::layout::computed_style::WhiteSpace;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
@@ -47,6 +49,45 @@ fn split_text(line: String, char_width: i64) -> Vec<String> {
     }
     result
 }
+
+/// タブの幅（文字数）
+/// https://www.w3.org/TR/css-text-3/#tab-size-property
+const TAB_SIZE: usize = 8;
+
+/// white-space: preのテキストを行に分ける。空白はそのまま残し、内容の幅を超える行は文字単位で
+/// 折り返す
+/// https://www.w3.org/TR/css-text-3/#white-space-property
+fn split_preformatted_text(text: &str, char_width: i64) -> Vec<String> {
+    let max_chars = ((CONTENT_AREA_WIDTH / char_width) as usize).max(1);
+    let mut result = Vec::new();
+    for line in text.lines() {
+        let mut expanded = String::new();
+        let mut column = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let spaces = TAB_SIZE - column % TAB_SIZE;
+                for _ in 0..spaces {
+                    expanded.push(' ');
+                }
+                column += spaces;
+            } else {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+
+        let chars: Vec<char> = expanded.chars().collect();
+        if chars.is_empty() {
+            result.push(String::new());
+            continue;
+        }
+        for chunk in chars.chunks(max_chars) {
+            result.push(chunk.iter().collect());
+        }
+    }
+    result
+}
+
 pub fn create_layout_object(
     node: &Option<Rc<RefCell<Node>>>,
     parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
@@ -85,6 +126,44 @@ pub fn create_layout_object(
     None
 }
 
+/// 子ノードの大きさを、前から順に足し合わせたもの
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChildrenSize {
+    /// ブロック要素の高さ。インライン要素が横に並んでいる場合は、最初のものの高さだけを足す
+    block_height: i64,
+    /// インライン要素の横幅と高さ。全ての子ノードを足す
+    width: i64,
+    height: i64,
+    last_kind: LayoutObjectKind,
+}
+
+impl ChildrenSize {
+    pub fn new() -> Self {
+        Self {
+            block_height: 0,
+            width: 0,
+            height: 0,
+            last_kind: LayoutObjectKind::Block,
+        }
+    }
+
+    /// 次の子ノードの大きさを足す
+    pub fn add(&mut self, child: &LayoutObject) {
+        if self.last_kind == LayoutObjectKind::Block || child.kind() == LayoutObjectKind::Block {
+            self.block_height += child.size.height();
+        }
+        self.width += child.size.width();
+        self.height += child.size.height();
+        self.last_kind = child.kind();
+    }
+}
+
+impl Default for ChildrenSize {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutObjectKind {
     Block,
@@ -145,9 +224,19 @@ impl LayoutObject {
                     }];
                 }
             }
-            LayoutObjectKind::Inline => { // (d2)
-                 // 本書のブラウザでは、描画するインライン要素はない。
-                 // <img>タグなどをサポートした場合はこのアームの中で処理をする
+            LayoutObjectKind::Inline => {
+                // (d2)
+                // 画像のデコードはサポートしていないので、<img>は代替テキストを表示する
+                if let Some(alt) = self.image_alt_text() {
+                    if alt.is_empty() {
+                        return vec![];
+                    }
+                    return vec![DisplayItem::Text {
+                        text: alt,
+                        style: self.style(),
+                        layout_point: self.point(),
+                    }];
+                }
             }
             LayoutObjectKind::Text => {
                 // (d3)
@@ -159,6 +248,24 @@ impl LayoutObject {
                         FontSize::XLarge => 2,
                         FontSize::XXLarge => 3,
                     };
+                    if self.style.white_space() == WhiteSpace::Pre {
+                        let lines = split_preformatted_text(&t, CHAR_WIDTH * ratio);
+                        for (i, line) in lines.into_iter().enumerate() {
+                            if line.is_empty() {
+                                continue;
+                            }
+                            v.push(DisplayItem::Text {
+                                text: line,
+                                style: self.style(),
+                                layout_point: LayoutPoint::new(
+                                    self.point().x(),
+                                    self.point().y() + CHAR_HEIGHT_WITH_PADDING * ratio * i as i64,
+                                ),
+                            });
+                        }
+                        return v;
+                    }
+
                     let plain_text = t
                         .replace("\n", " ")
                         .split(' ')
@@ -189,6 +296,19 @@ impl LayoutObject {
     }
 
     pub fn compute_size(&mut self, parent_size: LayoutSize) {
+        let mut children = ChildrenSize::new();
+        let mut child = self.first_child();
+        while let Some(c) = child {
+            children.add(&c.borrow());
+            child = c.borrow().next_sibling();
+        }
+
+        self.compute_size_with_children(parent_size, children);
+    }
+
+    /// compute_sizeと同じだが、子ノードの大きさの合計を受け取る。末尾に子ノードが追加された
+    /// ときに、すべての子ノードをたどり直さないために使う
+    pub fn compute_size_with_children(&mut self, parent_size: LayoutSize, children: ChildrenSize) {
         let mut size = LayoutSize::new(0, 0);
 
         match self.kind() {
@@ -197,45 +317,20 @@ impl LayoutObject {
 
                 // 全ての子ノードの高さを足し合わせた結果が高さになる。
                 // ただし、インライン要素が横に並んでいる場合は注意が必要
-                let mut height = 0;
-                let mut child = self.first_child();
-                let mut previous_child_kind = LayoutObjectKind::Block;
-                while child.is_some() {
-                    let c = match child {
-                        Some(c) => c,
-                        None => panic!("first child should exist"),
-                    };
-
-                    if previous_child_kind == LayoutObjectKind::Block
-                        || c.borrow().kind() == LayoutObjectKind::Block
-                    {
-                        height += c.borrow().size.height();
-                    }
-
-                    previous_child_kind = c.borrow().kind();
-                    child = c.borrow().next_sibling();
-                }
-                size.set_height(height);
+                size.set_height(children.block_height);
             }
             LayoutObjectKind::Inline => {
-                // 全ての子ノードの高さと横幅を足し合わせた結果が現在のノードの高さと横幅とになる
-                let mut width = 0;
-                let mut height = 0;
-                let mut child = self.first_child();
-                while child.is_some() {
-                    let c = match child {
-                        Some(c) => c,
-                        None => panic!("first child should exist"),
-                    };
-
-                    width += c.borrow().size.width();
-                    height += c.borrow().size.height();
-
-                    child = c.borrow().next_sibling();
+                if let Some(alt) = self.image_alt_text() {
+                    // <img>は代替テキストの大きさにする
+                    size.set_width(CHAR_WIDTH * alt.chars().count() as i64);
+                    size.set_height(CHAR_HEIGHT_WITH_PADDING);
+                    self.size = size;
+                    return;
                 }
 
-                size.set_width(width);
-                size.set_height(height);
+                // 全ての子ノードの高さと横幅を足し合わせた結果が現在のノードの高さと横幅とになる
+                size.set_width(children.width);
+                size.set_height(children.height);
             }
             LayoutObjectKind::Text => {
                 if let NodeKind::Text(t) = self.node_kind() {
@@ -244,6 +339,15 @@ impl LayoutObject {
                         FontSize::XLarge => 2,
                         FontSize::XXLarge => 3,
                     };
+                    if self.style.white_space() == WhiteSpace::Pre {
+                        let lines = split_preformatted_text(&t, CHAR_WIDTH * ratio);
+                        let max_chars = lines.iter().map(|l| l.chars().count()).max();
+                        size.set_width(CHAR_WIDTH * ratio * max_chars.unwrap_or(0) as i64);
+                        size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio * lines.len() as i64);
+                        self.size = size;
+                        return;
+                    }
+
                     let width = CHAR_WIDTH * ratio * t.len() as i64;
                     if width > CONTENT_AREA_WIDTH {
                         // テキストが複数行のとき
@@ -417,6 +521,21 @@ impl LayoutObject {
         self.kind
     }
 
+    /// <img>の場合は代替テキスト（alt属性）を返す
+    /// https://html.spec.whatwg.org/multipage/images.html#alternative-text
+    fn image_alt_text(&self) -> Option<String> {
+        match self.node.borrow().kind() {
+            NodeKind::Element(e) if e.kind() == ElementKind::Img => {
+                Some(e.get_attribute("alt").unwrap_or_default())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn node(&self) -> Rc<RefCell<Node>> {
+        self.node.clone()
+    }
+
     pub fn node_kind(&self) -> NodeKind {
         self.node.borrow().kind().clone()
     }
@@ -5,6 +5,7 @@ use crate::renderer::dom::api::get_target_element_node;
 use crate::renderer::dom::node::ElementKind;
 use crate::renderer::dom::node::Node;
 use crate::renderer::layout::layout_object::create_layout_object;
+use crate::renderer::layout::layout_object::ChildrenSize;
 use crate::renderer::layout::layout_object::LayoutObject;
 use crate::renderer::layout::layout_object::LayoutObjectKind;
 use crate::renderer::layout::layout_object::LayoutPoint;
@@ -100,9 +101,23 @@ fn build_layout_tree(
     layout_object
 }
 
+/// 各深さで最後のレイアウトオブジェクト。読み込み中の文書では、ノードは末尾にだけ追加されるので、
+/// 追加されたノードはこれらの子か兄弟になる
+#[derive(Debug, Clone)]
+struct TailObject {
+    object: Rc<RefCell<LayoutObject>>,
+    /// 前の兄弟の大きさの合計
+    preceding: ChildrenSize,
+    /// 表示項目のうち、このオブジェクトの項目の位置と数
+    item_start: usize,
+    item_count: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct LayoutView {
     root: Option<Rc<RefCell<LayoutObject>>>,
+    /// 最後に描画したときの、浅い順に並べた末尾のオブジェクト
+    tails: Vec<TailObject>,
 }
 
 impl LayoutView {
@@ -113,6 +128,7 @@ impl LayoutView {
 
         let mut tree = Self {
             root: build_layout_tree(&body_root, &None, cssom),
+            tails: Vec::new(),
         };
 
         tree.update_layout();
@@ -224,29 +240,206 @@ impl LayoutView {
         );
     }
 
-    fn paint_node(node: &Option<Rc<RefCell<LayoutObject>>>, display_items: &mut Vec<DisplayItem>) {
-        match node {
-            Some(n) => {
-                display_items.extend(n.borrow_mut().paint());
+    /// firstから続く兄弟とその子孫を、文書の順に描画する。最後の兄弟から最後の子をたどった
+    /// オブジェクトをtailsに追加する
+    fn paint_siblings(
+        first: Option<Rc<RefCell<LayoutObject>>>,
+        mut preceding: ChildrenSize,
+        display_items: &mut Vec<DisplayItem>,
+        tails: &mut Vec<TailObject>,
+    ) {
+        let depth = tails.len();
+        let mut node = first;
+        while let Some(n) = node {
+            // 前の兄弟の子孫は末尾ではなくなる
+            tails.truncate(depth);
+            let item_start = display_items.len();
+            display_items.extend(n.borrow_mut().paint());
+            tails.push(TailObject {
+                object: n.clone(),
+                preceding,
+                item_start,
+                item_count: display_items.len() - item_start,
+            });
+            preceding.add(&n.borrow());
 
-                let first_child = n.borrow().first_child();
-                Self::paint_node(&first_child, display_items);
+            let first_child = n.borrow().first_child();
+            Self::paint_siblings(first_child, ChildrenSize::new(), display_items, tails);
 
-                let next_sibling = n.borrow().next_sibling();
-                Self::paint_node(&next_sibling, display_items);
-            }
-            None => (),
+            node = n.borrow().next_sibling();
         }
     }
 
-    pub fn paint(&self) -> Vec<DisplayItem> {
+    /// すべてのオブジェクトを描画する。末尾のオブジェクトの表示項目の位置を覚えておき、
+    /// updateで使う
+    pub fn paint(&mut self) -> Vec<DisplayItem> {
         let mut display_items = Vec::new();
 
-        Self::paint_node(&self.root, &mut display_items);
+        self.tails.clear();
+        Self::paint_siblings(
+            self.root(),
+            ChildrenSize::new(),
+            &mut display_items,
+            &mut self.tails,
+        );
 
         display_items
     }
 
+    /// 子のレイアウトを計算するときに渡す親の大きさ。インライン要素の大きさは子の大きさから
+    /// 決まるので、新しく作ったレイアウトツリーと同じように0にする
+    fn size_for_children(parent: &LayoutObject) -> LayoutSize {
+        match parent.kind() {
+            LayoutObjectKind::Block => parent.size(),
+            _ => LayoutSize::new(0, 0),
+        }
+    }
+
+    /// 前回の描画の後に文書の末尾に追加されたノードだけをレイアウトし、display_itemsのうち
+    /// 大きさが変わった末尾のオブジェクトと、追加されたオブジェクトの項目だけを描き直す。
+    /// display_itemsは、このLayoutViewで前回描画したものでなければならない
+    pub fn update(
+        &mut self,
+        root: Rc<RefCell<Node>>,
+        cssom: &StyleSheet,
+        display_items: &mut Vec<DisplayItem>,
+    ) {
+        if self.root.is_none() || self.tails.is_empty() {
+            *self = Self::new(root, cssom);
+            *display_items = self.paint();
+            return;
+        }
+
+        let tails = core::mem::take(&mut self.tails);
+        let deepest = tails.len() - 1;
+
+        // 最も深いオブジェクトに追加された子をレイアウトする
+        let object = tails[deepest].object.clone();
+        let node = object.borrow().node();
+        let first_child =
+            build_layout_tree(&node.borrow().first_child(), &Some(object.clone()), cssom);
+        if first_child.is_some() {
+            object.borrow_mut().set_first_child(first_child.clone());
+            Self::calculate_node_size(&first_child, Self::size_for_children(&object.borrow()));
+            Self::calculate_node_position(
+                &first_child,
+                object.borrow().point(),
+                LayoutObjectKind::Block,
+                None,
+                None,
+            );
+        }
+
+        // 深い方から順に、末尾のオブジェクトの大きさを計算し直し、その後に追加された兄弟を
+        // レイアウトする。兄弟の位置は前の兄弟の大きさで決まるので、末尾のオブジェクトより前の
+        // オブジェクトは変わらない
+        let mut new_siblings = Vec::with_capacity(tails.len());
+        let mut children = ChildrenSize::new();
+        for depth in (0..=deepest).rev() {
+            let object = tails[depth].object.clone();
+            let (parent_size, parent_point) = match depth {
+                0 => (
+                    LayoutSize::new(CONTENT_AREA_WIDTH, 0),
+                    LayoutPoint::new(0, 0),
+                ),
+                _ => {
+                    let parent = tails[depth - 1].object.borrow();
+                    (Self::size_for_children(&parent), parent.point())
+                }
+            };
+
+            if depth == deepest {
+                object.borrow_mut().compute_size(parent_size);
+            } else {
+                object
+                    .borrow_mut()
+                    .compute_size_with_children(parent_size, children);
+            }
+
+            let mut siblings = tails[depth].preceding;
+            siblings.add(&object.borrow());
+            let next_sibling = object.borrow().node().borrow().next_sibling();
+            let sibling = build_layout_tree(&next_sibling, &None, cssom);
+            if sibling.is_some() {
+                object.borrow_mut().set_next_sibling(sibling.clone());
+                Self::calculate_node_size(&sibling, parent_size);
+                Self::calculate_node_position(
+                    &sibling,
+                    parent_point,
+                    object.borrow().kind(),
+                    Some(object.borrow().point()),
+                    Some(object.borrow().size()),
+                );
+
+                let mut node = sibling.clone();
+                while let Some(n) = node {
+                    siblings.add(&n.borrow());
+                    node = n.borrow().next_sibling();
+                }
+            }
+            new_siblings.push(sibling);
+            children = siblings;
+        }
+        new_siblings.reverse();
+
+        // 末尾のオブジェクトの項目を描き直す。最も深いオブジェクトより後ろの項目はないので、
+        // そこで切り詰める
+        display_items.truncate(tails[deepest].item_start);
+        let mut offset: isize = 0;
+        for tail in &tails[..deepest] {
+            let items = tail.object.borrow_mut().paint();
+            let item_start = (tail.item_start as isize + offset) as usize;
+            let item_count = items.len();
+            if item_count == tail.item_count {
+                for (i, item) in items.into_iter().enumerate() {
+                    display_items[item_start + i] = item;
+                }
+            } else {
+                // テキストの行数が変わった場合は、後ろの項目をずらす
+                display_items.splice(item_start..item_start + tail.item_count, items);
+                offset += item_count as isize - tail.item_count as isize;
+            }
+            self.tails.push(TailObject {
+                object: tail.object.clone(),
+                preceding: tail.preceding,
+                item_start,
+                item_count,
+            });
+        }
+
+        // 最も深いオブジェクトとその子、深い方から順に追加された兄弟を、文書の順に描画する
+        let tail = &tails[deepest];
+        let item_start = display_items.len();
+        display_items.extend(tail.object.borrow_mut().paint());
+        self.tails.push(TailObject {
+            object: tail.object.clone(),
+            preceding: tail.preceding,
+            item_start,
+            item_count: display_items.len() - item_start,
+        });
+        Self::paint_siblings(
+            first_child,
+            ChildrenSize::new(),
+            display_items,
+            &mut self.tails,
+        );
+
+        for depth in (0..=deepest).rev() {
+            if new_siblings[depth].is_none() {
+                continue;
+            }
+            self.tails.truncate(depth);
+            let mut preceding = tails[depth].preceding;
+            preceding.add(&tails[depth].object.borrow());
+            Self::paint_siblings(
+                new_siblings[depth].clone(),
+                preceding,
+                display_items,
+                &mut self.tails,
+            );
+        }
+    }
+
     pub fn root(&self) -> Option<Rc<RefCell<LayoutObject>>> {
         self.root.clone()
     }
@@ -264,6 +457,7 @@ mod tests {
     use crate::renderer::html::parser::HtmlParser;
     use crate::renderer::html::token::HtmlTokenizer;
     use alloc::string::String;
+    use alloc::vec;
     use alloc::vec::Vec;
 
     fn create_layout_view(html: String) -> LayoutView {
@@ -404,4 +598,84 @@ mod tests {
             .next_sibling()
             .is_none());
     }
+
+    fn texts(layout_view: &mut LayoutView) -> Vec<(String, i64, i64)> {
+        layout_view
+            .paint()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text {
+                    text, layout_point, ..
+                } => Some((text, layout_point.x(), layout_point.y())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pre() {
+        let html = "<html><head></head><body><pre>\n a\tb  c\n\nd</pre><p>e  f</p></body></html>"
+            .to_string();
+        let mut layout_view = create_layout_view(html);
+
+        // 空白と改行、タブをそのまま表示する
+        assert_eq!(
+            vec![
+                (" a      b  c".to_string(), 0, 0),
+                ("d".to_string(), 0, 40),
+                ("e f".to_string(), 0, 60),
+            ],
+            texts(&mut layout_view)
+        );
+    }
+
+    #[test]
+    fn test_img() {
+        let html =
+            "<html><head></head><body><img src=a.png alt=photo><img src=b.png></body></html>"
+                .to_string();
+        let mut layout_view = create_layout_view(html);
+
+        let img = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child()
+            .expect("img should exist");
+        assert_eq!(LayoutObjectKind::Inline, img.borrow().kind());
+        assert_eq!(LayoutSize::new(40, 20), img.borrow().size());
+
+        // 画像は代替テキストとして表示する
+        assert_eq!(vec![("photo".to_string(), 0, 0)], texts(&mut layout_view));
+    }
+
+    #[test]
+    fn test_update() {
+        let html = "<html><head><style>.a{width:50px;} span{color:red;}</style></head><body>\
+            <p>first paragraph</p><div class=a><p>in <span>nested</span> div</p>text</div>\
+            <a href=x>link</a> after<h1>title</h1><p>last</p></body></html>";
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(String::new()));
+        parser.push_str(&html[..80]);
+        let dom = parser.construct_tree().borrow().document();
+        let style = get_style_content(dom.clone());
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+        let mut layout_view = LayoutView::new(dom, &cssom);
+        let mut display_items = layout_view.paint();
+
+        // 少しずつ追加した文書を描き直した結果は、文書全体を描画した結果と同じになる
+        for end in (87..html.len()).step_by(7).chain([html.len()]) {
+            parser.push_str(&html[end.saturating_sub(7).max(80)..end]);
+            if end == html.len() {
+                parser.finish();
+            }
+            let dom = parser.construct_tree().borrow().document();
+            layout_view.update(dom.clone(), &cssom, &mut display_items);
+            assert_eq!(
+                LayoutView::new(dom, &cssom).paint(),
+                display_items,
+                "after {} bytes",
+                end
+            );
+        }
+    }
 }
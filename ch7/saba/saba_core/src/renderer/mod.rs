@@ -1,5 +1,6 @@
 pub mod css;
 pub mod dom;
+pub mod error_page;
 pub mod html;
 pub mod js;
 pub mod layout;
@@ -1,9 +1,15 @@
 use crate::browser::Browser;
 use crate::display_item::DisplayItem;
+use crate::encoding::stream::StreamDecoder;
+use crate::http::client::BodyReader;
+use crate::http::client::CompleteBody;
+use crate::http::content_disposition::ContentDisposition;
 use crate::http::HttpResponse;
+use crate::mime::MimeType;
 use crate::renderer::css::cssom::CssParser;
 use crate::renderer::css::cssom::StyleSheet;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::api::get_base_href;
 use crate::renderer::dom::api::get_js_content;
 use crate::renderer::dom::api::get_style_content;
 use crate::renderer::dom::node::ElementKind;
@@ -15,29 +21,96 @@ use crate::renderer::js::ast::JsParser;
 use crate::renderer::js::runtime::JsRuntime;
 use crate::renderer::js::token::JsLexer;
 use crate::renderer::layout::layout_view::LayoutView;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::format;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
+/// レスポンスのMIMEタイプによって決まる、ドキュメントの読み込み方
+/// https://html.spec.whatwg.org/multipage/browsing-the-web.html#loading-a-document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    /// HTMLとして解析する
+    Html,
+    /// <pre>の中のテキストとして表示する
+    Text,
+    /// 画像を1つだけ含むドキュメントを作って表示する
+    Image,
+    /// 表示できないので、ダウンロードとして扱う
+    Download,
+}
+
+impl DocumentKind {
+    /// Content-Dispositionでattachmentが指定されている場合は、MIMEタイプにかかわらずダウンロード
+    /// として扱う
+    pub fn for_response(response: &HttpResponse) -> Self {
+        let attachment = response
+            .header_value("Content-Disposition")
+            .ok()
+            .and_then(|value| ContentDisposition::parse(&value))
+            .map(|disposition| disposition.is_attachment())
+            .unwrap_or(false);
+        if attachment {
+            return DocumentKind::Download;
+        }
+        Self::for_mime_type(&response.computed_mime_type())
+    }
+
+    pub fn for_mime_type(mime_type: &MimeType) -> Self {
+        if mime_type.is_html() {
+            return DocumentKind::Html;
+        }
+        if mime_type.is_image() {
+            return DocumentKind::Image;
+        }
+        // XMLのパーサーはないので、HTMLとして解析する
+        if mime_type.is_xml() {
+            return DocumentKind::Html;
+        }
+        if mime_type.type_name() == "text" || mime_type.is_javascript() || mime_type.is_json() {
+            return DocumentKind::Text;
+        }
+        DocumentKind::Download
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Page {
     browser: Weak<RefCell<Browser>>,
+    /// https://html.spec.whatwg.org/multipage/dom.html#concept-document-url
+    url: Option<Url>,
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+    base_url: Option<Url>,
     frame: Option<Rc<RefCell<Window>>>,
     style: Option<StyleSheet>,
     layout_view: Option<LayoutView>,
     display_items: Vec<DisplayItem>,
+    /// 読み込み中の文書のパーサー。読み込みが終わるとNoneになる
+    parser: Option<HtmlParser>,
+    /// 読み込み中の文書の、まだ受信していないボディ
+    body: Option<Rc<RefCell<Box<dyn BodyReader>>>>,
+    /// 受信したボディを文字列にしてパーサーに渡す
+    decoder: Option<StreamDecoder>,
 }
 
 impl Page {
     pub fn new() -> Self {
         Self {
             browser: Weak::new(),
+            url: None,
+            base_url: None,
             frame: None,
             style: None,
             layout_view: None,
             display_items: Vec::new(),
+            parser: None,
+            body: None,
+            decoder: None,
         }
     }
 
@@ -51,7 +124,8 @@ impl Page {
             if let Some(parent) = n.borrow().parent().upgrade() {
                 if let NodeKind::Element(e) = parent.borrow().node_kind() {
                     if e.kind() == ElementKind::A {
-                        return e.get_attribute("href");
+                        let href = e.get_attribute("href")?;
+                        return self.resolve_url(&href).ok().map(|url| url.href());
                     }
                 }
             }
@@ -64,8 +138,139 @@ impl Page {
         self.browser = browser;
     }
 
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    /// <base href>があればそれを、なければドキュメントのURLを返す
+    pub fn base_url(&self) -> Option<Url> {
+        match &self.base_url {
+            Some(url) => Some(url.clone()),
+            None => self.url.clone(),
+        }
+    }
+
+    /// リンク先やサブリソースのURLを、ドキュメントのベースURLに対して解決する
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#encoding-parsing-a-url
+    pub fn resolve_url(&self, url: &str) -> Result<Url, String> {
+        match self.base_url() {
+            Some(base) => base.join(url),
+            None => Url::new(url.into()).parse(),
+        }
+    }
+
+    /// レスポンスのMIMEタイプに合わせてドキュメントを作る。ダウンロードとして扱うレスポンスの
+    /// 場合は、表示中のページを変更しない
     pub fn receive_response(&mut self, response: HttpResponse) {
-        self.create_frame(response.body());
+        self.begin_response(response, Box::new(CompleteBody::new(Vec::new())));
+        while self.process_loading() {}
+    }
+
+    /// レスポンスの読み込みを始めて、受信済みの部分だけを解析して描画する。responseのボディには
+    /// すでに受信した部分を入れ、続きはbodyから読む。続きはprocess_loadingを呼ぶたびに、受信した
+    /// 分ずつ解析する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing
+    pub fn begin_response(&mut self, response: HttpResponse, body: Box<dyn BodyReader>) {
+        let kind = DocumentKind::for_response(&response);
+        let tokenizer = match kind {
+            DocumentKind::Html | DocumentKind::Image => HtmlTokenizer::new(String::new()),
+            DocumentKind::Text => HtmlTokenizer::new_plaintext(String::new()),
+            DocumentKind::Download => return,
+        };
+
+        self.url = response.url();
+        self.base_url = None;
+        self.frame = None;
+        self.style = None;
+        self.layout_view = None;
+        self.display_items = Vec::new();
+
+        let mut parser = HtmlParser::new(tokenizer);
+        if kind == DocumentKind::Image {
+            // 画像のデータは使わないので、ボディの続きは読まない
+            parser.push_str(&Self::image_document(&response));
+            self.body = None;
+            self.decoder = None;
+        } else {
+            let mut decoder = StreamDecoder::new(response.header_value("Content-Type").ok());
+            parser.push_str(&decoder.push(&response.body()));
+            self.body = Some(Rc::new(RefCell::new(body)));
+            self.decoder = Some(decoder);
+        }
+        self.parser = Some(parser);
+
+        self.render_received(/*finished=*/ false);
+    }
+
+    /// 読み込み中の文書かどうか
+    pub fn is_loading(&self) -> bool {
+        self.parser.is_some()
+    }
+
+    /// 次に受信したボディを解析して、追加された部分だけをレイアウトして描画する。ボディを
+    /// すべて解析し終わったら、JavaScriptを実行して最後の描画をする。何も処理しなかった場合は
+    /// falseを返す
+    pub fn process_loading(&mut self) -> bool {
+        let parser = match self.parser.as_mut() {
+            Some(parser) => parser,
+            None => return false,
+        };
+
+        let chunk = match &self.body {
+            Some(body) => body.borrow_mut().read(),
+            None => Ok(None),
+        };
+        let finished = match chunk {
+            Ok(Some(bytes)) => {
+                if let Some(decoder) = self.decoder.as_mut() {
+                    parser.push_str(&decoder.push(&bytes));
+                }
+                false
+            }
+            // 受信に失敗した場合は、それまでに受信した部分だけで文書を作る
+            Ok(None) | Err(_) => true,
+        };
+        if finished {
+            if let Some(decoder) = self.decoder.as_mut() {
+                parser.push_str(&decoder.finish());
+            }
+            parser.finish();
+        }
+
+        self.render_received(finished);
+        true
+    }
+
+    /// パーサーに渡し終わった部分から文書を作って描画する
+    fn render_received(&mut self, finished: bool) {
+        let parser = match self.parser.as_mut() {
+            Some(parser) => parser,
+            None => return,
+        };
+        let frame = parser.construct_tree();
+        let metadata_changed = parser.take_metadata_changed();
+
+        if !finished {
+            // スタイルシートとベースURLは、それらが変わったときだけ作り直す
+            if metadata_changed || self.frame.is_none() {
+                self.create_frame(frame);
+                self.set_base_url();
+                self.set_layout_view();
+                self.paint_tree();
+            } else {
+                self.update_layout_view();
+            }
+            return;
+        }
+
+        // https://html.spec.whatwg.org/multipage/parsing.html#the-end
+        self.parser = None;
+        self.body = None;
+        self.decoder = None;
+
+        self.create_frame(frame);
+
+        self.set_base_url();
 
         self.execute_js();
 
@@ -87,12 +292,33 @@ impl Page {
         let ast = parser.parse_ast();
 
         let mut runtime = JsRuntime::new(dom);
+        if let (Some(browser), Some(url)) = (self.browser.upgrade(), &self.url) {
+            runtime.set_cookie_jar(url.clone(), browser.borrow().cookie_jar());
+        }
         runtime.execute(&ast);
     }
 
-    fn create_frame(&mut self, html: String) {
-        let html_tokenizer = HtmlTokenizer::new(html);
-        let frame = HtmlParser::new(html_tokenizer).construct_tree();
+    /// 画像を表示するためのドキュメント。画像のデコードはサポートしていないので、ファイル名と
+    /// MIMEタイプを代替テキストにする
+    /// https://html.spec.whatwg.org/multipage/document-lifecycle.html#navigate-media
+    fn image_document(response: &HttpResponse) -> String {
+        let (src, name) = match response.url() {
+            Some(url) => {
+                let path = url.path();
+                let name = path.rsplit('/').next().unwrap_or("").to_string();
+                (url.href(), name)
+            }
+            None => (String::new(), String::new()),
+        };
+        format!(
+            "<html><head></head><body><img src=\"{}\" alt=\"{} ({})\"></body></html>",
+            src,
+            name,
+            response.computed_mime_type().essence()
+        )
+    }
+
+    fn create_frame(&mut self, frame: Rc<RefCell<Window>>) {
         let dom = frame.borrow().document();
 
         let style = get_style_content(dom);
@@ -103,6 +329,22 @@ impl Page {
         self.style = Some(cssom);
     }
 
+    fn set_base_url(&mut self) {
+        self.base_url = None;
+
+        let dom = match &self.frame {
+            Some(frame) => frame.borrow().document(),
+            None => return,
+        };
+
+        if let Some(href) = get_base_href(dom) {
+            self.base_url = match &self.url {
+                Some(url) => url.join(&href).ok(),
+                None => Url::new(href).parse().ok(),
+            };
+        }
+    }
+
     fn set_layout_view(&mut self) {
         let dom = match &self.frame {
             Some(frame) => frame.borrow().document(),
@@ -120,11 +362,32 @@ impl Page {
     }
 
     fn paint_tree(&mut self) {
-        if let Some(layout_view) = &self.layout_view {
+        if let Some(layout_view) = self.layout_view.as_mut() {
             self.display_items = layout_view.paint();
         }
     }
 
+    /// 前回の描画の後に追加されたノードだけをレイアウトして描画する
+    fn update_layout_view(&mut self) {
+        let dom = match &self.frame {
+            Some(frame) => frame.borrow().document(),
+            None => return,
+        };
+
+        let style = match &self.style {
+            Some(style) => style,
+            None => return,
+        };
+
+        match self.layout_view.as_mut() {
+            Some(layout_view) => layout_view.update(dom, style, &mut self.display_items),
+            None => {
+                self.set_layout_view();
+                self.paint_tree();
+            }
+        }
+    }
+
     pub fn display_items(&self) -> Vec<DisplayItem> {
         self.display_items.clone()
     }
@@ -133,3 +396,239 @@ impl Page {
         self.display_items = Vec::new();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Header;
+    use crate::renderer::layout::computed_style::Color;
+    use alloc::vec;
+
+    fn create_page(url: &str, html: &str) -> Page {
+        create_page_with_headers(url, "", html.as_bytes())
+    }
+
+    fn create_page_with_headers(url: &str, headers: &str, body: &[u8]) -> Page {
+        let mut raw = format!("HTTP/1.1 200 OK\n{}\n", headers).into_bytes();
+        raw.extend_from_slice(body);
+        let mut response = HttpResponse::new(raw).expect("failed to parse http response");
+        response.set_url(
+            Url::new(url.to_string())
+                .parse()
+                .expect("failed to parse url"),
+        );
+
+        let mut page = Page::new();
+        page.receive_response(response);
+        page
+    }
+
+    #[test]
+    fn test_resolve_url_against_document_url() {
+        let page = create_page(
+            "http://host.test:8000/dir/test.html",
+            "<html><head></head><body>text</body></html>",
+        );
+        assert_eq!(
+            Ok("http://host.test:8000/dir/test1.html".to_string()),
+            page.resolve_url("test1.html").map(|u| u.href())
+        );
+        assert_eq!(
+            Ok("http://host.test:8000/docs/".to_string()),
+            page.resolve_url("/docs/").map(|u| u.href())
+        );
+        assert_eq!(
+            Ok("http://host.test:8000/dir/test.html?page=2".to_string()),
+            page.resolve_url("?page=2").map(|u| u.href())
+        );
+    }
+
+    #[test]
+    fn test_resolve_url_against_base_href() {
+        let page = create_page(
+            "http://host.test:8000/dir/test.html",
+            "<html><head><base href=/other/></head><body>text</body></html>",
+        );
+        assert_eq!(
+            Some("http://host.test:8000/other/".to_string()),
+            page.base_url().map(|u| u.href())
+        );
+        assert_eq!(
+            Ok("http://host.test:8000/other/test1.html".to_string()),
+            page.resolve_url("test1.html").map(|u| u.href())
+        );
+        assert_eq!(
+            Some("http://host.test:8000/dir/test.html".to_string()),
+            page.url().map(|u| u.href())
+        );
+    }
+
+    #[test]
+    fn test_resolve_url_without_document_url() {
+        let page = Page::new();
+        assert!(page.resolve_url("test1.html").is_err());
+        assert_eq!(
+            Ok("http://example.com/".to_string()),
+            page.resolve_url("http://example.com").map(|u| u.href())
+        );
+    }
+
+    fn texts(page: &Page) -> Vec<String> {
+        page.display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_document_kind() {
+        for (essence, expected) in [
+            ("text/html", DocumentKind::Html),
+            ("application/xhtml+xml", DocumentKind::Html),
+            ("text/plain", DocumentKind::Text),
+            ("text/css", DocumentKind::Text),
+            ("application/json", DocumentKind::Text),
+            ("application/javascript", DocumentKind::Text),
+            ("image/png", DocumentKind::Image),
+            ("image/svg+xml", DocumentKind::Image),
+            ("application/pdf", DocumentKind::Download),
+            ("application/octet-stream", DocumentKind::Download),
+        ] {
+            let mime_type = MimeType::parse(essence).expect("failed to parse");
+            assert_eq!(
+                expected,
+                DocumentKind::for_mime_type(&mime_type),
+                "{}",
+                essence
+            );
+        }
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let page = create_page_with_headers(
+            "http://host.test/a.txt",
+            "Content-Type: text/plain; charset=utf-8\n",
+            b"<p>not  html</p>\nline2",
+        );
+        assert_eq!(
+            vec!["<p>not  html</p>".to_string(), "line2".to_string()],
+            texts(&page)
+        );
+    }
+
+    #[test]
+    fn test_image() {
+        let page = create_page_with_headers(
+            "http://host.test/images/cat.png",
+            "Content-Type: image/png\n",
+            b"\x89PNG\r\n\x1A\n\x00\x00",
+        );
+        assert_eq!(vec!["cat.png (image/png)".to_string()], texts(&page));
+        assert_eq!(
+            Some("http://host.test/images/cat.png".to_string()),
+            page.url().map(|u| u.href())
+        );
+    }
+
+    #[test]
+    fn test_download() {
+        let mut page = create_page("http://host.test/", "<p>saba</p>");
+        let raw = b"HTTP/1.1 200 OK\nContent-Type: application/zip\n\nPK\x03\x04".to_vec();
+        let response = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(
+            DocumentKind::Download,
+            DocumentKind::for_response(&response)
+        );
+        let raw = b"HTTP/1.1 200 OK\nContent-Type: text/html\nContent-Disposition: attachment\n\n<p>a</p>"
+            .to_vec();
+        let attachment = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(
+            DocumentKind::Download,
+            DocumentKind::for_response(&attachment)
+        );
+
+        // ダウンロードの場合は、表示中のページをそのまま残す
+        page.receive_response(response);
+        assert_eq!(vec!["saba".to_string()], texts(&page));
+        assert_eq!(
+            Some("http://host.test/".to_string()),
+            page.url().map(|u| u.href())
+        );
+    }
+
+    #[test]
+    fn test_process_loading() {
+        let html = format!(
+            "<html><head></head><body><p>first</p>{}<p>last</p></body></html>",
+            "<p>日本語です</p>".repeat(200)
+        );
+        let mut response = HttpResponse::new(
+            b"HTTP/1.1 200 OK\nContent-Type: text/html; charset=utf-8\n\n".to_vec(),
+        )
+        .expect("failed to parse http response");
+        let mut body = html.into_bytes();
+        // 最初に受信した部分
+        let rest = body.split_off(40);
+        response.extend_body(&body);
+
+        let mut page = Page::new();
+        // 受信するボディは文字の途中で区切られる
+        page.begin_response(
+            response,
+            Box::new(CompleteBody::new(rest).with_chunk_size(1000)),
+        );
+        assert!(page.is_loading());
+        assert_eq!(vec!["first".to_string()], texts(&page));
+
+        assert!(page.process_loading());
+        let partial = texts(&page);
+        assert_eq!(Some(&"first".to_string()), partial.first());
+        assert!(partial.len() > 1);
+        assert!(!partial.contains(&"last".to_string()));
+        assert!(!partial.concat().contains('\u{FFFD}'));
+
+        while page.process_loading() {}
+        assert!(!page.is_loading());
+        let texts = texts(&page);
+        assert_eq!(202, texts.len());
+        assert_eq!(Some(&"last".to_string()), texts.last());
+        assert!(!texts.concat().contains('\u{FFFD}'));
+        assert!(!page.process_loading());
+    }
+
+    #[test]
+    fn test_style_while_loading() {
+        let html =
+            "<html><head><style>p{color:red;}</style></head><body><p>a</p><p>b</p></body></html>";
+        let mut page = Page::new();
+        page.begin_response(
+            HttpResponse::from_parts(
+                200,
+                "OK",
+                vec![Header::new(
+                    "Content-Type".to_string(),
+                    "text/html; charset=utf-8".to_string(),
+                )],
+                Vec::new(),
+            ),
+            Box::new(CompleteBody::new(html.as_bytes().to_vec()).with_chunk_size(7)),
+        );
+
+        // 途中まで描画するときも、揃ったスタイルシートを使う
+        let mut loaded = false;
+        while page.process_loading() {
+            for item in page.display_items() {
+                if let DisplayItem::Text { style, .. } = item {
+                    assert_eq!(Color::from_name("red"), Ok(style.color()));
+                    loaded = true;
+                }
+            }
+        }
+        assert!(loaded);
+        assert_eq!(vec!["a".to_string(), "b".to_string()], texts(&page));
+    }
+}
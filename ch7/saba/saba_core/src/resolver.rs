@@ -0,0 +1,514 @@
+use crate::error::Error;
+use crate::url::host::Host;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// 解決した結果をキャッシュする秒数
+const DEFAULT_TTL: u64 = 60;
+const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// 接続先のIPアドレス
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IpAddress {
+    V4([u8; 4]),
+    V6([u16; 8]),
+}
+
+impl IpAddress {
+    pub const LOCALHOST_V4: IpAddress = IpAddress::V4([127, 0, 0, 1]);
+    pub const LOCALHOST_V6: IpAddress = IpAddress::V6([0, 0, 0, 0, 0, 0, 0, 1]);
+
+    /// "127.0.0.1"や"::1"、"[::1]"のようなアドレスのリテラルを読む
+    pub fn parse(input: &str) -> Option<Self> {
+        // ドメインとして読まれないように、IPv6は角括弧で囲んでから読む
+        let host = if input.contains(':') && !input.starts_with('[') {
+            Host::parse(&format!("[{}]", input), false)
+        } else {
+            Host::parse(input, false)
+        };
+        match host {
+            Ok(Host::Ipv4(address)) => Some(IpAddress::V4(address.to_be_bytes())),
+            Ok(Host::Ipv6(pieces)) => Some(IpAddress::V6(pieces)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for IpAddress {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            IpAddress::V4(octets) => write!(f, "{}", Host::Ipv4(u32::from_be_bytes(*octets))),
+            IpAddress::V6(pieces) => write!(f, "{}", Host::Ipv6(*pieces)),
+        }
+    }
+}
+
+/// 名前解決の失敗
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// 名前が存在しない(NXDOMAIN)か、アドレスを持たない
+    NotFound(String),
+    /// 時間内にDNSサーバーから応答がなかった
+    Timeout(String),
+    /// 不正なホスト名や、OSのリゾルバの失敗
+    Other(String),
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ResolveError::NotFound(host) => write!(f, "could not resolve host: {}", host),
+            ResolveError::Timeout(host) => write!(f, "timed out resolving host: {}", host),
+            ResolveError::Other(message) => write!(f, "failed to resolve host: {}", message),
+        }
+    }
+}
+
+impl From<ResolveError> for Error {
+    fn from(error: ResolveError) -> Self {
+        Error::Dns(error.to_string())
+    }
+}
+
+/// ドメイン名をアドレスにする、OSに依存する名前解決。実装はsaba_coreの外に置く
+pub trait NameLookup: Debug {
+    fn lookup(&self, domain: &str) -> Result<Vec<IpAddress>, ResolveError>;
+}
+
+/// hostsファイルの形式で書く、名前解決より優先するアドレスの表
+/// https://man7.org/linux/man-pages/man5/hosts.5.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostsTable {
+    /// 小文字にした名前をキーにする
+    entries: BTreeMap<String, Vec<IpAddress>>,
+}
+
+impl HostsTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// "127.0.0.1 host.test www.host.test"のように、アドレスと名前を空白で区切った行を読む。
+    /// "#"から行末まではコメントにする。アドレスが読めない行は無視する
+    pub fn parse(input: &str) -> Self {
+        let mut table = Self::new();
+        for line in input.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_ascii_whitespace();
+            let address = match fields.next().and_then(IpAddress::parse) {
+                Some(address) => address,
+                None => continue,
+            };
+            for name in fields {
+                table.insert(name, address);
+            }
+        }
+        table
+    }
+
+    /// 同じ名前に複数のアドレスを加えた場合は、加えた順に使う
+    pub fn insert(&mut self, name: &str, address: IpAddress) {
+        let addresses = self.entries.entry(normalize(name)).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.entries.remove(&normalize(name));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec<IpAddress>> {
+        self.entries.get(&normalize(name)).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// 名前は大文字と小文字を区別せず、末尾の"."を無視する
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    addresses: Vec<IpAddress>,
+    expires_at: u64,
+}
+
+/// アドレスのリテラル、hostsの表、キャッシュ、OSの名前解決の順にホストを解決する
+#[derive(Clone)]
+pub struct Resolver {
+    lookup: Rc<dyn NameLookup>,
+    hosts: HostsTable,
+    cache: RefCell<BTreeMap<String, CacheEntry>>,
+    ttl: u64,
+    capacity: usize,
+    /// 現在の時刻の秒数を返す。ない場合は期限を判断できないので、結果をキャッシュしない
+    clock: Option<Rc<dyn Fn() -> u64>>,
+}
+
+impl Debug for Resolver {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Resolver")
+            .field("lookup", &self.lookup)
+            .field("hosts", &self.hosts)
+            .field("cached", &self.cache.borrow().len())
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl Resolver {
+    pub fn new(lookup: Rc<dyn NameLookup>) -> Self {
+        Self {
+            lookup,
+            hosts: HostsTable::new(),
+            cache: RefCell::new(BTreeMap::new()),
+            ttl: DEFAULT_TTL,
+            capacity: DEFAULT_CACHE_CAPACITY,
+            clock: None,
+        }
+    }
+
+    pub fn with_hosts(mut self, hosts: HostsTable) -> Self {
+        self.hosts = hosts;
+        self
+    }
+
+    /// 0を指定するとキャッシュしない
+    pub fn with_ttl(mut self, seconds: u64) -> Self {
+        self.ttl = seconds;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Rc<dyn Fn() -> u64>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn hosts(&self) -> &HostsTable {
+        &self.hosts
+    }
+
+    /// hostsの表を書き換える。キャッシュした結果より優先される
+    pub fn hosts_mut(&mut self) -> &mut HostsTable {
+        &mut self.hosts
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    /// URLのホストを解決する
+    pub fn resolve_host(&self, host: &Host) -> Result<Vec<IpAddress>, ResolveError> {
+        match host {
+            Host::Ipv4(address) => Ok(vec![IpAddress::V4(address.to_be_bytes())]),
+            Host::Ipv6(pieces) => Ok(vec![IpAddress::V6(*pieces)]),
+            Host::Domain(domain) => self.resolve_domain(domain),
+            Host::Opaque(_) | Host::Empty => Err(ResolveError::Other(format!(
+                "not a network host: {:?}",
+                host.serialize()
+            ))),
+        }
+    }
+
+    /// ホスト名かアドレスのリテラルを解決する
+    pub fn resolve(&self, host: &str) -> Result<Vec<IpAddress>, ResolveError> {
+        if let Some(address) = IpAddress::parse(host) {
+            return Ok(vec![address]);
+        }
+        match Host::parse(host, false) {
+            Ok(host @ Host::Domain(_)) => self.resolve_host(&host),
+            _ => Err(ResolveError::Other(format!("invalid host: {:?}", host))),
+        }
+    }
+
+    fn resolve_domain(&self, domain: &str) -> Result<Vec<IpAddress>, ResolveError> {
+        let name = normalize(domain);
+        if let Some(addresses) = self.hosts.get(&name) {
+            return Ok(addresses);
+        }
+        // localhostとそのサブドメインは、常にループバックアドレスにする
+        // https://www.rfc-editor.org/rfc/rfc6761#section-6.3
+        if name == "localhost" || name.ends_with(".localhost") {
+            return Ok(vec![IpAddress::LOCALHOST_V4, IpAddress::LOCALHOST_V6]);
+        }
+
+        let now = self.clock.as_ref().map(|clock| clock()).unwrap_or(0);
+        if let Some(entry) = self.cache.borrow().get(&name) {
+            if !self.is_expired(entry, now) {
+                return Ok(entry.addresses.clone());
+            }
+        }
+
+        let addresses = self.lookup.lookup(&name)?;
+        if addresses.is_empty() {
+            return Err(ResolveError::NotFound(name));
+        }
+        self.store(name, addresses.clone(), now);
+        Ok(addresses)
+    }
+
+    fn is_expired(&self, entry: &CacheEntry, now: u64) -> bool {
+        now >= entry.expires_at
+    }
+
+    fn store(&self, name: String, addresses: Vec<IpAddress>, now: u64) {
+        // 時計がない場合は、アドレスが変わったホストに古いアドレスで接続し続けないように、
+        // キャッシュしない
+        if self.clock.is_none() || self.ttl == 0 || self.capacity == 0 {
+            return;
+        }
+        let mut cache = self.cache.borrow_mut();
+        cache.retain(|_, entry| !self.is_expired(entry, now));
+        if cache.len() >= self.capacity && !cache.contains_key(&name) {
+            // 最も早く期限が切れるものを捨てる
+            if let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(name, _)| name.clone())
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            name,
+            CacheEntry {
+                addresses,
+                expires_at: now + self.ttl,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// 登録した名前だけを解決し、問い合わせの回数を数える
+    #[derive(Debug, Default)]
+    struct MockLookup {
+        names: BTreeMap<String, Result<Vec<IpAddress>, ResolveError>>,
+        count: Cell<usize>,
+    }
+
+    impl NameLookup for MockLookup {
+        fn lookup(&self, domain: &str) -> Result<Vec<IpAddress>, ResolveError> {
+            self.count.set(self.count.get() + 1);
+            self.names
+                .get(domain)
+                .cloned()
+                .unwrap_or_else(|| Err(ResolveError::NotFound(domain.to_string())))
+        }
+    }
+
+    fn mock(names: &[(&str, Result<Vec<IpAddress>, ResolveError>)]) -> Rc<MockLookup> {
+        Rc::new(MockLookup {
+            names: names
+                .iter()
+                .map(|(name, result)| (name.to_string(), result.clone()))
+                .collect(),
+            count: Cell::new(0),
+        })
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            Some(IpAddress::V4([192, 168, 0, 1])),
+            IpAddress::parse("192.168.0.1")
+        );
+        assert_eq!(Some(IpAddress::LOCALHOST_V6), IpAddress::parse("::1"));
+        assert_eq!(Some(IpAddress::LOCALHOST_V6), IpAddress::parse("[::1]"));
+        assert_eq!(None, IpAddress::parse("example.com"));
+        assert_eq!(None, IpAddress::parse("1.2.3.256"));
+        assert_eq!("[::1]", IpAddress::LOCALHOST_V6.to_string());
+        assert_eq!("127.0.0.1", IpAddress::LOCALHOST_V4.to_string());
+    }
+
+    #[test]
+    fn test_hosts_table() {
+        let table = HostsTable::parse(
+            "# local servers\n\
+             127.0.0.1\thost.test www.host.test # web\n\
+             ::1 host.test\n\
+             not-an-address ignored.test\n",
+        );
+        assert_eq!(2, table.len());
+        assert_eq!(
+            Some(vec![IpAddress::LOCALHOST_V4, IpAddress::LOCALHOST_V6]),
+            table.get("HOST.test.")
+        );
+        assert_eq!(
+            Some(vec![IpAddress::LOCALHOST_V4]),
+            table.get("www.host.test")
+        );
+        assert_eq!(None, table.get("ignored.test"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let lookup = mock(&[("example.com", Ok(vec![IpAddress::V4([93, 184, 216, 34])]))]);
+        let mut hosts = HostsTable::new();
+        hosts.insert("example.com", IpAddress::V4([10, 0, 0, 1]));
+        hosts.insert("host.test", IpAddress::LOCALHOST_V4);
+        let resolver = Resolver::new(lookup.clone()).with_hosts(hosts);
+
+        assert_eq!(
+            Ok(vec![IpAddress::LOCALHOST_V4]),
+            resolver.resolve("Host.Test")
+        );
+        // hostsの表はOSの名前解決より優先する
+        assert_eq!(
+            Ok(vec![IpAddress::V4([10, 0, 0, 1])]),
+            resolver.resolve("example.com")
+        );
+        assert_eq!(
+            Ok(vec![IpAddress::V4([10, 1, 2, 3])]),
+            resolver.resolve("10.1.2.3")
+        );
+        assert_eq!(Ok(vec![IpAddress::LOCALHOST_V6]), resolver.resolve("[::1]"));
+        assert_eq!(
+            Ok(vec![IpAddress::LOCALHOST_V4, IpAddress::LOCALHOST_V6]),
+            resolver.resolve("app.localhost")
+        );
+        assert_eq!(0, lookup.count.get());
+    }
+
+    #[test]
+    fn test_errors() {
+        let lookup = mock(&[
+            (
+                "slow.test",
+                Err(ResolveError::Timeout("slow.test".to_string())),
+            ),
+            ("empty.test", Ok(Vec::new())),
+        ]);
+        let resolver = Resolver::new(lookup);
+        assert_eq!(
+            Err(ResolveError::NotFound("missing.test".to_string())),
+            resolver.resolve("missing.test")
+        );
+        assert_eq!(
+            Err(ResolveError::NotFound("empty.test".to_string())),
+            resolver.resolve("empty.test")
+        );
+        assert_eq!(
+            Err(ResolveError::Timeout("slow.test".to_string())),
+            resolver.resolve("slow.test")
+        );
+        assert!(matches!(
+            resolver.resolve_host(&Host::Empty),
+            Err(ResolveError::Other(_))
+        ));
+        assert_eq!(
+            Error::Dns("timed out resolving host: slow.test".to_string()),
+            Error::from(ResolveError::Timeout("slow.test".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let lookup = mock(&[
+            ("a.test", Ok(vec![IpAddress::V4([10, 0, 0, 1])])),
+            ("b.test", Ok(vec![IpAddress::V4([10, 0, 0, 2])])),
+        ]);
+        let now = Rc::new(Cell::new(1000));
+        let clock = now.clone();
+        let mut resolver = Resolver::new(lookup.clone())
+            .with_ttl(30)
+            .with_clock(Rc::new(move || clock.get()));
+
+        assert!(resolver.resolve("a.test").is_ok());
+        assert!(resolver.resolve("A.TEST").is_ok());
+        assert_eq!(1, lookup.count.get());
+
+        // 期限が切れたら問い合わせ直す
+        now.set(1030);
+        assert!(resolver.resolve("a.test").is_ok());
+        assert_eq!(2, lookup.count.get());
+
+        // hostsの表はキャッシュより優先する
+        resolver
+            .hosts_mut()
+            .insert("a.test", IpAddress::LOCALHOST_V4);
+        assert_eq!(
+            Ok(vec![IpAddress::LOCALHOST_V4]),
+            resolver.resolve("a.test")
+        );
+
+        resolver.clear_cache();
+        resolver.hosts_mut().remove("a.test");
+        assert!(resolver.resolve("a.test").is_ok());
+        assert_eq!(3, lookup.count.get());
+
+        // 失敗した結果はキャッシュしない
+        assert!(resolver.resolve("c.test").is_err());
+        assert!(resolver.resolve("c.test").is_err());
+        assert_eq!(5, lookup.count.get());
+    }
+
+    /// 問い合わせるたびに違うアドレスを返す
+    #[derive(Debug, Default)]
+    struct ChangingLookup {
+        count: Cell<u8>,
+    }
+
+    impl NameLookup for ChangingLookup {
+        fn lookup(&self, _domain: &str) -> Result<Vec<IpAddress>, ResolveError> {
+            self.count.set(self.count.get() + 1);
+            Ok(vec![IpAddress::V4([10, 0, 0, self.count.get()])])
+        }
+    }
+
+    #[test]
+    fn test_no_cache_without_clock() {
+        let resolver = Resolver::new(Rc::new(ChangingLookup::default()));
+        // 時計がない場合は期限を判断できないので、毎回問い合わせて新しいアドレスを使う
+        assert_eq!(
+            Ok(vec![IpAddress::V4([10, 0, 0, 1])]),
+            resolver.resolve("a.test")
+        );
+        assert_eq!(
+            Ok(vec![IpAddress::V4([10, 0, 0, 2])]),
+            resolver.resolve("a.test")
+        );
+    }
+
+    #[test]
+    fn test_cache_capacity() {
+        let lookup = mock(&[
+            ("a.test", Ok(vec![IpAddress::V4([10, 0, 0, 1])])),
+            ("b.test", Ok(vec![IpAddress::V4([10, 0, 0, 2])])),
+        ]);
+        let now = Rc::new(Cell::new(1000));
+        let clock = now.clone();
+        let mut resolver = Resolver::new(lookup.clone()).with_clock(Rc::new(move || clock.get()));
+        resolver.capacity = 1;
+
+        assert!(resolver.resolve("a.test").is_ok());
+        now.set(1001);
+        assert!(resolver.resolve("b.test").is_ok());
+        // a.testは追い出されている
+        assert!(resolver.resolve("a.test").is_ok());
+        assert_eq!(3, lookup.count.get());
+    }
+}
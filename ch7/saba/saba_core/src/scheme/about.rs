@@ -0,0 +1,128 @@
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::scheme::handler::SchemeHandler;
+use crate::url::Url;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// ページの名前と、そのHTMLを生成する関数の組を保持する
+/// https://fetch.spec.whatwg.org/#scheme-fetch
+#[derive(Clone)]
+pub struct AboutHandler {
+    pages: Vec<(String, Rc<dyn Fn() -> String>)>,
+}
+
+impl AboutHandler {
+    pub fn new() -> Self {
+        let mut handler = Self { pages: Vec::new() };
+        handler.register_page("blank", Rc::new(String::new));
+        handler.register_page(
+            "version",
+            Rc::new(|| {
+                format!(
+                    "<html><head></head><body><h1>saba</h1><p>version {}</p></body></html>",
+                    env!("CARGO_PKG_VERSION")
+                )
+            }),
+        );
+        handler
+    }
+
+    /// 同じ名前のページがすでに登録されている場合は置き換える
+    pub fn register_page(&mut self, name: &str, page: Rc<dyn Fn() -> String>) {
+        let name = name.to_ascii_lowercase();
+        match self.pages.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = page,
+            None => self.pages.push((name, page)),
+        }
+    }
+
+    fn page(&self, name: &str) -> Option<Rc<dyn Fn() -> String>> {
+        self.pages
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, p)| p.clone())
+    }
+}
+
+impl Default for AboutHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for AboutHandler {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let names: Vec<&String> = self.pages.iter().map(|(n, _)| n).collect();
+        f.debug_struct("AboutHandler")
+            .field("pages", &names)
+            .finish()
+    }
+}
+
+impl SchemeHandler for AboutHandler {
+    fn load(&self, url: &Url) -> Result<HttpResponse, Error> {
+        // "about:blank"のパスは不透明パスの"blank"になる
+        let name = url.path().to_ascii_lowercase();
+        match self.page(&name) {
+            Some(page) => Ok(HttpResponse::from_parts(
+                200,
+                "OK",
+                vec![Header::new(
+                    "Content-Type".to_string(),
+                    "text/html;charset=utf-8".to_string(),
+                )],
+                page().into_bytes(),
+            )),
+            None => Err(Error::UnexpectedInput(format!(
+                "unknown about page: {}",
+                url.href()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(handler: &AboutHandler, url: &str) -> Result<HttpResponse, Error> {
+        let url = Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url");
+        handler.load(&url)
+    }
+
+    #[test]
+    fn test_blank() {
+        let response = load(&AboutHandler::new(), "about:blank").expect("failed to load");
+        assert_eq!(200, response.status_code());
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn test_version() {
+        let response = load(&AboutHandler::new(), "about:version").expect("failed to load");
+        assert!(response.text().contains(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_register_page() {
+        let mut handler = AboutHandler::new();
+        handler.register_page("test", Rc::new(|| "<p>test</p>".to_string()));
+        let response = load(&handler, "about:test").expect("failed to load");
+        assert_eq!(b"<p>test</p>".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_unknown_page() {
+        assert!(load(&AboutHandler::new(), "about:unknown").is_err());
+    }
+}
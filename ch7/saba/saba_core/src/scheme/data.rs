@@ -0,0 +1,160 @@
+use crate::base64;
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::scheme::handler::SchemeHandler;
+use crate::url::percent_encoding::percent_decode;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// https://fetch.spec.whatwg.org/#data-urls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUrl {
+    mime_type: String,
+    body: Vec<u8>,
+}
+
+impl DataUrl {
+    /// https://fetch.spec.whatwg.org/#data-url-processor
+    pub fn process(url: &Url) -> Result<Self, Error> {
+        let serialized = url.serialize(/*exclude_fragment=*/ true);
+        let input = match serialized.strip_prefix("data:") {
+            Some(input) => input,
+            None => {
+                return Err(Error::UnexpectedInput(format!(
+                    "not a data URL: {}",
+                    serialized
+                )))
+            }
+        };
+
+        let (mime_type, encoded_body) = match input.split_once(',') {
+            Some(split) => split,
+            None => {
+                return Err(Error::UnexpectedInput(format!(
+                    "data URL has no comma: {}",
+                    serialized
+                )))
+            }
+        };
+
+        let mut mime_type = mime_type.trim_matches(|c: char| c.is_ascii_whitespace());
+        let mut body = percent_decode(encoded_body.as_bytes());
+
+        if let Some(rest) = strip_base64_suffix(mime_type) {
+            body = base64::forgiving_decode(&body)?;
+            mime_type = rest;
+        }
+
+        let mut mime_type = mime_type.to_string();
+        if mime_type.starts_with(';') {
+            mime_type.insert_str(0, "text/plain");
+        }
+        if mime_type.is_empty() || !mime_type.contains('/') {
+            mime_type = "text/plain;charset=US-ASCII".to_string();
+        }
+
+        Ok(Self { mime_type, body })
+    }
+
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+}
+
+/// ";base64"（大文字小文字は区別しない、前後の空白は許容する）で終わっていれば、それを取り除く
+fn strip_base64_suffix(mime_type: &str) -> Option<&str> {
+    let index = mime_type.rfind(';')?;
+    let parameter = mime_type[index + 1..].trim_matches(|c: char| c.is_ascii_whitespace());
+    if parameter.eq_ignore_ascii_case("base64") {
+        Some(&mime_type[..index])
+    } else {
+        None
+    }
+}
+
+/// https://fetch.spec.whatwg.org/#concept-scheme-fetch
+#[derive(Debug, Clone, Default)]
+pub struct DataHandler {}
+
+impl DataHandler {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl SchemeHandler for DataHandler {
+    fn load(&self, url: &Url) -> Result<HttpResponse, Error> {
+        let data = DataUrl::process(url)?;
+        Ok(HttpResponse::from_parts(
+            200,
+            "OK",
+            vec![Header::new("Content-Type".to_string(), data.mime_type())],
+            data.body(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(url: &str) -> Result<DataUrl, Error> {
+        let url = Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url");
+        DataUrl::process(&url)
+    }
+
+    #[test]
+    fn test_percent_encoded() {
+        let data = process("data:text/html,%3Cp%3EHello%20saba%3C/p%3E").expect("failed");
+        assert_eq!("text/html".to_string(), data.mime_type());
+        assert_eq!(b"<p>Hello saba</p>".to_vec(), data.body());
+    }
+
+    #[test]
+    fn test_base64() {
+        let data = process("data:text/html;charset=utf-8;BASE64,PHA+c2FiYTwvcD4=").expect("failed");
+        assert_eq!("text/html;charset=utf-8".to_string(), data.mime_type());
+        assert_eq!(b"<p>saba</p>".to_vec(), data.body());
+    }
+
+    #[test]
+    fn test_default_mime_type() {
+        let data = process("data:,Hello#fragment").expect("failed");
+        assert_eq!("text/plain;charset=US-ASCII".to_string(), data.mime_type());
+        assert_eq!(b"Hello".to_vec(), data.body());
+
+        let data = process("data:;charset=utf-8,Hello").expect("failed");
+        assert_eq!("text/plain;charset=utf-8".to_string(), data.mime_type());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(process("data:text/html").is_err());
+        assert!(process("data:;base64,!!!").is_err());
+    }
+
+    #[test]
+    fn test_handler() {
+        let url = Url::new("data:text/html,<h1>saba</h1>".to_string())
+            .parse()
+            .expect("failed to parse url");
+        let response = DataHandler::new().load(&url).expect("failed to load");
+        assert_eq!(200, response.status_code());
+        assert_eq!(
+            Ok("text/html".to_string()),
+            response.header_value("Content-Type")
+        );
+        assert_eq!(b"<h1>saba</h1>".to_vec(), response.body());
+    }
+}
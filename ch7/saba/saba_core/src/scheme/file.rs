@@ -0,0 +1,129 @@
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::scheme::handler::SchemeHandler;
+use crate::url::percent_encoding::percent_decode_str;
+use crate::url::Url;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// OSごとのファイルの読み込み方法を抽象化する
+pub trait FileSystem: Debug {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct FileHandler {
+    fs: Rc<dyn FileSystem>,
+}
+
+impl FileHandler {
+    pub fn new(fs: Rc<dyn FileSystem>) -> Self {
+        Self { fs }
+    }
+}
+
+/// 拡張子からContent-Typeを推測する
+fn content_type(path: &str) -> &'static str {
+    let extension = match path.rsplit_once('.') {
+        Some((_, extension)) => extension.to_ascii_lowercase(),
+        None => String::new(),
+    };
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "txt" => "text/plain",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// https://fetch.spec.whatwg.org/#scheme-fetch
+impl SchemeHandler for FileHandler {
+    fn load(&self, url: &Url) -> Result<HttpResponse, Error> {
+        let path = percent_decode_str(&url.path());
+        let body = self.fs.read(&path)?;
+        Ok(HttpResponse::from_parts(
+            200,
+            "OK",
+            vec![Header::new(
+                "Content-Type".to_string(),
+                content_type(&path).to_string(),
+            )],
+            body,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[derive(Debug)]
+    struct MockFileSystem {
+        files: Vec<(String, Vec<u8>)>,
+    }
+
+    impl FileSystem for MockFileSystem {
+        fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+            self.files
+                .iter()
+                .find(|(p, _)| p == path)
+                .map(|(_, b)| b.clone())
+                .ok_or_else(|| Error::Other(format!("file not found: {}", path)))
+        }
+    }
+
+    fn handler() -> FileHandler {
+        FileHandler::new(Rc::new(MockFileSystem {
+            files: vec![
+                (
+                    "/home/saba/index.html".to_string(),
+                    b"<p>hello</p>".to_vec(),
+                ),
+                ("/home/saba/my page.txt".to_string(), b"text".to_vec()),
+            ],
+        }))
+    }
+
+    fn load(url: &str) -> Result<HttpResponse, Error> {
+        let url = Url::new(url.to_string())
+            .parse()
+            .expect("failed to parse url");
+        handler().load(&url)
+    }
+
+    #[test]
+    fn test_html() {
+        let response = load("file:///home/saba/index.html").expect("failed to load");
+        assert_eq!(
+            Ok("text/html".to_string()),
+            response.header_value("Content-Type")
+        );
+        assert_eq!(b"<p>hello</p>".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_percent_encoded_path() {
+        let response = load("file:///home/saba/my%20page.txt").expect("failed to load");
+        assert_eq!(
+            Ok("text/plain".to_string()),
+            response.header_value("Content-Type")
+        );
+        assert_eq!(b"text".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_not_found() {
+        assert!(load("file:///home/saba/missing.html").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::Error;
+use crate::http::client::BodyReader;
+use crate::http::client::CompleteBody;
+use crate::http::settings::Destination;
+use crate::http::HttpResponse;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// URLのスキームごとに、リソースを読み込んでレスポンスを作る
+pub trait SchemeHandler: Debug {
+    fn load(&self, url: &Url) -> Result<HttpResponse, Error>;
+
+    /// スタイルシートや画像など、使い道に合わせて読み込む。リクエストのヘッダを使い道で
+    /// 変えないハンドラは、load()と同じでよい
+    fn load_with_destination(
+        &self,
+        url: &Url,
+        _destination: Destination,
+    ) -> Result<HttpResponse, Error> {
+        self.load(url)
+    }
+
+    /// ヘッダが揃った時点でレスポンスを返し、ボディは返したBodyReaderから受信した分ずつ
+    /// 読み出す。少しずつ受信しないハンドラは、load_with_destination()で読み込んだボディを
+    /// まとめて返す
+    fn open(
+        &self,
+        url: &Url,
+        destination: Destination,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let mut response = self.load_with_destination(url, destination)?;
+        let body = response.take_body();
+        Ok((response, Box::new(CompleteBody::new(body))))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemeRegistry {
+    handlers: Vec<(String, Rc<dyn SchemeHandler>)>,
+}
+
+impl SchemeRegistry {
+    pub fn new() -> Self {
+        Self {
+            handlers: Vec::new(),
+        }
+    }
+
+    /// 同じスキームのハンドラがすでに登録されている場合は置き換える
+    pub fn register(&mut self, scheme: &str, handler: Rc<dyn SchemeHandler>) {
+        let scheme = scheme.to_ascii_lowercase();
+        match self.handlers.iter_mut().find(|(s, _)| *s == scheme) {
+            Some(entry) => entry.1 = handler,
+            None => self.handlers.push((scheme, handler)),
+        }
+    }
+
+    pub fn handler(&self, scheme: &str) -> Option<Rc<dyn SchemeHandler>> {
+        self.handlers
+            .iter()
+            .find(|(s, _)| s == scheme)
+            .map(|(_, h)| h.clone())
+    }
+
+    pub fn load(&self, url: &Url) -> Result<HttpResponse, Error> {
+        self.load_with_destination(url, Destination::Document)
+    }
+
+    pub fn load_with_destination(
+        &self,
+        url: &Url,
+        destination: Destination,
+    ) -> Result<HttpResponse, Error> {
+        let mut response = self
+            .scheme_handler(url)?
+            .load_with_destination(url, destination)?;
+        // リダイレクトされた場合はハンドラが最終的なURLを設定している
+        if response.url().is_none() {
+            response.set_url(url.clone());
+        }
+        Ok(response)
+    }
+
+    /// load_with_destination()と同じように読み込み、ボディは返したBodyReaderから読み出す
+    pub fn open(
+        &self,
+        url: &Url,
+        destination: Destination,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        let (mut response, body) = self.scheme_handler(url)?.open(url, destination)?;
+        if response.url().is_none() {
+            response.set_url(url.clone());
+        }
+        Ok((response, body))
+    }
+
+    fn scheme_handler(&self, url: &Url) -> Result<Rc<dyn SchemeHandler>, Error> {
+        match self.handler(&url.scheme()) {
+            Some(handler) => Ok(handler),
+            None => Err(Error::UnexpectedInput(format!(
+                "unsupported scheme: {}",
+                url.scheme()
+            ))),
+        }
+    }
+}
+
+impl Default for SchemeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    #[derive(Debug)]
+    struct EchoHandler(&'static str);
+
+    impl SchemeHandler for EchoHandler {
+        fn load(&self, url: &Url) -> Result<HttpResponse, Error> {
+            Ok(HttpResponse::from_parts(
+                200,
+                "OK",
+                Vec::new(),
+                (self.0.to_string() + &url.path()).into_bytes(),
+            ))
+        }
+    }
+
+    fn parse(url: &str) -> Url {
+        Url::new(url.to_string()).parse().expect("failed to parse")
+    }
+
+    #[test]
+    fn test_dispatch_by_scheme() {
+        let mut registry = SchemeRegistry::new();
+        registry.register("http", Rc::new(EchoHandler("http:")));
+        registry.register("test", Rc::new(EchoHandler("test:")));
+
+        let response = registry
+            .load(&parse("http://example.com/index.html"))
+            .expect("failed to load");
+        assert_eq!(b"http:/index.html".to_vec(), response.body());
+        assert_eq!(
+            Some("http://example.com/index.html".to_string()),
+            response.url().map(|u| u.href())
+        );
+
+        let response = registry.load(&parse("test:page")).expect("failed to load");
+        assert_eq!(b"test:page".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_replace_handler() {
+        let mut registry = SchemeRegistry::new();
+        registry.register("test", Rc::new(EchoHandler("old:")));
+        registry.register("TEST", Rc::new(EchoHandler("new:")));
+
+        let response = registry.load(&parse("test:page")).expect("failed to load");
+        assert_eq!(b"new:page".to_vec(), response.body());
+    }
+
+    #[test]
+    fn test_unsupported_scheme() {
+        let registry = SchemeRegistry::new();
+        assert!(registry.load(&parse("gopher://example.com/")).is_err());
+    }
+
+    #[test]
+    fn test_open() {
+        let mut registry = SchemeRegistry::new();
+        registry.register("test", Rc::new(EchoHandler("test:")));
+
+        let (response, mut body) = registry
+            .open(&parse("test:page"), Destination::Document)
+            .expect("failed to open");
+        assert!(response.body().is_empty());
+        assert_eq!(
+            Some("test:page".to_string()),
+            response.url().map(|u| u.href())
+        );
+        assert_eq!(
+            Some(b"test:page".to_vec()),
+            body.read().expect("failed to read")
+        );
+        assert_eq!(None, body.read().expect("failed to read"));
+    }
+}
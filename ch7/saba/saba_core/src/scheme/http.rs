@@ -0,0 +1,149 @@
+use crate::error::Error;
+use crate::http::cache::HttpCache;
+use crate::http::client::BodyReader;
+use crate::http::client::NetworkClient;
+use crate::http::content_coding::ACCEPT_ENCODING;
+use crate::http::cookie::CookieJar;
+use crate::http::redirect::Redirector;
+use crate::http::request::HttpRequest;
+use crate::http::settings::Destination;
+use crate::http::settings::RequestSettings;
+use crate::http::HttpResponse;
+use crate::scheme::handler::SchemeHandler;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+/// http:のURLを、ネットワークからリソースを取得して読み込む
+#[derive(Debug, Clone)]
+pub struct HttpHandler {
+    // 接続を使い回すために、リクエストをまたいで同じクライアントを使う
+    client: Rc<dyn NetworkClient>,
+    cookie_jar: Rc<RefCell<CookieJar>>,
+    http_cache: Rc<RefCell<HttpCache>>,
+    settings: Rc<RefCell<RequestSettings>>,
+}
+
+impl HttpHandler {
+    pub fn new(
+        client: Rc<dyn NetworkClient>,
+        cookie_jar: Rc<RefCell<CookieJar>>,
+        http_cache: Rc<RefCell<HttpCache>>,
+        settings: Rc<RefCell<RequestSettings>>,
+    ) -> Self {
+        Self {
+            client,
+            cookie_jar,
+            http_cache,
+            settings,
+        }
+    }
+
+    pub fn client(&self) -> Rc<dyn NetworkClient> {
+        self.client.clone()
+    }
+
+    /// リクエストの設定を適用して送信する。リダイレクトされた場合は、転送先のURLにリクエストを
+    /// 送り直す。Rangeを付けたダウンロードの再開のように、部分的なレスポンスを受け取る
+    /// リクエストでは、use_cacheをfalseにしてキャッシュを迂回する
+    pub fn fetch(
+        &self,
+        request: HttpRequest,
+        destination: Destination,
+        use_cache: bool,
+    ) -> Result<HttpResponse, Error> {
+        self.follow(request, destination, |request| {
+            let send = |request: &HttpRequest| {
+                let response = self.client.send(request)?;
+                self.store_cookies(request, &response);
+                Ok(response)
+            };
+            let response = if use_cache {
+                self.http_cache.borrow_mut().fetch(request, send)?
+            } else {
+                send(request)?
+            };
+            Ok((response, ()))
+        })
+        .map(|(response, ())| response)
+    }
+
+    /// fetch()と同じように送信し、ヘッダを受信した時点でレスポンスを返す。ボディは返した
+    /// BodyReaderから受信した分ずつ読み出す
+    pub fn fetch_streaming(
+        &self,
+        request: HttpRequest,
+        destination: Destination,
+        use_cache: bool,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        self.follow(request, destination, |request| {
+            let open = |request: &HttpRequest| {
+                let (response, body) = self.client.open(request)?;
+                self.store_cookies(request, &response);
+                Ok((response, body))
+            };
+            if use_cache {
+                HttpCache::fetch_streaming(&self.http_cache, request, open)
+            } else {
+                open(request)
+            }
+        })
+    }
+
+    /// fetch()とfetch_streaming()に共通する処理。リダイレクトのたびに、転送先のURLに合う
+    /// Cookieを付けてsendに渡す
+    fn follow<T, F>(
+        &self,
+        mut request: HttpRequest,
+        destination: Destination,
+        mut send: F,
+    ) -> Result<(HttpResponse, T), Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<(HttpResponse, T), Error>,
+    {
+        self.settings.borrow().apply(&mut request, destination);
+        Redirector::new()
+            .with_cookie_jar(self.cookie_jar.clone())
+            .follow(request, |request| {
+                let mut request = request.clone();
+                self.cookie_jar
+                    .borrow_mut()
+                    .add_cookie_header(&mut request, None);
+                send(&request)
+            })
+    }
+
+    /// Set-Cookieは、キャッシュからではなくネットワークから受け取ったときだけ保存する
+    fn store_cookies(&self, request: &HttpRequest, response: &HttpResponse) {
+        self.cookie_jar
+            .borrow_mut()
+            .store_response_cookies(&request.url(), response);
+    }
+}
+
+fn document_request(url: &Url) -> HttpRequest {
+    HttpRequest::get(url.clone()).with_header("Accept-Encoding", ACCEPT_ENCODING)
+}
+
+impl SchemeHandler for HttpHandler {
+    fn load(&self, url: &Url) -> Result<HttpResponse, Error> {
+        self.load_with_destination(url, Destination::Document)
+    }
+
+    fn load_with_destination(
+        &self,
+        url: &Url,
+        destination: Destination,
+    ) -> Result<HttpResponse, Error> {
+        self.fetch(document_request(url), destination, /*use_cache=*/ true)
+    }
+
+    fn open(
+        &self,
+        url: &Url,
+        destination: Destination,
+    ) -> Result<(HttpResponse, Box<dyn BodyReader>), Error> {
+        self.fetch_streaming(document_request(url), destination, /*use_cache=*/ true)
+    }
+}
@@ -0,0 +1,5 @@
+pub mod about;
+pub mod data;
+pub mod file;
+pub mod handler;
+pub mod http;
@@ -0,0 +1,190 @@
+use crate::crypto::chacha20poly1305::ChaCha20Poly1305;
+use crate::crypto::gcm::AesGcm;
+use crate::crypto::gcm::NONCE_LEN;
+use crate::crypto::hash::HashAlgorithm;
+use alloc::vec::Vec;
+
+/// サポートしている暗号スイート。TLS 1.2では、鍵交換はECDHEだけを使う
+/// https://www.rfc-editor.org/rfc/rfc8446#appendix-B.4
+/// https://www.rfc-editor.org/rfc/rfc8422#section-6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    Tls13Aes128GcmSha256,
+    Tls13Aes256GcmSha384,
+    Tls13ChaCha20Poly1305Sha256,
+    EcdheEcdsaAes128GcmSha256,
+    EcdheRsaAes128GcmSha256,
+    EcdheEcdsaAes256GcmSha384,
+    EcdheRsaAes256GcmSha384,
+    EcdheEcdsaChaCha20Poly1305Sha256,
+    EcdheRsaChaCha20Poly1305Sha256,
+}
+
+impl CipherSuite {
+    /// ClientHelloで送る順に並べる
+    pub const ALL: [CipherSuite; 9] = [
+        CipherSuite::Tls13Aes128GcmSha256,
+        CipherSuite::Tls13Aes256GcmSha384,
+        CipherSuite::Tls13ChaCha20Poly1305Sha256,
+        CipherSuite::EcdheEcdsaAes128GcmSha256,
+        CipherSuite::EcdheRsaAes128GcmSha256,
+        CipherSuite::EcdheEcdsaAes256GcmSha384,
+        CipherSuite::EcdheRsaAes256GcmSha384,
+        CipherSuite::EcdheEcdsaChaCha20Poly1305Sha256,
+        CipherSuite::EcdheRsaChaCha20Poly1305Sha256,
+    ];
+
+    pub fn from_u16(value: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|suite| suite.value() == value)
+    }
+
+    pub fn value(&self) -> u16 {
+        match self {
+            CipherSuite::Tls13Aes128GcmSha256 => 0x1301,
+            CipherSuite::Tls13Aes256GcmSha384 => 0x1302,
+            CipherSuite::Tls13ChaCha20Poly1305Sha256 => 0x1303,
+            CipherSuite::EcdheEcdsaAes128GcmSha256 => 0xc02b,
+            CipherSuite::EcdheRsaAes128GcmSha256 => 0xc02f,
+            CipherSuite::EcdheEcdsaAes256GcmSha384 => 0xc02c,
+            CipherSuite::EcdheRsaAes256GcmSha384 => 0xc030,
+            CipherSuite::EcdheEcdsaChaCha20Poly1305Sha256 => 0xcca9,
+            CipherSuite::EcdheRsaChaCha20Poly1305Sha256 => 0xcca8,
+        }
+    }
+
+    pub fn is_tls13(&self) -> bool {
+        matches!(
+            self,
+            CipherSuite::Tls13Aes128GcmSha256
+                | CipherSuite::Tls13Aes256GcmSha384
+                | CipherSuite::Tls13ChaCha20Poly1305Sha256
+        )
+    }
+
+    /// TLS 1.2の暗号スイートで、サーバーがECDSAの鍵で署名するかどうか
+    pub fn is_ecdsa(&self) -> bool {
+        matches!(
+            self,
+            CipherSuite::EcdheEcdsaAes128GcmSha256
+                | CipherSuite::EcdheEcdsaAes256GcmSha384
+                | CipherSuite::EcdheEcdsaChaCha20Poly1305Sha256
+        )
+    }
+
+    /// HKDFやPRF、トランスクリプトに使うハッシュ関数
+    pub fn hash(&self) -> HashAlgorithm {
+        match self {
+            CipherSuite::Tls13Aes256GcmSha384
+            | CipherSuite::EcdheEcdsaAes256GcmSha384
+            | CipherSuite::EcdheRsaAes256GcmSha384 => HashAlgorithm::Sha384,
+            _ => HashAlgorithm::Sha256,
+        }
+    }
+
+    pub fn aead(&self) -> AeadAlgorithm {
+        match self {
+            CipherSuite::Tls13Aes128GcmSha256
+            | CipherSuite::EcdheEcdsaAes128GcmSha256
+            | CipherSuite::EcdheRsaAes128GcmSha256 => AeadAlgorithm::Aes128Gcm,
+            CipherSuite::Tls13Aes256GcmSha384
+            | CipherSuite::EcdheEcdsaAes256GcmSha384
+            | CipherSuite::EcdheRsaAes256GcmSha384 => AeadAlgorithm::Aes256Gcm,
+            CipherSuite::Tls13ChaCha20Poly1305Sha256
+            | CipherSuite::EcdheEcdsaChaCha20Poly1305Sha256
+            | CipherSuite::EcdheRsaChaCha20Poly1305Sha256 => AeadAlgorithm::ChaCha20Poly1305,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AeadAlgorithm {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl AeadAlgorithm {
+    pub fn key_len(&self) -> usize {
+        match self {
+            AeadAlgorithm::Aes128Gcm => 16,
+            AeadAlgorithm::Aes256Gcm | AeadAlgorithm::ChaCha20Poly1305 => 32,
+        }
+    }
+}
+
+/// 認証付き暗号
+#[derive(Debug, Clone)]
+pub enum Aead {
+    AesGcm(AesGcm),
+    ChaCha20Poly1305(ChaCha20Poly1305),
+}
+
+impl Aead {
+    pub fn new(algorithm: AeadAlgorithm, key: &[u8]) -> Option<Self> {
+        if key.len() != algorithm.key_len() {
+            return None;
+        }
+        match algorithm {
+            AeadAlgorithm::Aes128Gcm | AeadAlgorithm::Aes256Gcm => {
+                AesGcm::new(key).map(Aead::AesGcm)
+            }
+            AeadAlgorithm::ChaCha20Poly1305 => {
+                ChaCha20Poly1305::new(key).map(Aead::ChaCha20Poly1305)
+            }
+        }
+    }
+
+    pub fn seal(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        match self {
+            Aead::AesGcm(aead) => aead.seal(nonce, aad, plaintext),
+            Aead::ChaCha20Poly1305(aead) => aead.seal(nonce, aad, plaintext),
+        }
+    }
+
+    pub fn open(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], input: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Aead::AesGcm(aead) => aead.open(nonce, aad, input),
+            Aead::ChaCha20Poly1305(aead) => aead.open(nonce, aad, input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cipher_suite() {
+        for suite in CipherSuite::ALL {
+            assert_eq!(Some(suite), CipherSuite::from_u16(suite.value()));
+        }
+        assert_eq!(None, CipherSuite::from_u16(0x002f));
+        assert!(CipherSuite::Tls13ChaCha20Poly1305Sha256.is_tls13());
+        assert!(!CipherSuite::EcdheRsaAes128GcmSha256.is_tls13());
+        assert_eq!(
+            HashAlgorithm::Sha384,
+            CipherSuite::EcdheRsaAes256GcmSha384.hash()
+        );
+        assert_eq!(
+            AeadAlgorithm::ChaCha20Poly1305,
+            CipherSuite::EcdheEcdsaChaCha20Poly1305Sha256.aead()
+        );
+    }
+
+    #[test]
+    fn test_aead() {
+        let nonce = [7u8; NONCE_LEN];
+        for algorithm in [
+            AeadAlgorithm::Aes128Gcm,
+            AeadAlgorithm::Aes256Gcm,
+            AeadAlgorithm::ChaCha20Poly1305,
+        ] {
+            let key: Vec<u8> = (0..algorithm.key_len() as u8).collect();
+            let aead = Aead::new(algorithm, &key).expect("invalid key");
+            let sealed = aead.seal(&nonce, b"aad", b"saba");
+            assert_eq!(Some(b"saba".to_vec()), aead.open(&nonce, b"aad", &sealed));
+            assert_eq!(None, aead.open(&nonce, b"aaa", &sealed));
+        }
+        assert!(Aead::new(AeadAlgorithm::Aes128Gcm, &[0; 32]).is_none());
+    }
+}
@@ -0,0 +1,1373 @@
+use crate::crypto::ec::Curve;
+use crate::crypto::ec::NamedCurve;
+use crate::crypto::hash::constant_time_eq;
+use crate::crypto::x25519;
+use crate::error::Error;
+use crate::tls::cipher_suite::AeadAlgorithm;
+use crate::tls::cipher_suite::CipherSuite;
+use crate::tls::codec::put_u16;
+use crate::tls::codec::put_vec16;
+use crate::tls::codec::put_vec24;
+use crate::tls::codec::put_vec8;
+use crate::tls::codec::Reader;
+use crate::tls::key_schedule::derive_secret;
+use crate::tls::key_schedule::finished_verify_data;
+use crate::tls::key_schedule::handshake_secret;
+use crate::tls::key_schedule::master_secret;
+use crate::tls::key_schedule::next_traffic_secret;
+use crate::tls::key_schedule::prf;
+use crate::tls::key_schedule::traffic_key;
+use crate::tls::record::header;
+use crate::tls::record::RecordCipher;
+use crate::tls::record::CONTENT_ALERT;
+use crate::tls::record::CONTENT_APPLICATION_DATA;
+use crate::tls::record::CONTENT_CHANGE_CIPHER_SPEC;
+use crate::tls::record::CONTENT_HANDSHAKE;
+use crate::tls::record::HEADER_LEN;
+use crate::tls::record::MAX_CIPHERTEXT_LEN;
+use crate::tls::record::MAX_FRAGMENT_LEN;
+use crate::tls::tls_error;
+use crate::tls::trust::TrustStore;
+use crate::tls::x509::Certificate;
+use crate::tls::x509::PublicKey;
+use crate::tls::x509::SignatureScheme;
+use crate::url::host::Host;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+const TLS12: u16 = 0x0303;
+const TLS13: u16 = 0x0304;
+
+/// https://www.rfc-editor.org/rfc/rfc8446#section-4
+const HELLO_REQUEST: u8 = 0;
+const CLIENT_HELLO: u8 = 1;
+const SERVER_HELLO: u8 = 2;
+const NEW_SESSION_TICKET: u8 = 4;
+const ENCRYPTED_EXTENSIONS: u8 = 8;
+const CERTIFICATE: u8 = 11;
+const SERVER_KEY_EXCHANGE: u8 = 12;
+const CERTIFICATE_REQUEST: u8 = 13;
+const SERVER_HELLO_DONE: u8 = 14;
+const CERTIFICATE_VERIFY: u8 = 15;
+const CLIENT_KEY_EXCHANGE: u8 = 16;
+const FINISHED: u8 = 20;
+const KEY_UPDATE: u8 = 24;
+const MESSAGE_HASH: u8 = 254;
+
+/// https://www.rfc-editor.org/rfc/rfc8446#section-4.2
+const EXTENSION_SERVER_NAME: u16 = 0;
+const EXTENSION_SUPPORTED_GROUPS: u16 = 10;
+const EXTENSION_EC_POINT_FORMATS: u16 = 11;
+const EXTENSION_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXTENSION_ALPN: u16 = 16;
+const EXTENSION_EXTENDED_MASTER_SECRET: u16 = 23;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+const EXTENSION_COOKIE: u16 = 44;
+const EXTENSION_KEY_SHARE: u16 = 51;
+const EXTENSION_RENEGOTIATION_INFO: u16 = 0xff01;
+
+/// https://www.rfc-editor.org/rfc/rfc8446#section-6
+const ALERT_WARNING: u8 = 1;
+const ALERT_FATAL: u8 = 2;
+const ALERT_CLOSE_NOTIFY: u8 = 0;
+const ALERT_UNEXPECTED_MESSAGE: u8 = 10;
+const ALERT_BAD_RECORD_MAC: u8 = 20;
+const ALERT_RECORD_OVERFLOW: u8 = 22;
+const ALERT_HANDSHAKE_FAILURE: u8 = 40;
+const ALERT_BAD_CERTIFICATE: u8 = 42;
+const ALERT_ILLEGAL_PARAMETER: u8 = 47;
+const ALERT_DECODE_ERROR: u8 = 50;
+const ALERT_DECRYPT_ERROR: u8 = 51;
+const ALERT_PROTOCOL_VERSION: u8 = 70;
+const ALERT_INTERNAL_ERROR: u8 = 80;
+const ALERT_NO_RENEGOTIATION: u8 = 100;
+const ALERT_UNSUPPORTED_EXTENSION: u8 = 110;
+
+/// HelloRetryRequestのServerHello.random
+/// https://www.rfc-editor.org/rfc/rfc8446#section-4.1.3
+const HELLO_RETRY_REQUEST_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+/// TLS 1.3をサポートするサーバーが、より古いバージョンを選んだときにrandomの末尾に置く値
+const DOWNGRADE_SENTINEL: &[u8] = b"DOWNGRD";
+
+fn alert_name(description: u8) -> &'static str {
+    match description {
+        ALERT_CLOSE_NOTIFY => "close_notify",
+        ALERT_UNEXPECTED_MESSAGE => "unexpected_message",
+        ALERT_BAD_RECORD_MAC => "bad_record_mac",
+        ALERT_HANDSHAKE_FAILURE => "handshake_failure",
+        ALERT_BAD_CERTIFICATE => "bad_certificate",
+        48 => "unknown_ca",
+        ALERT_ILLEGAL_PARAMETER => "illegal_parameter",
+        ALERT_DECODE_ERROR => "decode_error",
+        ALERT_DECRYPT_ERROR => "decrypt_error",
+        ALERT_PROTOCOL_VERSION => "protocol_version",
+        71 => "insufficient_security",
+        ALERT_INTERNAL_ERROR => "internal_error",
+        ALERT_UNSUPPORTED_EXTENSION => "unsupported_extension",
+        112 => "unrecognized_name",
+        116 => "certificate_required",
+        120 => "no_application_protocol",
+        _ => "unknown",
+    }
+}
+
+/// 鍵共有に使うグループ
+/// https://www.rfc-editor.org/rfc/rfc8446#section-4.2.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NamedGroup {
+    X25519,
+    Secp256r1,
+    Secp384r1,
+}
+
+impl NamedGroup {
+    const ALL: [NamedGroup; 3] = [
+        NamedGroup::X25519,
+        NamedGroup::Secp256r1,
+        NamedGroup::Secp384r1,
+    ];
+
+    fn from_u16(value: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|group| group.value() == value)
+    }
+
+    fn value(&self) -> u16 {
+        match self {
+            NamedGroup::X25519 => 0x001d,
+            NamedGroup::Secp256r1 => 0x0017,
+            NamedGroup::Secp384r1 => 0x0018,
+        }
+    }
+
+    fn curve(&self) -> Option<NamedCurve> {
+        match self {
+            NamedGroup::X25519 => None,
+            NamedGroup::Secp256r1 => Some(NamedCurve::P256),
+            NamedGroup::Secp384r1 => Some(NamedCurve::P384),
+        }
+    }
+}
+
+/// ECDHEの一時的な鍵
+struct KeyShare {
+    group: NamedGroup,
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl KeyShare {
+    fn generate(group: NamedGroup, random: &dyn Fn(&mut [u8])) -> Self {
+        match group.curve() {
+            None => {
+                let mut private_key = [0u8; 32];
+                random(&mut private_key);
+                Self {
+                    group,
+                    private_key: private_key.to_vec(),
+                    public_key: x25519::public_key(&private_key).to_vec(),
+                }
+            }
+            Some(curve) => {
+                let curve = Curve::new(curve);
+                // 秘密鍵は1以上n未満でなければならないので、範囲に入るまで作り直す
+                loop {
+                    let mut private_key = vec![0u8; curve.coordinate_len()];
+                    random(&mut private_key);
+                    if let Some(public_key) = curve.public_key(&private_key) {
+                        return Self {
+                            group,
+                            private_key,
+                            public_key,
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    fn agree(&self, peer_public_key: &[u8]) -> Option<Vec<u8>> {
+        match self.group.curve() {
+            None => {
+                let mut scalar = [0u8; 32];
+                scalar.copy_from_slice(&self.private_key);
+                let peer: [u8; 32] = peer_public_key.try_into().ok()?;
+                let shared_secret = x25519::x25519(&scalar, &peer);
+                // 位数の小さい点を送られた場合は0になる
+                // https://www.rfc-editor.org/rfc/rfc7748#section-6.1
+                if shared_secret.iter().all(|b| *b == 0) {
+                    return None;
+                }
+                Some(shared_secret.to_vec())
+            }
+            Some(curve) => Curve::new(curve).shared_secret(&self.private_key, peer_public_key),
+        }
+    }
+}
+
+/// 暗号学的に安全な乱数でバッファを埋める関数
+pub type RandomSource = Rc<dyn Fn(&mut [u8])>;
+
+/// TLSの接続の設定。同じ設定を複数の接続で共有する
+#[derive(Clone)]
+pub struct TlsConfig {
+    trust_store: TrustStore,
+    /// ALPNで提示するプロトコルを、優先する順に並べる
+    alpn_protocols: Vec<String>,
+    random: RandomSource,
+    /// 現在のUNIX時間の秒数を返す。ない場合は証明書の有効期間を確かめない
+    clock: Option<Rc<dyn Fn() -> u64>>,
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("trust_store", &self.trust_store.len())
+            .field("alpn_protocols", &self.alpn_protocols)
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    pub fn new(trust_store: TrustStore, random: RandomSource) -> Self {
+        Self {
+            trust_store,
+            alpn_protocols: vec!["http/1.1".to_string()],
+            random,
+            clock: None,
+        }
+    }
+
+    pub fn with_alpn_protocols(mut self, protocols: Vec<String>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Rc<dyn Fn() -> u64>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn trust_store(&self) -> &TrustStore {
+        &self.trust_store
+    }
+
+    pub fn alpn_protocols(&self) -> &[String] {
+        &self.alpn_protocols
+    }
+}
+
+/// ハンドシェイクで次に受け取るメッセージ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    ServerHello,
+    /// TLS 1.3
+    EncryptedExtensions,
+    CertificateOrCertificateRequest,
+    Certificate,
+    CertificateVerify,
+    Finished,
+    /// TLS 1.2
+    ServerKeyExchange,
+    ServerHelloDone,
+    ChangeCipherSpec,
+    Tls12Finished,
+    Connected,
+    /// close_notifyを受け取った
+    Closed,
+}
+
+/// 送るアラートと、呼び出し元に返すエラー。受け取ったアラートによる失敗の場合は、アラートを
+/// 送り返さない
+struct Failure {
+    alert: Option<u8>,
+    error: Error,
+}
+
+impl From<Error> for Failure {
+    /// メッセージやDERの読み取りの失敗
+    fn from(error: Error) -> Self {
+        Self {
+            alert: Some(ALERT_DECODE_ERROR),
+            error,
+        }
+    }
+}
+
+fn failure(alert: u8, message: &str) -> Failure {
+    Failure {
+        alert: Some(alert),
+        error: tls_error(message),
+    }
+}
+
+/// 拡張の一覧を読む。同じ種類の拡張が複数ある場合は誤り
+fn parse_extensions(input: &[u8]) -> Result<Vec<(u16, &[u8])>, Failure> {
+    let mut reader = Reader::new(input);
+    let mut extensions: Vec<(u16, &[u8])> = Vec::new();
+    while !reader.is_empty() {
+        let extension_type = reader.u16()?;
+        let data = reader.vec16()?;
+        if extensions.iter().any(|(t, _)| *t == extension_type) {
+            return Err(failure(ALERT_ILLEGAL_PARAMETER, "duplicate extension"));
+        }
+        extensions.push((extension_type, data));
+    }
+    Ok(extensions)
+}
+
+fn find_extension<'a>(extensions: &[(u16, &'a [u8])], extension_type: u16) -> Option<&'a [u8]> {
+    extensions
+        .iter()
+        .find(|(t, _)| *t == extension_type)
+        .map(|(_, data)| *data)
+}
+
+fn put_extension(buf: &mut Vec<u8>, extension_type: u16, data: &[u8]) {
+    put_u16(buf, extension_type);
+    put_vec16(buf, data);
+}
+
+/// ハンドシェイクメッセージにヘッダを付ける
+fn handshake_message(message_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![message_type];
+    put_vec24(&mut message, body);
+    message
+}
+
+/// ソケットなどの入出力を持たないTLSのクライアント。受信したバイト列をread_tlsに渡し、
+/// take_outgoingで取り出したバイト列を送信する
+/// https://www.rfc-editor.org/rfc/rfc8446
+/// https://www.rfc-editor.org/rfc/rfc5246
+pub struct TlsClient {
+    config: Rc<TlsConfig>,
+    host: Host,
+    state: State,
+    version: Option<u16>,
+    suite: Option<CipherSuite>,
+    client_random: [u8; 32],
+    server_random: Vec<u8>,
+    session_id: Vec<u8>,
+    key_shares: Vec<KeyShare>,
+    /// HelloRetryRequestで送られたクッキー
+    cookie: Option<Vec<u8>>,
+    retried: bool,
+    /// これまでのハンドシェイクメッセージ
+    transcript: Vec<u8>,
+    /// サーバーの証明書の公開鍵
+    server_key: Option<PublicKey>,
+    /// CertificateRequestのcertificate_request_context。TLS 1.2では空にする
+    certificate_request: Option<Vec<u8>>,
+    alpn_protocol: Option<String>,
+    /// TLS 1.3のハンドシェイクとアプリケーションデータのトラフィックの秘密
+    client_secret: Vec<u8>,
+    server_secret: Vec<u8>,
+    /// TLS 1.2
+    extended_master_secret: bool,
+    server_share: Option<(NamedGroup, Vec<u8>)>,
+    master_secret: Vec<u8>,
+    sent_change_cipher_spec: bool,
+    read_cipher: Option<RecordCipher>,
+    pending_read_cipher: Option<RecordCipher>,
+    write_cipher: Option<RecordCipher>,
+    incoming: Vec<u8>,
+    handshake_buffer: Vec<u8>,
+    outgoing: Vec<u8>,
+    plaintext: Vec<u8>,
+    error: Option<Error>,
+}
+
+impl fmt::Debug for TlsClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsClient")
+            .field("host", &self.host)
+            .field("state", &self.state)
+            .field("version", &self.version)
+            .field("suite", &self.suite)
+            .field("alpn_protocol", &self.alpn_protocol)
+            .finish()
+    }
+}
+
+impl TlsClient {
+    /// ClientHelloを作り、送信するバイト列に入れる
+    pub fn new(config: Rc<TlsConfig>, host: &Host) -> Result<Self, Error> {
+        if !matches!(host, Host::Domain(_) | Host::Ipv4(_) | Host::Ipv6(_)) {
+            return Err(tls_error(&format!("invalid server name: {}", host)));
+        }
+
+        let mut client_random = [0u8; 32];
+        (config.random)(&mut client_random);
+        // ミドルボックスとの互換性のために、TLS 1.3でもセッションIDを送る
+        // https://www.rfc-editor.org/rfc/rfc8446#appendix-D.4
+        let mut session_id = vec![0u8; 32];
+        (config.random)(&mut session_id);
+        let key_shares = vec![KeyShare::generate(NamedGroup::X25519, &*config.random)];
+
+        let mut client = Self {
+            config,
+            host: host.clone(),
+            state: State::ServerHello,
+            version: None,
+            suite: None,
+            client_random,
+            server_random: Vec::new(),
+            session_id,
+            key_shares,
+            cookie: None,
+            retried: false,
+            transcript: Vec::new(),
+            server_key: None,
+            certificate_request: None,
+            alpn_protocol: None,
+            client_secret: Vec::new(),
+            server_secret: Vec::new(),
+            extended_master_secret: false,
+            server_share: None,
+            master_secret: Vec::new(),
+            sent_change_cipher_spec: false,
+            read_cipher: None,
+            pending_read_cipher: None,
+            write_cipher: None,
+            incoming: Vec::new(),
+            handshake_buffer: Vec::new(),
+            outgoing: Vec::new(),
+            plaintext: Vec::new(),
+            error: None,
+        };
+        client.send_client_hello();
+        Ok(client)
+    }
+
+    pub fn is_handshaking(&self) -> bool {
+        !matches!(self.state, State::Connected | State::Closed)
+    }
+
+    /// サーバーがclose_notifyを送ってきたかどうか
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    /// 0x0303(TLS 1.2)か0x0304(TLS 1.3)
+    pub fn version(&self) -> Option<u16> {
+        self.version
+    }
+
+    pub fn cipher_suite(&self) -> Option<CipherSuite> {
+        self.suite
+    }
+
+    /// ALPNでサーバーが選んだプロトコル
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// サーバーに送信するバイト列を取り出す
+    pub fn take_outgoing(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.outgoing)
+    }
+
+    /// 受信した平文を読む。読むものがない場合は0を返す
+    pub fn read_plaintext(&mut self, buf: &mut [u8]) -> usize {
+        let length = buf.len().min(self.plaintext.len());
+        buf[..length].copy_from_slice(&self.plaintext[..length]);
+        self.plaintext.drain(..length);
+        length
+    }
+
+    /// 平文を暗号化して、送信するバイト列に入れる
+    pub fn write_plaintext(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        if self.state != State::Connected {
+            return Err(tls_error("connection is not established"));
+        }
+        self.send_record(CONTENT_APPLICATION_DATA, data);
+        Ok(())
+    }
+
+    /// close_notifyを送る
+    pub fn close(&mut self) {
+        if self.error.is_none() {
+            self.send_alert(ALERT_WARNING, ALERT_CLOSE_NOTIFY);
+            self.error = Some(tls_error("connection is closed"));
+        }
+    }
+
+    /// サーバーから受信したバイト列を処理する
+    pub fn read_tls(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        self.incoming.extend_from_slice(data);
+        match self.process_records() {
+            Ok(()) => Ok(()),
+            Err(failure) => {
+                if let Some(alert) = failure.alert {
+                    self.send_alert(ALERT_FATAL, alert);
+                }
+                self.error = Some(failure.error.clone());
+                Err(failure.error)
+            }
+        }
+    }
+
+    fn process_records(&mut self) -> Result<(), Failure> {
+        while self.incoming.len() >= HEADER_LEN && self.state != State::Closed {
+            let length = u16::from_be_bytes([self.incoming[3], self.incoming[4]]) as usize;
+            if length > MAX_CIPHERTEXT_LEN {
+                return Err(failure(ALERT_RECORD_OVERFLOW, "record overflow"));
+            }
+            if self.incoming[1] != 0x03 {
+                return Err(failure(ALERT_PROTOCOL_VERSION, "not a TLS record"));
+            }
+            if self.incoming.len() < HEADER_LEN + length {
+                break;
+            }
+            let record: Vec<u8> = self.incoming.drain(..HEADER_LEN + length).collect();
+            let (header, payload) = record.split_at(HEADER_LEN);
+            self.process_record(header, payload)?;
+        }
+        Ok(())
+    }
+
+    fn process_record(&mut self, header: &[u8], payload: &[u8]) -> Result<(), Failure> {
+        let content_type = header[0];
+        if content_type == CONTENT_CHANGE_CIPHER_SPEC {
+            if payload != [1] {
+                return Err(failure(
+                    ALERT_UNEXPECTED_MESSAGE,
+                    "invalid change_cipher_spec",
+                ));
+            }
+            return match (self.version, self.state) {
+                (Some(TLS12), State::ChangeCipherSpec) => {
+                    if !self.handshake_buffer.is_empty() {
+                        return Err(failure(
+                            ALERT_UNEXPECTED_MESSAGE,
+                            "handshake message spans a key change",
+                        ));
+                    }
+                    self.read_cipher = self.pending_read_cipher.take();
+                    self.state = State::Tls12Finished;
+                    Ok(())
+                }
+                // TLS 1.3のミドルボックスとの互換性のためのものは無視する
+                (_, State::ServerHello)
+                | (Some(TLS13), State::EncryptedExtensions)
+                | (Some(TLS13), State::CertificateOrCertificateRequest)
+                | (Some(TLS13), State::Certificate)
+                | (Some(TLS13), State::CertificateVerify)
+                | (Some(TLS13), State::Finished) => Ok(()),
+                _ => Err(failure(
+                    ALERT_UNEXPECTED_MESSAGE,
+                    "unexpected change_cipher_spec",
+                )),
+            };
+        }
+
+        let (content_type, data) = match self.read_cipher.as_mut() {
+            // TLS 1.3で、サーバーがクライアントの鍵を使う前に送ったアラート
+            Some(_) if content_type == CONTENT_ALERT && self.version == Some(TLS13) => {
+                (content_type, payload.to_vec())
+            }
+            Some(cipher) => cipher.decrypt(header, payload).map_err(|error| Failure {
+                alert: Some(ALERT_BAD_RECORD_MAC),
+                error,
+            })?,
+            None => (content_type, payload.to_vec()),
+        };
+
+        match content_type {
+            CONTENT_ALERT => self.process_alert(&data),
+            CONTENT_HANDSHAKE => {
+                if data.is_empty() {
+                    return Err(failure(ALERT_UNEXPECTED_MESSAGE, "empty handshake record"));
+                }
+                self.handshake_buffer.extend_from_slice(&data);
+                // 1つのレコードに複数のメッセージが入っていることも、1つのメッセージが複数の
+                // レコードに分かれていることもある
+                while self.handshake_buffer.len() >= 4 {
+                    let length = Reader::new(&self.handshake_buffer[1..4]).u24()?;
+                    if self.handshake_buffer.len() < 4 + length {
+                        break;
+                    }
+                    let message: Vec<u8> = self.handshake_buffer.drain(..4 + length).collect();
+                    self.process_handshake(&message)?;
+                    if self.state == State::Closed {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            CONTENT_APPLICATION_DATA => {
+                if self.state != State::Connected {
+                    return Err(failure(
+                        ALERT_UNEXPECTED_MESSAGE,
+                        "application data before the handshake",
+                    ));
+                }
+                self.plaintext.extend_from_slice(&data);
+                Ok(())
+            }
+            _ => Err(failure(ALERT_UNEXPECTED_MESSAGE, "unknown record type")),
+        }
+    }
+
+    fn process_alert(&mut self, data: &[u8]) -> Result<(), Failure> {
+        let (level, description) = match data {
+            [level, description] => (*level, *description),
+            _ => return Err(failure(ALERT_DECODE_ERROR, "invalid alert")),
+        };
+        if description == ALERT_CLOSE_NOTIFY {
+            if self.is_handshaking() {
+                return Err(Failure {
+                    alert: None,
+                    error: tls_error("connection closed during the handshake"),
+                });
+            }
+            self.state = State::Closed;
+            return Ok(());
+        }
+        // TLS 1.2の警告のアラートは無視する
+        if level == ALERT_WARNING && self.version == Some(TLS12) {
+            return Ok(());
+        }
+        Err(Failure {
+            alert: None,
+            error: tls_error(&format!(
+                "received alert: {} ({})",
+                alert_name(description),
+                description
+            )),
+        })
+    }
+
+    fn process_handshake(&mut self, message: &[u8]) -> Result<(), Failure> {
+        let message_type = message[0];
+        let body = &message[4..];
+
+        // ハンドシェイク後のメッセージはトランスクリプトに含めない
+        let before = self.transcript.len();
+        if self.is_handshaking() {
+            self.transcript.extend_from_slice(message);
+        }
+
+        match (self.state, message_type) {
+            (State::ServerHello, SERVER_HELLO) => self.process_server_hello(body, before),
+            (State::EncryptedExtensions, ENCRYPTED_EXTENSIONS) => {
+                self.process_encrypted_extensions(body)
+            }
+            (State::CertificateOrCertificateRequest, CERTIFICATE_REQUEST) => {
+                let mut reader = Reader::new(body);
+                let context = reader.vec8()?;
+                parse_extensions(reader.vec16()?)?;
+                reader.finish()?;
+                self.certificate_request = Some(context.to_vec());
+                self.state = State::Certificate;
+                Ok(())
+            }
+            (State::CertificateOrCertificateRequest | State::Certificate, CERTIFICATE) => {
+                self.process_certificate(body)
+            }
+            (State::CertificateVerify, CERTIFICATE_VERIFY) => {
+                self.process_certificate_verify(body, before)
+            }
+            (State::Finished, FINISHED) => self.process_finished(body, before),
+            (State::ServerKeyExchange, SERVER_KEY_EXCHANGE) => {
+                self.process_server_key_exchange(body)
+            }
+            (State::ServerHelloDone, CERTIFICATE_REQUEST) => {
+                self.certificate_request = Some(Vec::new());
+                Ok(())
+            }
+            (State::ServerHelloDone, SERVER_HELLO_DONE) => {
+                if !body.is_empty() {
+                    return Err(failure(ALERT_DECODE_ERROR, "invalid server_hello_done"));
+                }
+                self.send_client_key_exchange()
+            }
+            (State::Tls12Finished, FINISHED) => self.process_tls12_finished(body, before),
+            (State::Connected, NEW_SESSION_TICKET) if self.version == Some(TLS13) => Ok(()),
+            (State::Connected, KEY_UPDATE) if self.version == Some(TLS13) => {
+                self.process_key_update(body)
+            }
+            // 再ネゴシエーションはサポートしない
+            (State::Connected, HELLO_REQUEST) if self.version == Some(TLS12) => {
+                self.send_alert(ALERT_WARNING, ALERT_NO_RENEGOTIATION);
+                Ok(())
+            }
+            _ => Err(failure(
+                ALERT_UNEXPECTED_MESSAGE,
+                &format!("unexpected handshake message: {}", message_type),
+            )),
+        }
+    }
+
+    /// トランスクリプトの先頭からlengthバイトのハッシュ値
+    fn transcript_hash(&self, length: usize) -> Vec<u8> {
+        let hash = self.suite().hash();
+        hash.digest(&self.transcript[..length])
+    }
+
+    fn suite(&self) -> CipherSuite {
+        self.suite.expect("cipher suite is selected in ServerHello")
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.1.2
+    fn send_client_hello(&mut self) {
+        let mut body = Vec::new();
+        put_u16(&mut body, TLS12);
+        body.extend_from_slice(&self.client_random);
+        put_vec8(&mut body, &self.session_id);
+
+        let mut suites = Vec::new();
+        for suite in CipherSuite::ALL {
+            put_u16(&mut suites, suite.value());
+        }
+        put_vec16(&mut body, &suites);
+        // 圧縮はしない
+        put_vec8(&mut body, &[0]);
+
+        let mut extensions = Vec::new();
+        // IPアドレスはSNIで送らない
+        // https://www.rfc-editor.org/rfc/rfc6066#section-3
+        if let Host::Domain(domain) = &self.host {
+            let mut names = vec![0];
+            put_vec16(&mut names, domain.trim_end_matches('.').as_bytes());
+            let mut data = Vec::new();
+            put_vec16(&mut data, &names);
+            put_extension(&mut extensions, EXTENSION_SERVER_NAME, &data);
+        }
+
+        let mut groups = Vec::new();
+        for group in NamedGroup::ALL {
+            put_u16(&mut groups, group.value());
+        }
+        let mut data = Vec::new();
+        put_vec16(&mut data, &groups);
+        put_extension(&mut extensions, EXTENSION_SUPPORTED_GROUPS, &data);
+        // 圧縮されていない点だけを使う
+        put_extension(&mut extensions, EXTENSION_EC_POINT_FORMATS, &[1, 0]);
+
+        let mut schemes = Vec::new();
+        for scheme in SignatureScheme::ALL {
+            put_u16(&mut schemes, scheme.value());
+        }
+        let mut data = Vec::new();
+        put_vec16(&mut data, &schemes);
+        put_extension(&mut extensions, EXTENSION_SIGNATURE_ALGORITHMS, &data);
+
+        let mut versions = Vec::new();
+        put_u16(&mut versions, TLS13);
+        put_u16(&mut versions, TLS12);
+        let mut data = Vec::new();
+        put_vec8(&mut data, &versions);
+        put_extension(&mut extensions, EXTENSION_SUPPORTED_VERSIONS, &data);
+
+        let mut shares = Vec::new();
+        for share in &self.key_shares {
+            put_u16(&mut shares, share.group.value());
+            put_vec16(&mut shares, &share.public_key);
+        }
+        let mut data = Vec::new();
+        put_vec16(&mut data, &shares);
+        put_extension(&mut extensions, EXTENSION_KEY_SHARE, &data);
+
+        if let Some(cookie) = &self.cookie {
+            let mut data = Vec::new();
+            put_vec16(&mut data, cookie);
+            put_extension(&mut extensions, EXTENSION_COOKIE, &data);
+        }
+
+        if !self.config.alpn_protocols.is_empty() {
+            let mut protocols = Vec::new();
+            for protocol in &self.config.alpn_protocols {
+                put_vec8(&mut protocols, protocol.as_bytes());
+            }
+            let mut data = Vec::new();
+            put_vec16(&mut data, &protocols);
+            put_extension(&mut extensions, EXTENSION_ALPN, &data);
+        }
+
+        // https://www.rfc-editor.org/rfc/rfc7627
+        put_extension(&mut extensions, EXTENSION_EXTENDED_MASTER_SECRET, &[]);
+        // https://www.rfc-editor.org/rfc/rfc5746#section-3.4
+        put_extension(&mut extensions, EXTENSION_RENEGOTIATION_INFO, &[0]);
+        put_vec16(&mut body, &extensions);
+
+        self.send_handshake(&handshake_message(CLIENT_HELLO, &body));
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.1.3
+    fn process_server_hello(&mut self, body: &[u8], before: usize) -> Result<(), Failure> {
+        let mut reader = Reader::new(body);
+        let legacy_version = reader.u16()?;
+        let random = reader.bytes(32)?;
+        let session_id = reader.vec8()?;
+        let suite = reader.u16()?;
+        let compression = reader.u8()?;
+        // TLS 1.2では拡張を省略できる
+        let extensions = if reader.is_empty() {
+            Vec::new()
+        } else {
+            parse_extensions(reader.vec16()?)?
+        };
+        reader.finish()?;
+
+        let version = match find_extension(&extensions, EXTENSION_SUPPORTED_VERSIONS) {
+            Some(data) => {
+                let version = Reader::new(data).u16()?;
+                if data.len() != 2 || version != TLS13 {
+                    return Err(failure(
+                        ALERT_ILLEGAL_PARAMETER,
+                        "invalid supported_versions",
+                    ));
+                }
+                TLS13
+            }
+            None if legacy_version == TLS12 => TLS12,
+            None => {
+                return Err(failure(
+                    ALERT_PROTOCOL_VERSION,
+                    "server does not support TLS 1.2 or later",
+                ))
+            }
+        };
+        let suite = match CipherSuite::from_u16(suite) {
+            Some(suite) if suite.is_tls13() == (version == TLS13) => suite,
+            _ => return Err(failure(ALERT_ILLEGAL_PARAMETER, "unexpected cipher suite")),
+        };
+        if compression != 0 {
+            return Err(failure(ALERT_ILLEGAL_PARAMETER, "unexpected compression"));
+        }
+        if self.retried && self.suite != Some(suite) {
+            return Err(failure(
+                ALERT_ILLEGAL_PARAMETER,
+                "cipher suite changed after HelloRetryRequest",
+            ));
+        }
+        for (extension_type, _) in &extensions {
+            let allowed: &[u16] = if version == TLS13 {
+                &[
+                    EXTENSION_SUPPORTED_VERSIONS,
+                    EXTENSION_KEY_SHARE,
+                    EXTENSION_COOKIE,
+                ]
+            } else {
+                &[
+                    EXTENSION_SERVER_NAME,
+                    EXTENSION_EC_POINT_FORMATS,
+                    EXTENSION_ALPN,
+                    EXTENSION_EXTENDED_MASTER_SECRET,
+                    EXTENSION_RENEGOTIATION_INFO,
+                ]
+            };
+            if !allowed.contains(extension_type) {
+                return Err(failure(ALERT_UNSUPPORTED_EXTENSION, "unexpected extension"));
+            }
+        }
+
+        if version == TLS13 && session_id != self.session_id {
+            return Err(failure(ALERT_ILLEGAL_PARAMETER, "session id mismatch"));
+        }
+        if random == HELLO_RETRY_REQUEST_RANDOM {
+            if version != TLS13 {
+                return Err(failure(
+                    ALERT_ILLEGAL_PARAMETER,
+                    "invalid HelloRetryRequest",
+                ));
+            }
+            return self.process_hello_retry_request(suite, &extensions, before);
+        }
+
+        self.version = Some(version);
+        self.suite = Some(suite);
+        self.server_random = random.to_vec();
+
+        if version == TLS12 {
+            // TLS 1.3をサポートするサーバーがTLS 1.2を選んだ場合は、ダウングレード攻撃を受けている
+            // https://www.rfc-editor.org/rfc/rfc8446#section-4.1.3
+            if &random[24..31] == DOWNGRADE_SENTINEL {
+                return Err(failure(ALERT_ILLEGAL_PARAMETER, "downgrade detected"));
+            }
+            if let Some(data) = find_extension(&extensions, EXTENSION_RENEGOTIATION_INFO) {
+                if data != [0] {
+                    return Err(failure(
+                        ALERT_HANDSHAKE_FAILURE,
+                        "invalid renegotiation_info",
+                    ));
+                }
+            }
+            if let Some(data) = find_extension(&extensions, EXTENSION_ALPN) {
+                self.process_alpn(data)?;
+            }
+            self.extended_master_secret =
+                find_extension(&extensions, EXTENSION_EXTENDED_MASTER_SECRET).is_some();
+            self.state = State::Certificate;
+            return Ok(());
+        }
+
+        // TLS 1.3の鍵共有
+        let data = find_extension(&extensions, EXTENSION_KEY_SHARE)
+            .ok_or_else(|| failure(ALERT_HANDSHAKE_FAILURE, "no key share"))?;
+        let mut reader = Reader::new(data);
+        let group = reader.u16()?;
+        let public_key = reader.vec16()?;
+        reader.finish()?;
+        let share = self
+            .key_shares
+            .iter()
+            .find(|share| share.group.value() == group)
+            .ok_or_else(|| failure(ALERT_ILLEGAL_PARAMETER, "unexpected key share"))?;
+        let shared_secret = share
+            .agree(public_key)
+            .ok_or_else(|| failure(ALERT_ILLEGAL_PARAMETER, "invalid key share"))?;
+        self.key_shares.clear();
+
+        // https://www.rfc-editor.org/rfc/rfc8446#section-7.1
+        let hash = suite.hash();
+        let handshake_secret = handshake_secret(hash, &shared_secret);
+        let transcript_hash = self.transcript_hash(self.transcript.len());
+        self.client_secret =
+            derive_secret(hash, &handshake_secret, b"c hs traffic", &transcript_hash);
+        self.server_secret =
+            derive_secret(hash, &handshake_secret, b"s hs traffic", &transcript_hash);
+        self.master_secret = master_secret(hash, &handshake_secret);
+        self.read_cipher = Some(self.tls13_cipher(&self.server_secret)?);
+
+        self.check_key_change()?;
+        self.state = State::EncryptedExtensions;
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.1.4
+    fn process_hello_retry_request(
+        &mut self,
+        suite: CipherSuite,
+        extensions: &[(u16, &[u8])],
+        before: usize,
+    ) -> Result<(), Failure> {
+        if self.retried {
+            return Err(failure(
+                ALERT_UNEXPECTED_MESSAGE,
+                "second HelloRetryRequest",
+            ));
+        }
+        self.retried = true;
+        self.suite = Some(suite);
+
+        let mut changed = false;
+        if let Some(data) = find_extension(extensions, EXTENSION_KEY_SHARE) {
+            let mut reader = Reader::new(data);
+            let group = reader.u16()?;
+            reader.finish()?;
+            let group = NamedGroup::from_u16(group)
+                .filter(|group| self.key_shares.iter().all(|share| share.group != *group))
+                .ok_or_else(|| failure(ALERT_ILLEGAL_PARAMETER, "unexpected key share group"))?;
+            self.key_shares = vec![KeyShare::generate(group, &*self.config.random)];
+            changed = true;
+        }
+        if let Some(data) = find_extension(extensions, EXTENSION_COOKIE) {
+            let mut reader = Reader::new(data);
+            let cookie = reader.vec16()?;
+            reader.finish()?;
+            self.cookie = Some(cookie.to_vec());
+            changed = true;
+        }
+        if !changed {
+            return Err(failure(
+                ALERT_ILLEGAL_PARAMETER,
+                "HelloRetryRequest changes nothing",
+            ));
+        }
+
+        // 最初のClientHelloを、そのハッシュ値を含むメッセージに置き換える
+        let hello_retry_request = self.transcript.split_off(before);
+        let hash = self.transcript_hash(before);
+        self.transcript = handshake_message(MESSAGE_HASH, &hash);
+        self.transcript.extend_from_slice(&hello_retry_request);
+
+        self.send_change_cipher_spec();
+        self.send_client_hello();
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc7301#section-3.1
+    fn process_alpn(&mut self, data: &[u8]) -> Result<(), Failure> {
+        let mut reader = Reader::new(data);
+        let mut protocols = Reader::new(reader.vec16()?);
+        reader.finish()?;
+        let protocol = protocols.vec8()?;
+        protocols.finish()?;
+
+        match self
+            .config
+            .alpn_protocols
+            .iter()
+            .find(|p| p.as_bytes() == protocol)
+        {
+            Some(protocol) => {
+                self.alpn_protocol = Some(protocol.clone());
+                Ok(())
+            }
+            None => Err(failure(
+                ALERT_ILLEGAL_PARAMETER,
+                "server selected an unknown application protocol",
+            )),
+        }
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.3.1
+    fn process_encrypted_extensions(&mut self, body: &[u8]) -> Result<(), Failure> {
+        let mut reader = Reader::new(body);
+        let extensions = parse_extensions(reader.vec16()?)?;
+        reader.finish()?;
+        for (extension_type, data) in extensions {
+            match extension_type {
+                EXTENSION_ALPN => self.process_alpn(data)?,
+                EXTENSION_SERVER_NAME | EXTENSION_SUPPORTED_GROUPS => {}
+                _ => return Err(failure(ALERT_UNSUPPORTED_EXTENSION, "unexpected extension")),
+            }
+        }
+        self.state = State::CertificateOrCertificateRequest;
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.4.2
+    /// https://www.rfc-editor.org/rfc/rfc5246#section-7.4.2
+    fn process_certificate(&mut self, body: &[u8]) -> Result<(), Failure> {
+        let mut reader = Reader::new(body);
+        if self.version == Some(TLS13) && !reader.vec8()?.is_empty() {
+            return Err(failure(
+                ALERT_ILLEGAL_PARAMETER,
+                "unexpected certificate_request_context",
+            ));
+        }
+        let mut entries = Reader::new(reader.vec24()?);
+        reader.finish()?;
+
+        let mut chain = Vec::new();
+        while !entries.is_empty() {
+            chain.push(entries.vec24()?.to_vec());
+            if self.version == Some(TLS13) {
+                entries.vec16()?;
+            }
+        }
+
+        let now = self.config.clock.as_ref().map(|clock| clock());
+        let bad_certificate = |error| Failure {
+            alert: Some(ALERT_BAD_CERTIFICATE),
+            error,
+        };
+        self.config
+            .trust_store
+            .verify(&chain, &self.host, now)
+            .map_err(bad_certificate)?;
+        let leaf = Certificate::parse(&chain[0]).map_err(bad_certificate)?;
+
+        if self.version == Some(TLS12) {
+            let is_ec = matches!(leaf.public_key(), PublicKey::Ec(..));
+            if is_ec != self.suite().is_ecdsa() {
+                return Err(failure(
+                    ALERT_BAD_CERTIFICATE,
+                    "certificate key does not match the cipher suite",
+                ));
+            }
+            self.state = State::ServerKeyExchange;
+        } else {
+            self.state = State::CertificateVerify;
+        }
+        self.server_key = Some(leaf.public_key().clone());
+        Ok(())
+    }
+
+    /// サーバーの署名を検証する
+    fn verify_signature(
+        &self,
+        scheme: u16,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), Failure> {
+        let key = self.server_key.as_ref().expect("certificate is received");
+        let scheme = SignatureScheme::from_u16(scheme)
+            .ok_or_else(|| failure(ALERT_ILLEGAL_PARAMETER, "unexpected signature scheme"))?;
+        if self.version == Some(TLS13) {
+            // TLS 1.3ではPKCS#1 v1.5の署名は使えず、ECDSAの曲線は鍵と一致しなければならない
+            let allowed = match (key, scheme.curve()) {
+                (PublicKey::Ec(curve, _), Some(scheme_curve)) => *curve == scheme_curve,
+                (PublicKey::Rsa(_), None) => !scheme.is_pkcs1(),
+                _ => false,
+            };
+            if !allowed {
+                return Err(failure(
+                    ALERT_ILLEGAL_PARAMETER,
+                    "unexpected signature scheme",
+                ));
+            }
+        }
+        if !key.verify(scheme, message, signature) {
+            return Err(failure(ALERT_DECRYPT_ERROR, "invalid signature"));
+        }
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.4.3
+    fn process_certificate_verify(&mut self, body: &[u8], before: usize) -> Result<(), Failure> {
+        let mut reader = Reader::new(body);
+        let scheme = reader.u16()?;
+        let signature = reader.vec16()?;
+        reader.finish()?;
+
+        let mut message = vec![0x20; 64];
+        message.extend_from_slice(b"TLS 1.3, server CertificateVerify\0");
+        message.extend_from_slice(&self.transcript_hash(before));
+        self.verify_signature(scheme, &message, signature)?;
+        self.state = State::Finished;
+        Ok(())
+    }
+
+    /// サーバーのFinishedを検証し、クライアントのFinishedを送る
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.4.4
+    fn process_finished(&mut self, body: &[u8], before: usize) -> Result<(), Failure> {
+        let hash = self.suite().hash();
+        let expected =
+            finished_verify_data(hash, &self.server_secret, &self.transcript_hash(before));
+        if !constant_time_eq(&expected, body) {
+            return Err(failure(ALERT_DECRYPT_ERROR, "invalid finished"));
+        }
+        self.check_key_change()?;
+
+        let transcript_hash = self.transcript_hash(self.transcript.len());
+        let server_secret =
+            derive_secret(hash, &self.master_secret, b"s ap traffic", &transcript_hash);
+        let client_secret =
+            derive_secret(hash, &self.master_secret, b"c ap traffic", &transcript_hash);
+
+        self.send_change_cipher_spec();
+        self.write_cipher = Some(self.tls13_cipher(&self.client_secret)?);
+        // クライアント証明書は送らない
+        if let Some(context) = self.certificate_request.take() {
+            let mut body = Vec::new();
+            put_vec8(&mut body, &context);
+            put_vec24(&mut body, &[]);
+            self.send_handshake(&handshake_message(CERTIFICATE, &body));
+        }
+        let verify_data = finished_verify_data(
+            hash,
+            &self.client_secret,
+            &self.transcript_hash(self.transcript.len()),
+        );
+        self.send_handshake(&handshake_message(FINISHED, &verify_data));
+
+        self.read_cipher = Some(self.tls13_cipher(&server_secret)?);
+        self.write_cipher = Some(self.tls13_cipher(&client_secret)?);
+        self.server_secret = server_secret;
+        self.client_secret = client_secret;
+        self.finish_handshake();
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-4.6.3
+    fn process_key_update(&mut self, body: &[u8]) -> Result<(), Failure> {
+        let request_update = match body {
+            [0] => false,
+            [1] => true,
+            _ => return Err(failure(ALERT_ILLEGAL_PARAMETER, "invalid key_update")),
+        };
+        self.check_key_change()?;
+
+        let hash = self.suite().hash();
+        self.server_secret = next_traffic_secret(hash, &self.server_secret);
+        self.read_cipher = Some(self.tls13_cipher(&self.server_secret)?);
+        if request_update {
+            self.send_record(CONTENT_HANDSHAKE, &handshake_message(KEY_UPDATE, &[0]));
+            self.client_secret = next_traffic_secret(hash, &self.client_secret);
+            self.write_cipher = Some(self.tls13_cipher(&self.client_secret)?);
+        }
+        Ok(())
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc8422#section-5.4
+    fn process_server_key_exchange(&mut self, body: &[u8]) -> Result<(), Failure> {
+        let mut reader = Reader::new(body);
+        // named_curve
+        if reader.u8()? != 3 {
+            return Err(failure(ALERT_ILLEGAL_PARAMETER, "unsupported curve type"));
+        }
+        let group = NamedGroup::from_u16(reader.u16()?)
+            .ok_or_else(|| failure(ALERT_ILLEGAL_PARAMETER, "unsupported group"))?;
+        let public_key = reader.vec8()?;
+        let params_len = reader.position();
+        let scheme = reader.u16()?;
+        let signature = reader.vec16()?;
+        reader.finish()?;
+
+        let mut message = self.client_random.to_vec();
+        message.extend_from_slice(&self.server_random);
+        message.extend_from_slice(&body[..params_len]);
+        self.verify_signature(scheme, &message, signature)?;
+
+        self.server_share = Some((group, public_key.to_vec()));
+        self.state = State::ServerHelloDone;
+        Ok(())
+    }
+
+    /// ClientKeyExchange、ChangeCipherSpec、Finishedを送る
+    /// https://www.rfc-editor.org/rfc/rfc5246#section-7.4.7
+    fn send_client_key_exchange(&mut self) -> Result<(), Failure> {
+        let (group, server_public_key) = self.server_share.take().expect("key is exchanged");
+        let share = KeyShare::generate(group, &*self.config.random);
+        let premaster_secret = share
+            .agree(&server_public_key)
+            .ok_or_else(|| failure(ALERT_ILLEGAL_PARAMETER, "invalid server key"))?;
+
+        if self.certificate_request.take().is_some() {
+            self.send_handshake(&handshake_message(CERTIFICATE, &[0, 0, 0]));
+        }
+        let mut body = Vec::new();
+        put_vec8(&mut body, &share.public_key);
+        self.send_handshake(&handshake_message(CLIENT_KEY_EXCHANGE, &body));
+
+        // https://www.rfc-editor.org/rfc/rfc7627#section-4
+        let suite = self.suite();
+        let hash = suite.hash();
+        self.master_secret = if self.extended_master_secret {
+            let session_hash = self.transcript_hash(self.transcript.len());
+            prf(
+                hash,
+                &premaster_secret,
+                b"extended master secret",
+                &session_hash,
+                48,
+            )
+        } else {
+            let mut seed = self.client_random.to_vec();
+            seed.extend_from_slice(&self.server_random);
+            prf(hash, &premaster_secret, b"master secret", &seed, 48)
+        };
+
+        // https://www.rfc-editor.org/rfc/rfc5246#section-6.3
+        let key_len = suite.aead().key_len();
+        let iv_len = match suite.aead() {
+            AeadAlgorithm::ChaCha20Poly1305 => 12,
+            _ => 4,
+        };
+        let mut seed = self.server_random.clone();
+        seed.extend_from_slice(&self.client_random);
+        let key_block = prf(
+            hash,
+            &self.master_secret,
+            b"key expansion",
+            &seed,
+            2 * key_len + 2 * iv_len,
+        );
+        let (client_key, rest) = key_block.split_at(key_len);
+        let (server_key, rest) = rest.split_at(key_len);
+        let (client_iv, server_iv) = rest.split_at(iv_len);
+        let invalid_key = || failure(ALERT_INTERNAL_ERROR, "invalid traffic key");
+
+        self.send_change_cipher_spec();
+        self.write_cipher =
+            Some(RecordCipher::tls12(suite, client_key, client_iv).ok_or_else(invalid_key)?);
+        self.pending_read_cipher =
+            Some(RecordCipher::tls12(suite, server_key, server_iv).ok_or_else(invalid_key)?);
+
+        // https://www.rfc-editor.org/rfc/rfc5246#section-7.4.9
+        let verify_data = prf(
+            hash,
+            &self.master_secret,
+            b"client finished",
+            &self.transcript_hash(self.transcript.len()),
+            12,
+        );
+        self.send_handshake(&handshake_message(FINISHED, &verify_data));
+        self.state = State::ChangeCipherSpec;
+        Ok(())
+    }
+
+    fn process_tls12_finished(&mut self, body: &[u8], before: usize) -> Result<(), Failure> {
+        let expected = prf(
+            self.suite().hash(),
+            &self.master_secret,
+            b"server finished",
+            &self.transcript_hash(before),
+            12,
+        );
+        if !constant_time_eq(&expected, body) {
+            return Err(failure(ALERT_DECRYPT_ERROR, "invalid finished"));
+        }
+        self.finish_handshake();
+        Ok(())
+    }
+
+    fn finish_handshake(&mut self) {
+        self.transcript.clear();
+        self.server_key = None;
+        self.state = State::Connected;
+    }
+
+    /// 鍵が変わる時点では、次の鍵で暗号化されるはずのメッセージを受け取っていてはならない
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-5.1
+    fn check_key_change(&self) -> Result<(), Failure> {
+        if !self.handshake_buffer.is_empty() {
+            return Err(failure(
+                ALERT_UNEXPECTED_MESSAGE,
+                "handshake message spans a key change",
+            ));
+        }
+        Ok(())
+    }
+
+    fn tls13_cipher(&self, secret: &[u8]) -> Result<RecordCipher, Failure> {
+        let suite = self.suite();
+        let (key, iv) = traffic_key(suite.hash(), suite.aead(), secret);
+        RecordCipher::tls13(suite, &key, &iv)
+            .ok_or_else(|| failure(ALERT_INTERNAL_ERROR, "invalid traffic key"))
+    }
+
+    fn send_handshake(&mut self, message: &[u8]) {
+        self.transcript.extend_from_slice(message);
+        self.send_record(CONTENT_HANDSHAKE, message);
+    }
+
+    /// TLS 1.3でも、ミドルボックスとの互換性のために1度だけ送る
+    fn send_change_cipher_spec(&mut self) {
+        if self.sent_change_cipher_spec {
+            return;
+        }
+        self.sent_change_cipher_spec = true;
+        self.outgoing
+            .extend_from_slice(&header(CONTENT_CHANGE_CIPHER_SPEC, 1));
+        self.outgoing.push(1);
+    }
+
+    fn send_alert(&mut self, level: u8, description: u8) {
+        self.send_record(CONTENT_ALERT, &[level, description]);
+    }
+
+    fn send_record(&mut self, content_type: u8, data: &[u8]) {
+        for fragment in data.chunks(MAX_FRAGMENT_LEN) {
+            match self.write_cipher.as_mut() {
+                Some(cipher) => {
+                    let record = cipher.encrypt(content_type, fragment);
+                    self.outgoing.extend_from_slice(&record);
+                }
+                None => {
+                    self.outgoing
+                        .extend_from_slice(&header(content_type, fragment.len()));
+                    self.outgoing.extend_from_slice(fragment);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::error::Error;
+use crate::tls::tls_error;
+use alloc::vec::Vec;
+
+/// TLSのメッセージを読むための、ビッグエンディアンの整数と長さ付きのバイト列の読み取り
+/// https://www.rfc-editor.org/rfc/rfc8446#section-3
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    /// これまでに読んだバイト数
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.input.len() - self.position < length {
+            return Err(tls_error("decode error"));
+        }
+        let bytes = &self.input[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u24(&mut self) -> Result<usize, Error> {
+        let bytes = self.bytes(3)?;
+        Ok(((bytes[0] as usize) << 16) | ((bytes[1] as usize) << 8) | bytes[2] as usize)
+    }
+
+    /// 1バイトの長さが前に付いたバイト列
+    pub fn vec8(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.u8()? as usize;
+        self.bytes(length)
+    }
+
+    pub fn vec16(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.u16()? as usize;
+        self.bytes(length)
+    }
+
+    pub fn vec24(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.u24()?;
+        self.bytes(length)
+    }
+
+    /// 残りのバイト列がないことを確かめる
+    pub fn finish(&self) -> Result<(), Error> {
+        if !self.is_empty() {
+            return Err(tls_error("decode error"));
+        }
+        Ok(())
+    }
+}
+
+pub fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn put_u24(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+}
+
+pub fn put_vec8(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.push(bytes.len() as u8);
+    buf.extend_from_slice(bytes);
+}
+
+pub fn put_vec16(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u16(buf, bytes.len() as u16);
+    buf.extend_from_slice(bytes);
+}
+
+pub fn put_vec24(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u24(buf, bytes.len());
+    buf.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_round_trip() {
+        let mut buf = Vec::new();
+        buf.push(1);
+        put_u16(&mut buf, 0x0303);
+        put_u24(&mut buf, 0x010203);
+        put_vec8(&mut buf, b"a");
+        put_vec16(&mut buf, b"bc");
+        put_vec24(&mut buf, b"def");
+        assert_eq!(
+            vec![1, 3, 3, 1, 2, 3, 1, b'a', 0, 2, b'b', b'c', 0, 0, 3, b'd', b'e', b'f'],
+            buf
+        );
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!(Ok(1), reader.u8());
+        assert_eq!(Ok(0x0303), reader.u16());
+        assert_eq!(Ok(0x010203), reader.u24());
+        assert_eq!(Ok(&b"a"[..]), reader.vec8());
+        assert_eq!(Ok(&b"bc"[..]), reader.vec16());
+        assert_eq!(Ok(&b"def"[..]), reader.vec24());
+        assert!(reader.finish().is_ok());
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert!(Reader::new(&[0, 3, 1, 2]).vec16().is_err());
+        assert!(Reader::new(&[0]).u16().is_err());
+        assert!(Reader::new(&[0, 1]).finish().is_err());
+    }
+}
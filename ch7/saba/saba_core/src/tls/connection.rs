@@ -0,0 +1,203 @@
+use crate::error::Error;
+use crate::http::pool::Connection;
+use crate::tls::client::TlsClient;
+use crate::tls::client::TlsConfig;
+use crate::tls::tls_error;
+use crate::url::host::Host;
+use alloc::rc::Rc;
+
+const READ_BUFFER_LEN: usize = 16 * 1024;
+
+/// TCPなどの接続の上に張ったTLSの接続
+#[derive(Debug)]
+pub struct TlsConnection<C: Connection> {
+    connection: C,
+    client: TlsClient,
+}
+
+impl<C: Connection> TlsConnection<C> {
+    /// ハンドシェイクが終わるまで、接続を通してTLSのメッセージを送受信する
+    pub fn connect(connection: C, config: Rc<TlsConfig>, host: &Host) -> Result<Self, Error> {
+        let client = TlsClient::new(config, host)?;
+        let mut connection = Self { connection, client };
+        while connection.client.is_handshaking() {
+            connection.flush()?;
+            if !connection.fill()? {
+                return Err(tls_error("connection closed during the handshake"));
+            }
+        }
+        connection.flush()?;
+        Ok(connection)
+    }
+
+    pub fn client(&self) -> &TlsClient {
+        &self.client
+    }
+
+    /// ALPNで決まったプロトコル
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.client.alpn_protocol()
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let outgoing = self.client.take_outgoing();
+        let mut written = 0;
+        while written < outgoing.len() {
+            match self.connection.write(&outgoing[written..])? {
+                0 => return Err(tls_error("connection closed while sending")),
+                bytes => written += bytes,
+            }
+        }
+        Ok(())
+    }
+
+    /// 受信したバイト列をTLSのクライアントに渡す。接続が閉じられている場合はfalseを返す
+    fn fill(&mut self) -> Result<bool, Error> {
+        let mut buf = [0u8; READ_BUFFER_LEN];
+        let bytes = self.connection.read(&mut buf)?;
+        if bytes == 0 {
+            return Ok(false);
+        }
+        let result = self.client.read_tls(&buf[..bytes]);
+        // エラーの場合もアラートを送る。送れなくても元のエラーを返す
+        let _ = self.flush();
+        result.map(|_| true)
+    }
+}
+
+impl<C: Connection> Connection for TlsConnection<C> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.client.write_plaintext(buf)?;
+        self.flush()?;
+        Ok(buf.len())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            let bytes = self.client.read_plaintext(buf);
+            if bytes > 0 || buf.is_empty() || self.client.is_closed() {
+                return Ok(bytes);
+            }
+            // close_notifyなしで閉じられた場合も、HTTPのメッセージの長さで切り詰めを検出できる
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+impl<C: Connection> Drop for TlsConnection<C> {
+    fn drop(&mut self) {
+        if !self.client.is_handshaking() && !self.client.is_closed() {
+            self.client.close();
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::codec::Reader;
+    use crate::tls::trust::TrustStore;
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+
+    /// 書き込まれたバイト列を記録し、読み込みでは何も返さない接続
+    struct RecordingConnection {
+        written: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Connection for RecordingConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.written.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Error> {
+            Ok(0)
+        }
+    }
+
+    fn config() -> Rc<TlsConfig> {
+        Rc::new(TlsConfig::new(
+            TrustStore::new(),
+            Rc::new(|buf: &mut [u8]| buf.fill(7)),
+        ))
+    }
+
+    /// ClientHelloの拡張を取り出す
+    fn client_hello_extensions(record: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let mut reader = Reader::new(&record[5..]);
+        assert_eq!(1, reader.u8().expect("decode error"));
+        let mut hello = Reader::new(reader.vec24().expect("decode error"));
+        assert_eq!(0x0303, hello.u16().expect("decode error"));
+        hello.bytes(32).expect("decode error");
+        assert_eq!(32, hello.vec8().expect("decode error").len());
+        hello.vec16().expect("decode error");
+        hello.vec8().expect("decode error");
+
+        let mut extensions = Reader::new(hello.vec16().expect("decode error"));
+        let mut result = Vec::new();
+        while !extensions.is_empty() {
+            let extension_type = extensions.u16().expect("decode error");
+            let data = extensions.vec16().expect("decode error");
+            result.push((extension_type, data.to_vec()));
+        }
+        result
+    }
+
+    #[test]
+    fn test_client_hello() {
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let connection = RecordingConnection {
+            written: written.clone(),
+        };
+        let host = Host::Domain(String::from("example.com"));
+        assert_eq!(
+            Err(Error::Tls(String::from(
+                "tls: connection closed during the handshake"
+            ))),
+            TlsConnection::connect(connection, config(), &host).map(|_| ())
+        );
+
+        let written = written.borrow();
+        assert_eq!(22, written[0]);
+        let extensions = client_hello_extensions(&written);
+        let find = |extension_type| {
+            extensions
+                .iter()
+                .find(|(t, _)| *t == extension_type)
+                .map(|(_, data)| data.clone())
+        };
+        let mut server_name = vec![0, 14, 0, 0, 11];
+        server_name.extend_from_slice(b"example.com");
+        assert_eq!(Some(server_name), find(0));
+        let mut alpn = vec![0, 9, 8];
+        alpn.extend_from_slice(b"http/1.1");
+        assert_eq!(Some(alpn), find(16));
+        assert_eq!(Some(vec![4, 3, 4, 3, 3]), find(43));
+    }
+
+    #[test]
+    fn test_client_hello_ip_address() {
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let connection = RecordingConnection {
+            written: written.clone(),
+        };
+        assert!(TlsConnection::connect(connection, config(), &Host::Ipv4(0x7f000001)).is_err());
+        // IPアドレスはSNIで送らない
+        let extensions = client_hello_extensions(&written.borrow());
+        assert!(extensions.iter().all(|(t, _)| *t != 0));
+    }
+
+    #[test]
+    fn test_invalid_host() {
+        let connection = RecordingConnection {
+            written: Rc::new(RefCell::new(Vec::new())),
+        };
+        assert!(TlsConnection::connect(connection, config(), &Host::Empty).is_err());
+    }
+}
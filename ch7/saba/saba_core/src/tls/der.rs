@@ -0,0 +1,294 @@
+use crate::error::Error;
+use crate::http::date::days_from_civil;
+use crate::tls::tls_error;
+
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+/// [n]の形式の、構造化されたコンテキスト固有のタグ
+pub const fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// [n]の形式の、構造化されていないコンテキスト固有のタグ
+pub const fn context_primitive(n: u8) -> u8 {
+    0x80 | n
+}
+
+/// タグ、内容、タグと長さを含む全体のバイト列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Element<'a> {
+    pub tag: u8,
+    pub contents: &'a [u8],
+    pub raw: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    pub fn reader(&self) -> DerReader<'a> {
+        DerReader::new(self.contents)
+    }
+}
+
+/// X.509の証明書を読むための、DERで符号化されたバイト列の読み取り
+/// https://www.itu.int/rec/T-REC-X.690
+#[derive(Debug, Clone)]
+pub struct DerReader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> DerReader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    /// 次の要素を読む。証明書では31以上のタグ番号は使われないので扱わない
+    pub fn read(&mut self) -> Result<Element<'a>, Error> {
+        let start = self.position;
+        let rest = &self.input[start..];
+        if rest.len() < 2 {
+            return Err(tls_error("truncated DER element"));
+        }
+        let tag = rest[0];
+        if tag & 0x1f == 0x1f {
+            return Err(tls_error("unsupported DER tag"));
+        }
+
+        let (length, header_len) = match rest[1] {
+            length @ 0..=0x7f => (length as usize, 2),
+            // 長さの長い形式。DERでは最短の形式でなければならない
+            0x81..=0x84 => {
+                let count = (rest[1] & 0x7f) as usize;
+                if rest.len() < 2 + count || rest[2] == 0 {
+                    return Err(tls_error("invalid DER length"));
+                }
+                let length = rest[2..2 + count]
+                    .iter()
+                    .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+                if length < 0x80 {
+                    return Err(tls_error("invalid DER length"));
+                }
+                (length, 2 + count)
+            }
+            _ => return Err(tls_error("invalid DER length")),
+        };
+
+        if rest.len() - header_len < length {
+            return Err(tls_error("truncated DER element"));
+        }
+        let end = start + header_len + length;
+        self.position = end;
+        Ok(Element {
+            tag,
+            contents: &self.input[start + header_len..end],
+            raw: &self.input[start..end],
+        })
+    }
+
+    /// 指定したタグの要素を読む
+    pub fn expect(&mut self, tag: u8) -> Result<Element<'a>, Error> {
+        let element = self.read()?;
+        if element.tag != tag {
+            return Err(tls_error("unexpected DER tag"));
+        }
+        Ok(element)
+    }
+
+    /// 次の要素が指定したタグの場合だけ読む
+    pub fn optional(&mut self, tag: u8) -> Result<Option<Element<'a>>, Error> {
+        if self.peek_tag() == Some(tag) {
+            return self.expect(tag).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// 残りの要素がないことを確かめる
+    pub fn finish(&self) -> Result<(), Error> {
+        if !self.is_empty() {
+            return Err(tls_error("trailing data in DER element"));
+        }
+        Ok(())
+    }
+
+    pub fn read_oid(&mut self) -> Result<&'a [u8], Error> {
+        Ok(self.expect(TAG_OID)?.contents)
+    }
+
+    pub fn read_boolean(&mut self) -> Result<bool, Error> {
+        match self.expect(TAG_BOOLEAN)?.contents {
+            [0x00] => Ok(false),
+            [0xff] => Ok(true),
+            _ => Err(tls_error("invalid DER boolean")),
+        }
+    }
+
+    /// 負でない整数を、先頭の0を取り除いたビッグエンディアンのバイト列として読む
+    pub fn read_unsigned_integer(&mut self) -> Result<&'a [u8], Error> {
+        let contents = self.expect(TAG_INTEGER)?.contents;
+        match contents {
+            [] => Err(tls_error("invalid DER integer")),
+            [first, ..] if first & 0x80 != 0 => Err(tls_error("negative DER integer")),
+            [0, second, ..] if second & 0x80 == 0 => Err(tls_error("invalid DER integer")),
+            [0, rest @ ..] if !rest.is_empty() => Ok(rest),
+            _ => Ok(contents),
+        }
+    }
+
+    pub fn read_small_integer(&mut self) -> Result<u64, Error> {
+        let bytes = self.read_unsigned_integer()?;
+        if bytes.len() > 8 {
+            return Err(tls_error("DER integer is too large"));
+        }
+        Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    /// 未使用のビットがないBIT STRINGを読む
+    pub fn read_bit_string_bytes(&mut self) -> Result<&'a [u8], Error> {
+        match self.expect(TAG_BIT_STRING)?.contents {
+            [0, rest @ ..] => Ok(rest),
+            _ => Err(tls_error("invalid DER bit string")),
+        }
+    }
+
+    /// UTCTimeかGeneralizedTimeを、UNIX時間の秒数として読む
+    /// https://www.rfc-editor.org/rfc/rfc5280#section-4.1.2.5
+    pub fn read_time(&mut self) -> Result<u64, Error> {
+        let element = self.read()?;
+        let digits = match element.tag {
+            TAG_UTC_TIME if element.contents.len() == 13 => {
+                let year = parse_digits(&element.contents[..2])?;
+                // 50以上は19xx年、50未満は20xx年
+                let century = if year >= 50 { b"19" } else { b"20" };
+                let mut digits = century.to_vec();
+                digits.extend_from_slice(element.contents);
+                digits
+            }
+            TAG_GENERALIZED_TIME if element.contents.len() == 15 => element.contents.to_vec(),
+            _ => return Err(tls_error("invalid DER time")),
+        };
+        if digits[14] != b'Z' {
+            return Err(tls_error("invalid DER time"));
+        }
+
+        let year = parse_digits(&digits[0..4])?;
+        let month = parse_digits(&digits[4..6])?;
+        let day = parse_digits(&digits[6..8])?;
+        let hour = parse_digits(&digits[8..10])?;
+        let minute = parse_digits(&digits[10..12])?;
+        let second = parse_digits(&digits[12..14])?;
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(tls_error("invalid DER time"));
+        }
+
+        let days = days_from_civil(year as i64, month, day);
+        let seconds = days * 86400 + (hour * 3600 + minute * 60 + second) as i64;
+        Ok(seconds.max(0) as u64)
+    }
+}
+
+fn parse_digits(digits: &[u8]) -> Result<u32, Error> {
+    digits.iter().try_fold(0u32, |acc, d| {
+        if d.is_ascii_digit() {
+            Ok(acc * 10 + (d - b'0') as u32)
+        } else {
+            Err(tls_error("invalid DER time"))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_read() {
+        let input = [0x30, 0x06, 0x02, 0x01, 0x05, 0x01, 0x01, 0xff];
+        let mut reader = DerReader::new(&input);
+        let sequence = reader.expect(TAG_SEQUENCE).expect("failed to read");
+        assert!(reader.is_empty());
+        assert_eq!(&input[..], sequence.raw);
+
+        let mut inner = sequence.reader();
+        assert_eq!(5, inner.read_small_integer().expect("failed to read"));
+        assert!(inner.read_boolean().expect("failed to read"));
+        assert!(inner.finish().is_ok());
+    }
+
+    #[test]
+    fn test_long_length() {
+        let mut input = vec![0x04, 0x81, 0x80];
+        input.extend_from_slice(&[0xaa; 0x80]);
+        let mut reader = DerReader::new(&input);
+        assert_eq!(0x80, reader.read().expect("failed to read").contents.len());
+
+        // 長い形式で短い長さを表すのはDERでは誤り
+        assert!(DerReader::new(&[0x04, 0x81, 0x01, 0xaa]).read().is_err());
+        assert!(DerReader::new(&[0x04, 0x05, 0xaa]).read().is_err());
+    }
+
+    #[test]
+    fn test_integer() {
+        let input = [0x02, 0x03, 0x00, 0x80, 0x01];
+        assert_eq!(
+            &[0x80, 0x01][..],
+            DerReader::new(&input)
+                .read_unsigned_integer()
+                .expect("failed to read")
+        );
+        assert!(DerReader::new(&[0x02, 0x01, 0x80])
+            .read_unsigned_integer()
+            .is_err());
+        assert!(DerReader::new(&[0x02, 0x02, 0x00, 0x01])
+            .read_unsigned_integer()
+            .is_err());
+    }
+
+    #[test]
+    fn test_time() {
+        let mut input = vec![TAG_UTC_TIME, 13];
+        input.extend_from_slice(b"491231235959Z");
+        assert_eq!(
+            2524607999,
+            DerReader::new(&input).read_time().expect("failed to read")
+        );
+
+        let mut input = vec![TAG_UTC_TIME, 13];
+        input.extend_from_slice(b"700101000000Z");
+        assert_eq!(
+            0,
+            DerReader::new(&input).read_time().expect("failed to read")
+        );
+
+        let mut input = vec![TAG_GENERALIZED_TIME, 15];
+        input.extend_from_slice(b"20500101000000Z");
+        assert_eq!(
+            2524608000,
+            DerReader::new(&input).read_time().expect("failed to read")
+        );
+
+        let mut input = vec![TAG_UTC_TIME, 13];
+        input.extend_from_slice(b"491331235959Z");
+        assert!(DerReader::new(&input).read_time().is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::crypto::gcm::NONCE_LEN;
+use crate::crypto::hash::hkdf_expand;
+use crate::crypto::hash::hkdf_extract;
+use crate::crypto::hash::hmac;
+use crate::crypto::hash::HashAlgorithm;
+use crate::tls::cipher_suite::AeadAlgorithm;
+use crate::tls::codec::put_u16;
+use crate::tls::codec::put_vec8;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// https://www.rfc-editor.org/rfc/rfc8446#section-7.1
+pub fn hkdf_expand_label(
+    hash: HashAlgorithm,
+    secret: &[u8],
+    label: &[u8],
+    context: &[u8],
+    length: usize,
+) -> Vec<u8> {
+    let mut full_label = b"tls13 ".to_vec();
+    full_label.extend_from_slice(label);
+
+    let mut info = Vec::new();
+    put_u16(&mut info, length as u16);
+    put_vec8(&mut info, &full_label);
+    put_vec8(&mut info, context);
+    hkdf_expand(hash, secret, &info, length)
+}
+
+/// transcript_hashは、それまでのハンドシェイクメッセージのハッシュ値
+pub fn derive_secret(
+    hash: HashAlgorithm,
+    secret: &[u8],
+    label: &[u8],
+    transcript_hash: &[u8],
+) -> Vec<u8> {
+    hkdf_expand_label(hash, secret, label, transcript_hash, hash.output_len())
+}
+
+/// 鍵共有の結果から、ハンドシェイクの秘密を求める。PSKは使わない
+pub fn handshake_secret(hash: HashAlgorithm, shared_secret: &[u8]) -> Vec<u8> {
+    let zeros = vec![0; hash.output_len()];
+    let early_secret = hkdf_extract(hash, &zeros, &zeros);
+    let salt = derive_secret(hash, &early_secret, b"derived", &hash.digest(&[]));
+    hkdf_extract(hash, &salt, shared_secret)
+}
+
+pub fn master_secret(hash: HashAlgorithm, handshake_secret: &[u8]) -> Vec<u8> {
+    let zeros = vec![0; hash.output_len()];
+    let salt = derive_secret(hash, handshake_secret, b"derived", &hash.digest(&[]));
+    hkdf_extract(hash, &salt, &zeros)
+}
+
+/// トラフィックの秘密から、レコードを暗号化する鍵とIVを求める
+/// https://www.rfc-editor.org/rfc/rfc8446#section-7.3
+pub fn traffic_key(hash: HashAlgorithm, aead: AeadAlgorithm, secret: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    (
+        hkdf_expand_label(hash, secret, b"key", &[], aead.key_len()),
+        hkdf_expand_label(hash, secret, b"iv", &[], NONCE_LEN),
+    )
+}
+
+/// https://www.rfc-editor.org/rfc/rfc8446#section-4.4.4
+pub fn finished_verify_data(
+    hash: HashAlgorithm,
+    base_key: &[u8],
+    transcript_hash: &[u8],
+) -> Vec<u8> {
+    let finished_key = hkdf_expand_label(hash, base_key, b"finished", &[], hash.output_len());
+    hmac(hash, &finished_key, transcript_hash)
+}
+
+/// KeyUpdateの後に使うトラフィックの秘密
+/// https://www.rfc-editor.org/rfc/rfc8446#section-7.2
+pub fn next_traffic_secret(hash: HashAlgorithm, secret: &[u8]) -> Vec<u8> {
+    hkdf_expand_label(hash, secret, b"traffic upd", &[], hash.output_len())
+}
+
+/// TLS 1.2の擬似乱数関数
+/// https://www.rfc-editor.org/rfc/rfc5246#section-5
+pub fn prf(
+    hash: HashAlgorithm,
+    secret: &[u8],
+    label: &[u8],
+    seed: &[u8],
+    length: usize,
+) -> Vec<u8> {
+    let mut label_seed = label.to_vec();
+    label_seed.extend_from_slice(seed);
+
+    let mut output = Vec::new();
+    let mut a = hmac(hash, secret, &label_seed);
+    while output.len() < length {
+        let mut input = a.clone();
+        input.extend_from_slice(&label_seed);
+        output.extend_from_slice(&hmac(hash, secret, &input));
+        a = hmac(hash, secret, &a);
+    }
+    output.truncate(length);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+
+    #[test]
+    fn test_hkdf_expand_label() {
+        let secret: Vec<u8> = (0..32).collect();
+        let (key, iv) = traffic_key(HashAlgorithm::Sha256, AeadAlgorithm::Aes128Gcm, &secret);
+        assert_eq!(from_hex("9c9783cf77ea32d44f369da41f19f3cc"), key);
+        assert_eq!(from_hex("2f41c846a431a163814bcd71"), iv);
+        assert_eq!(
+            from_hex("a5b1caa258481fdf573ac069f281e534e4a2379ec9e457e0c8494c227efb40e6"),
+            derive_secret(
+                HashAlgorithm::Sha256,
+                &secret,
+                b"derived",
+                &HashAlgorithm::Sha256.digest(&[])
+            )
+        );
+    }
+
+    #[test]
+    fn test_handshake_secret() {
+        // https://www.rfc-editor.org/rfc/rfc8448#section-3
+        let shared_secret =
+            from_hex("8bd4054fb55b9d63fdfbacf9f04b9f0d35e6d63f537563efd46272900f89492d");
+        assert_eq!(
+            from_hex("1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac"),
+            handshake_secret(HashAlgorithm::Sha256, &shared_secret)
+        );
+    }
+
+    #[test]
+    fn test_prf() {
+        let secret: Vec<u8> = (0..16).collect();
+        assert_eq!(
+            from_hex(
+                "5901d25d7aaa63b54928db939d66f6f12dd75314edb658d1a6f5d680645005a748d20ca0965150d3"
+            ),
+            prf(HashAlgorithm::Sha256, &secret, b"test label", b"seed", 40)
+        );
+        assert_eq!(
+            from_hex(
+                "4c0c8216210e1c622125713ee2e5f4fb141855662cbc41804a36863aa08feeeb02dab2cb81ece5d7\
+                 c613c61d2b9e0291f16647ef6bcffd41f1b2901d"
+            ),
+            prf(HashAlgorithm::Sha384, &secret, b"test label", b"seed", 60)
+        );
+    }
+}
@@ -0,0 +1,17 @@
+pub mod cipher_suite;
+pub mod client;
+pub mod codec;
+pub mod connection;
+pub mod der;
+pub mod key_schedule;
+pub mod record;
+pub mod trust;
+pub mod x509;
+
+use crate::error::Error;
+use alloc::format;
+
+/// TLSの失敗はすべてネットワークのエラーとして扱う
+pub(crate) fn tls_error(message: &str) -> Error {
+    Error::Tls(format!("tls: {}", message))
+}
@@ -0,0 +1,287 @@
+use crate::crypto::gcm::NONCE_LEN;
+use crate::crypto::gcm::TAG_LEN;
+use crate::error::Error;
+use crate::tls::cipher_suite::Aead;
+use crate::tls::cipher_suite::AeadAlgorithm;
+use crate::tls::cipher_suite::CipherSuite;
+use crate::tls::codec::put_u16;
+use crate::tls::tls_error;
+use alloc::vec::Vec;
+
+/// https://www.rfc-editor.org/rfc/rfc8446#section-5.1
+pub const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_ALERT: u8 = 21;
+pub const CONTENT_HANDSHAKE: u8 = 22;
+pub const CONTENT_APPLICATION_DATA: u8 = 23;
+
+pub const HEADER_LEN: usize = 5;
+/// 平文のレコードの長さの上限
+pub const MAX_FRAGMENT_LEN: usize = 16384;
+/// 暗号化されたレコードは、平文より256バイトまで長くなれる
+pub const MAX_CIPHERTEXT_LEN: usize = MAX_FRAGMENT_LEN + 256;
+
+const LEGACY_VERSION: u16 = 0x0303;
+/// TLS 1.2のAES-GCMで、レコードに含める明示的なnonceの長さ
+const EXPLICIT_NONCE_LEN: usize = 8;
+
+/// レコードのヘッダ
+pub fn header(content_type: u8, length: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.push(content_type);
+    put_u16(&mut header, LEGACY_VERSION);
+    put_u16(&mut header, length as u16);
+    header
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NonceMode {
+    /// IVとシーケンス番号のXOR。内側の平文にコンテントタイプを含める
+    /// https://www.rfc-editor.org/rfc/rfc8446#section-5.2
+    Tls13,
+    /// 4バイトのソルトと、レコードに含める8バイトのnonce
+    /// https://www.rfc-editor.org/rfc/rfc5288#section-3
+    Tls12Explicit,
+    /// IVとシーケンス番号のXOR
+    /// https://www.rfc-editor.org/rfc/rfc7905#section-2
+    Tls12,
+}
+
+/// 一方向のレコードの暗号化と復号
+#[derive(Debug, Clone)]
+pub struct RecordCipher {
+    aead: Aead,
+    iv: Vec<u8>,
+    sequence: u64,
+    mode: NonceMode,
+}
+
+impl RecordCipher {
+    pub fn tls13(suite: CipherSuite, key: &[u8], iv: &[u8]) -> Option<Self> {
+        Self::new(suite, key, iv, NonceMode::Tls13)
+    }
+
+    /// TLS 1.2では、AES-GCMのIVは4バイトのソルトになる
+    pub fn tls12(suite: CipherSuite, key: &[u8], iv: &[u8]) -> Option<Self> {
+        let mode = match suite.aead() {
+            AeadAlgorithm::ChaCha20Poly1305 => NonceMode::Tls12,
+            _ => NonceMode::Tls12Explicit,
+        };
+        Self::new(suite, key, iv, mode)
+    }
+
+    fn new(suite: CipherSuite, key: &[u8], iv: &[u8], mode: NonceMode) -> Option<Self> {
+        let iv_len = match mode {
+            NonceMode::Tls12Explicit => NONCE_LEN - EXPLICIT_NONCE_LEN,
+            _ => NONCE_LEN,
+        };
+        if iv.len() != iv_len {
+            return None;
+        }
+        Some(Self {
+            aead: Aead::new(suite.aead(), key)?,
+            iv: iv.to_vec(),
+            sequence: 0,
+            mode,
+        })
+    }
+
+    /// TLS 1.2のAES-GCM以外のnonce
+    fn xor_nonce(&self) -> [u8; NONCE_LEN] {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&self.iv);
+        for (n, s) in nonce[NONCE_LEN - 8..]
+            .iter_mut()
+            .zip(self.sequence.to_be_bytes())
+        {
+            *n ^= s;
+        }
+        nonce
+    }
+
+    /// TLS 1.2の追加の認証データ
+    /// https://www.rfc-editor.org/rfc/rfc5246#section-6.2.3.3
+    fn tls12_aad(&self, content_type: u8, length: usize) -> Vec<u8> {
+        let mut aad = self.sequence.to_be_bytes().to_vec();
+        aad.push(content_type);
+        put_u16(&mut aad, LEGACY_VERSION);
+        put_u16(&mut aad, length as u16);
+        aad
+    }
+
+    /// 平文を暗号化し、ヘッダを含むレコードにする
+    pub fn encrypt(&mut self, content_type: u8, data: &[u8]) -> Vec<u8> {
+        let record = match self.mode {
+            NonceMode::Tls13 => {
+                let mut inner = data.to_vec();
+                inner.push(content_type);
+                let mut record = header(CONTENT_APPLICATION_DATA, inner.len() + TAG_LEN);
+                let sealed = self.aead.seal(&self.xor_nonce(), &record, &inner);
+                record.extend_from_slice(&sealed);
+                record
+            }
+            NonceMode::Tls12Explicit => {
+                let explicit = self.sequence.to_be_bytes();
+                let mut nonce = [0u8; NONCE_LEN];
+                nonce[..4].copy_from_slice(&self.iv);
+                nonce[4..].copy_from_slice(&explicit);
+                let aad = self.tls12_aad(content_type, data.len());
+                let mut record = header(content_type, EXPLICIT_NONCE_LEN + data.len() + TAG_LEN);
+                record.extend_from_slice(&explicit);
+                record.extend_from_slice(&self.aead.seal(&nonce, &aad, data));
+                record
+            }
+            NonceMode::Tls12 => {
+                let aad = self.tls12_aad(content_type, data.len());
+                let mut record = header(content_type, data.len() + TAG_LEN);
+                record.extend_from_slice(&self.aead.seal(&self.xor_nonce(), &aad, data));
+                record
+            }
+        };
+        self.sequence += 1;
+        record
+    }
+
+    /// レコードを復号し、コンテントタイプと平文を返す。headerは受信したレコードのヘッダ
+    pub fn decrypt(&mut self, header: &[u8], payload: &[u8]) -> Result<(u8, Vec<u8>), Error> {
+        let bad_record_mac = || tls_error("bad record mac");
+        let content_type = header[0];
+        let result = match self.mode {
+            NonceMode::Tls13 => {
+                if content_type != CONTENT_APPLICATION_DATA {
+                    return Err(tls_error("unexpected unencrypted record"));
+                }
+                let mut inner = self
+                    .aead
+                    .open(&self.xor_nonce(), header, payload)
+                    .ok_or_else(bad_record_mac)?;
+                // 末尾の0の詰め物を取り除くと、最後のバイトが本当のコンテントタイプになる
+                let length = inner
+                    .iter()
+                    .rposition(|b| *b != 0)
+                    .ok_or_else(|| tls_error("no content type in record"))?;
+                let inner_type = inner[length];
+                inner.truncate(length);
+                (inner_type, inner)
+            }
+            NonceMode::Tls12Explicit => {
+                if payload.len() < EXPLICIT_NONCE_LEN + TAG_LEN {
+                    return Err(bad_record_mac());
+                }
+                let mut nonce = [0u8; NONCE_LEN];
+                nonce[..4].copy_from_slice(&self.iv);
+                nonce[4..].copy_from_slice(&payload[..EXPLICIT_NONCE_LEN]);
+                let aad =
+                    self.tls12_aad(content_type, payload.len() - EXPLICIT_NONCE_LEN - TAG_LEN);
+                let data = self
+                    .aead
+                    .open(&nonce, &aad, &payload[EXPLICIT_NONCE_LEN..])
+                    .ok_or_else(bad_record_mac)?;
+                (content_type, data)
+            }
+            NonceMode::Tls12 => {
+                if payload.len() < TAG_LEN {
+                    return Err(bad_record_mac());
+                }
+                let aad = self.tls12_aad(content_type, payload.len() - TAG_LEN);
+                let data = self
+                    .aead
+                    .open(&self.xor_nonce(), &aad, payload)
+                    .ok_or_else(bad_record_mac)?;
+                (content_type, data)
+            }
+        };
+        if result.1.len() > MAX_FRAGMENT_LEN {
+            return Err(tls_error("record overflow"));
+        }
+        self.sequence += 1;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::from_hex;
+    use alloc::vec;
+
+    fn round_trip(mut writer: RecordCipher, mut reader: RecordCipher) {
+        for data in [&b"GET / HTTP/1.1\r\n\r\n"[..], &b"saba"[..]] {
+            let record = writer.encrypt(CONTENT_APPLICATION_DATA, data);
+            assert_eq!(
+                record.len() - HEADER_LEN,
+                (record[3] as usize) << 8 | record[4] as usize
+            );
+            let (header, payload) = record.split_at(HEADER_LEN);
+            assert_eq!(
+                Ok((CONTENT_APPLICATION_DATA, data.to_vec())),
+                reader.decrypt(header, payload)
+            );
+        }
+
+        // 改ざんされたレコード
+        let mut record = writer.encrypt(CONTENT_HANDSHAKE, b"saba");
+        let last = record.len() - 1;
+        record[last] ^= 1;
+        let (header, payload) = record.split_at(HEADER_LEN);
+        assert!(reader.decrypt(header, payload).is_err());
+    }
+
+    #[test]
+    fn test_tls13() {
+        for suite in [
+            CipherSuite::Tls13Aes128GcmSha256,
+            CipherSuite::Tls13Aes256GcmSha384,
+            CipherSuite::Tls13ChaCha20Poly1305Sha256,
+        ] {
+            let key = vec![1; suite.aead().key_len()];
+            let iv = [2; NONCE_LEN];
+            round_trip(
+                RecordCipher::tls13(suite, &key, &iv).expect("invalid key"),
+                RecordCipher::tls13(suite, &key, &iv).expect("invalid key"),
+            );
+        }
+    }
+
+    #[test]
+    fn test_tls13_record() {
+        // https://www.rfc-editor.org/rfc/rfc8448#section-3 (クライアントのFinished)
+        let mut cipher = RecordCipher::tls13(
+            CipherSuite::Tls13Aes128GcmSha256,
+            &from_hex("dbfaa693d1762c5b666af5d950258d01"),
+            &from_hex("5bd3c71b836e0b76bb73265f"),
+        )
+        .expect("invalid key");
+        let finished =
+            from_hex("14000020a8ec436d677634ae525ac1fcebe11a039ec17694fac6e98527b642f2edd5ce61");
+        assert_eq!(
+            from_hex(
+                "1703030035\
+                 75ec4dc238cce60b298044a71e219c56cc77b0517fe9b93c7a4bfc44d87f38f80338ac98fc\
+                 46deb384bd1caeacab6867d726c40546"
+            ),
+            cipher.encrypt(CONTENT_HANDSHAKE, &finished)
+        );
+    }
+
+    #[test]
+    fn test_tls12() {
+        for suite in [
+            CipherSuite::EcdheRsaAes128GcmSha256,
+            CipherSuite::EcdheEcdsaAes256GcmSha384,
+            CipherSuite::EcdheRsaChaCha20Poly1305Sha256,
+        ] {
+            let key = vec![1; suite.aead().key_len()];
+            let iv = match suite.aead() {
+                AeadAlgorithm::ChaCha20Poly1305 => vec![2; 12],
+                _ => vec![2; 4],
+            };
+            round_trip(
+                RecordCipher::tls12(suite, &key, &iv).expect("invalid key"),
+                RecordCipher::tls12(suite, &key, &iv).expect("invalid key"),
+            );
+        }
+        assert!(
+            RecordCipher::tls12(CipherSuite::EcdheRsaAes128GcmSha256, &[1; 16], &[2; 12]).is_none()
+        );
+    }
+}
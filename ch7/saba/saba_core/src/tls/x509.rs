@@ -0,0 +1,559 @@
+use crate::crypto::bignum::BigUint;
+use crate::crypto::ec::Curve;
+use crate::crypto::ec::NamedCurve;
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::rsa::RsaPublicKey;
+use crate::error::Error;
+use crate::tls::der::context;
+use crate::tls::der::context_primitive;
+use crate::tls::der::DerReader;
+use crate::tls::der::TAG_BIT_STRING;
+use crate::tls::der::TAG_BOOLEAN;
+use crate::tls::der::TAG_NULL;
+use crate::tls::der::TAG_OCTET_STRING;
+use crate::tls::der::TAG_SEQUENCE;
+use crate::tls::tls_error;
+use crate::url::host::Host;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const OID_SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const OID_SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_EXT_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const OID_SERVER_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+const OID_ANY_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25, 0x00];
+
+/// https://www.rfc-editor.org/rfc/rfc5280#section-4.2.1.3
+pub const KEY_USAGE_DIGITAL_SIGNATURE: u16 = 1 << 0;
+pub const KEY_USAGE_KEY_CERT_SIGN: u16 = 1 << 5;
+
+/// TLSで使う署名アルゴリズム。証明書の署名アルゴリズムもこれで表す
+/// https://www.rfc-editor.org/rfc/rfc8446#section-4.2.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    EcdsaSecp256r1Sha256,
+    EcdsaSecp384r1Sha384,
+    RsaPssRsaeSha256,
+    RsaPssRsaeSha384,
+    RsaPssRsaeSha512,
+    RsaPkcs1Sha256,
+    RsaPkcs1Sha384,
+    RsaPkcs1Sha512,
+}
+
+impl SignatureScheme {
+    /// ClientHelloで送る順に並べる
+    pub const ALL: [SignatureScheme; 8] = [
+        SignatureScheme::EcdsaSecp256r1Sha256,
+        SignatureScheme::EcdsaSecp384r1Sha384,
+        SignatureScheme::RsaPssRsaeSha256,
+        SignatureScheme::RsaPssRsaeSha384,
+        SignatureScheme::RsaPssRsaeSha512,
+        SignatureScheme::RsaPkcs1Sha256,
+        SignatureScheme::RsaPkcs1Sha384,
+        SignatureScheme::RsaPkcs1Sha512,
+    ];
+
+    pub fn from_u16(value: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|scheme| scheme.value() == value)
+    }
+
+    pub fn value(&self) -> u16 {
+        match self {
+            SignatureScheme::EcdsaSecp256r1Sha256 => 0x0403,
+            SignatureScheme::EcdsaSecp384r1Sha384 => 0x0503,
+            SignatureScheme::RsaPssRsaeSha256 => 0x0804,
+            SignatureScheme::RsaPssRsaeSha384 => 0x0805,
+            SignatureScheme::RsaPssRsaeSha512 => 0x0806,
+            SignatureScheme::RsaPkcs1Sha256 => 0x0401,
+            SignatureScheme::RsaPkcs1Sha384 => 0x0501,
+            SignatureScheme::RsaPkcs1Sha512 => 0x0601,
+        }
+    }
+
+    pub fn hash(&self) -> HashAlgorithm {
+        match self {
+            SignatureScheme::EcdsaSecp256r1Sha256
+            | SignatureScheme::RsaPssRsaeSha256
+            | SignatureScheme::RsaPkcs1Sha256 => HashAlgorithm::Sha256,
+            SignatureScheme::EcdsaSecp384r1Sha384
+            | SignatureScheme::RsaPssRsaeSha384
+            | SignatureScheme::RsaPkcs1Sha384 => HashAlgorithm::Sha384,
+            SignatureScheme::RsaPssRsaeSha512 | SignatureScheme::RsaPkcs1Sha512 => {
+                HashAlgorithm::Sha512
+            }
+        }
+    }
+
+    /// TLS 1.3では、ECDSAの署名アルゴリズムは曲線も決める
+    pub fn curve(&self) -> Option<NamedCurve> {
+        match self {
+            SignatureScheme::EcdsaSecp256r1Sha256 => Some(NamedCurve::P256),
+            SignatureScheme::EcdsaSecp384r1Sha384 => Some(NamedCurve::P384),
+            _ => None,
+        }
+    }
+
+    pub fn is_pkcs1(&self) -> bool {
+        matches!(
+            self,
+            SignatureScheme::RsaPkcs1Sha256
+                | SignatureScheme::RsaPkcs1Sha384
+                | SignatureScheme::RsaPkcs1Sha512
+        )
+    }
+}
+
+/// 証明書に含まれる公開鍵
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    Rsa(RsaPublicKey),
+    /// 曲線と、符号化された点
+    Ec(NamedCurve, Vec<u8>),
+}
+
+impl PublicKey {
+    /// https://www.rfc-editor.org/rfc/rfc5280#section-4.1.2.7
+    fn parse(spki: &mut DerReader) -> Result<Self, Error> {
+        let mut algorithm = spki.expect(TAG_SEQUENCE)?.reader();
+        let key = spki.read_bit_string_bytes()?;
+        spki.finish()?;
+
+        match algorithm.read_oid()? {
+            // https://www.rfc-editor.org/rfc/rfc3279#section-2.3.1
+            OID_RSA_ENCRYPTION => {
+                let mut key = DerReader::new(key).expect(TAG_SEQUENCE)?.reader();
+                let n = key.read_unsigned_integer()?;
+                let e = key.read_unsigned_integer()?;
+                key.finish()?;
+                match RsaPublicKey::new(n, e) {
+                    Some(key) => Ok(PublicKey::Rsa(key)),
+                    None => Err(tls_error("unsupported RSA key")),
+                }
+            }
+            // https://www.rfc-editor.org/rfc/rfc5480#section-2.1.1
+            OID_EC_PUBLIC_KEY => {
+                let curve = match algorithm.read_oid()? {
+                    OID_PRIME256V1 => NamedCurve::P256,
+                    OID_SECP384R1 => NamedCurve::P384,
+                    _ => return Err(tls_error("unsupported elliptic curve")),
+                };
+                Ok(PublicKey::Ec(curve, key.to_vec()))
+            }
+            _ => Err(tls_error("unsupported public key algorithm")),
+        }
+    }
+
+    /// 署名を検証する。ECDSAでは、曲線は鍵のものを使い、ハッシュ関数だけを署名アルゴリズム
+    /// から決める
+    pub fn verify(&self, scheme: SignatureScheme, message: &[u8], signature: &[u8]) -> bool {
+        match (self, scheme.curve()) {
+            (PublicKey::Rsa(key), None) if scheme.is_pkcs1() => {
+                key.verify_pkcs1(scheme.hash(), message, signature)
+            }
+            (PublicKey::Rsa(key), None) => key.verify_pss(scheme.hash(), message, signature),
+            (PublicKey::Ec(curve, point), Some(_)) => {
+                // https://www.rfc-editor.org/rfc/rfc3279#section-2.2.3
+                let parse = || -> Result<(BigUint, BigUint), Error> {
+                    let mut reader = DerReader::new(signature);
+                    let mut values = reader.expect(TAG_SEQUENCE)?.reader();
+                    reader.finish()?;
+                    let r = BigUint::from_bytes_be(values.read_unsigned_integer()?);
+                    let s = BigUint::from_bytes_be(values.read_unsigned_integer()?);
+                    values.finish()?;
+                    Ok((r, s))
+                };
+                match parse() {
+                    Ok((r, s)) => {
+                        Curve::new(*curve).verify(point, &scheme.hash().digest(message), &r, &s)
+                    }
+                    Err(_) => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// X.509の証明書のうち、サーバーの認証に必要な部分
+/// https://www.rfc-editor.org/rfc/rfc5280#section-4.1
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    raw: Vec<u8>,
+    tbs: Vec<u8>,
+    signature_scheme: Option<SignatureScheme>,
+    signature: Vec<u8>,
+    /// 名前の比較は、DERのバイト列どうしで行う
+    issuer: Vec<u8>,
+    subject: Vec<u8>,
+    not_before: u64,
+    not_after: u64,
+    public_key: PublicKey,
+    is_ca: bool,
+    path_len: Option<u64>,
+    key_usage: Option<u16>,
+    /// ExtendedKeyUsageがないか、serverAuthを含んでいる
+    server_auth: bool,
+    dns_names: Vec<String>,
+    ip_addresses: Vec<Vec<u8>>,
+}
+
+impl Certificate {
+    pub fn parse(der: &[u8]) -> Result<Self, Error> {
+        let mut reader = DerReader::new(der);
+        let mut certificate = reader.expect(TAG_SEQUENCE)?.reader();
+        reader.finish()?;
+
+        let tbs = certificate.expect(TAG_SEQUENCE)?;
+        let signature_algorithm = certificate.expect(TAG_SEQUENCE)?;
+        let signature = certificate.read_bit_string_bytes()?;
+        certificate.finish()?;
+
+        let mut tbs_reader = tbs.reader();
+        let version = match tbs_reader.optional(context(0))? {
+            Some(version) => {
+                let mut version = version.reader();
+                let value = version.read_small_integer()?;
+                version.finish()?;
+                value
+            }
+            None => 0,
+        };
+        // シリアル番号は負の値の証明書もあるので、中身は見ない
+        tbs_reader.read()?;
+        let inner_algorithm = tbs_reader.expect(TAG_SEQUENCE)?;
+        if inner_algorithm.raw != signature_algorithm.raw {
+            return Err(tls_error("mismatched signature algorithms in certificate"));
+        }
+        let issuer = tbs_reader.expect(TAG_SEQUENCE)?.raw.to_vec();
+        let mut validity = tbs_reader.expect(TAG_SEQUENCE)?.reader();
+        let not_before = validity.read_time()?;
+        let not_after = validity.read_time()?;
+        validity.finish()?;
+        let subject = tbs_reader.expect(TAG_SEQUENCE)?.raw.to_vec();
+        let public_key = PublicKey::parse(&mut tbs_reader.expect(TAG_SEQUENCE)?.reader())?;
+        tbs_reader.optional(context_primitive(1))?;
+        tbs_reader.optional(context_primitive(2))?;
+
+        let mut certificate = Self {
+            raw: der.to_vec(),
+            tbs: tbs.raw.to_vec(),
+            signature_scheme: signature_scheme(&mut signature_algorithm.reader())?,
+            signature: signature.to_vec(),
+            issuer,
+            subject,
+            not_before,
+            not_after,
+            public_key,
+            is_ca: false,
+            path_len: None,
+            key_usage: None,
+            server_auth: true,
+            dns_names: Vec::new(),
+            ip_addresses: Vec::new(),
+        };
+
+        if let Some(extensions) = tbs_reader.optional(context(3))? {
+            if version != 2 {
+                return Err(tls_error("extensions in a certificate older than v3"));
+            }
+            let mut outer = extensions.reader();
+            let mut extensions = outer.expect(TAG_SEQUENCE)?.reader();
+            outer.finish()?;
+            while !extensions.is_empty() {
+                certificate.parse_extension(&mut extensions.expect(TAG_SEQUENCE)?.reader())?;
+            }
+        }
+        tbs_reader.finish()?;
+
+        Ok(certificate)
+    }
+
+    /// https://www.rfc-editor.org/rfc/rfc5280#section-4.2
+    fn parse_extension(&mut self, extension: &mut DerReader) -> Result<(), Error> {
+        let id = extension.read_oid()?;
+        let critical = match extension.peek_tag() {
+            Some(TAG_BOOLEAN) => extension.read_boolean()?,
+            _ => false,
+        };
+        let value = extension.expect(TAG_OCTET_STRING)?.contents;
+        extension.finish()?;
+
+        let mut value = DerReader::new(value);
+        match id {
+            // https://www.rfc-editor.org/rfc/rfc5280#section-4.2.1.9
+            OID_BASIC_CONSTRAINTS => {
+                let mut constraints = value.expect(TAG_SEQUENCE)?.reader();
+                if constraints.peek_tag() == Some(TAG_BOOLEAN) {
+                    self.is_ca = constraints.read_boolean()?;
+                }
+                if !constraints.is_empty() {
+                    self.path_len = Some(constraints.read_small_integer()?);
+                }
+                constraints.finish()?;
+            }
+            // https://www.rfc-editor.org/rfc/rfc5280#section-4.2.1.3
+            OID_KEY_USAGE => {
+                let bits = match value.expect(TAG_BIT_STRING)?.contents {
+                    [_, rest @ ..] => rest,
+                    [] => return Err(tls_error("invalid key usage")),
+                };
+                let mut usage = 0u16;
+                for bit in 0..9 {
+                    if let Some(byte) = bits.get(bit / 8) {
+                        if byte & (0x80 >> (bit % 8)) != 0 {
+                            usage |= 1 << bit;
+                        }
+                    }
+                }
+                self.key_usage = Some(usage);
+            }
+            // https://www.rfc-editor.org/rfc/rfc5280#section-4.2.1.6
+            OID_SUBJECT_ALT_NAME => {
+                let mut names = value.expect(TAG_SEQUENCE)?.reader();
+                while !names.is_empty() {
+                    let name = names.read()?;
+                    match name.tag {
+                        // dNSName
+                        0x82 => match core::str::from_utf8(name.contents) {
+                            Ok(dns_name) if dns_name.is_ascii() => {
+                                self.dns_names.push(dns_name.to_ascii_lowercase())
+                            }
+                            _ => return Err(tls_error("invalid DNS name in certificate")),
+                        },
+                        // iPAddress
+                        0x87 => self.ip_addresses.push(name.contents.to_vec()),
+                        _ => {}
+                    }
+                }
+            }
+            // https://www.rfc-editor.org/rfc/rfc5280#section-4.2.1.12
+            OID_EXT_KEY_USAGE => {
+                let mut purposes = value.expect(TAG_SEQUENCE)?.reader();
+                self.server_auth = false;
+                while !purposes.is_empty() {
+                    let purpose = purposes.read_oid()?;
+                    if purpose == OID_SERVER_AUTH || purpose == OID_ANY_EXTENDED_KEY_USAGE {
+                        self.server_auth = true;
+                    }
+                }
+            }
+            // 理解できない重要な拡張を含む証明書は使ってはならない
+            _ if critical => return Err(tls_error("unsupported critical extension")),
+            _ => return Ok(()),
+        }
+        value.finish()
+    }
+
+    /// 証明書のDERのバイト列
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn issuer(&self) -> &[u8] {
+        &self.issuer
+    }
+
+    pub fn subject(&self) -> &[u8] {
+        &self.subject
+    }
+
+    pub fn not_before(&self) -> u64 {
+        self.not_before
+    }
+
+    pub fn not_after(&self) -> u64 {
+        self.not_after
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn is_ca(&self) -> bool {
+        self.is_ca
+    }
+
+    pub fn path_len(&self) -> Option<u64> {
+        self.path_len
+    }
+
+    /// KeyUsage拡張がない場合は、どの用途にも使える
+    pub fn allows_key_usage(&self, usage: u16) -> bool {
+        match self.key_usage {
+            Some(key_usage) => key_usage & usage == usage,
+            None => true,
+        }
+    }
+
+    pub fn allows_server_auth(&self) -> bool {
+        self.server_auth
+    }
+
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    /// 発行者の公開鍵で、この証明書の署名を検証する
+    pub fn is_signed_by(&self, issuer: &PublicKey) -> bool {
+        match self.signature_scheme {
+            Some(scheme) => issuer.verify(scheme, &self.tbs, &self.signature),
+            None => false,
+        }
+    }
+
+    /// 証明書がホストのものかどうか。主体者の共通名は見ずに、SubjectAltNameだけを使う
+    /// https://www.rfc-editor.org/rfc/rfc6125#section-6.4
+    pub fn matches_host(&self, host: &Host) -> bool {
+        match host {
+            Host::Domain(domain) => {
+                let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                self.dns_names
+                    .iter()
+                    .any(|name| matches_dns_name(name.trim_end_matches('.'), &domain))
+            }
+            Host::Ipv4(address) => self
+                .ip_addresses
+                .iter()
+                .any(|ip| ip[..] == address.to_be_bytes()),
+            Host::Ipv6(pieces) => {
+                let bytes: Vec<u8> = pieces.iter().flat_map(|p| p.to_be_bytes()).collect();
+                self.ip_addresses.iter().any(|ip| *ip == bytes)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 証明書の署名アルゴリズム。検証できないアルゴリズムの場合はNoneを返す
+fn signature_scheme(algorithm: &mut DerReader) -> Result<Option<SignatureScheme>, Error> {
+    let scheme = match algorithm.read_oid()? {
+        OID_SHA256_WITH_RSA => SignatureScheme::RsaPkcs1Sha256,
+        OID_SHA384_WITH_RSA => SignatureScheme::RsaPkcs1Sha384,
+        OID_SHA512_WITH_RSA => SignatureScheme::RsaPkcs1Sha512,
+        OID_ECDSA_WITH_SHA256 => SignatureScheme::EcdsaSecp256r1Sha256,
+        OID_ECDSA_WITH_SHA384 => SignatureScheme::EcdsaSecp384r1Sha384,
+        _ => return Ok(None),
+    };
+    // RSAのパラメータはNULLか省略される
+    algorithm.optional(TAG_NULL)?;
+    algorithm.finish()?;
+    Ok(Some(scheme))
+}
+
+/// 左端のラベルが"*"のワイルドカードは、その1つのラベルだけに一致する
+/// https://www.rfc-editor.org/rfc/rfc6125#section-6.4.3
+fn matches_dns_name(pattern: &str, domain: &str) -> bool {
+    if let Some(suffix) = pattern.strip_prefix("*.") {
+        // "*.com"のような、広すぎるワイルドカードは使わない
+        if !suffix.contains('.') {
+            return false;
+        }
+        return match domain.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest == suffix,
+            None => false,
+        };
+    }
+    pattern == domain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::trust::pem_certificates;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn parse(pem: &str) -> Certificate {
+        let der = pem_certificates(pem).expect("invalid pem").remove(0);
+        Certificate::parse(&der).expect("failed to parse a certificate")
+    }
+
+    #[test]
+    fn test_parse() {
+        let root = parse(include_str!("../../testdata/tls/ca.pem"));
+        assert!(root.is_ca());
+        assert_eq!(None, root.path_len());
+        assert!(root.allows_key_usage(KEY_USAGE_KEY_CERT_SIGN));
+        assert_eq!(root.issuer(), root.subject());
+        assert!(root.is_signed_by(root.public_key()));
+        assert!(matches!(
+            root.public_key(),
+            PublicKey::Ec(NamedCurve::P256, _)
+        ));
+        // 2024-01-01から2124-01-01まで
+        assert_eq!(1704067200, root.not_before());
+        assert_eq!(4859740800, root.not_after());
+
+        let intermediate = parse(include_str!("../../testdata/tls/intermediate.pem"));
+        assert!(intermediate.is_ca());
+        assert_eq!(Some(0), intermediate.path_len());
+        assert_eq!(root.subject(), intermediate.issuer());
+        assert!(intermediate.is_signed_by(root.public_key()));
+        assert!(matches!(intermediate.public_key(), PublicKey::Rsa(_)));
+
+        let leaf = parse(include_str!("../../testdata/tls/rsa.pem"));
+        assert!(!leaf.is_ca());
+        assert!(leaf.allows_key_usage(KEY_USAGE_DIGITAL_SIGNATURE));
+        assert!(!leaf.allows_key_usage(KEY_USAGE_KEY_CERT_SIGN));
+        assert!(leaf.allows_server_auth());
+        assert_eq!(intermediate.subject(), leaf.issuer());
+        assert!(leaf.is_signed_by(intermediate.public_key()));
+        assert!(!leaf.is_signed_by(root.public_key()));
+        assert_eq!(
+            &vec!["localhost".to_string(), "*.saba.test".to_string()][..],
+            leaf.dns_names()
+        );
+
+        let leaf = parse(include_str!("../../testdata/tls/ecdsa.pem"));
+        assert!(matches!(
+            leaf.public_key(),
+            PublicKey::Ec(NamedCurve::P384, _)
+        ));
+        assert!(leaf.is_signed_by(root.public_key()));
+    }
+
+    #[test]
+    fn test_matches_host() {
+        let leaf = parse(include_str!("../../testdata/tls/rsa.pem"));
+        let domain = |name: &str| Host::Domain(name.to_string());
+        assert!(leaf.matches_host(&domain("localhost")));
+        assert!(leaf.matches_host(&domain("www.saba.test")));
+        assert!(leaf.matches_host(&domain("WWW.saba.test.")));
+        assert!(!leaf.matches_host(&domain("saba.test")));
+        assert!(!leaf.matches_host(&domain("a.b.saba.test")));
+        assert!(!leaf.matches_host(&domain("example.com")));
+        assert!(leaf.matches_host(&Host::Ipv4(0x7f000001)));
+        assert!(!leaf.matches_host(&Host::Ipv4(0x7f000002)));
+        assert!(leaf.matches_host(&Host::Ipv6([0, 0, 0, 0, 0, 0, 0, 1])));
+    }
+
+    #[test]
+    fn test_matches_dns_name() {
+        assert!(matches_dns_name("example.com", "example.com"));
+        assert!(matches_dns_name("*.example.com", "www.example.com"));
+        assert!(!matches_dns_name("*.example.com", "example.com"));
+        assert!(!matches_dns_name("*.com", "example.com"));
+        assert!(!matches_dns_name("w*.example.com", "www.example.com"));
+    }
+
+    #[test]
+    fn test_invalid_certificate() {
+        assert!(Certificate::parse(&[]).is_err());
+        let mut der = pem_certificates(include_str!("../../testdata/tls/rsa.pem"))
+            .expect("invalid pem")
+            .remove(0);
+        der.truncate(der.len() - 1);
+        assert!(Certificate::parse(&der).is_err());
+    }
+}
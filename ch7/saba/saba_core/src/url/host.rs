@@ -0,0 +1,476 @@
+use crate::url::idna;
+use crate::url::percent_encoding::percent_decode;
+use crate::url::percent_encoding::utf8_percent_encode;
+use crate::url::percent_encoding::EncodeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// https://url.spec.whatwg.org/#concept-host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Host {
+    /// https://url.spec.whatwg.org/#concept-domain
+    Domain(String),
+    /// https://url.spec.whatwg.org/#concept-ipv4
+    Ipv4(u32),
+    /// https://url.spec.whatwg.org/#concept-ipv6
+    Ipv6([u16; 8]),
+    /// https://url.spec.whatwg.org/#opaque-host
+    Opaque(String),
+    /// https://url.spec.whatwg.org/#empty-host
+    Empty,
+}
+
+impl Host {
+    /// https://url.spec.whatwg.org/#concept-host-parser
+    pub fn parse(input: &str, is_opaque: bool) -> Result<Self, String> {
+        if let Some(rest) = input.strip_prefix('[') {
+            return match rest.strip_suffix(']') {
+                Some(address) => Ok(Host::Ipv6(parse_ipv6(address)?)),
+                None => Err(format!("invalid IPv6 address: {}", input)),
+            };
+        }
+
+        if is_opaque {
+            return parse_opaque_host(input);
+        }
+
+        if input.is_empty() {
+            return Err("empty host".to_string());
+        }
+
+        let domain = String::from_utf8_lossy(&percent_decode(input.as_bytes())).into_owned();
+        let ascii_domain = domain_to_ascii(&domain)?;
+
+        if ascii_domain.chars().any(is_forbidden_domain_code_point) {
+            return Err(format!("forbidden code point in host: {}", input));
+        }
+
+        if ends_in_a_number(&ascii_domain) {
+            return Ok(Host::Ipv4(parse_ipv4(&ascii_domain)?));
+        }
+
+        Ok(Host::Domain(ascii_domain))
+    }
+
+    /// https://url.spec.whatwg.org/#concept-host-serializer
+    pub fn serialize(&self) -> String {
+        match self {
+            Host::Domain(domain) => domain.clone(),
+            Host::Ipv4(address) => serialize_ipv4(*address),
+            Host::Ipv6(address) => format!("[{}]", serialize_ipv6(address)),
+            Host::Opaque(host) => host.clone(),
+            Host::Empty => String::new(),
+        }
+    }
+
+    /// ドメインを、アドレスバーなどで表示するためのUnicode形式でシリアライズする
+    /// https://url.spec.whatwg.org/#concept-domain-to-unicode
+    pub fn to_unicode(&self) -> String {
+        match self {
+            Host::Domain(domain) => idna::domain_to_unicode(domain),
+            _ => self.serialize(),
+        }
+    }
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.serialize())
+    }
+}
+
+/// https://url.spec.whatwg.org/#forbidden-host-code-point
+fn is_forbidden_host_code_point(c: char) -> bool {
+    matches!(
+        c,
+        '\0' | '\t'
+            | '\n'
+            | '\r'
+            | ' '
+            | '#'
+            | '/'
+            | ':'
+            | '<'
+            | '>'
+            | '?'
+            | '@'
+            | '['
+            | '\\'
+            | ']'
+            | '^'
+            | '|'
+    )
+}
+
+/// https://url.spec.whatwg.org/#forbidden-domain-code-point
+fn is_forbidden_domain_code_point(c: char) -> bool {
+    is_forbidden_host_code_point(c) || c.is_ascii_control() || c == '%'
+}
+
+/// https://url.spec.whatwg.org/#concept-domain-to-ascii
+fn domain_to_ascii(domain: &str) -> Result<String, String> {
+    let result = idna::domain_to_ascii(domain)?;
+    if result.is_empty() {
+        return Err(format!("invalid domain: {}", domain));
+    }
+    Ok(result)
+}
+
+/// https://url.spec.whatwg.org/#concept-opaque-host-parser
+fn parse_opaque_host(input: &str) -> Result<Host, String> {
+    if input.chars().any(is_forbidden_host_code_point) {
+        return Err(format!("forbidden code point in host: {}", input));
+    }
+    Ok(Host::Opaque(utf8_percent_encode(
+        input,
+        EncodeSet::C0Control,
+    )))
+}
+
+/// https://url.spec.whatwg.org/#ends-in-a-number-checker
+fn ends_in_a_number(input: &str) -> bool {
+    let mut parts: Vec<&str> = input.split('.').collect();
+    if parts.last() == Some(&"") {
+        if parts.len() == 1 {
+            return false;
+        }
+        parts.pop();
+    }
+
+    let last = match parts.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+
+    if !last.is_empty() && last.bytes().all(|b| b.is_ascii_digit()) {
+        return true;
+    }
+
+    parse_ipv4_number(last).is_ok()
+}
+
+/// https://url.spec.whatwg.org/#ipv4-number-parser
+fn parse_ipv4_number(input: &str) -> Result<u64, String> {
+    if input.is_empty() {
+        return Err("empty IPv4 number".to_string());
+    }
+
+    let (digits, radix) = if let Some(hex) = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if input.len() >= 2 && input.starts_with('0') {
+        (&input[1..], 8)
+    } else {
+        (input, 10)
+    };
+
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    let mut value: u64 = 0;
+    for c in digits.chars() {
+        let digit = match c.to_digit(radix) {
+            Some(d) => d as u64,
+            None => return Err(format!("invalid IPv4 number: {}", input)),
+        };
+        value = match value
+            .checked_mul(radix as u64)
+            .and_then(|v| v.checked_add(digit))
+        {
+            Some(v) => v,
+            None => return Err(format!("IPv4 number is too large: {}", input)),
+        };
+    }
+
+    Ok(value)
+}
+
+/// https://url.spec.whatwg.org/#concept-ipv4-parser
+fn parse_ipv4(input: &str) -> Result<u32, String> {
+    let mut parts: Vec<&str> = input.split('.').collect();
+    if parts.last() == Some(&"") && parts.len() > 1 {
+        parts.pop();
+    }
+
+    if parts.len() > 4 {
+        return Err(format!("too many parts in IPv4 address: {}", input));
+    }
+
+    let mut numbers = Vec::new();
+    for part in parts {
+        numbers.push(parse_ipv4_number(part)?);
+    }
+
+    let (last, init) = match numbers.split_last() {
+        Some(split) => split,
+        None => return Err(format!("invalid IPv4 address: {}", input)),
+    };
+
+    if init.iter().any(|n| *n > 255) {
+        return Err(format!("IPv4 part is out of range: {}", input));
+    }
+
+    if *last >= 256u64.pow(5 - numbers.len() as u32) {
+        return Err(format!("IPv4 address is out of range: {}", input));
+    }
+
+    let mut ipv4 = *last;
+    for (i, n) in init.iter().enumerate() {
+        ipv4 += n * 256u64.pow(3 - i as u32);
+    }
+
+    Ok(ipv4 as u32)
+}
+
+/// https://url.spec.whatwg.org/#concept-ipv6-parser
+fn parse_ipv6(input: &str) -> Result<[u16; 8], String> {
+    let error = || format!("invalid IPv6 address: {}", input);
+
+    let chars: Vec<char> = input.chars().collect();
+    let mut address = [0u16; 8];
+    let mut piece_index = 0;
+    let mut compress: Option<usize> = None;
+    let mut pointer = 0;
+
+    if chars.first() == Some(&':') {
+        if chars.get(1) != Some(&':') {
+            return Err(error());
+        }
+        pointer += 2;
+        piece_index += 1;
+        compress = Some(piece_index);
+    }
+
+    while pointer < chars.len() {
+        if piece_index == 8 {
+            return Err(error());
+        }
+
+        if chars[pointer] == ':' {
+            if compress.is_some() {
+                return Err(error());
+            }
+            pointer += 1;
+            piece_index += 1;
+            compress = Some(piece_index);
+            continue;
+        }
+
+        let mut value: u32 = 0;
+        let mut length = 0;
+        while length < 4 && pointer < chars.len() {
+            match chars[pointer].to_digit(16) {
+                Some(d) => value = value * 0x10 + d,
+                None => break,
+            }
+            pointer += 1;
+            length += 1;
+        }
+
+        if chars.get(pointer) == Some(&'.') {
+            // IPv4アドレスが埋め込まれている場合（例: ::ffff:192.168.0.1）
+            if length == 0 {
+                return Err(error());
+            }
+            pointer -= length;
+
+            if piece_index > 6 {
+                return Err(error());
+            }
+
+            let mut numbers_seen = 0;
+            while pointer < chars.len() {
+                let mut ipv4_piece: Option<u32> = None;
+
+                if numbers_seen > 0 {
+                    if chars[pointer] == '.' && numbers_seen < 4 {
+                        pointer += 1;
+                    } else {
+                        return Err(error());
+                    }
+                }
+
+                match chars.get(pointer) {
+                    Some(c) if c.is_ascii_digit() => {}
+                    _ => return Err(error()),
+                }
+
+                while let Some(number) = chars.get(pointer).and_then(|c| c.to_digit(10)) {
+                    ipv4_piece = match ipv4_piece {
+                        None => Some(number),
+                        Some(0) => return Err(error()),
+                        Some(piece) => Some(piece * 10 + number),
+                    };
+                    if ipv4_piece > Some(255) {
+                        return Err(error());
+                    }
+                    pointer += 1;
+                }
+
+                let piece = ipv4_piece.unwrap_or(0);
+                address[piece_index] = address[piece_index] * 0x100 + piece as u16;
+                numbers_seen += 1;
+                if numbers_seen == 2 || numbers_seen == 4 {
+                    piece_index += 1;
+                }
+            }
+
+            if numbers_seen != 4 {
+                return Err(error());
+            }
+            break;
+        } else if chars.get(pointer) == Some(&':') {
+            pointer += 1;
+            if pointer >= chars.len() {
+                return Err(error());
+            }
+        } else if pointer < chars.len() {
+            return Err(error());
+        }
+
+        address[piece_index] = value as u16;
+        piece_index += 1;
+    }
+
+    if let Some(compress) = compress {
+        let mut swaps = piece_index - compress;
+        piece_index = 7;
+        while piece_index != 0 && swaps > 0 {
+            address.swap(piece_index, compress + swaps - 1);
+            piece_index -= 1;
+            swaps -= 1;
+        }
+    } else if piece_index != 8 {
+        return Err(error());
+    }
+
+    Ok(address)
+}
+
+/// https://url.spec.whatwg.org/#concept-ipv4-serializer
+fn serialize_ipv4(address: u32) -> String {
+    let octets = address.to_be_bytes();
+    format!("{}.{}.{}.{}", octets[0], octets[1], octets[2], octets[3])
+}
+
+/// https://url.spec.whatwg.org/#concept-ipv6-serializer
+fn serialize_ipv6(address: &[u16; 8]) -> String {
+    // 最も長い0の連続（長さ2以上）を見つけて、"::"で圧縮する
+    let mut compress: Option<(usize, usize)> = None;
+    let mut i = 0;
+    while i < 8 {
+        if address[i] == 0 {
+            let start = i;
+            while i < 8 && address[i] == 0 {
+                i += 1;
+            }
+            let len = i - start;
+            if len > 1 && compress.map_or(true, |(_, l)| len > l) {
+                compress = Some((start, len));
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut output = String::new();
+    let mut ignore0 = false;
+    for (index, piece) in address.iter().enumerate() {
+        if ignore0 && *piece == 0 {
+            continue;
+        }
+        ignore0 = false;
+
+        if let Some((start, _)) = compress {
+            if start == index {
+                output.push_str(if index == 0 { "::" } else { ":" });
+                ignore0 = true;
+                continue;
+            }
+        }
+
+        output.push_str(&format!("{:x}", piece));
+        if index != 7 {
+            output.push(':');
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain() {
+        assert_eq!(
+            Ok(Host::Domain("example.com".to_string())),
+            Host::parse("EXAMPLE.com", false)
+        );
+    }
+
+    #[test]
+    fn test_ipv4() {
+        assert_eq!(Ok(Host::Ipv4(0x7F000001)), Host::parse("127.0.0.1", false));
+        assert_eq!(
+            Ok(Host::Ipv4(0xC0A80001)),
+            Host::parse("0xC0.0250.1", false)
+        );
+        assert_eq!(Ok(Host::Ipv4(0x7F000001)), Host::parse("2130706433", false));
+        assert!(Host::parse("256.0.0.1", false).is_err());
+        assert!(Host::parse("1.2.3.4.5", false).is_err());
+        assert!(Host::parse("example.09", false).is_err());
+    }
+
+    #[test]
+    fn test_ipv6() {
+        let host = Host::parse("[2001:DB8::1]", false).expect("failed to parse IPv6");
+        assert_eq!(Host::Ipv6([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1]), host);
+        assert_eq!("[2001:db8::1]".to_string(), host.serialize());
+
+        let host = Host::parse("[::ffff:192.168.0.1]", false).expect("failed to parse IPv6");
+        assert_eq!("[::ffff:c0a8:1]".to_string(), host.serialize());
+
+        let host = Host::parse("[1:0:0:2:0:0:0:3]", false).expect("failed to parse IPv6");
+        assert_eq!("[1:0:0:2::3]".to_string(), host.serialize());
+
+        assert!(Host::parse("[1::2::3]", false).is_err());
+        assert!(Host::parse("[1:2:3:4:5:6:7:8:9]", false).is_err());
+        assert!(Host::parse("[::1", false).is_err());
+    }
+
+    #[test]
+    fn test_idn() {
+        let host = Host::parse("例え.テスト", false).expect("failed to parse IDN");
+        assert_eq!(Host::Domain("xn--r8jz45g.xn--zckzah".to_string()), host);
+        assert_eq!("例え.テスト".to_string(), host.to_unicode());
+
+        let host = Host::parse("%E4%BE%8B%E3%81%88.test", false).expect("failed to parse IDN");
+        assert_eq!(Host::Domain("xn--r8jz45g.test".to_string()), host);
+
+        assert!(Host::parse("xn--abc-!.test", false).is_err());
+    }
+
+    #[test]
+    fn test_opaque_host() {
+        assert_eq!(
+            Ok(Host::Opaque("Ex%C3%A4mple".to_string())),
+            Host::parse("Exämple", true)
+        );
+        assert!(Host::parse("a b", true).is_err());
+    }
+
+    #[test]
+    fn test_forbidden_domain_code_point() {
+        assert!(Host::parse("exa%mple.com", false).is_err());
+        assert!(Host::parse("exa<mple.com", false).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::url::punycode;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 半角カタカナ（U+FF61〜U+FF9F）の互換分解先
+/// https://www.unicode.org/Public/idna/latest/IdnaMappingTable.txt
+const HALFWIDTH_KATAKANA: [u32; 63] = [
+    0x3002, 0x300C, 0x300D, 0x3001, 0x30FB, 0x30F2, 0x30A1, 0x30A3, 0x30A5, 0x30A7, 0x30A9, 0x30E3,
+    0x30E5, 0x30E7, 0x30C3, 0x30FC, 0x30A2, 0x30A4, 0x30A6, 0x30A8, 0x30AA, 0x30AB, 0x30AD, 0x30AF,
+    0x30B1, 0x30B3, 0x30B5, 0x30B7, 0x30B9, 0x30BB, 0x30BD, 0x30BF, 0x30C1, 0x30C4, 0x30C6, 0x30C8,
+    0x30CA, 0x30CB, 0x30CC, 0x30CD, 0x30CE, 0x30CF, 0x30D2, 0x30D5, 0x30D8, 0x30DB, 0x30DE, 0x30DF,
+    0x30E0, 0x30E1, 0x30E2, 0x30E4, 0x30E6, 0x30E8, 0x30E9, 0x30EA, 0x30EB, 0x30EC, 0x30ED, 0x30EF,
+    0x30F3, 0x3099, 0x309A,
+];
+
+const COMBINING_VOICED_SOUND_MARK: char = '\u{3099}';
+const COMBINING_SEMI_VOICED_SOUND_MARK: char = '\u{309A}';
+
+/// https://www.unicode.org/reports/tr46/#IDNA_Mapping_Table
+fn is_ignored(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{180B}'..='\u{180D}'
+            | '\u{180F}'
+            | '\u{200B}'
+            | '\u{2060}'
+            | '\u{2064}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FEFF}'
+    )
+}
+
+/// https://www.unicode.org/reports/tr46/#Notation
+fn is_label_separator(c: char) -> bool {
+    matches!(c, '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}')
+}
+
+/// UTS #46のマッピングのうち、大文字から小文字への変換、全角英数字と半角カタカナの
+/// 互換文字の変換、無視される文字の削除を行う
+/// https://www.unicode.org/reports/tr46/#ProcessingStepMap
+fn map_char(c: char, output: &mut Vec<char>) {
+    if is_ignored(c) {
+        return;
+    }
+
+    if is_label_separator(c) {
+        output.push('.');
+        return;
+    }
+
+    let code = c as u32;
+    if (0xFF01..=0xFF5E).contains(&code) {
+        // 全角ASCII
+        if let Some(ascii) = char::from_u32(code - 0xFEE0) {
+            output.extend(ascii.to_lowercase());
+        }
+        return;
+    }
+    if (0xFF62..=0xFF9F).contains(&code) {
+        if let Some(kana) = char::from_u32(HALFWIDTH_KATAKANA[(code - 0xFF61) as usize]) {
+            output.push(kana);
+        }
+        return;
+    }
+
+    output.extend(c.to_lowercase());
+}
+
+/// 濁点・半濁点の結合文字を直前のかなと合成する。NFC正規化のうち、
+/// 半角カタカナの変換で必要になる部分のみを扱う
+/// https://www.unicode.org/reports/tr46/#ProcessingStepNormalize
+fn compose_kana(base: char, mark: char) -> Option<char> {
+    let code = base as u32;
+    // ひらがなとカタカナは0x60離れて同じ並びになっている
+    let (hiragana, offset) = match code {
+        0x3041..=0x309F => (code, 0),
+        0x30A1..=0x30FF => (code - 0x60, 0x60),
+        _ => return None,
+    };
+
+    let composed = if mark == COMBINING_VOICED_SOUND_MARK {
+        match hiragana {
+            // か〜ぢ
+            0x304B..=0x3061 if hiragana % 2 == 1 => hiragana + 1,
+            // つ、て、と
+            0x3064 | 0x3066 | 0x3068 => hiragana + 1,
+            // は、ひ、ふ、へ、ほ
+            0x306F | 0x3072 | 0x3075 | 0x3078 | 0x307B => hiragana + 1,
+            // う
+            0x3046 => 0x3094,
+            // ゝ、ヽ
+            0x309D => 0x309E,
+            // ワ、ヰ、ヱ、ヲ（カタカナのみ）
+            0x308F..=0x3092 if offset == 0x60 => hiragana + 8,
+            _ => return None,
+        }
+    } else if mark == COMBINING_SEMI_VOICED_SOUND_MARK {
+        match hiragana {
+            0x306F | 0x3072 | 0x3075 | 0x3078 | 0x307B => hiragana + 2,
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+
+    char::from_u32(composed + offset)
+}
+
+fn map_and_normalize(domain: &str) -> Vec<char> {
+    let mut mapped = Vec::new();
+    for c in domain.chars() {
+        map_char(c, &mut mapped);
+    }
+
+    let mut normalized: Vec<char> = Vec::with_capacity(mapped.len());
+    for c in mapped {
+        if let Some(last) = normalized.last_mut() {
+            if let Some(composed) = compose_kana(*last, c) {
+                *last = composed;
+                continue;
+            }
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// https://www.unicode.org/reports/tr46/#Validity_Criteria
+fn validate_label(label: &str) -> Result<(), String> {
+    let mapped: String = map_and_normalize(label).into_iter().collect();
+    if mapped != label {
+        return Err(format!("label is not in normalized form: {}", label));
+    }
+
+    match label.chars().next() {
+        Some(COMBINING_VOICED_SOUND_MARK) | Some(COMBINING_SEMI_VOICED_SOUND_MARK) => {
+            Err(format!("label begins with a combining mark: {}", label))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// マッピングと正規化を行い、"xn--"で始まるラベルを検証した上で、ラベルのリストを返す
+/// https://www.unicode.org/reports/tr46/#Processing
+fn process(domain: &str) -> Result<Vec<String>, String> {
+    let normalized: String = map_and_normalize(domain).into_iter().collect();
+
+    let mut labels = Vec::new();
+    for label in normalized.split('.') {
+        if let Some(encoded) = label.strip_prefix("xn--") {
+            let decoded = punycode::decode(encoded)?;
+            if decoded.is_empty() || decoded.is_ascii() {
+                return Err(format!("invalid punycode label: {}", label));
+            }
+            validate_label(&decoded)?;
+            labels.push(decoded);
+        } else {
+            validate_label(label)?;
+            labels.push(String::from(label));
+        }
+    }
+
+    Ok(labels)
+}
+
+/// Unicodeのドメインを、DNSやHostヘッダで使うASCII（"xn--"）形式に変換する
+/// https://www.unicode.org/reports/tr46/#ToASCII
+pub fn domain_to_ascii(domain: &str) -> Result<String, String> {
+    let labels = process(domain)?;
+
+    let mut ascii_labels = Vec::with_capacity(labels.len());
+    for label in labels {
+        if label.is_ascii() {
+            ascii_labels.push(label);
+        } else {
+            ascii_labels.push(format!("xn--{}", punycode::encode(&label)?));
+        }
+    }
+
+    Ok(ascii_labels.join("."))
+}
+
+/// ASCII（"xn--"）形式のドメインを、アドレスバーに表示するためのUnicode形式に変換する。
+/// 変換できないラベルはそのまま残す
+/// https://www.unicode.org/reports/tr46/#ToUnicode
+pub fn domain_to_unicode(domain: &str) -> String {
+    let labels: Vec<String> = domain
+        .split('.')
+        .map(|label| {
+            let lower = label.to_ascii_lowercase();
+            match lower.strip_prefix("xn--") {
+                Some(encoded) => match punycode::decode(encoded) {
+                    Ok(decoded) if validate_label(&decoded).is_ok() => decoded,
+                    _ => String::from(label),
+                },
+                None => String::from(label),
+            }
+        })
+        .collect();
+
+    labels.join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_to_ascii() {
+        let cases = [
+            ("example.com", "example.com"),
+            ("EXAMPLE.com", "example.com"),
+            ("例え.テスト", "xn--r8jz45g.xn--zckzah"),
+            ("日本語。ｊｐ", "xn--wgv71a119e.jp"),
+            ("ﾃｽﾄ.test", "xn--zckzah.test"),
+            ("ﾊﾟﾌｨｰ.test", "xn--dckxem1n.test"),
+            ("ｶﾞｷﾞ.test", "xn--mcke.test"),
+            ("Bücher.de", "xn--bcher-kva.de"),
+            ("xn--r8jz45g.test", "xn--r8jz45g.test"),
+            ("so\u{00AD}ft.test", "soft.test"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                Ok(expected.to_string()),
+                domain_to_ascii(input),
+                "converting {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_ascii_invalid() {
+        assert!(domain_to_ascii("xn--abc-!.test").is_err());
+        // "Ü"にデコードされるラベルは、マッピング後の形と一致しないので不正
+        assert!(domain_to_ascii("xn--wca.test").is_err());
+        // ASCIIのみにデコードされるラベルは不正
+        assert!(domain_to_ascii("xn--abc-.test").is_err());
+    }
+
+    #[test]
+    fn test_to_unicode() {
+        assert_eq!(
+            "例え.テスト".to_string(),
+            domain_to_unicode("xn--r8jz45g.xn--zckzah")
+        );
+        assert_eq!("example.com".to_string(), domain_to_unicode("example.com"));
+        assert_eq!(
+            "xn--abc-!.test".to_string(),
+            domain_to_unicode("xn--abc-!.test")
+        );
+    }
+}
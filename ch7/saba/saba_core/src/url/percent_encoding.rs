@@ -0,0 +1,164 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// https://url.spec.whatwg.org/#percent-encoded-bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeSet {
+    /// https://url.spec.whatwg.org/#c0-control-percent-encode-set
+    C0Control,
+    /// https://url.spec.whatwg.org/#fragment-percent-encode-set
+    Fragment,
+    /// https://url.spec.whatwg.org/#query-percent-encode-set
+    Query,
+    /// https://url.spec.whatwg.org/#special-query-percent-encode-set
+    SpecialQuery,
+    /// https://url.spec.whatwg.org/#path-percent-encode-set
+    Path,
+    /// https://url.spec.whatwg.org/#userinfo-percent-encode-set
+    Userinfo,
+    /// https://url.spec.whatwg.org/#component-percent-encode-set
+    Component,
+    /// https://url.spec.whatwg.org/#application-x-www-form-urlencoded-percent-encode-set
+    FormUrlencoded,
+}
+
+impl EncodeSet {
+    pub fn contains(&self, byte: u8) -> bool {
+        let c0_control = !(0x20..=0x7E).contains(&byte);
+
+        match self {
+            EncodeSet::C0Control => c0_control,
+            EncodeSet::Fragment => c0_control || matches!(byte, b' ' | b'"' | b'<' | b'>' | b'`'),
+            EncodeSet::Query => c0_control || matches!(byte, b' ' | b'"' | b'#' | b'<' | b'>'),
+            EncodeSet::SpecialQuery => EncodeSet::Query.contains(byte) || byte == b'\'',
+            EncodeSet::Path => {
+                EncodeSet::Query.contains(byte) || matches!(byte, b'?' | b'`' | b'{' | b'}')
+            }
+            EncodeSet::Userinfo => {
+                EncodeSet::Path.contains(byte)
+                    || matches!(
+                        byte,
+                        b'/' | b':' | b';' | b'=' | b'@' | b'[' | b'\\' | b']' | b'^' | b'|'
+                    )
+            }
+            EncodeSet::Component => {
+                EncodeSet::Userinfo.contains(byte)
+                    || matches!(byte, b'$' | b'%' | b'&' | b'+' | b',')
+            }
+            EncodeSet::FormUrlencoded => {
+                EncodeSet::Component.contains(byte)
+                    || matches!(byte, b'!' | b'\'' | b'(' | b')' | b'~')
+            }
+        }
+    }
+}
+
+fn hex_upper(n: u8) -> char {
+    match n {
+        0..=9 => (b'0' + n) as char,
+        _ => (b'A' + n - 10) as char,
+    }
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// https://url.spec.whatwg.org/#percent-encode
+pub fn percent_encode_byte(byte: u8, output: &mut String) {
+    output.push('%');
+    output.push(hex_upper(byte >> 4));
+    output.push(hex_upper(byte & 0x0F));
+}
+
+/// https://url.spec.whatwg.org/#utf-8-percent-encode
+pub fn utf8_percent_encode_char(c: char, set: EncodeSet, output: &mut String) {
+    let mut buf = [0u8; 4];
+    for byte in c.encode_utf8(&mut buf).bytes() {
+        if set.contains(byte) {
+            percent_encode_byte(byte, output);
+        } else {
+            output.push(byte as char);
+        }
+    }
+}
+
+/// https://url.spec.whatwg.org/#string-utf-8-percent-encode
+pub fn utf8_percent_encode(input: &str, set: EncodeSet) -> String {
+    let mut output = String::new();
+    for c in input.chars() {
+        utf8_percent_encode_char(c, set, &mut output);
+    }
+    output
+}
+
+/// https://url.spec.whatwg.org/#percent-decode
+pub fn percent_decode(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        let byte = input[i];
+        if byte == b'%' && i + 2 < input.len() {
+            if let (Some(high), Some(low)) = (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                output.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(byte);
+        i += 1;
+    }
+    output
+}
+
+/// https://url.spec.whatwg.org/#string-percent-decode
+pub fn percent_decode_str(input: &str) -> String {
+    String::from_utf8_lossy(&percent_decode(input.as_bytes())).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_encode_path() {
+        assert_eq!(
+            "/a%20b%3Fc%7B%7D".to_string(),
+            utf8_percent_encode("/a b?c{}", EncodeSet::Path)
+        );
+    }
+
+    #[test]
+    fn test_encode_non_ascii() {
+        assert_eq!(
+            "%E3%81%82".to_string(),
+            utf8_percent_encode("あ", EncodeSet::C0Control)
+        );
+    }
+
+    #[test]
+    fn test_encode_userinfo() {
+        assert_eq!(
+            "a%40b%3Ac".to_string(),
+            utf8_percent_encode("a@b:c", EncodeSet::Userinfo)
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!("a b".to_string(), percent_decode_str("a%20b"));
+        assert_eq!("あ".to_string(), percent_decode_str("%E3%81%82"));
+    }
+
+    #[test]
+    fn test_decode_invalid_sequence() {
+        assert_eq!("%zz%4".to_string(), percent_decode_str("%zz%4"));
+        assert_eq!("100%".to_string(), percent_decode_str("100%"));
+    }
+}
@@ -0,0 +1,220 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// https://www.rfc-editor.org/rfc/rfc3492#section-5
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// https://www.rfc-editor.org/rfc/rfc3492#section-6.1
+fn adapt(delta: u32, numpoints: u32, firsttime: bool) -> u32 {
+    let mut delta = if firsttime { delta / DAMP } else { delta / 2 };
+    delta += delta / numpoints;
+
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        TMIN
+    } else if k >= bias + TMAX {
+        TMAX
+    } else {
+        k - bias
+    }
+}
+
+fn encode_digit(d: u32) -> char {
+    match d {
+        0..=25 => (b'a' + d as u8) as char,
+        _ => (b'0' + (d - 26) as u8) as char,
+    }
+}
+
+fn decode_digit(c: char) -> Option<u32> {
+    match c {
+        'a'..='z' => Some(c as u32 - 'a' as u32),
+        'A'..='Z' => Some(c as u32 - 'A' as u32),
+        '0'..='9' => Some(c as u32 - '0' as u32 + 26),
+        _ => None,
+    }
+}
+
+/// "xn--"の接頭辞を含まない文字列を返す
+/// https://www.rfc-editor.org/rfc/rfc3492#section-6.3
+pub fn encode(input: &str) -> Result<String, String> {
+    let overflow = || format!("punycode overflow: {}", input);
+
+    let input: Vec<u32> = input.chars().map(|c| c as u32).collect();
+    let mut output: String = input
+        .iter()
+        .filter(|c| **c < 0x80)
+        .map(|c| char::from(*c as u8))
+        .collect();
+
+    let basic_length = output.len() as u32;
+    let mut handled = basic_length;
+    if basic_length > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+
+    while (handled as usize) < input.len() {
+        let m = match input.iter().filter(|c| **c >= n).min() {
+            Some(m) => *m,
+            None => break,
+        };
+
+        delta = (m - n)
+            .checked_mul(handled + 1)
+            .and_then(|d| d.checked_add(delta))
+            .ok_or_else(overflow)?;
+        n = m;
+
+        for c in &input {
+            if *c < n {
+                delta = delta.checked_add(1).ok_or_else(overflow)?;
+            }
+            if *c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic_length);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta += 1;
+        n += 1;
+    }
+
+    Ok(output)
+}
+
+/// "xn--"の接頭辞を取り除いた文字列を受け取る
+/// https://www.rfc-editor.org/rfc/rfc3492#section-6.2
+pub fn decode(input: &str) -> Result<String, String> {
+    let error = || format!("invalid punycode: {}", input);
+
+    let (basic, extended) = match input.rfind('-') {
+        Some(index) => (&input[..index], &input[index + 1..]),
+        None => ("", input),
+    };
+
+    if !basic.is_ascii() {
+        return Err(error());
+    }
+
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+
+    let mut chars = extended.chars().peekable();
+    while chars.peek().is_some() {
+        let old_i = i;
+        let mut w: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let digit = match chars.next().and_then(decode_digit) {
+                Some(d) => d,
+                None => return Err(error()),
+            };
+            i = digit
+                .checked_mul(w)
+                .and_then(|d| d.checked_add(i))
+                .ok_or_else(error)?;
+
+            let t = threshold(k, bias);
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t).ok_or_else(error)?;
+            k += BASE;
+        }
+
+        let length = output.len() as u32 + 1;
+        bias = adapt(i - old_i, length, old_i == 0);
+        n = n.checked_add(i / length).ok_or_else(error)?;
+        i %= length;
+
+        let c = char::from_u32(n).ok_or_else(error)?;
+        if (c as u32) < 0x80 {
+            return Err(error());
+        }
+        output.insert(i as usize, c);
+        i += 1;
+    }
+
+    Ok(output.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    // https://www.rfc-editor.org/rfc/rfc3492#section-7.1
+    const SAMPLES: [(&str, &str); 7] = [
+        ("日本語", "wgv71a119e"),
+        ("bücher", "bcher-kva"),
+        ("3年B組金八先生", "3B-ww4c5e180e575a65lsy2b"),
+        (
+            "安室奈美恵-with-SUPER-MONKEYS",
+            "-with-SUPER-MONKEYS-pc58ag80a8qai00g7n9n",
+        ),
+        ("パフィーdeルンバ", "de-jg4avhby1noc0d"),
+        ("そのスピードで", "d9juau41awczczp"),
+        ("例え", "r8jz45g"),
+    ];
+
+    #[test]
+    fn test_encode() {
+        for (unicode, ascii) in SAMPLES {
+            assert_eq!(
+                Ok(ascii.to_string()),
+                encode(unicode),
+                "encoding {}",
+                unicode
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        for (unicode, ascii) in SAMPLES {
+            assert_eq!(Ok(unicode.to_string()), decode(ascii), "decoding {}", ascii);
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode("abc-!").is_err());
+        assert!(decode("99999999999").is_err());
+        assert!(decode("ü-abc").is_err());
+    }
+}
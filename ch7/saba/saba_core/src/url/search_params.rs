@@ -0,0 +1,220 @@
+use crate::url::percent_encoding::percent_decode;
+use crate::url::percent_encoding::utf8_percent_encode_char;
+use crate::url::percent_encoding::EncodeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// 名前と値の組を、挿入された順番のまま保持する
+/// https://url.spec.whatwg.org/#interface-urlsearchparams
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlSearchParams {
+    list: Vec<(String, String)>,
+}
+
+impl UrlSearchParams {
+    pub fn new() -> Self {
+        Self { list: Vec::new() }
+    }
+
+    /// 先頭の"?"は無視する
+    /// https://url.spec.whatwg.org/#concept-urlencoded-parser
+    pub fn parse(input: &str) -> Self {
+        let input = input.strip_prefix('?').unwrap_or(input);
+
+        let mut list = Vec::new();
+        for sequence in input.split('&') {
+            if sequence.is_empty() {
+                continue;
+            }
+
+            let (name, value) = match sequence.split_once('=') {
+                Some((name, value)) => (name, value),
+                None => (sequence, ""),
+            };
+
+            list.push((decode(name), decode(value)));
+        }
+
+        Self { list }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        self.list.clone()
+    }
+
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-append
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.list.push((String::from(name), String::from(value)));
+    }
+
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-delete
+    pub fn delete(&mut self, name: &str) {
+        self.list.retain(|(n, _)| n != name);
+    }
+
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-get
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.list
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-getall
+    pub fn get_all(&self, name: &str) -> Vec<String> {
+        self.list
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .collect()
+    }
+
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-has
+    pub fn has(&self, name: &str) -> bool {
+        self.list.iter().any(|(n, _)| n == name)
+    }
+
+    /// 最初に見つかった組の値を書き換え、同じ名前の残りの組を削除する。
+    /// 見つからない場合は末尾に追加する
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-set
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.list.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.list[index].1 = String::from(value);
+                let mut i = 0;
+                self.list.retain(|(n, _)| {
+                    let keep = i <= index || n != name;
+                    i += 1;
+                    keep
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    /// 名前のUTF-16コード単位の順に安定ソートする
+    /// https://url.spec.whatwg.org/#dom-urlsearchparams-sort
+    pub fn sort(&mut self) {
+        self.list
+            .sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+    }
+
+    /// https://url.spec.whatwg.org/#concept-urlencoded-serializer
+    pub fn serialize(&self) -> String {
+        let mut output = String::new();
+        for (name, value) in &self.list {
+            if !output.is_empty() {
+                output.push('&');
+            }
+            encode(name, &mut output);
+            output.push('=');
+            encode(value, &mut output);
+        }
+        output
+    }
+}
+
+impl Display for UrlSearchParams {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.serialize())
+    }
+}
+
+fn decode(input: &str) -> String {
+    let bytes: Vec<u8> = input
+        .bytes()
+        .map(|b| if b == b'+' { b' ' } else { b })
+        .collect();
+    String::from_utf8_lossy(&percent_decode(&bytes)).into_owned()
+}
+
+/// https://url.spec.whatwg.org/#concept-urlencoded-byte-serializer
+fn encode(input: &str, output: &mut String) {
+    for c in input.chars() {
+        if c == ' ' {
+            output.push('+');
+        } else {
+            utf8_percent_encode_char(c, EncodeSet::FormUrlencoded, output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_parse() {
+        let params = UrlSearchParams::parse("?a=1&b=x+y&&c&a=%E3%81%82&d=%zz");
+        assert_eq!(
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "x y".to_string()),
+                ("c".to_string(), "".to_string()),
+                ("a".to_string(), "あ".to_string()),
+                ("d".to_string(), "%zz".to_string()),
+            ],
+            params.pairs()
+        );
+        assert_eq!(Some("1".to_string()), params.get("a"));
+        assert_eq!(vec!["1".to_string(), "あ".to_string()], params.get_all("a"));
+        assert!(params.has("c"));
+        assert!(!params.has("e"));
+    }
+
+    #[test]
+    fn test_value_with_equal_sign() {
+        let params = UrlSearchParams::parse("q=a=b");
+        assert_eq!(Some("a=b".to_string()), params.get("q"));
+    }
+
+    #[test]
+    fn test_set_and_delete() {
+        let mut params = UrlSearchParams::parse("a=1&b=2&a=3&c=4");
+        params.set("a", "5");
+        assert_eq!("a=5&b=2&c=4".to_string(), params.serialize());
+
+        params.set("d", "6");
+        assert_eq!("a=5&b=2&c=4&d=6".to_string(), params.serialize());
+
+        params.delete("b");
+        assert_eq!("a=5&c=4&d=6".to_string(), params.serialize());
+    }
+
+    #[test]
+    fn test_sort_is_stable() {
+        let mut params = UrlSearchParams::parse("z=1&a=2&z=0&a=1");
+        params.sort();
+        assert_eq!("a=2&a=1&z=1&z=0".to_string(), params.serialize());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut params = UrlSearchParams::new();
+        params.append("q", "saba browser");
+        params.append("lang", "日本語");
+        params.append("sym", "a+b&c=d~*");
+        assert_eq!(
+            "q=saba+browser&lang=%E6%97%A5%E6%9C%AC%E8%AA%9E&sym=a%2Bb%26c%3Dd%7E*".to_string(),
+            params.serialize()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = "name=%E3%81%95%E3%81%B0&q=a+b";
+        assert_eq!(input.to_string(), UrlSearchParams::parse(input).serialize());
+    }
+}
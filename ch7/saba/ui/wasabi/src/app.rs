@@ -21,9 +21,10 @@ use saba_core::constants::WINDOW_WIDTH;
 use saba_core::constants::*;
 use saba_core::display_item::DisplayItem;
 use saba_core::error::Error;
-use saba_core::http::HttpResponse;
+use saba_core::http::cache::CacheMode;
 use saba_core::renderer::layout::computed_style::FontSize;
 use saba_core::renderer::layout::computed_style::TextDecoration;
+use saba_core::renderer::page::DocumentKind;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum InputMode {
@@ -59,31 +60,29 @@ impl WasabiUI {
         }
     }
 
-    pub fn start(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-    ) -> Result<(), Error> {
+    pub fn start(&mut self) -> Result<(), Error> {
         self.setup()?;
 
-        self.run_app(handle_url)?;
+        self.run_app()?;
 
         Ok(())
     }
 
-    fn run_app(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-    ) -> Result<(), Error> {
+    fn run_app(&mut self) -> Result<(), Error> {
         loop {
-            self.handle_mouse_input(handle_url)?;
-            self.handle_key_input(handle_url)?;
+            self.handle_mouse_input()?;
+            self.handle_key_input()?;
+            // 読み込み中のページを少しずつ解析し、途中までの内容を描画し直す
+            if self.browser.borrow().process_loading() {
+                self.clear_content_area()?;
+                self.update_ui()?;
+            }
+            // ダウンロード中のファイルを少しずつ保存する
+            self.browser.borrow().process_downloads();
         }
     }
 
-    fn handle_mouse_input(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-    ) -> Result<(), Error> {
+    fn handle_mouse_input(&mut self) -> Result<(), Error> {
         if let Some(MouseEvent { button, position }) = Api::get_mouse_cursor_info() {
             self.window.flush_area(self.cursor.rect());
             self.cursor.set_position(position.x, position.y);
@@ -131,7 +130,7 @@ impl WasabiUI {
                 if let Some(url) = next_destination {
                     self.input_url = url.clone();
                     self.update_address_bar()?;
-                    self.start_navigation(handle_url, url)?;
+                    self.start_navigation(url, CacheMode::Default)?;
                 }
             }
         }
@@ -139,20 +138,26 @@ impl WasabiUI {
         Ok(())
     }
 
-    fn handle_key_input(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-    ) -> Result<(), Error> {
+    fn handle_key_input(&mut self) -> Result<(), Error> {
         match self.input_mode {
             InputMode::Normal => {
-                // InputModeがNormalのとき、キー入力を無視する
-                let _ = Api::read_key();
+                // InputModeがNormalのとき、rキーで再読み込みし、Rキーでキャッシュを使わずに
+                // 再読み込みする。それ以外のキー入力は無視する
+                let mode = match Api::read_key() {
+                    Some('r') => CacheMode::NoCache,
+                    Some('R') => CacheMode::Reload,
+                    _ => return Ok(()),
+                };
+                let url = self.browser.borrow().current_page().borrow().url();
+                if let Some(url) = url {
+                    self.start_navigation(url.href(), mode)?;
+                }
             }
             InputMode::Editing => {
                 if let Some(c) = Api::read_key() {
                     if c == 0x0A as char {
                         // エンターキーが押されたので、ナビゲーションを開始する
-                        self.start_navigation(handle_url, self.input_url.clone())?;
+                        self.start_navigation(self.input_url.clone(), CacheMode::Default)?;
 
                         self.input_url = String::new();
                         self.input_mode = InputMode::Normal;
@@ -171,23 +176,27 @@ impl WasabiUI {
         Ok(())
     }
 
-    fn start_navigation(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-        destination: String,
-    ) -> Result<(), Error> {
+    fn start_navigation(&mut self, destination: String, mode: CacheMode) -> Result<(), Error> {
         self.clear_content_area()?;
 
-        match handle_url(destination) {
-            Ok(response) => {
-                let page = self.browser.borrow().current_page();
-                page.borrow_mut().receive_response(response);
-            }
-            Err(e) => {
-                return Err(e);
-            }
+        // 読み込みに失敗した場合も、ブラウザがエラーページを表示するので、アプリケーションは終了しない
+        let kind = self.browser.borrow().navigate(&destination, mode);
+        if kind == DocumentKind::Download {
+            // 表示できないレスポンスの場合は、表示中のページをそのまま描画し直す。
+            // ダウンロードの状況はabout:downloadsで確認できる
+            self.update_ui()?;
+            return Ok(());
         }
 
+        // アドレスバーには、ホストをUnicode形式にしたURLを表示する。正しいURLでない場合は、
+        // 入力された文字列をそのまま表示する
+        let url = self.browser.borrow().current_page().borrow().url();
+        self.input_url = match url {
+            Some(url) => url.display_href(),
+            None => destination,
+        };
+        self.update_address_bar()?;
+
         self.update_ui()?;
 
         Ok(())